@@ -0,0 +1,142 @@
+(() => {
+  // Map tab: markers come from /api/activities/map for the visible bbox, with
+  // the page's filters. Clusters are built server-side per zoom level.
+  const el = document.getElementById("activities-map");
+  if (!el || !window.L) return;
+
+  const status = document.getElementById("activities-map-status");
+  const baseQuery = new URLSearchParams(el.getAttribute("data-map-query") || "");
+  baseQuery.delete("notice");
+  baseQuery.delete("after");
+
+  const map = L.map(el, { zoomControl: true, attributionControl: true });
+  L.tileLayer("https://tile.openstreetmap.org/{z}/{x}/{y}.png", {
+    maxZoom: 19,
+    attribution:
+      '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a>',
+  }).addTo(map);
+
+  const lat = parseFloat(el.getAttribute("data-lat") || "");
+  const lon = parseFloat(el.getAttribute("data-lon") || "");
+  const radiusKm = parseFloat(el.getAttribute("data-radius-km") || "25") || 25;
+  if (Number.isFinite(lat) && Number.isFinite(lon)) {
+    map.fitBounds(L.latLng(lat, lon).toBounds(radiusKm * 2000));
+  } else {
+    // Without a location: the Netherlands.
+    map.setView([52.2, 5.3], 7);
+  }
+
+  const markers = L.layerGroup().addTo(map);
+  let controller = null;
+  let timer = null;
+
+  function setStatus(text) {
+    if (status) status.textContent = text;
+  }
+
+  function clusterIcon(count) {
+    const size = count < 10 ? 34 : count < 100 ? 42 : 50;
+    return L.divIcon({
+      className: "activities-map-cluster",
+      html: "<span>" + (count > 999 ? "999+" : String(count)) + "</span>",
+      iconSize: [size, size],
+    });
+  }
+
+  function popupContent(p) {
+    const root = document.createElement("div");
+    root.className = "activities-map-popup";
+
+    const title = document.createElement("div");
+    title.className = "activities-map-popup-title";
+    title.textContent = p.title || "";
+    root.appendChild(title);
+
+    const when = [p.date_label, p.time_label].filter(Boolean).join(" • ");
+    const meta = [when, p.city, p.category].filter(Boolean).join(" · ");
+    if (meta) {
+      const line = document.createElement("div");
+      line.className = "activities-map-popup-meta";
+      line.textContent = meta;
+      root.appendChild(line);
+    }
+    if (p.is_full) {
+      const full = document.createElement("div");
+      full.className = "activities-map-popup-meta";
+      full.textContent = "Vol";
+      root.appendChild(full);
+    }
+
+    const link = document.createElement("a");
+    link.className = "activities-map-popup-link";
+    link.href = p.url;
+    link.textContent = "Bekijk";
+    root.appendChild(link);
+    return root;
+  }
+
+  function render(collection) {
+    markers.clearLayers();
+    const features = collection.features || [];
+    features.forEach((f) => {
+      const [lng, lat] = f.geometry.coordinates;
+      const p = f.properties || {};
+      if (p.cluster) {
+        const marker = L.marker([lat, lng], { icon: clusterIcon(p.count) });
+        marker.on("click", () => {
+          const b = f.bbox;
+          if (b && (b[0] !== b[2] || b[1] !== b[3])) {
+            map.fitBounds(
+              [
+                [b[1], b[0]],
+                [b[3], b[2]],
+              ],
+              { padding: [40, 40] }
+            );
+          } else {
+            map.setView([lat, lng], map.getZoom() + 2);
+          }
+        });
+        markers.addLayer(marker);
+      } else {
+        const marker = L.circleMarker([lat, lng], {
+          radius: 9,
+          weight: 3,
+          color: "#FFFFFF",
+          fillColor: p.is_full ? "#6B7280" : "#FF0066",
+          fillOpacity: 1,
+        });
+        marker.bindPopup(() => popupContent(p));
+        markers.addLayer(marker);
+      }
+    });
+
+    if (features.length === 0) setStatus("Geen activiteiten in dit gebied.");
+    else if (collection.truncated) setStatus("Zoom in om alle activiteiten te zien.");
+    else setStatus("");
+  }
+
+  async function load() {
+    controller?.abort();
+    controller = new AbortController();
+    const params = new URLSearchParams(baseQuery);
+    params.set("bbox", map.getBounds().toBBoxString());
+    params.set("zoom", String(map.getZoom()));
+    try {
+      const resp = await fetch("/api/activities/map?" + params.toString(), {
+        credentials: "same-origin",
+        signal: controller.signal,
+      });
+      if (!resp.ok) throw new Error(String(resp.status));
+      render(await resp.json());
+    } catch (e) {
+      if (e.name !== "AbortError") setStatus("Kon de kaart niet laden.");
+    }
+  }
+
+  map.on("moveend", () => {
+    clearTimeout(timer);
+    timer = setTimeout(load, 250);
+  });
+  load();
+})();
@@ -67,16 +67,23 @@
     window.setInterval(next, intervalMs);
   }
 
-  function boot() {
-    document
+  function boot(root) {
+    (root || document)
       .querySelectorAll(
         '.activity-participants-strip[data-participants-carousel="1"]'
       )
-      .forEach(initCarousel);
+      .forEach((strip) => {
+        if (strip.getAttribute("data-carousel-ready") === "1") return;
+        strip.setAttribute("data-carousel-ready", "1");
+        initCarousel(strip);
+      });
   }
 
+  // Cards appended later (infinite scroll) call this with their container.
+  window.initParticipantsCarousels = boot;
+
   if (document.readyState === "loading") {
-    document.addEventListener("DOMContentLoaded", boot, { once: true });
+    document.addEventListener("DOMContentLoaded", () => boot(), { once: true });
   } else {
     boot();
   }
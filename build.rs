@@ -8,6 +8,9 @@ fn main() {
     // it's easy to end up with a stale binary during dev.
     rerun_if_changed_dir("templates");
 
+    // Migrations are embedded via `sqlx::migrate!`.
+    println!("cargo:rerun-if-changed=migrations");
+
     // Helpful dev marker so we can see whether the running server is actually
     // the newest binary.
     let build_id = SystemTime::now()
@@ -0,0 +1,40 @@
+-- Restore the real apply triggers now that the UDFs are registered from Rust
+-- (see src/database/sp_functions.rs) on every pool connection.
+--
+-- Replaces the dev dummy triggers from 006 (signup/waitlist) and 019 (friendship).
+-- The UDF returns 1 on success; anything else rolls the command insert back.
+-- sp_call_log is kept for history but no longer written to by these tables.
+
+DROP TRIGGER IF EXISTS trg_activity_signup_commands_apply;
+DROP TRIGGER IF EXISTS trg_activity_waitlist_commands_apply;
+DROP TRIGGER IF EXISTS trg_friendship_commands_apply;
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_signup_commands_apply
+AFTER INSERT ON activity_signup_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_signup_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_signup_command failed')
+        END;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_waitlist_commands_apply
+AFTER INSERT ON activity_waitlist_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_waitlist_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_waitlist_command failed')
+        END;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_friendship_commands_apply
+AFTER INSERT ON friendship_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_friendship_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_friendship_command failed')
+        END;
+END;
@@ -0,0 +1,51 @@
+-- Activity invitations: inbox filter + respond write path (accept/decline).
+--
+-- activity_invitations is a snapshot table; invitee_user_id is filled by sync
+-- when upstream sends it (NULL = invite addressed to the snapshot owner).
+--
+-- activity_invitation_commands follows the signup command pattern:
+--   sp_apply_activity_invitation_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- local snapshot row is marked responded so the inbox updates before sync.
+
+ALTER TABLE activity_invitations ADD COLUMN invitee_user_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_invitations_invitee_status
+ON activity_invitations(invitee_user_id, status);
+
+CREATE TABLE IF NOT EXISTS activity_invitation_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    -- Actor = the invitee responding
+    actor_user_id TEXT NOT NULL,
+
+    invitation_id TEXT NOT NULL,
+    activity_id TEXT NOT NULL,
+
+    action TEXT NOT NULL CHECK (action IN ('accept', 'decline')),
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_invitation_commands_invitation_created
+ON activity_invitation_commands(invitation_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_invitation_commands_actor_created
+ON activity_invitation_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_invitation_commands_apply
+AFTER INSERT ON activity_invitation_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_invitation_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_invitation_command failed')
+        END;
+
+    UPDATE activity_invitations
+    SET status = CASE NEW.action WHEN 'accept' THEN 'accepted' ELSE 'declined' END,
+        responded_at = datetime('now')
+    WHERE invitation_id = NEW.invitation_id;
+END;
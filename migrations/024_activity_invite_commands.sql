@@ -0,0 +1,42 @@
+-- Organizer write path: invite a user to an activity.
+-- Responses from the invitee go through activity_invitation_commands (023).
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_activity_invite_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK.
+--
+-- Committed rows double as the organizer's local record of who was invited;
+-- the invitee's status comes from activity_invitations / activity_participants.
+
+CREATE TABLE IF NOT EXISTS activity_invite_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    -- Actor = organizer / manager sending the invite
+    actor_user_id TEXT NOT NULL,
+
+    activity_id TEXT NOT NULL,
+    invitee_user_id TEXT NOT NULL,
+
+    -- Optional personal message shown to the invitee
+    message TEXT,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_invite_commands_activity_created
+ON activity_invite_commands(activity_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_invite_commands_invitee_created
+ON activity_invite_commands(invitee_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_invite_commands_apply
+AFTER INSERT ON activity_invite_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_invite_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_invite_command failed')
+        END;
+END;
@@ -0,0 +1,74 @@
+-- Notifications write path: mark read / archive / execute an embedded action.
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_notification_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- local snapshot is updated right away (whole group for grouped rows), sync
+-- confirms it later.
+
+CREATE TABLE IF NOT EXISTS notification_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    actor_user_id TEXT NOT NULL,
+
+    -- NULL for read_all
+    notification_id TEXT,
+
+    action TEXT NOT NULL CHECK (action IN ('read', 'archive', 'read_all', 'execute')),
+
+    -- actions[].id of the embedded button (execute only)
+    action_id TEXT,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT,
+
+    CHECK (action = 'read_all' OR notification_id IS NOT NULL),
+    CHECK (action != 'execute' OR action_id IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_commands_notification_created
+ON notification_commands(notification_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_notification_commands_actor_created
+ON notification_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_notification_commands_apply
+AFTER INSERT ON notification_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_notification_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_notification_command failed')
+        END;
+
+    UPDATE notifications
+    SET status = CASE WHEN NEW.action = 'archive' THEN 'archived' ELSE 'read' END,
+        read_at = COALESCE(read_at, datetime('now'))
+    WHERE (is_deleted = 0 OR is_deleted IS NULL)
+      AND (
+        (NEW.action = 'read_all' AND status = 'unread')
+        OR (
+          NEW.action != 'read_all'
+          AND (NEW.action = 'archive' OR status = 'unread')
+          AND (
+            notification_id = NEW.notification_id
+            OR group_id = (
+              SELECT n.group_id
+              FROM notifications n
+              WHERE n.notification_id = NEW.notification_id
+            )
+          )
+        )
+      );
+END;
+
+-- Unread badge: count what the inbox actually shows (expired rows are hidden).
+DROP VIEW IF EXISTS v_unread_notification_count;
+
+CREATE VIEW v_unread_notification_count AS
+SELECT COUNT(*) AS count
+FROM notifications
+WHERE status = 'unread'
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+  AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'));
@@ -0,0 +1,68 @@
+-- Onboarding wizard for users with status 'pending_onboarding'.
+--
+-- onboarding_progress is local-only: it keeps the wizard's answers per user so
+-- a user who drops off halfway resumes on the page they left.
+--
+-- onboarding_commands follows the command pattern:
+--   sp_apply_onboarding_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- snapshot user becomes 'active' with the chosen interests and location, and
+-- the saved progress is dropped.
+
+CREATE TABLE IF NOT EXISTS onboarding_progress (
+    user_id TEXT PRIMARY KEY,
+
+    -- 0-based wizard step; the catalog pages come first, then the location step
+    current_step INTEGER NOT NULL DEFAULT 0,
+
+    -- JSON object: {page_id: [interest_id, ...], ...}
+    selections TEXT NOT NULL DEFAULT '{}',
+
+    location_name TEXT,
+    latitude REAL,
+    longitude REAL,
+
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    actor_user_id TEXT NOT NULL,
+
+    -- Same shape as users.interests: [{interest_id, name, emoji, category_name}...]
+    interests TEXT NOT NULL DEFAULT '[]',
+
+    city TEXT NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_onboarding_commands_actor_created
+ON onboarding_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_onboarding_commands_apply
+AFTER INSERT ON onboarding_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_onboarding_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_onboarding_command failed')
+        END;
+
+    UPDATE users
+    SET status = 'active',
+        interests = NEW.interests,
+        city = NEW.city,
+        latitude = NEW.latitude,
+        longitude = NEW.longitude
+    WHERE user_id = NEW.actor_user_id
+      AND status = 'pending_onboarding';
+
+    DELETE FROM onboarding_progress
+    WHERE user_id = NEW.actor_user_id;
+END;
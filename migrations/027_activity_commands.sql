@@ -0,0 +1,205 @@
+-- Organizer write path: create and edit activities from the website.
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_activity_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- local snapshot is updated right away so the organizer sees the activity
+-- before sync confirms it:
+-- - create: new activities row owned by the actor (+ organizer participant)
+-- - update: edits the row, only where the actor can manage the activity
+
+CREATE TABLE IF NOT EXISTS activity_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    -- Actor = organizer creating or editing
+    actor_user_id TEXT NOT NULL,
+
+    -- Generated by the website for 'create'
+    activity_id TEXT NOT NULL,
+
+    action TEXT NOT NULL CHECK (action IN ('create', 'update')),
+
+    title TEXT NOT NULL,
+    description TEXT,
+    activity_type TEXT NOT NULL CHECK (activity_type IN ('standard', 'xxl', 'womens_only', 'mens_only')),
+    privacy_level TEXT NOT NULL CHECK (privacy_level IN ('public', 'friends_only', 'invite_only')),
+    status TEXT NOT NULL CHECK (status IN ('draft', 'published')),
+
+    scheduled_at TEXT NOT NULL,  -- ISO timestamp
+    duration_minutes INTEGER,
+    max_participants INTEGER NOT NULL,
+    language TEXT NOT NULL,
+
+    -- JSON array: ["tag1", "tag2", ...]
+    tags TEXT NOT NULL DEFAULT '[]',
+    -- JSON: {category_id, name, slug, icon_url} or '{}'
+    category TEXT NOT NULL DEFAULT '{}',
+    -- JSON: {venue_name, city, latitude, longitude, place_id}
+    location TEXT NOT NULL DEFAULT '{}',
+    city TEXT,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+
+    main_photo_asset_id TEXT,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_commands_activity_created
+ON activity_commands(activity_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_commands_actor_created
+ON activity_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_commands_apply
+AFTER INSERT ON activity_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_command failed')
+        END;
+
+    INSERT INTO activities (
+        activity_id,
+        title,
+        description,
+        activity_type,
+        privacy_level,
+        status,
+        scheduled_at,
+        duration_minutes,
+        max_participants,
+        current_participants_count,
+        language,
+        city,
+        location,
+        latitude,
+        longitude,
+        organizer,
+        primary_organizer_user_id,
+        primary_organizer_name,
+        primary_organizer_photo_asset_id,
+        tags,
+        category,
+        my_role,
+        my_participation_status,
+        main_photo_asset_id,
+        is_joined,
+        can_manage_activity,
+        can_manage_attendance,
+        created_at,
+        updated_at,
+        row_hash,
+        changed_at
+    )
+    SELECT
+        NEW.activity_id,
+        NEW.title,
+        NEW.description,
+        NEW.activity_type,
+        NEW.privacy_level,
+        NEW.status,
+        NEW.scheduled_at,
+        NEW.duration_minutes,
+        NEW.max_participants,
+        1,
+        NEW.language,
+        NEW.city,
+        NEW.location,
+        NEW.latitude,
+        NEW.longitude,
+        json_object(
+            'user_id', NEW.actor_user_id,
+            'name', u.name,
+            'photo_url', u.main_photo_url,
+            'is_captain', COALESCE(u.is_captain, 0),
+            'subscription_level', u.subscription_level
+        ),
+        NEW.actor_user_id,
+        u.name,
+        -- Same id extraction as the 008 backfill: the column holds an image
+        -- id, main_photo_url may be a full /api/v1/images/<id> URL.
+        CASE
+            WHEN ph.photo_url IS NULL OR trim(ph.photo_url) = '' THEN NULL
+            WHEN ph.p > 0 THEN
+                CASE
+                    WHEN instr(substr(ph.photo_url, ph.p + length('/api/v1/images/')), '/') > 0 THEN
+                        substr(
+                            substr(ph.photo_url, ph.p + length('/api/v1/images/')),
+                            1,
+                            instr(substr(ph.photo_url, ph.p + length('/api/v1/images/')), '/') - 1
+                        )
+                    ELSE substr(ph.photo_url, ph.p + length('/api/v1/images/'))
+                END
+            WHEN instr(ph.photo_url, '/') = 0 THEN ph.photo_url
+            ELSE NULL
+        END,
+        NEW.tags,
+        NEW.category,
+        'organizer',
+        'registered',
+        NEW.main_photo_asset_id,
+        1,
+        1,
+        1,
+        datetime('now'),
+        datetime('now'),
+        'local:' || NEW.id,
+        datetime('now')
+    FROM (SELECT NEW.actor_user_id AS user_id) me
+    LEFT JOIN users u ON u.user_id = me.user_id
+    LEFT JOIN (
+        SELECT
+            user_id,
+            main_photo_url AS photo_url,
+            instr(main_photo_url, '/api/v1/images/') AS p
+        FROM users
+    ) ph ON ph.user_id = me.user_id
+    WHERE NEW.action = 'create';
+
+    INSERT OR REPLACE INTO activity_participants (
+        activity_id,
+        user_id,
+        name,
+        photo_url,
+        role,
+        participation_status,
+        joined_at
+    )
+    SELECT
+        NEW.activity_id,
+        NEW.actor_user_id,
+        u.name,
+        u.main_photo_url,
+        'organizer',
+        'registered',
+        datetime('now')
+    FROM (SELECT NEW.actor_user_id AS user_id) me
+    LEFT JOIN users u ON u.user_id = me.user_id
+    WHERE NEW.action = 'create';
+
+    UPDATE activities
+    SET title = NEW.title,
+        description = NEW.description,
+        activity_type = NEW.activity_type,
+        privacy_level = NEW.privacy_level,
+        status = NEW.status,
+        scheduled_at = NEW.scheduled_at,
+        duration_minutes = NEW.duration_minutes,
+        max_participants = NEW.max_participants,
+        language = NEW.language,
+        city = NEW.city,
+        location = NEW.location,
+        latitude = NEW.latitude,
+        longitude = NEW.longitude,
+        tags = NEW.tags,
+        category = NEW.category,
+        main_photo_asset_id = NEW.main_photo_asset_id,
+        updated_at = datetime('now')
+    WHERE NEW.action = 'update'
+      AND activity_id = NEW.activity_id
+      AND can_manage_activity = 1;
+END;
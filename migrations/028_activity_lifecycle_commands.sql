@@ -0,0 +1,75 @@
+-- Organizer lifecycle actions: cancel (with reason), reschedule and complete.
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_activity_lifecycle_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. The central side
+-- fans out: it posts `announcement` as a system message in the activity chat
+-- and sends every participant an `activity_update` notification. On success
+-- the local snapshot is updated right away; sync confirms it later.
+
+-- Shown on cards and the detail page; filled by sync as well.
+ALTER TABLE activities ADD COLUMN cancellation_reason TEXT;
+-- scheduled_at before the most recent reschedule (NULL if never moved).
+ALTER TABLE activities ADD COLUMN rescheduled_from TEXT;
+
+CREATE TABLE IF NOT EXISTS activity_lifecycle_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    -- Actor = organizer
+    actor_user_id TEXT NOT NULL,
+    activity_id TEXT NOT NULL,
+
+    action TEXT NOT NULL CHECK (action IN ('cancel', 'reschedule', 'complete')),
+
+    -- cancel only
+    reason TEXT,
+    -- reschedule only: new ISO timestamp
+    scheduled_at TEXT,
+
+    -- System message for the activity chat / participant notification body
+    announcement TEXT NOT NULL,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT,
+
+    CHECK (action != 'cancel' OR reason IS NOT NULL),
+    CHECK (action != 'reschedule' OR scheduled_at IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_lifecycle_commands_activity_created
+ON activity_lifecycle_commands(activity_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_lifecycle_commands_actor_created
+ON activity_lifecycle_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_lifecycle_commands_apply
+AFTER INSERT ON activity_lifecycle_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_lifecycle_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_lifecycle_command failed')
+        END;
+
+    UPDATE activities
+    SET status = CASE NEW.action
+            WHEN 'cancel' THEN 'cancelled'
+            WHEN 'complete' THEN 'completed'
+            ELSE status
+        END,
+        cancelled_at = CASE WHEN NEW.action = 'cancel' THEN datetime('now') ELSE cancelled_at END,
+        cancellation_reason = CASE WHEN NEW.action = 'cancel' THEN NEW.reason ELSE cancellation_reason END,
+        completed_at = CASE WHEN NEW.action = 'complete' THEN datetime('now') ELSE completed_at END,
+        rescheduled_from = CASE WHEN NEW.action = 'reschedule' THEN scheduled_at ELSE rescheduled_from END,
+        scheduled_at = CASE WHEN NEW.action = 'reschedule' THEN NEW.scheduled_at ELSE scheduled_at END,
+        updated_at = datetime('now')
+    WHERE activity_id = NEW.activity_id
+      AND can_manage_activity = 1;
+
+    UPDATE chat_conversations
+    SET activity_status = (SELECT a.status FROM activities a WHERE a.activity_id = NEW.activity_id),
+        activity_scheduled_at = (SELECT a.scheduled_at FROM activities a WHERE a.activity_id = NEW.activity_id)
+    WHERE chat_context = 'activity'
+      AND target_id = NEW.activity_id;
+END;
@@ -0,0 +1,86 @@
+-- Attendance write path: managers mark registered participants as attended or
+-- no-show, in bulk.
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_activity_attendance_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- local snapshot is updated right away; sync confirms it later:
+-- - activity_participants.attendance_status per mark
+-- - users.no_show_count moves with marks into / out of no_show
+-- - activities.my_attendance_status when the actor marked themselves
+
+CREATE TABLE IF NOT EXISTS activity_attendance_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    -- Actor = organizer / attendance manager
+    actor_user_id TEXT NOT NULL,
+    activity_id TEXT NOT NULL,
+
+    -- JSON array: [{"user_id": "...", "status": "attended|no_show|registered"}, ...]
+    -- `registered` resets a mark.
+    marks TEXT NOT NULL CHECK (json_valid(marks) AND json_type(marks) = 'array'),
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_attendance_commands_activity_created
+ON activity_attendance_commands(activity_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_attendance_commands_actor_created
+ON activity_attendance_commands(actor_user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_attendance_commands_apply
+AFTER INSERT ON activity_attendance_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_attendance_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_attendance_command failed')
+        END;
+
+    -- Counter first: it compares against the status before this command.
+    UPDATE users
+    SET no_show_count = MAX(0, COALESCE(no_show_count, 0) + (
+        SELECT
+            SUM(CASE WHEN json_extract(m.value, '$.status') = 'no_show' THEN 1 ELSE 0 END)
+            - SUM(CASE WHEN COALESCE(ap.attendance_status, '') = 'no_show' THEN 1 ELSE 0 END)
+        FROM json_each(NEW.marks) m
+        JOIN activity_participants ap
+          ON ap.activity_id = NEW.activity_id
+         AND ap.user_id = json_extract(m.value, '$.user_id')
+        WHERE ap.user_id = users.user_id
+    ))
+    WHERE user_id IN (
+        SELECT json_extract(value, '$.user_id') FROM json_each(NEW.marks)
+    );
+
+    UPDATE activity_participants
+    SET attendance_status = (
+            SELECT json_extract(m.value, '$.status')
+            FROM json_each(NEW.marks) m
+            WHERE json_extract(m.value, '$.user_id') = activity_participants.user_id
+            LIMIT 1
+        ),
+        updated_at = datetime('now')
+    WHERE activity_id = NEW.activity_id
+      AND user_id IN (
+        SELECT json_extract(value, '$.user_id') FROM json_each(NEW.marks)
+      );
+
+    UPDATE activities
+    SET my_attendance_status = (
+            SELECT json_extract(m.value, '$.status')
+            FROM json_each(NEW.marks) m
+            WHERE json_extract(m.value, '$.user_id') = NEW.actor_user_id
+            LIMIT 1
+        ),
+        updated_at = datetime('now')
+    WHERE activity_id = NEW.activity_id
+      AND EXISTS (
+        SELECT 1
+        FROM json_each(NEW.marks) m
+        WHERE json_extract(m.value, '$.user_id') = NEW.actor_user_id
+      );
+END;
@@ -0,0 +1,107 @@
+-- Reviews write path: one editable review per attendee of a completed
+-- activity, plus "helpful" votes on other people's reviews.
+--
+-- Requires a Rust-registered SQLite UDF that maps 1:1 to the central stored procedure:
+--   sp_apply_activity_review_command(command_id TEXT) -> INTEGER
+-- Return 1 for success; anything else triggers a ROLLBACK. On success the
+-- local snapshot is updated right away; sync confirms it later:
+-- - upsert: creates or edits the review, refreshes activities.review_count/avg_rating
+-- - helpful: +1 helpful_count, once per actor per review (earlier commands are the ledger)
+
+CREATE TABLE IF NOT EXISTS activity_review_commands (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+
+    actor_user_id TEXT NOT NULL,
+    activity_id TEXT NOT NULL,
+
+    -- upsert: the actor's existing review id, or a new one generated by the website
+    review_id TEXT NOT NULL,
+
+    action TEXT NOT NULL CHECK (action IN ('upsert', 'helpful')),
+
+    -- upsert only
+    rating INTEGER CHECK (rating IS NULL OR (rating >= 1 AND rating <= 5)),
+    review_text TEXT,
+
+    -- Optional debug / operator note (never secrets)
+    note TEXT,
+
+    CHECK (action != 'upsert' OR rating IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_review_commands_activity_created
+ON activity_review_commands(activity_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_activity_review_commands_review_actor
+ON activity_review_commands(review_id, actor_user_id, action);
+
+CREATE TRIGGER IF NOT EXISTS trg_activity_review_commands_apply
+AFTER INSERT ON activity_review_commands
+BEGIN
+    SELECT
+        CASE
+            WHEN sp_apply_activity_review_command(NEW.id) = 1 THEN 1
+            ELSE RAISE(ROLLBACK, 'sp_apply_activity_review_command failed')
+        END;
+
+    INSERT INTO activity_reviews (
+        review_id,
+        activity_id,
+        reviewer,
+        rating,
+        review_text,
+        helpful_count,
+        is_mine,
+        created_at,
+        updated_at,
+        row_hash,
+        changed_at
+    )
+    SELECT
+        NEW.review_id,
+        NEW.activity_id,
+        json_object(
+            'user_id', NEW.actor_user_id,
+            'name', u.name,
+            'photo_url', u.main_photo_url,
+            'is_captain', COALESCE(u.is_captain, 0)
+        ),
+        NEW.rating,
+        NEW.review_text,
+        0,
+        1,
+        datetime('now'),
+        datetime('now'),
+        'local:' || NEW.id,
+        datetime('now')
+    FROM (SELECT NEW.actor_user_id AS user_id) me
+    LEFT JOIN users u ON u.user_id = me.user_id
+    WHERE NEW.action = 'upsert'
+    ON CONFLICT(review_id) DO UPDATE SET
+        rating = excluded.rating,
+        review_text = excluded.review_text,
+        updated_at = excluded.updated_at,
+        is_deleted = 0
+    WHERE json_extract(activity_reviews.reviewer, '$.user_id') = NEW.actor_user_id;
+
+    UPDATE activity_reviews
+    SET helpful_count = COALESCE(helpful_count, 0) + 1
+    WHERE NEW.action = 'helpful'
+      AND review_id = NEW.review_id
+      AND COALESCE(json_extract(reviewer, '$.user_id'), '') != NEW.actor_user_id
+      AND NOT EXISTS (
+        SELECT 1
+        FROM activity_review_commands c
+        WHERE c.review_id = NEW.review_id
+          AND c.actor_user_id = NEW.actor_user_id
+          AND c.action = 'helpful'
+          AND c.id != NEW.id
+      );
+
+    UPDATE activities
+    SET review_count = COALESCE((SELECT r.review_count FROM v_activity_ratings r WHERE r.activity_id = NEW.activity_id), 0),
+        avg_rating = (SELECT r.avg_rating FROM v_activity_ratings r WHERE r.activity_id = NEW.activity_id)
+    WHERE NEW.action = 'upsert'
+      AND activity_id = NEW.activity_id;
+END;
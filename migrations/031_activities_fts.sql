@@ -0,0 +1,75 @@
+-- Full-text search over activities (title, description, tags, category,
+-- venue, organizer). Kept in sync with `activities` by triggers; FTS rowids
+-- follow `activities.rowid`, which the sync upsert (ON CONFLICT DO UPDATE)
+-- never changes.
+--
+-- `remove_diacritics 2` folds accents on both sides, so "cafe" finds "Café"
+-- and "reunie" finds "reünie". Prefix indexes keep "pad"* cheap while typing.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS activities_fts USING fts5(
+    title,
+    description,
+    tags,
+    category,
+    venue,
+    organizer,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- One searchable document per live activity. JSON columns are guarded with
+-- json_valid so a malformed snapshot value never fails the sync write.
+CREATE VIEW IF NOT EXISTS v_activities_fts_source AS
+SELECT
+    a.rowid AS activity_rowid,
+    COALESCE(a.title, '') AS title,
+    COALESCE(a.description, '') AS description,
+    CASE
+        WHEN json_valid(a.tags) THEN (SELECT group_concat(t.value, ' ') FROM json_each(a.tags) t)
+    END AS tags,
+    CASE
+        WHEN json_valid(a.category) THEN json_extract(a.category, '$.name')
+    END AS category,
+    trim(
+        COALESCE(CASE WHEN json_valid(a.location) THEN json_extract(a.location, '$.venue_name') END, '')
+        || ' ' || COALESCE(a.city, '')
+    ) AS venue,
+    COALESCE(
+        a.primary_organizer_name,
+        CASE WHEN json_valid(a.organizer) THEN json_extract(a.organizer, '$.name') END
+    ) AS organizer
+FROM activities a
+WHERE a.is_deleted = 0 OR a.is_deleted IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS trg_activities_fts_insert
+AFTER INSERT ON activities
+BEGIN
+    INSERT INTO activities_fts (rowid, title, description, tags, category, venue, organizer)
+    SELECT activity_rowid, title, description, tags, category, venue, organizer
+    FROM v_activities_fts_source
+    WHERE activity_rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_activities_fts_update
+AFTER UPDATE OF
+    title, description, tags, category, location, city,
+    organizer, primary_organizer_name, is_deleted
+ON activities
+BEGIN
+    DELETE FROM activities_fts WHERE rowid = OLD.rowid;
+
+    INSERT INTO activities_fts (rowid, title, description, tags, category, venue, organizer)
+    SELECT activity_rowid, title, description, tags, category, venue, organizer
+    FROM v_activities_fts_source
+    WHERE activity_rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_activities_fts_delete
+AFTER DELETE ON activities
+BEGIN
+    DELETE FROM activities_fts WHERE rowid = OLD.rowid;
+END;
+
+INSERT INTO activities_fts (rowid, title, description, tags, category, venue, organizer)
+SELECT activity_rowid, title, description, tags, category, venue, organizer
+FROM v_activities_fts_source;
@@ -0,0 +1,81 @@
+-- Spatial index over activity coordinates, the activities counterpart of
+-- `users_geo_index`. Radius queries resolve their bounding box against the
+-- R-tree and join back on `activities.rowid`, which the sync upsert
+-- (ON CONFLICT DO UPDATE) never changes.
+--
+-- Activities without coordinates are left out of the index: an R-tree stores
+-- NULL as 0.0, which would place them in the Gulf of Guinea.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS activities_geo_index USING rtree(
+    activity_rowid,
+    min_lat, max_lat,
+    min_lng, max_lng
+);
+
+CREATE TRIGGER IF NOT EXISTS trg_activities_geo_insert
+AFTER INSERT ON activities
+WHEN NEW.latitude IS NOT NULL AND NEW.longitude IS NOT NULL
+BEGIN
+    INSERT INTO activities_geo_index (activity_rowid, min_lat, max_lat, min_lng, max_lng)
+    VALUES (NEW.rowid, NEW.latitude, NEW.latitude, NEW.longitude, NEW.longitude);
+END;
+
+-- Delete + reinsert so an activity that gains (or loses) coordinates enters
+-- (or leaves) the index instead of updating a row that is not there.
+CREATE TRIGGER IF NOT EXISTS trg_activities_geo_update
+AFTER UPDATE OF latitude, longitude ON activities
+BEGIN
+    DELETE FROM activities_geo_index WHERE activity_rowid = OLD.rowid;
+    INSERT INTO activities_geo_index (activity_rowid, min_lat, max_lat, min_lng, max_lng)
+    SELECT NEW.rowid, NEW.latitude, NEW.latitude, NEW.longitude, NEW.longitude
+    WHERE NEW.latitude IS NOT NULL AND NEW.longitude IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_activities_geo_delete
+AFTER DELETE ON activities
+BEGIN
+    DELETE FROM activities_geo_index WHERE activity_rowid = OLD.rowid;
+END;
+
+-- Backfill existing activities.
+DELETE FROM activities_geo_index;
+INSERT INTO activities_geo_index (activity_rowid, min_lat, max_lat, min_lng, max_lng)
+SELECT rowid, latitude, latitude, longitude, longitude
+FROM activities
+WHERE latitude IS NOT NULL AND longitude IS NOT NULL;
+
+-- The users index has the same NULL problem: users without a location were
+-- indexed at (0, 0), and a user whose first location arrived by UPDATE was
+-- never indexed at all. Recreate its triggers the way the activities ones
+-- work and rebuild it, so discovery can rely on it.
+CREATE VIRTUAL TABLE IF NOT EXISTS users_geo_index USING rtree(
+    user_rowid,
+    min_lat, max_lat,
+    min_lng, max_lng
+);
+
+DROP TRIGGER IF EXISTS users_geo_insert;
+DROP TRIGGER IF EXISTS users_geo_update;
+
+CREATE TRIGGER users_geo_insert
+AFTER INSERT ON users
+WHEN NEW.latitude IS NOT NULL AND NEW.longitude IS NOT NULL
+BEGIN
+    INSERT INTO users_geo_index (user_rowid, min_lat, max_lat, min_lng, max_lng)
+    VALUES (NEW.rowid, NEW.latitude, NEW.latitude, NEW.longitude, NEW.longitude);
+END;
+
+CREATE TRIGGER users_geo_update
+AFTER UPDATE OF latitude, longitude ON users
+BEGIN
+    DELETE FROM users_geo_index WHERE user_rowid = OLD.rowid;
+    INSERT INTO users_geo_index (user_rowid, min_lat, max_lat, min_lng, max_lng)
+    SELECT NEW.rowid, NEW.latitude, NEW.latitude, NEW.longitude, NEW.longitude
+    WHERE NEW.latitude IS NOT NULL AND NEW.longitude IS NOT NULL;
+END;
+
+DELETE FROM users_geo_index;
+INSERT INTO users_geo_index (user_rowid, min_lat, max_lat, min_lng, max_lng)
+SELECT rowid, latitude, latitude, longitude, longitude
+FROM users
+WHERE latitude IS NOT NULL AND longitude IS NOT NULL;
@@ -0,0 +1,10 @@
+-- Live events route new notifications and unread totals to their recipient.
+--
+-- notifications is a snapshot table; recipient_user_id is filled by sync when
+-- upstream sends it (NULL = addressed to the snapshot owner), like
+-- activity_invitations.invitee_user_id in 023.
+
+ALTER TABLE notifications ADD COLUMN recipient_user_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_notifications_recipient_status
+ON notifications(recipient_user_id, status);
@@ -1,11 +1,24 @@
 #!/usr/bin/env bash
 set -euo pipefail
 
-# Baseline/repair `_sqlx_migrations` for an existing SQLite DB that already has the schema.
-# This does NOT run migrations; it records them as applied so `sqlx migrate run` won't
-# try to re-apply old migrations and fail on duplicate columns/tables.
+# Baseline `_sqlx_migrations` for an existing SQLite DB that already has the schema.
+# This does NOT run migrations; it records them as applied so startup won't try to
+# re-apply old migrations and fail on duplicate columns/tables.
+#
+# Usage: scripts/sqlx-baseline.sh [UP_TO]
+#
+# Only versions <= UP_TO (default 21, the last one folded into goamet_schema.sql)
+# are recorded; later ones add tables the baseline schema does not have and must
+# really run, which the app does on its next start. Versions already recorded are
+# left alone, so a changed file still shows up as drift.
 
 database_url="${DATABASE_URL:-sqlite://goamet.db}"
+up_to="${1:-21}"
+if ! [[ "$up_to" =~ ^[0-9]+$ ]]; then
+  echo "UP_TO must be a migration version number, got: $up_to" >&2
+  exit 2
+fi
+up_to=$((10#$up_to))
 
 db_path_from_url() {
   local url="$1"
@@ -59,6 +72,10 @@ for f in "${migration_files[@]}"; do
   base="$(basename "$f")"
   ver="${base%%_*}"
   ver_num=$((10#$ver))
+  if ((ver_num > up_to)); then
+    echo "Skipping $base (> $up_to): runs on next start"
+    continue
+  fi
   desc="${base#*_}"
   desc="${desc%.sql}"
   desc="${desc//_/ }"
@@ -75,11 +92,8 @@ for f in "${migration_files[@]}"; do
   sqlite3 "$db_file" <<SQL
 INSERT INTO _sqlx_migrations (version, description, installed_on, success, checksum, execution_time)
 VALUES ($ver_num, '$desc_sql', datetime('now'), 1, X'$checksum_hex', 0)
-ON CONFLICT(version) DO UPDATE SET
-  description=excluded.description,
-  success=1,
-  checksum=excluded.checksum;
+ON CONFLICT(version) DO NOTHING;
 SQL
 done
 
-echo "Baselined _sqlx_migrations for $db_file"
+echo "Baselined _sqlx_migrations up to $up_to for $db_file"
@@ -2,6 +2,7 @@ use dotenvy::dotenv;
 use sqlx::sqlite::SqlitePoolOptions;
 use std::env;
 
+use website::database::migrations;
 use website::services::activity_geo_service;
 
 #[tokio::main]
@@ -15,6 +16,17 @@ async fn main() {
         .await
         .expect("Kan niet verbinden met DB");
 
+    let migration_mode = migrations::MigrationMode::from_args(env::args().skip(1));
+    if migrations::apply_on_startup(&pool, migration_mode)
+        .await
+        .is_err()
+    {
+        std::process::exit(1);
+    }
+    if migration_mode.exits_after_migrations() {
+        return;
+    }
+
     let limit: i64 = env::var("BACKFILL_LIMIT")
         .ok()
         .and_then(|v| v.parse().ok())
@@ -0,0 +1,247 @@
+//! Radius-query benchmark for the `users_geo_index` and
+//! `activities_geo_index` R-trees.
+//!
+//! Seeds an in-memory database (`goamet_schema.sql` plus the migrations) with
+//! `BENCH_ROWS` users and activities spread over the Netherlands, 100k each
+//! by default, then times the bounding-box lookups against the plain
+//! `(latitude, longitude)` B-tree and the Discover feed with and without a
+//! radius, in time order and ranked.
+//!
+//!     cargo run --release --bin bench_geo
+//!     BENCH_ROWS=250000 BENCH_RADIUS_KM=25 cargo run --release --bin bench_geo
+
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::env;
+use std::time::{Duration, Instant};
+
+use website::database::activities_repo::{self, DiscoverRanking, FeedFilters};
+use website::database::{discovery_repo, migrations};
+use website::models::DiscoveryUserRow;
+use website::services::activities_service::RANK_WEIGHTS;
+
+const SCHEMA: &str = include_str!("../../goamet_schema.sql");
+
+/// Utrecht, roughly the middle of the seeded area.
+const ORIGIN: (f64, f64) = (52.0907, 5.1214);
+const RUNS: u32 = 20;
+const FEED_PAGE: i64 = 20;
+
+const SQL_SEED_USERS: &str = r#"
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?1)
+INSERT INTO users (user_id, name, main_photo_url, latitude, longitude, row_hash, changed_at)
+SELECT
+    'u' || i, 'Gebruiker ' || i, 'https://example.invalid/' || i || '.jpg',
+    50.75 + ((i * 7919) % 100003) / 100003.0 * 2.75,
+    3.35 + ((i * 104729) % 99991) / 99991.0 * 3.85,
+    'h', '2030-01-01T00:00:00Z'
+FROM n
+"#;
+
+const SQL_SEED_ACTIVITIES: &str = r#"
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?1)
+INSERT INTO activities (
+    activity_id, title, scheduled_at, max_participants, current_participants_count,
+    status, is_joined, is_deleted, latitude, longitude, row_hash, changed_at
+)
+SELECT
+    'a' || i, 'Activiteit ' || i,
+    strftime('%Y-%m-%dT%H:00:00', 'now', '+' || (1 + i % 60) || ' days', '+' || (i % 12) || ' hours'),
+    10, i % 11, 'published', 0, 0,
+    50.75 + ((i * 15485863) % 100019) / 100019.0 * 2.75,
+    3.35 + ((i * 32452843) % 99989) / 99989.0 * 3.85,
+    'h', '2030-01-01T00:00:00Z'
+FROM n
+"#;
+
+/// Discovery's radius filter as it was before the R-tree.
+const SQL_USERS_BETWEEN: &str =
+    " AND u.user_id != ? AND u.latitude BETWEEN ? AND ? AND u.longitude BETWEEN ? AND ? LIMIT 500";
+
+/// Both activity lookups read `title`, like the feed does, so the B-tree
+/// cannot answer from the index alone.
+const SQL_ACTIVITIES_BETWEEN: &str = r#"
+SELECT COUNT(a.title)
+FROM activities a
+WHERE a.latitude BETWEEN ? AND ?
+  AND a.longitude BETWEEN ? AND ?
+"#;
+
+const SQL_ACTIVITIES_RTREE: &str = r#"
+SELECT COUNT(a.title)
+FROM activities a
+WHERE a.rowid IN (
+  SELECT g.activity_rowid
+  FROM activities_geo_index g
+  WHERE g.max_lat >= ? AND g.min_lat <= ?
+    AND g.max_lng >= ? AND g.min_lng <= ?
+)
+"#;
+
+#[tokio::main]
+async fn main() {
+    let rows: i64 = env::var("BENCH_ROWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100_000);
+    let radius_km: f64 = env::var("BENCH_RADIUS_KM")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10.0);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Kan geen in-memory DB openen");
+    seed(&pool, rows).await.expect("Seeden mislukt");
+
+    let bbox = bounding_box(ORIGIN.0, ORIGIN.1, radius_km);
+    println!(
+        "{} users / {} activiteiten, straal {} km, gemiddelde over {} runs",
+        rows, rows, radius_km, RUNS
+    );
+
+    let users_btree = time(|| users_between(&pool, bbox)).await;
+    let users_rtree = time(|| async {
+        discovery_repo::load_discovery_candidates(&pool, "u0", Some(bbox))
+            .await
+            .map(|r| r.len())
+    })
+    .await;
+    report("discovery", "b-tree", users_btree, "r-tree", users_rtree);
+
+    let act_btree = time(|| count(&pool, SQL_ACTIVITIES_BETWEEN, bbox)).await;
+    let act_rtree = time(|| count(&pool, SQL_ACTIVITIES_RTREE, bbox)).await;
+    report("activiteiten", "b-tree", act_btree, "r-tree", act_rtree);
+
+    let feed_all = time(|| discover(&pool, None, false)).await;
+    let feed_radius = time(|| discover(&pool, Some(bbox), false)).await;
+    report(
+        "discover-feed",
+        "zonder straal",
+        feed_all,
+        "met straal",
+        feed_radius,
+    );
+
+    let ranked_all = time(|| discover(&pool, None, true)).await;
+    let ranked_radius = time(|| discover(&pool, Some(bbox), true)).await;
+    report(
+        "gerangschikt",
+        "zonder straal",
+        ranked_all,
+        "met straal",
+        ranked_radius,
+    );
+}
+
+async fn seed(pool: &SqlitePool, rows: i64) -> sqlx::Result<()> {
+    sqlx::query(SCHEMA).execute(pool).await?;
+    for migration in migrations::MIGRATOR.iter() {
+        match sqlx::query(&migration.sql).execute(pool).await {
+            Ok(_) => {}
+            // Everything these add is already in goamet_schema.sql.
+            Err(_) if migrations::FOLDED_INTO_SCHEMA.contains(&migration.version) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    sqlx::query(SQL_SEED_USERS).bind(rows).execute(pool).await?;
+    sqlx::query(SQL_SEED_ACTIVITIES)
+        .bind(rows)
+        .execute(pool)
+        .await?;
+    sqlx::query("ANALYZE").execute(pool).await?;
+    Ok(())
+}
+
+async fn users_between(pool: &SqlitePool, bbox: (f64, f64, f64, f64)) -> sqlx::Result<usize> {
+    let sql = format!(
+        "{}{}",
+        discovery_repo::SQL_DISCOVERY_BASE,
+        SQL_USERS_BETWEEN
+    );
+    sqlx::query_as::<_, DiscoveryUserRow>(&sql)
+        .bind("u0")
+        .bind("u0")
+        .bind("u0")
+        .bind(bbox.0)
+        .bind(bbox.1)
+        .bind(bbox.2)
+        .bind(bbox.3)
+        .fetch_all(pool)
+        .await
+        .map(|rows| rows.len())
+}
+
+async fn count(pool: &SqlitePool, sql: &str, bbox: (f64, f64, f64, f64)) -> sqlx::Result<usize> {
+    sqlx::query_scalar::<_, i64>(sql)
+        .bind(bbox.0)
+        .bind(bbox.1)
+        .bind(bbox.2)
+        .bind(bbox.3)
+        .fetch_one(pool)
+        .await
+        .map(|n| n as usize)
+}
+
+/// One page of Discover, in time order or ranked from `ORIGIN` the way the
+/// feed ranks it.
+async fn discover(
+    pool: &SqlitePool,
+    bbox: Option<(f64, f64, f64, f64)>,
+    ranked: bool,
+) -> sqlx::Result<usize> {
+    let filters = FeedFilters {
+        bbox,
+        ..FeedFilters::default()
+    };
+    let ranking = ranked.then_some(DiscoverRanking {
+        weights: RANK_WEIGHTS,
+        interests_json: Some(r#"["wandelen","padel"]"#),
+    });
+    let origin = ranked.then_some(ORIGIN);
+    activities_repo::list_discover(pool, "u0", filters, origin, ranking, None, FEED_PAGE)
+        .await
+        .map(|rows| rows.len())
+}
+
+/// Rows found and the average wall time over `RUNS` runs, after a warm-up.
+async fn time<F, Fut>(mut run: F) -> (usize, Duration)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = sqlx::Result<usize>>,
+{
+    let found = run().await.expect("Query mislukt");
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        run().await.expect("Query mislukt");
+    }
+    (found, start.elapsed() / RUNS)
+}
+
+fn report(label: &str, a_name: &str, a: (usize, Duration), b_name: &str, b: (usize, Duration)) {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    println!(
+        "{:<14} {:>13} {:>9.3} ms ({:>5} rijen)   {:>10} {:>9.3} ms ({:>5} rijen)   {:>5.1}x",
+        label,
+        a_name,
+        ms(a.1),
+        a.0,
+        b_name,
+        ms(b.1),
+        b.0,
+        ms(a.1) / ms(b.1).max(f64::EPSILON),
+    );
+}
+
+fn bounding_box(lat: f64, lon: f64, radius_km: f64) -> (f64, f64, f64, f64) {
+    let lat_change = radius_km / 111.0;
+    let lon_change = (radius_km / 111.0) / lat.to_radians().cos().abs();
+    (
+        lat - lat_change,
+        lat + lat_change,
+        lon - lon_change,
+        lon + lon_change,
+    )
+}
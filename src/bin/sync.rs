@@ -0,0 +1,74 @@
+use dotenvy::dotenv;
+use sqlx::sqlite::SqlitePoolOptions;
+use std::env;
+
+use website::database::migrations;
+use website::services::sync_service::{self, SyncConfig, SyncEngine};
+
+/// One-shot snapshot sync. Pass entity types to limit the run, e.g.
+/// `cargo run --bin sync -- activities users`.
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL moet in .env staan");
+    let pool = SqlitePoolOptions::new()
+        .connect(&db_url)
+        .await
+        .expect("Kan niet verbinden met DB");
+
+    let migration_mode = migrations::MigrationMode::from_args(env::args().skip(1));
+    if migrations::apply_on_startup(&pool, migration_mode)
+        .await
+        .is_err()
+    {
+        std::process::exit(1);
+    }
+    if migration_mode.exits_after_migrations() {
+        return;
+    }
+
+    let requested: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    let engine = SyncEngine::new(pool, SyncConfig::from_env());
+
+    let results = if requested.is_empty() {
+        engine.sync_all().await
+    } else {
+        let mut out = Vec::new();
+        for name in &requested {
+            let Some(entity) = sync_service::find_entity(name) else {
+                eprintln!("Onbekend entity type: {}", name);
+                std::process::exit(2);
+            };
+            out.push((entity.entity_type, engine.sync_entity(entity).await));
+        }
+        out
+    };
+
+    let mut failed = 0;
+    for (entity_type, result) in &results {
+        match result {
+            Ok(r) if r.skipped => println!("{}: overgeslagen (tabel ontbreekt)", entity_type),
+            Ok(r) => println!(
+                "{}: pages={}, upserted={}, unchanged={}, deleted={}{}",
+                entity_type,
+                r.pages,
+                r.upserted,
+                r.unchanged,
+                r.deleted,
+                if r.full_resync { " (full resync)" } else { "" }
+            ),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: sync failed: {}", entity_type, e);
+            }
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -1,11 +1,34 @@
-use sqlx::SqlitePool;
+use sqlx::query::QueryAs;
+use sqlx::sqlite::SqliteArguments;
+use sqlx::{Sqlite, SqlitePool};
 
 #[derive(Debug, sqlx::FromRow, Clone)]
 pub struct ActivityFeedRow {
     pub activity_id: String,
     pub title: String,
     pub scheduled_at: String,
+    /// Keyset position within the feed order; see [`FeedAfter`].
+    pub sort_key: String,
     pub status: String,
+    pub rescheduled_from: Option<String>,
+    pub review_count: i64,
+    pub avg_rating: Option<f64>,
+    /// FTS snippet with matches wrapped in `\u{2}`…`\u{3}`; only when searching.
+    pub search_snippet: Option<String>,
+    /// Discover ranking components in 0..=1 and the weighted total; only
+    /// `list_discover` selects them, and only while ranking.
+    #[sqlx(default)]
+    pub rank_interests: Option<f64>,
+    #[sqlx(default)]
+    pub rank_distance: Option<f64>,
+    #[sqlx(default)]
+    pub rank_friends: Option<f64>,
+    #[sqlx(default)]
+    pub rank_soon: Option<f64>,
+    #[sqlx(default)]
+    pub rank_capacity: Option<f64>,
+    #[sqlx(default)]
+    pub rank_score: Option<f64>,
     pub is_joined: i64,
     pub city: Option<String>,
     pub venue_name: Option<String>,
@@ -24,13 +47,136 @@ pub struct ActivityFeedRow {
     pub is_past: i64,
 }
 
+/// Filters shared by every feed tab and the facet counts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeedFilters<'a> {
+    /// FTS5 MATCH expression.
+    pub search: Option<&'a str>,
+    /// `(min_lat, max_lat, min_lon, max_lon)`
+    pub bbox: Option<(f64, f64, f64, f64)>,
+    /// JSON array of category slugs; any of them matches.
+    pub categories_json: Option<&'a str>,
+    /// JSON array of lowercase tags; any of them matches.
+    pub tags_json: Option<&'a str>,
+    /// Naive `YYYY-MM-DD HH:MM:SS` window on `scheduled_at`, in the
+    /// user's wall-clock time like `scheduled_at` itself.
+    pub scheduled_from: Option<&'a str>,
+    pub scheduled_before: Option<&'a str>,
+    /// Only activities starting at or after this hour of the day.
+    pub min_hour: Option<i64>,
+}
+
+/// Keyset cursor: the `sort_key` and `activity_id` of the last row already
+/// shown. Every feed orders on `(sort_key, activity_id)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedAfter {
+    pub sort_key: String,
+    pub activity_id: String,
+}
+
+// Radius filter, only appended when there is a bounding box. The R-tree's
+// rowids drive the query then; without a box the feed queries scan the live
+// activities instead, which beats seeking every one of them by rowid.
+// Overlap rather than containment: the R-tree stores 32-bit floats rounded
+// outwards.
+const SQL_GEO_SCOPE: &str = r#"
+  AND a.rowid IN (
+    SELECT g.activity_rowid
+    FROM activities_geo_index g
+    WHERE g.max_lat >= ? AND g.min_lat <= ?
+      AND g.max_lng >= ? AND g.min_lng <= ?
+  )"#;
+
+/// `head`, the radius filter when there is a bounding box, then `tail`.
+fn with_geo_scope(head: &str, geo_scope: &str, tail: &str, has_bbox: bool) -> String {
+    let mut sql = String::from(head);
+    if has_bbox {
+        sql.push_str(geo_scope);
+    }
+    sql.push_str(tail);
+    sql
+}
+
+// The category, tag, date-window and hour filters on `a`. Every feed query
+// appends them through `with_feed_filters` and binds them through
+// `bind_feed_filters`; the facets count the first two as columns instead.
+const SQL_CATEGORY_FILTER: &str = r#"(
+    ? IS NULL
+    OR CASE WHEN json_valid(a.category) THEN lower(json_extract(a.category, '$.slug')) END
+      IN (SELECT value FROM json_each(?))
+  )"#;
+
+// Also used for the map's interests, which match tags the same way.
+const SQL_TAG_FILTER: &str = r#"(
+    ? IS NULL
+    OR EXISTS (
+      SELECT 1
+      FROM json_each(CASE WHEN json_valid(a.tags) THEN a.tags ELSE '[]' END) t
+      WHERE lower(trim(t.value)) IN (SELECT value FROM json_each(?))
+    )
+  )"#;
+
+const SQL_SCHEDULE_FILTER: &str = r#"
+  AND (? IS NULL OR datetime(a.scheduled_at) >= ?)
+  AND (? IS NULL OR datetime(a.scheduled_at) < ?)
+  AND (? IS NULL OR CAST(strftime('%H', a.scheduled_at) AS INTEGER) >= ?)"#;
+
+/// `head` followed by every [`FeedFilters`] predicate but search and radius.
+fn with_feed_filters(head: &str) -> String {
+    let mut sql = String::from(head);
+    sql.push_str("\n  AND ");
+    sql.push_str(SQL_CATEGORY_FILTER);
+    sql.push_str("\n  AND ");
+    sql.push_str(SQL_TAG_FILTER);
+    sql.push_str(SQL_SCHEDULE_FILTER);
+    sql
+}
+
+/// Binds the predicates appended by [`with_feed_filters`], in their order.
+fn bind_feed_filters<'q, O>(
+    query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    filters: &FeedFilters<'q>,
+) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+    bind_schedule_filter(
+        query
+            .bind(filters.categories_json)
+            .bind(filters.categories_json)
+            .bind(filters.tags_json)
+            .bind(filters.tags_json),
+        filters,
+    )
+}
+
+fn bind_schedule_filter<'q, O>(
+    query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    filters: &FeedFilters<'q>,
+) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+    query
+        .bind(filters.scheduled_from)
+        .bind(filters.scheduled_from)
+        .bind(filters.scheduled_before)
+        .bind(filters.scheduled_before)
+        .bind(filters.min_hour)
+        .bind(filters.min_hour)
+}
+
 const SQL_LIST_UPCOMING: &str = r#"
 SELECT
   a.activity_id,
+  -- Searching ranks by bm25 (negative, best first), offset to sort as text.
+  CASE
+    WHEN m.fts_rank IS NOT NULL THEN printf('%024.12f', 1000000.0 + m.fts_rank)
+    ELSE datetime(a.scheduled_at)
+  END AS sort_key,
   a.title,
   a.scheduled_at,
   a.status,
-  a.is_joined,
+  a.rescheduled_from,
+  COALESCE(a.review_count, 0) AS review_count,
+  a.avg_rating,
+  m.fts_snippet AS search_snippet,
+  -- Accepted invites count as joined until sync flips a.is_joined.
+  1 AS is_joined,
   a.city,
   json_extract(a.location, '$.venue_name') AS venue_name,
   a.primary_organizer_user_id AS organizer_user_id,
@@ -92,60 +238,207 @@ SELECT
 FROM activities a
 LEFT JOIN activity_settings s
   ON s.activity_id = a.activity_id
+LEFT JOIN (
+  SELECT
+    rowid AS fts_rowid,
+    bm25(activities_fts, 10.0, 2.0, 4.0, 4.0, 3.0, 3.0) AS fts_rank,
+    snippet(activities_fts, -1, char(2), char(3), '…', 12) AS fts_snippet
+  FROM activities_fts
+  WHERE ? IS NOT NULL
+    AND activities_fts MATCH ?
+) m
+  ON m.fts_rowid = a.rowid
 WHERE a.is_deleted = 0
-  AND a.is_joined = 1
-  AND datetime(a.scheduled_at) > datetime('now')
   AND (
-    ? = ''
-    OR lower(a.title) LIKE ?
-    OR lower(COALESCE(a.city, '')) LIKE ?
-    OR lower(COALESCE(json_extract(a.location, '$.venue_name'), '')) LIKE ?
+    a.is_joined = 1
+    OR EXISTS (
+      SELECT 1
+      FROM activity_invitations i
+      WHERE i.activity_id = a.activity_id
+        AND i.status = 'accepted'
+        AND (i.is_deleted = 0 OR i.is_deleted IS NULL)
+        AND (i.invitee_user_id IS NULL OR i.invitee_user_id = ?)
+    )
   )
+  AND datetime(a.scheduled_at) > datetime('now')
+  AND (? IS NULL OR m.fts_rowid IS NOT NULL)"#;
+
+const SQL_LIST_UPCOMING_PAGE: &str = r#"
   AND (
     ? IS NULL
-    OR (
-      a.latitude BETWEEN ? AND ?
-      AND a.longitude BETWEEN ? AND ?
-    )
+    OR sort_key > ?
+    OR (sort_key = ? AND a.activity_id > ?)
   )
-ORDER BY datetime(a.scheduled_at) ASC
+ORDER BY sort_key ASC, a.activity_id ASC
 LIMIT ?
 "#;
 
 pub async fn list_upcoming(
     pool: &SqlitePool,
     auth_user_id: &str,
-    q_like: &str,
-    bbox: Option<(f64, f64, f64, f64)>,
+    filters: FeedFilters<'_>,
+    after: Option<&FeedAfter>,
     limit: i64,
 ) -> sqlx::Result<Vec<ActivityFeedRow>> {
-    let (min_lat, max_lat, min_lon, max_lon) = bbox
-        .map(|v| (Some(v.0), Some(v.1), Some(v.2), Some(v.3)))
-        .unwrap_or((None, None, None, None));
+    let FeedFilters { search, bbox, .. } = filters;
 
-    sqlx::query_as::<_, ActivityFeedRow>(SQL_LIST_UPCOMING)
-        .bind(auth_user_id)
-        .bind(auth_user_id)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(min_lat)
-        .bind(min_lat)
-        .bind(max_lat)
-        .bind(min_lon)
-        .bind(max_lon)
+    let sql = with_geo_scope(
+        &with_feed_filters(SQL_LIST_UPCOMING),
+        SQL_GEO_SCOPE,
+        SQL_LIST_UPCOMING_PAGE,
+        bbox.is_some(),
+    );
+    let mut query = bind_feed_filters(
+        sqlx::query_as::<_, ActivityFeedRow>(&sql)
+            .bind(auth_user_id)
+            .bind(auth_user_id)
+            .bind(search)
+            .bind(search)
+            .bind(auth_user_id)
+            .bind(search),
+        &filters,
+    );
+    if let Some((min_lat, max_lat, min_lon, max_lon)) = bbox {
+        query = query
+            .bind(min_lat)
+            .bind(max_lat)
+            .bind(min_lon)
+            .bind(max_lon);
+    }
+    query
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.activity_id.as_str()))
         .bind(limit)
         .fetch_all(pool)
         .await
 }
 
 const SQL_LIST_DISCOVER: &str = r#"
+WITH params AS (
+  SELECT ? AS ranked, ? AS origin_lat, ? AS origin_lon, ? AS origin_cos_lat
+),
+-- The feed's rows with their ranking components, computed once per query
+-- and only for activities that pass the filters.
+scoped AS MATERIALIZED (
+  SELECT
+    a.rowid AS activity_rowid,
+    m.fts_rank,
+    m.fts_snippet,
+    -- Tags matching the viewer's interests, capped at three.
+    CASE WHEN p.ranked IS NOT NULL THEN
+      MIN((
+        SELECT COUNT(*)
+        FROM json_each(CASE WHEN json_valid(a.tags) THEN a.tags ELSE '[]' END) t
+        WHERE lower(trim(t.value)) IN (SELECT value FROM json_each(?))
+      ), 3) / 3.0
+    END AS rank_interests,
+    -- Squared equirectangular distance to the viewer in km² (111.32 km
+    -- per degree, longitude scaled by cos(latitude)).
+    CASE
+      WHEN p.origin_lat IS NULL OR a.latitude IS NULL OR a.longitude IS NULL THEN NULL
+      ELSE (
+        (a.latitude - p.origin_lat) * (a.latitude - p.origin_lat)
+          + ((a.longitude - p.origin_lon) * p.origin_cos_lat)
+            * ((a.longitude - p.origin_lon) * p.origin_cos_lat)
+      ) * 12392.1424
+    END AS distance_km2,
+    -- Accepted friends among the participants, capped at three.
+    CASE WHEN p.ranked IS NOT NULL THEN
+      MIN((
+        SELECT COUNT(*)
+        FROM activity_participants ap
+        JOIN friends f ON (
+          (f.friendship_id = ? || ':' || ap.user_id OR f.friendship_id = ap.user_id || ':' || ?)
+          AND f.status = 'accepted'
+          AND (f.is_deleted = 0 OR f.is_deleted IS NULL)
+        )
+        WHERE ap.activity_id = a.activity_id
+          AND ap.is_deleted = 0
+      ), 3) / 3.0
+    END AS rank_friends,
+    -- Hours counted from the start of today, so scores hold still while
+    -- paging through the feed.
+    CASE WHEN p.ranked IS NOT NULL THEN
+      1.0 / (
+        1.0 + MAX(julianday(a.scheduled_at) - julianday(date('now')), 0.0) * 24.0 / ?
+      )
+    END AS rank_soon,
+    CASE
+      WHEN p.ranked IS NULL THEN NULL
+      WHEN COALESCE(a.max_participants, 0) > 0 THEN
+        MAX(a.max_participants - COALESCE(a.current_participants_count, 0), 0) * 1.0
+          / a.max_participants
+      ELSE 0.0
+    END AS rank_capacity
+  FROM params p
+  CROSS JOIN activities a
+  LEFT JOIN (
+    SELECT
+      rowid AS fts_rowid,
+      bm25(activities_fts, 10.0, 2.0, 4.0, 4.0, 3.0, 3.0) AS fts_rank,
+      snippet(activities_fts, -1, char(2), char(3), '…', 12) AS fts_snippet
+    FROM activities_fts
+    WHERE ? IS NOT NULL
+      AND activities_fts MATCH ?
+  ) m
+    ON m.fts_rowid = a.rowid
+  WHERE a.is_deleted = 0
+    AND a.is_joined = 0
+    AND a.status = 'published'
+    AND datetime(a.scheduled_at) > datetime('now')
+    AND (? IS NULL OR m.fts_rowid IS NOT NULL)"#;
+
+const SQL_LIST_DISCOVER_PAGE: &str = r#"
+),
+ranked AS (
+  SELECT
+    d.*,
+    ? * d.rank_interests
+      + ? * d.rank_distance
+      + ? * d.rank_friends
+      + ? * d.rank_soon
+      + ? * d.rank_capacity AS rank_score
+  FROM (
+    SELECT
+      sc.*,
+      -- 1 / (1 + (d / scale)^2)
+      CASE
+        WHEN p.ranked IS NULL THEN NULL
+        WHEN sc.distance_km2 IS NULL THEN 0.0
+        ELSE 1.0 / (1.0 + sc.distance_km2 / (? * ?))
+      END AS rank_distance
+    FROM params p
+    CROSS JOIN scoped sc
+  ) d
+)
 SELECT
   a.activity_id,
+  -- Searching ranks by bm25 (negative, best first), otherwise the ranking
+  -- score goes highest first; both are offset to sort as text. Without
+  -- ranking (a date window is picked) the feed goes day by day, nearest
+  -- first within a day when the viewer has a location, else by time.
+  CASE
+    WHEN r.fts_rank IS NOT NULL THEN printf('%024.12f', 1000000.0 + r.fts_rank)
+    WHEN r.rank_score IS NOT NULL THEN printf('%024.12f', 1000000.0 - r.rank_score)
+    WHEN p.origin_lat IS NOT NULL THEN
+      date(a.scheduled_at) || ' ' || printf('%024.6f', COALESCE(r.distance_km2, 1e15))
+    ELSE datetime(a.scheduled_at)
+  END AS sort_key,
   a.title,
   a.scheduled_at,
   a.status,
+  a.rescheduled_from,
+  COALESCE(a.review_count, 0) AS review_count,
+  a.avg_rating,
+  r.fts_snippet AS search_snippet,
+  r.rank_interests,
+  r.rank_distance,
+  r.rank_friends,
+  r.rank_soon,
+  r.rank_capacity,
+  r.rank_score,
   a.is_joined,
   a.city,
   json_extract(a.location, '$.venue_name') AS venue_name,
@@ -205,53 +498,101 @@ SELECT
   ) AS participants_preview_json,
   COALESCE(s.waitlist_enabled, 1) AS waitlist_enabled,
   CASE WHEN datetime(a.scheduled_at) <= datetime('now') THEN 1 ELSE 0 END AS is_past
-FROM activities a
+FROM params p
+CROSS JOIN ranked r
+JOIN activities a
+  ON a.rowid = r.activity_rowid
 LEFT JOIN activity_settings s
   ON s.activity_id = a.activity_id
-WHERE a.is_deleted = 0
-  AND a.is_joined = 0
-  AND a.status = 'published'
-  AND datetime(a.scheduled_at) > datetime('now')
-  AND (
-    ? = ''
-    OR lower(a.title) LIKE ?
-    OR lower(COALESCE(a.city, '')) LIKE ?
-    OR lower(COALESCE(json_extract(a.location, '$.venue_name'), '')) LIKE ?
-  )
-  AND (
+WHERE (
     ? IS NULL
-    OR (
-      a.latitude BETWEEN ? AND ?
-      AND a.longitude BETWEEN ? AND ?
-    )
+    OR sort_key > ?
+    OR (sort_key = ? AND a.activity_id > ?)
   )
-ORDER BY datetime(a.scheduled_at) ASC
+ORDER BY sort_key ASC, a.activity_id ASC
 LIMIT ?
 "#;
 
+/// Tunable inputs of the Discover score; each component is in 0..=1.
+#[derive(Debug, Clone, Copy)]
+pub struct RankWeights {
+    pub interests: f64,
+    pub distance: f64,
+    pub friends: f64,
+    pub soon: f64,
+    pub capacity: f64,
+    /// Distance at which the distance component halves.
+    pub distance_scale_km: f64,
+    /// Hours until start at which the "soon" component halves.
+    pub soon_scale_hours: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoverRanking<'a> {
+    pub weights: RankWeights,
+    /// JSON array of the viewer's lowercase interest names.
+    pub interests_json: Option<&'a str>,
+}
+
+/// Ordered by `ranking` when given, otherwise day by day with the ones
+/// nearest to `origin` first; a search always orders by relevance.
 pub async fn list_discover(
     pool: &SqlitePool,
     auth_user_id: &str,
-    q_like: &str,
-    bbox: Option<(f64, f64, f64, f64)>,
+    filters: FeedFilters<'_>,
+    origin: Option<(f64, f64)>,
+    ranking: Option<DiscoverRanking<'_>>,
+    after: Option<&FeedAfter>,
     limit: i64,
 ) -> sqlx::Result<Vec<ActivityFeedRow>> {
-    let (min_lat, max_lat, min_lon, max_lon) = bbox
-        .map(|v| (Some(v.0), Some(v.1), Some(v.2), Some(v.3)))
-        .unwrap_or((None, None, None, None));
+    let FeedFilters { search, bbox, .. } = filters;
+    let weights = ranking.map(|r| r.weights);
+    let (lat, lon) = origin.unzip();
+    let cos_lat = lat.map(|l: f64| l.to_radians().cos());
+    let scale_km = weights.map(|w| w.distance_scale_km);
 
-    sqlx::query_as::<_, ActivityFeedRow>(SQL_LIST_DISCOVER)
+    let sql = with_geo_scope(
+        &with_feed_filters(SQL_LIST_DISCOVER),
+        SQL_GEO_SCOPE,
+        SQL_LIST_DISCOVER_PAGE,
+        bbox.is_some(),
+    );
+    let mut query = bind_feed_filters(
+        sqlx::query_as::<_, ActivityFeedRow>(&sql)
+            .bind(ranking.map(|_| 1_i64))
+            .bind(lat)
+            .bind(lon)
+            .bind(cos_lat)
+            .bind(ranking.and_then(|r| r.interests_json))
+            .bind(auth_user_id)
+            .bind(auth_user_id)
+            .bind(weights.map(|w| w.soon_scale_hours))
+            .bind(search)
+            .bind(search)
+            .bind(search),
+        &filters,
+    );
+    if let Some((min_lat, max_lat, min_lon, max_lon)) = bbox {
+        query = query
+            .bind(min_lat)
+            .bind(max_lat)
+            .bind(min_lon)
+            .bind(max_lon);
+    }
+    query
+        .bind(weights.map(|w| w.interests))
+        .bind(weights.map(|w| w.distance))
+        .bind(weights.map(|w| w.friends))
+        .bind(weights.map(|w| w.soon))
+        .bind(weights.map(|w| w.capacity))
+        .bind(scale_km)
+        .bind(scale_km)
         .bind(auth_user_id)
         .bind(auth_user_id)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(min_lat)
-        .bind(min_lat)
-        .bind(max_lat)
-        .bind(min_lon)
-        .bind(max_lon)
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.activity_id.as_str()))
         .bind(limit)
         .fetch_all(pool)
         .await
@@ -260,9 +601,18 @@ pub async fn list_discover(
 const SQL_LIST_HISTORY: &str = r#"
 SELECT
   a.activity_id,
+  -- Searching ranks by bm25 (negative, best first), flipped for the DESC order.
+  CASE
+    WHEN m.fts_rank IS NOT NULL THEN printf('%024.12f', 1000000.0 - m.fts_rank)
+    ELSE datetime(a.scheduled_at)
+  END AS sort_key,
   a.title,
   a.scheduled_at,
   a.status,
+  a.rescheduled_from,
+  COALESCE(a.review_count, 0) AS review_count,
+  a.avg_rating,
+  m.fts_snippet AS search_snippet,
   a.is_joined,
   a.city,
   json_extract(a.location, '$.venue_name') AS venue_name,
@@ -325,50 +675,757 @@ SELECT
 FROM activities a
 LEFT JOIN activity_settings s
   ON s.activity_id = a.activity_id
+LEFT JOIN (
+  SELECT
+    rowid AS fts_rowid,
+    bm25(activities_fts, 10.0, 2.0, 4.0, 4.0, 3.0, 3.0) AS fts_rank,
+    snippet(activities_fts, -1, char(2), char(3), '…', 12) AS fts_snippet
+  FROM activities_fts
+  WHERE ? IS NOT NULL
+    AND activities_fts MATCH ?
+) m
+  ON m.fts_rowid = a.rowid
 WHERE a.is_deleted = 0
   AND a.is_joined = 1
   AND datetime(a.scheduled_at) <= datetime('now')
-  AND (
-    ? = ''
-    OR lower(a.title) LIKE ?
-    OR lower(COALESCE(a.city, '')) LIKE ?
-    OR lower(COALESCE(json_extract(a.location, '$.venue_name'), '')) LIKE ?
-  )
+  AND (? IS NULL OR m.fts_rowid IS NOT NULL)"#;
+
+const SQL_LIST_HISTORY_PAGE: &str = r#"
   AND (
     ? IS NULL
-    OR (
-      a.latitude BETWEEN ? AND ?
-      AND a.longitude BETWEEN ? AND ?
-    )
+    OR sort_key < ?
+    OR (sort_key = ? AND a.activity_id < ?)
   )
-ORDER BY datetime(a.scheduled_at) DESC
+ORDER BY sort_key DESC, a.activity_id DESC
 LIMIT ?
 "#;
 
 pub async fn list_history(
     pool: &SqlitePool,
     auth_user_id: &str,
-    q_like: &str,
-    bbox: Option<(f64, f64, f64, f64)>,
+    filters: FeedFilters<'_>,
+    after: Option<&FeedAfter>,
     limit: i64,
 ) -> sqlx::Result<Vec<ActivityFeedRow>> {
-    let (min_lat, max_lat, min_lon, max_lon) = bbox
-        .map(|v| (Some(v.0), Some(v.1), Some(v.2), Some(v.3)))
-        .unwrap_or((None, None, None, None));
+    let FeedFilters { search, bbox, .. } = filters;
 
-    sqlx::query_as::<_, ActivityFeedRow>(SQL_LIST_HISTORY)
-        .bind(auth_user_id)
-        .bind(auth_user_id)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(q_like)
-        .bind(min_lat)
-        .bind(min_lat)
-        .bind(max_lat)
-        .bind(min_lon)
-        .bind(max_lon)
+    let sql = with_geo_scope(
+        &with_feed_filters(SQL_LIST_HISTORY),
+        SQL_GEO_SCOPE,
+        SQL_LIST_HISTORY_PAGE,
+        bbox.is_some(),
+    );
+    let mut query = bind_feed_filters(
+        sqlx::query_as::<_, ActivityFeedRow>(&sql)
+            .bind(auth_user_id)
+            .bind(auth_user_id)
+            .bind(search)
+            .bind(search)
+            .bind(search),
+        &filters,
+    );
+    if let Some((min_lat, max_lat, min_lon, max_lon)) = bbox {
+        query = query
+            .bind(min_lat)
+            .bind(max_lat)
+            .bind(min_lon)
+            .bind(max_lon);
+    }
+    query
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.sort_key.as_str()))
+        .bind(after.map(|c| c.activity_id.as_str()))
         .bind(limit)
         .fetch_all(pool)
         .await
 }
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct FeedFacetRow {
+    /// `category` or `tag`
+    pub facet: String,
+    /// Category slug or lowercase tag.
+    pub value: String,
+    pub label: String,
+    pub icon_url: Option<String>,
+    pub activity_count: i64,
+}
+
+// Each facet is counted with every filter except its own, so picking a
+// category still shows the other categories' counts: the category and tag
+// filters become `category_ok` / `tags_ok` columns between these two parts.
+const SQL_LIST_FEED_FACETS: &str = r#"
+WITH scoped AS (
+  SELECT
+    a.activity_id,
+    CASE WHEN json_valid(a.category) THEN lower(json_extract(a.category, '$.slug')) END AS category_slug,
+    CASE WHEN json_valid(a.tags) THEN a.tags ELSE '[]' END AS tags,
+    "#;
+
+// The tab (upcoming|discover|history), the viewer and the FTS query.
+const SQL_LIST_FEED_FACETS_SCOPE: &str = r#"
+  FROM activities a
+  LEFT JOIN (
+    SELECT rowid AS fts_rowid
+    FROM activities_fts
+    WHERE ? IS NOT NULL
+      AND activities_fts MATCH ?
+  ) m
+    ON m.fts_rowid = a.rowid
+  WHERE a.is_deleted = 0
+    AND CASE ?
+      WHEN 'upcoming' THEN (
+        (
+          a.is_joined = 1
+          OR EXISTS (
+            SELECT 1
+            FROM activity_invitations i
+            WHERE i.activity_id = a.activity_id
+              AND i.status = 'accepted'
+              AND (i.is_deleted = 0 OR i.is_deleted IS NULL)
+              AND (i.invitee_user_id IS NULL OR i.invitee_user_id = ?)
+          )
+        )
+        AND datetime(a.scheduled_at) > datetime('now')
+      )
+      WHEN 'history' THEN (
+        a.is_joined = 1
+        AND datetime(a.scheduled_at) <= datetime('now')
+      )
+      ELSE (
+        a.is_joined = 0
+        AND a.status = 'published'
+        AND datetime(a.scheduled_at) > datetime('now')
+      )
+    END
+    AND (? IS NULL OR m.fts_rowid IS NOT NULL)"#;
+
+const SQL_LIST_FEED_FACETS_COUNTS: &str = r#"
+)
+SELECT facet, value, label, icon_url, activity_count
+FROM (
+  SELECT
+    'category' AS facet,
+    lower(c.slug) AS value,
+    c.name AS label,
+    c.icon_url,
+    (
+      SELECT COUNT(*)
+      FROM scoped s
+      WHERE s.category_slug = lower(c.slug)
+        AND s.tags_ok
+    ) AS activity_count,
+    COALESCE(c.display_order, 0) AS sort_order
+  FROM categories c
+  WHERE c.is_active = 1
+    AND (c.is_deleted = 0 OR c.is_deleted IS NULL)
+  UNION ALL
+  SELECT *
+  FROM (
+    SELECT
+      'tag' AS facet,
+      lower(trim(t.value)) AS value,
+      lower(trim(t.value)) AS label,
+      NULL AS icon_url,
+      COUNT(DISTINCT s.activity_id) AS activity_count,
+      -COUNT(DISTINCT s.activity_id) AS sort_order
+    FROM scoped s, json_each(s.tags) t
+    WHERE s.category_ok
+      AND t.type = 'text'
+      AND trim(t.value) != ''
+    GROUP BY lower(trim(t.value))
+    ORDER BY activity_count DESC, value ASC
+    LIMIT ?
+  )
+)
+ORDER BY facet ASC, sort_order ASC, label ASC
+"#;
+
+/// Category and tag chips with counts for the feed `tab`. Only the SQL
+/// filters apply; interests and "hide full" are narrowed in the service.
+pub async fn list_feed_facets(
+    pool: &SqlitePool,
+    tab: &str,
+    auth_user_id: &str,
+    filters: FeedFilters<'_>,
+    tag_limit: i64,
+) -> sqlx::Result<Vec<FeedFacetRow>> {
+    let mut sql = String::from(SQL_LIST_FEED_FACETS);
+    sql.push_str(SQL_CATEGORY_FILTER);
+    sql.push_str(" AS category_ok,\n    ");
+    sql.push_str(SQL_TAG_FILTER);
+    sql.push_str(" AS tags_ok");
+    sql.push_str(SQL_LIST_FEED_FACETS_SCOPE);
+    sql.push_str(SQL_SCHEDULE_FILTER);
+    let sql = with_geo_scope(
+        &sql,
+        SQL_GEO_SCOPE,
+        SQL_LIST_FEED_FACETS_COUNTS,
+        filters.bbox.is_some(),
+    );
+    let mut query = bind_schedule_filter(
+        sqlx::query_as::<_, FeedFacetRow>(&sql)
+            .bind(filters.categories_json)
+            .bind(filters.categories_json)
+            .bind(filters.tags_json)
+            .bind(filters.tags_json)
+            .bind(filters.search)
+            .bind(filters.search)
+            .bind(tab)
+            .bind(auth_user_id)
+            .bind(filters.search),
+        &filters,
+    );
+    if let Some((min_lat, max_lat, min_lon, max_lon)) = filters.bbox {
+        query = query
+            .bind(min_lat)
+            .bind(max_lat)
+            .bind(min_lon)
+            .bind(max_lon);
+    }
+    query.bind(tag_limit).fetch_all(pool).await
+}
+
+/// One map marker: a single activity, or a cluster of `activity_count`
+/// activities drawn at their mean position. The detail columns are only
+/// meaningful when `activity_count` is 1.
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct MapCellRow {
+    pub activity_count: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lng: f64,
+    pub max_lng: f64,
+    pub activity_id: String,
+    pub title: String,
+    pub scheduled_at: String,
+    pub city: Option<String>,
+    pub category_name: Option<String>,
+    pub current_participants_count: i64,
+    pub max_participants: i64,
+}
+
+/// Map-only options on top of [`FeedFilters`], whose `bbox` is the viewport.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapOptions<'a> {
+    pub hide_full: bool,
+    /// JSON array of lowercase interests; one of them must be a tag.
+    pub interests_json: Option<&'a str>,
+    /// Grid cell `(lon, lat)` in degrees to cluster on; `None` returns
+    /// every activity on its own.
+    pub cell_deg: Option<(f64, f64)>,
+}
+
+// Discover's activities inside the viewport, grouped per grid cell. Cells are
+// counted from the antimeridian and south pole so the key never goes
+// negative (CAST truncates towards zero) and stays put while panning.
+// Interests and "hide full" are applied here, unlike the feed: a cluster
+// cannot be narrowed afterwards.
+const SQL_LIST_MAP_CELLS: &str = r#"
+SELECT
+  COUNT(*) AS activity_count,
+  AVG(a.latitude) AS latitude,
+  AVG(a.longitude) AS longitude,
+  MIN(a.latitude) AS min_lat,
+  MAX(a.latitude) AS max_lat,
+  MIN(a.longitude) AS min_lng,
+  MAX(a.longitude) AS max_lng,
+  MIN(a.activity_id) AS activity_id,
+  MIN(a.title) AS title,
+  MIN(a.scheduled_at) AS scheduled_at,
+  MIN(a.city) AS city,
+  MIN(CASE WHEN json_valid(a.category) THEN json_extract(a.category, '$.name') END) AS category_name,
+  MIN(a.current_participants_count) AS current_participants_count,
+  MIN(a.max_participants) AS max_participants
+FROM activities a
+LEFT JOIN (
+  SELECT rowid AS fts_rowid
+  FROM activities_fts
+  WHERE ? IS NOT NULL
+    AND activities_fts MATCH ?
+) m
+  ON m.fts_rowid = a.rowid
+WHERE a.is_deleted = 0
+  AND a.is_joined = 0
+  AND a.status = 'published'
+  AND datetime(a.scheduled_at) > datetime('now')
+  AND (? IS NULL OR m.fts_rowid IS NOT NULL)"#;
+
+// "Hide full", then the interests, matched like the tag filter.
+const SQL_LIST_MAP_CELLS_OPTIONS: &str = r#"
+  AND (? IS NULL OR a.current_participants_count < a.max_participants)
+  AND "#;
+
+const SQL_LIST_MAP_CELLS_GROUP: &str = r#"
+GROUP BY CASE
+  WHEN ? IS NULL THEN a.activity_id
+  ELSE CAST((a.longitude + 180.0) / ? AS INTEGER) || ':' || CAST((a.latitude + 90.0) / ? AS INTEGER)
+END
+ORDER BY activity_count DESC, activity_id ASC
+LIMIT ?
+"#;
+
+/// Map markers for the viewport in `filters.bbox`; nothing without one.
+pub async fn list_map_cells(
+    pool: &SqlitePool,
+    filters: FeedFilters<'_>,
+    options: MapOptions<'_>,
+    limit: i64,
+) -> sqlx::Result<Vec<MapCellRow>> {
+    let Some((min_lat, max_lat, min_lon, max_lon)) = filters.bbox else {
+        return Ok(Vec::new());
+    };
+    let (cell_lon, cell_lat) = options.cell_deg.unzip();
+
+    let mut sql = with_feed_filters(SQL_LIST_MAP_CELLS);
+    sql.push_str(SQL_LIST_MAP_CELLS_OPTIONS);
+    sql.push_str(SQL_TAG_FILTER);
+    let sql = with_geo_scope(&sql, SQL_GEO_SCOPE, SQL_LIST_MAP_CELLS_GROUP, true);
+    bind_feed_filters(
+        sqlx::query_as::<_, MapCellRow>(&sql)
+            .bind(filters.search)
+            .bind(filters.search)
+            .bind(filters.search),
+        &filters,
+    )
+    .bind(options.hide_full.then_some(1_i64))
+    .bind(options.interests_json)
+    .bind(options.interests_json)
+    .bind(min_lat)
+    .bind(max_lat)
+    .bind(min_lon)
+    .bind(max_lon)
+    .bind(cell_lon)
+    .bind(cell_lon)
+    .bind(cell_lat)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn insert_activity(pool: &SqlitePool, id: &str, days: i64, joined: bool, at: (f64, f64)) {
+        sqlx::query(
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, \
+             current_participants_count, status, is_joined, is_deleted, latitude, longitude, \
+             row_hash, changed_at) \
+             VALUES (?, ?, strftime('%Y-%m-%dT%H:%M:%S', 'now', ? || ' days'), 10, 0, \
+             'published', ?, 0, ?, ?, 'h', 't')",
+        )
+        .bind(id)
+        .bind(format!("Activiteit {}", id))
+        .bind(days)
+        .bind(joined as i64)
+        .bind(at.0)
+        .bind(at.1)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn ids(rows: Vec<ActivityFeedRow>) -> Vec<String> {
+        let mut ids: Vec<String> = rows.into_iter().map(|r| r.activity_id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn feed_queries_only_add_the_radius_with_a_bounding_box() {
+        let pool = migrations::schema_test_pool().await;
+        let utrecht = (52.09, 5.12);
+        let groningen = (53.22, 6.57);
+        insert_activity(&pool, "d-utr", 3, false, utrecht).await;
+        insert_activity(&pool, "d-gro", 3, false, groningen).await;
+        insert_activity(&pool, "u-utr", 3, true, utrecht).await;
+        insert_activity(&pool, "u-gro", 3, true, groningen).await;
+        insert_activity(&pool, "h-utr", -3, true, utrecht).await;
+        insert_activity(&pool, "h-gro", -3, true, groningen).await;
+
+        let all = FeedFilters::default();
+        let near = FeedFilters {
+            bbox: Some((51.9, 52.3, 4.8, 5.4)),
+            ..FeedFilters::default()
+        };
+        for (filters, expected) in [(all, ["gro", "utr"].as_slice()), (near, ["utr"].as_slice())] {
+            let with = |prefix: &str| -> Vec<String> {
+                expected
+                    .iter()
+                    .map(|e| format!("{}-{}", prefix, e))
+                    .collect()
+            };
+            let discover = list_discover(&pool, "me", filters, None, None, None, 10)
+                .await
+                .unwrap();
+            assert_eq!(ids(discover), with("d"));
+            let upcoming = list_upcoming(&pool, "me", filters, None, 10).await.unwrap();
+            assert_eq!(ids(upcoming), with("u"));
+            let history = list_history(&pool, "me", filters, None, 10).await.unwrap();
+            assert_eq!(ids(history), with("h"));
+        }
+
+        // Ranked, the score is only computed for the rows in the box.
+        let ranking = DiscoverRanking {
+            weights: RankWeights {
+                interests: 3.0,
+                distance: 2.0,
+                friends: 2.5,
+                soon: 1.5,
+                capacity: 1.0,
+                distance_scale_km: 10.0,
+                soon_scale_hours: 72.0,
+            },
+            interests_json: None,
+        };
+        let ranked = list_discover(&pool, "me", all, Some(utrecht), Some(ranking), None, 10)
+            .await
+            .unwrap();
+        let order: Vec<&str> = ranked.iter().map(|r| r.activity_id.as_str()).collect();
+        assert_eq!(order, ["d-utr", "d-gro"]);
+        assert!(ranked[0].rank_score > ranked[1].rank_score);
+        let ranked_near = list_discover(&pool, "me", near, Some(utrecht), Some(ranking), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(ranked_near.len(), 1);
+        assert_eq!(ranked_near[0].activity_id, "d-utr");
+        assert_eq!(ranked_near[0].rank_distance, Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn facets_count_with_every_filter_but_their_own() {
+        let pool = migrations::schema_test_pool().await;
+        let utrecht = (52.09, 5.12);
+        for (id, slug, tags, at) in [
+            ("a1", "sport", r#"["padel"]"#, utrecht),
+            ("a2", "sport", r#"["Yoga"]"#, utrecht),
+            ("a3", "Cultuur", r#"["padel"]"#, utrecht),
+            ("a4", "sport", r#"["padel"]"#, (53.22, 6.57)),
+        ] {
+            insert_activity(&pool, id, 3, false, at).await;
+            sqlx::query("UPDATE activities SET category = json_object('slug', ?), tags = ? WHERE activity_id = ?")
+                .bind(slug)
+                .bind(tags)
+                .bind(id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO categories (category_id, name, slug, display_order, row_hash, changed_at) \
+             VALUES ('c1', 'Sport', 'sport', 1, 'h', 't'), ('c2', 'Cultuur', 'cultuur', 2, 'h', 't')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let filters = FeedFilters {
+            bbox: Some((51.9, 52.3, 4.8, 5.4)),
+            categories_json: Some(r#"["sport"]"#),
+            tags_json: Some(r#"["padel"]"#),
+            ..FeedFilters::default()
+        };
+        let facets = list_feed_facets(&pool, "discover", "me", filters, 10)
+            .await
+            .unwrap();
+        let counts: Vec<(&str, &str, i64)> = facets
+            .iter()
+            .map(|f| (f.facet.as_str(), f.value.as_str(), f.activity_count))
+            .collect();
+        // Categories ignore the category filter but keep the tag filter and
+        // the radius; tags the other way round.
+        assert_eq!(
+            counts,
+            [
+                ("category", "sport", 1),
+                ("category", "cultuur", 1),
+                ("tag", "padel", 1),
+                ("tag", "yoga", 1),
+            ]
+        );
+
+        // Slugs match whatever case the snapshot has.
+        let cultuur = FeedFilters {
+            categories_json: Some(r#"["cultuur"]"#),
+            ..FeedFilters::default()
+        };
+        let rows = list_discover(&pool, "me", cultuur, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(rows), ["a3"]);
+    }
+
+    #[tokio::test]
+    async fn map_cells_group_per_grid_cell() {
+        let pool = migrations::schema_test_pool().await;
+        for (id, at) in [
+            ("a1", (52.09, 5.12)),
+            ("a2", (52.11, 5.14)),
+            ("a3", (52.37, 4.90)),
+            ("a4", (53.22, 6.57)),
+        ] {
+            insert_activity(&pool, id, 3, false, at).await;
+        }
+        sqlx::query(
+            "UPDATE activities SET current_participants_count = max_participants \
+             WHERE activity_id = 'a2'",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE activities SET tags = '[\"Padel\"]' WHERE activity_id IN ('a2', 'a3')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let filters = FeedFilters {
+            bbox: Some((51.9, 52.5, 4.7, 5.4)),
+            ..FeedFilters::default()
+        };
+        let cells = |rows: Vec<MapCellRow>| -> Vec<(i64, String)> {
+            rows.into_iter()
+                .map(|r| (r.activity_count, r.activity_id))
+                .collect()
+        };
+        let grid = MapOptions {
+            cell_deg: Some((0.5, 0.5)),
+            ..MapOptions::default()
+        };
+        let rows = list_map_cells(&pool, filters, grid, 10).await.unwrap();
+        assert_eq!((rows[0].min_lat, rows[0].max_lng), (52.09, 5.14));
+        assert_eq!(cells(rows), [(2, "a1".into()), (1, "a3".into())]);
+
+        let single = list_map_cells(&pool, filters, MapOptions::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(single.len(), 3);
+        let limited = list_map_cells(&pool, filters, grid, 1).await.unwrap();
+        assert_eq!(cells(limited), [(2, "a1".into())]);
+
+        let narrowed = MapOptions {
+            hide_full: true,
+            interests_json: Some(r#"["padel"]"#),
+            ..grid
+        };
+        let rows = list_map_cells(&pool, filters, narrowed, 10).await.unwrap();
+        assert_eq!(cells(rows), [(1, "a3".into())]);
+    }
+
+    #[tokio::test]
+    async fn date_window_and_hour_narrow_every_query_alike() {
+        let pool = migrations::schema_test_pool().await;
+        let utrecht = (52.09, 5.12);
+        for (id, days, time) in [("a1", 3, "19:00"), ("a2", 3, "09:00"), ("a3", 10, "19:00")] {
+            insert_activity(&pool, id, days, false, utrecht).await;
+            sqlx::query(
+                "UPDATE activities SET tags = '[\"padel\"]', \
+                 scheduled_at = date('now', ? || ' days') || 'T' || ? || ':00' \
+                 WHERE activity_id = ?",
+            )
+            .bind(days)
+            .bind(time)
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let (from, before): (String, String) = sqlx::query_as(
+            "SELECT datetime('now', 'start of day', '+2 days'), \
+             datetime('now', 'start of day', '+5 days')",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let filters = FeedFilters {
+            bbox: Some((51.9, 52.3, 4.8, 5.4)),
+            scheduled_from: Some(&from),
+            scheduled_before: Some(&before),
+            min_hour: Some(18),
+            ..FeedFilters::default()
+        };
+        let rows = list_discover(&pool, "me", filters, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(rows), ["a1"]);
+        let cells = list_map_cells(&pool, filters, MapOptions::default(), 10)
+            .await
+            .unwrap();
+        let cells: Vec<&str> = cells.iter().map(|c| c.activity_id.as_str()).collect();
+        assert_eq!(cells, ["a1"]);
+        let facets = list_feed_facets(&pool, "discover", "me", filters, 10)
+            .await
+            .unwrap();
+        let tags: Vec<(&str, i64)> = facets
+            .iter()
+            .map(|f| (f.value.as_str(), f.activity_count))
+            .collect();
+        assert_eq!(tags, [("padel", 1)]);
+    }
+
+    #[tokio::test]
+    async fn date_window_orders_each_day_by_distance() {
+        let pool = migrations::schema_test_pool().await;
+        let utrecht = (52.09, 5.12);
+        for (id, days, time, at) in [
+            ("far", 3, "19:00", (53.22, 6.57)),
+            ("near", 3, "10:00", utrecht),
+            ("unplaced", 3, "12:00", utrecht),
+            ("next-day", 4, "09:00", utrecht),
+        ] {
+            insert_activity(&pool, id, days, false, at).await;
+            sqlx::query(
+                "UPDATE activities SET scheduled_at = date('now', ? || ' days') || 'T' || ? || ':00' \
+                 WHERE activity_id = ?",
+            )
+            .bind(days)
+            .bind(time)
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "UPDATE activities SET latitude = NULL, longitude = NULL WHERE activity_id = 'unplaced'",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let from: String = sqlx::query_scalar("SELECT datetime('now', 'start of day', '+1 day')")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let window = FeedFilters {
+            scheduled_from: Some(&from),
+            ..FeedFilters::default()
+        };
+        let order = |rows: &[ActivityFeedRow]| -> Vec<String> {
+            rows.iter().map(|r| r.activity_id.clone()).collect()
+        };
+
+        // Day by day, nearest first within a day; no coordinates go last.
+        let first = list_discover(&pool, "me", window, Some(utrecht), None, None, 2)
+            .await
+            .unwrap();
+        assert_eq!(order(&first), ["near", "far"]);
+        let after = FeedAfter {
+            sort_key: first[1].sort_key.clone(),
+            activity_id: first[1].activity_id.clone(),
+        };
+        let rest = list_discover(&pool, "me", window, Some(utrecht), None, Some(&after), 10)
+            .await
+            .unwrap();
+        assert_eq!(order(&rest), ["unplaced", "next-day"]);
+        // The day leads the sort key, which the feed's day headers read.
+        for row in first.iter().chain(&rest) {
+            assert_eq!(row.sort_key.get(0..10), row.scheduled_at.get(0..10));
+        }
+
+        // Without a location the window stays in time order.
+        let rows = list_discover(&pool, "me", window, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(order(&rows), ["near", "unplaced", "far", "next-day"]);
+    }
+
+    #[tokio::test]
+    async fn geo_index_follows_activity_coordinates() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for sql in [
+            "CREATE TABLE users (user_id TEXT PRIMARY KEY, latitude REAL, longitude REAL)",
+            "CREATE TABLE activities (activity_id TEXT PRIMARY KEY, latitude REAL, longitude REAL)",
+            "INSERT INTO activities VALUES ('a1', 52.09, 5.12), ('a2', NULL, NULL)",
+            include_str!("../../migrations/032_activities_geo_index.sql"),
+            "INSERT INTO activities VALUES ('a3', 51.92, 4.48)",
+            "UPDATE activities SET latitude = 52.37, longitude = 4.90 WHERE activity_id = 'a2'",
+            "UPDATE activities SET latitude = NULL WHERE activity_id = 'a3'",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        let indexed: Vec<String> = sqlx::query_scalar(
+            "SELECT a.activity_id FROM activities a \
+             JOIN activities_geo_index g ON g.activity_rowid = a.rowid \
+             ORDER BY a.activity_id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(indexed, vec!["a1", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn fts_index_follows_activities_and_folds_diacritics() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for sql in [
+            "CREATE TABLE activities (activity_id TEXT PRIMARY KEY, title TEXT, description TEXT, \
+             tags TEXT, category TEXT, location TEXT, city TEXT, organizer TEXT, \
+             primary_organizer_name TEXT, is_deleted INTEGER DEFAULT 0)",
+            "INSERT INTO activities (activity_id, title, tags) VALUES ('a1', 'Koffie in Café Zuid', '[\"padel\"]')",
+            include_str!("../../migrations/031_activities_fts.sql"),
+            "INSERT INTO activities (activity_id, title, city) VALUES ('a2', 'Reünie', 'Utrecht')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        async fn search(pool: &SqlitePool, fts: &str) -> Vec<String> {
+            sqlx::query_scalar(
+                "SELECT a.activity_id FROM activities_fts f \
+                 JOIN activities a ON a.rowid = f.rowid \
+                 WHERE activities_fts MATCH ? ORDER BY a.activity_id",
+            )
+            .bind(fts)
+            .fetch_all(pool)
+            .await
+            .unwrap()
+        }
+        async fn exec(pool: &SqlitePool, sql: &str) {
+            sqlx::query(sql).execute(pool).await.unwrap();
+        }
+
+        // Backfilled and inserted rows; accents fold on both sides.
+        assert_eq!(search(&pool, "\"cafe\"*").await, ["a1"]);
+        assert_eq!(search(&pool, "\"café\"*").await, ["a1"]);
+        assert_eq!(search(&pool, "\"padel\"*").await, ["a1"]);
+        assert_eq!(search(&pool, "\"reunie\"*").await, ["a2"]);
+        assert_eq!(search(&pool, "\"utr\"*").await, ["a2"]);
+
+        exec(
+            &pool,
+            "UPDATE activities SET title = 'Thee' WHERE activity_id = 'a1'",
+        )
+        .await;
+        assert!(search(&pool, "\"cafe\"*").await.is_empty());
+        assert_eq!(search(&pool, "\"thee\"*").await, ["a1"]);
+
+        exec(
+            &pool,
+            "UPDATE activities SET is_deleted = 1 WHERE activity_id = 'a2'",
+        )
+        .await;
+        assert!(search(&pool, "\"reunie\"*").await.is_empty());
+        exec(
+            &pool,
+            "UPDATE activities SET is_deleted = 0 WHERE activity_id = 'a2'",
+        )
+        .await;
+        assert_eq!(search(&pool, "\"reunie\"*").await, ["a2"]);
+
+        exec(&pool, "DELETE FROM activities WHERE activity_id = 'a1'").await;
+        assert!(search(&pool, "\"thee\"*").await.is_empty());
+    }
+}
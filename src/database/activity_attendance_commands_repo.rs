@@ -0,0 +1,98 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_ATTENDANCE_COMMAND: &str = r#"
+INSERT INTO activity_attendance_commands (
+  id,
+  actor_user_id,
+  activity_id,
+  marks,
+  note
+) VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityAttendanceCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub activity_id: &'a str,
+    pub marks: &'a str, // JSON array of {user_id, status}
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_attendance_command(
+    pool: &SqlitePool,
+    cmd: NewActivityAttendanceCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_ATTENDANCE_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.marks)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, Default)]
+pub struct AttendanceActivityRow {
+    pub activity_id: String,
+    pub title: String,
+    pub status: String,
+    pub scheduled_at: String,
+    pub can_manage_attendance: i64,
+    pub opens_at: String,
+    pub closes_at: String,
+    /// `before` | `open` | `closed`
+    pub window_state: String,
+}
+
+// Check-in window: ?2 minutes before the start until ?4 minutes after the end
+// (start + duration, ?3 minutes when unknown).
+const SQL_LOAD_ATTENDANCE_ACTIVITY: &str = r#"
+SELECT
+  activity_id,
+  title,
+  status,
+  scheduled_at,
+  can_manage_attendance,
+  opens_at,
+  closes_at,
+  CASE
+    WHEN datetime('now') < opens_at THEN 'before'
+    WHEN datetime('now') > closes_at THEN 'closed'
+    ELSE 'open'
+  END AS window_state
+FROM (
+  SELECT
+    activity_id,
+    title,
+    COALESCE(status, 'published') AS status,
+    scheduled_at,
+    can_manage_attendance,
+    datetime(scheduled_at, printf('-%d minutes', ?2)) AS opens_at,
+    datetime(
+      scheduled_at,
+      printf('+%d minutes', COALESCE(duration_minutes, ?3) + ?4)
+    ) AS closes_at
+  FROM activities
+  WHERE activity_id = ?1
+    AND (is_deleted = 0 OR is_deleted IS NULL)
+  LIMIT 1
+)
+"#;
+
+pub async fn load_attendance_activity(
+    pool: &SqlitePool,
+    activity_id: &str,
+    opens_before_minutes: i64,
+    default_duration_minutes: i64,
+    closes_after_minutes: i64,
+) -> sqlx::Result<Option<AttendanceActivityRow>> {
+    sqlx::query_as::<_, AttendanceActivityRow>(SQL_LOAD_ATTENDANCE_ACTIVITY)
+        .bind(activity_id)
+        .bind(opens_before_minutes)
+        .bind(default_duration_minutes)
+        .bind(closes_after_minutes)
+        .fetch_optional(pool)
+        .await
+}
@@ -0,0 +1,403 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_ACTIVITY_COMMAND: &str = r#"
+INSERT INTO activity_commands (
+  id,
+  actor_user_id,
+  activity_id,
+  action,
+  title,
+  description,
+  activity_type,
+  privacy_level,
+  status,
+  scheduled_at,
+  duration_minutes,
+  max_participants,
+  language,
+  tags,
+  category,
+  location,
+  city,
+  latitude,
+  longitude,
+  main_photo_asset_id,
+  note
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub activity_id: &'a str,
+    pub action: &'a str, // create|update
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub activity_type: &'a str,
+    pub privacy_level: &'a str,
+    pub status: &'a str, // draft|published
+    pub scheduled_at: &'a str,
+    pub duration_minutes: Option<i64>,
+    pub max_participants: i64,
+    pub language: &'a str,
+    pub tags: &'a str,     // JSON array
+    pub category: &'a str, // JSON object
+    pub location: &'a str, // JSON object
+    pub city: Option<&'a str>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub main_photo_asset_id: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_activity_command(
+    pool: &SqlitePool,
+    cmd: NewActivityCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_ACTIVITY_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.action)
+        .bind(cmd.title)
+        .bind(cmd.description)
+        .bind(cmd.activity_type)
+        .bind(cmd.privacy_level)
+        .bind(cmd.status)
+        .bind(cmd.scheduled_at)
+        .bind(cmd.duration_minutes)
+        .bind(cmd.max_participants)
+        .bind(cmd.language)
+        .bind(cmd.tags)
+        .bind(cmd.category)
+        .bind(cmd.location)
+        .bind(cmd.city)
+        .bind(cmd.latitude)
+        .bind(cmd.longitude)
+        .bind(cmd.main_photo_asset_id)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct EditableActivityRow {
+    pub activity_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub activity_type: Option<String>,
+    pub privacy_level: Option<String>,
+    pub status: Option<String>,
+    pub scheduled_at: String,
+    pub duration_minutes: Option<i64>,
+    pub max_participants: i64,
+    pub current_participants_count: Option<i64>,
+    pub language: Option<String>,
+    pub city: Option<String>,
+    pub location: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub tags: Option<String>,
+    pub category: Option<String>,
+    pub main_photo_asset_id: Option<String>,
+    pub can_manage_activity: i64,
+}
+
+const SQL_LOAD_EDITABLE_ACTIVITY: &str = r#"
+SELECT
+  activity_id,
+  title,
+  description,
+  activity_type,
+  privacy_level,
+  status,
+  scheduled_at,
+  duration_minutes,
+  max_participants,
+  current_participants_count,
+  language,
+  city,
+  location,
+  latitude,
+  longitude,
+  tags,
+  category,
+  main_photo_asset_id,
+  can_manage_activity
+FROM activities
+WHERE activity_id = ?
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+LIMIT 1
+"#;
+
+pub async fn load_editable_activity(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Option<EditableActivityRow>> {
+    sqlx::query_as::<_, EditableActivityRow>(SQL_LOAD_EDITABLE_ACTIVITY)
+        .bind(activity_id)
+        .fetch_optional(pool)
+        .await
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct CategoryOptionRow {
+    pub category_id: String,
+    pub name: String,
+    pub slug: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+// Categories are only known through the activities that embed them.
+const SQL_LIST_CATEGORY_OPTIONS: &str = r#"
+SELECT
+  CAST(json_extract(category, '$.category_id') AS TEXT) AS category_id,
+  MAX(json_extract(category, '$.name')) AS name,
+  MAX(json_extract(category, '$.slug')) AS slug,
+  MAX(json_extract(category, '$.icon_url')) AS icon_url
+FROM activities
+WHERE (is_deleted = 0 OR is_deleted IS NULL)
+  AND json_valid(category)
+  AND json_extract(category, '$.category_id') IS NOT NULL
+  AND TRIM(COALESCE(json_extract(category, '$.name'), '')) != ''
+GROUP BY 1
+ORDER BY name COLLATE NOCASE ASC
+"#;
+
+pub async fn list_category_options(pool: &SqlitePool) -> sqlx::Result<Vec<CategoryOptionRow>> {
+    sqlx::query_as::<_, CategoryOptionRow>(SQL_LIST_CATEGORY_OPTIONS)
+        .fetch_all(pool)
+        .await
+}
+
+// The user's own photos plus covers of activities they organize, as image
+// ids. Photo columns may hold a full /api/v1/images/<id> URL; the id is
+// extracted the same way as in 027, and anything else is dropped.
+const SQL_LIST_COVER_OPTIONS: &str = r#"
+WITH photos AS (
+  SELECT photo_url, instr(photo_url, '/api/v1/images/') AS p, rank
+  FROM (
+    SELECT main_photo_url AS photo_url, 0 AS rank
+    FROM users
+    WHERE user_id = ?1
+    UNION ALL
+    SELECT p.value AS photo_url, 1 AS rank
+    FROM users u, json_each(CASE WHEN json_valid(u.profile_photos_extra) THEN u.profile_photos_extra ELSE '[]' END) p
+    WHERE u.user_id = ?1
+      AND p.type = 'text'
+    UNION ALL
+    SELECT main_photo_asset_id AS photo_url, 2 AS rank
+    FROM activities
+    WHERE can_manage_activity = 1
+      AND (is_deleted = 0 OR is_deleted IS NULL)
+  )
+  WHERE TRIM(COALESCE(photo_url, '')) != ''
+)
+SELECT image_id
+FROM (
+  SELECT
+    CASE
+      WHEN p > 0 THEN
+        CASE
+          WHEN instr(substr(photo_url, p + length('/api/v1/images/')), '/') > 0 THEN
+            substr(
+              substr(photo_url, p + length('/api/v1/images/')),
+              1,
+              instr(substr(photo_url, p + length('/api/v1/images/')), '/') - 1
+            )
+          ELSE substr(photo_url, p + length('/api/v1/images/'))
+        END
+      WHEN instr(photo_url, '/') = 0 THEN trim(photo_url)
+      ELSE NULL
+    END AS image_id,
+    rank
+  FROM photos
+)
+WHERE TRIM(COALESCE(image_id, '')) != ''
+GROUP BY image_id
+ORDER BY MIN(rank) ASC, image_id ASC
+LIMIT ?2
+"#;
+
+pub async fn list_cover_options(
+    pool: &SqlitePool,
+    user_id: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar::<_, String>(SQL_LIST_COVER_OPTIONS)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+const SQL_IS_FUTURE: &str = r#"
+SELECT datetime(?) > datetime('now')
+"#;
+
+/// True when the ISO timestamp lies in the future (SQLite's clock, UTC).
+pub async fn is_future(pool: &SqlitePool, timestamp: &str) -> sqlx::Result<bool> {
+    sqlx::query_scalar::<_, Option<bool>>(SQL_IS_FUTURE)
+        .bind(timestamp)
+        .fetch_one(pool)
+        .await
+        .map(|v| v.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::sp_functions::register_apply_functions;
+    use crate::services::central_apply_service::{CentralApply, StubCentralApply};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Arc;
+
+    async fn pool() -> SqlitePool {
+        let backend: Arc<dyn CentralApply> = Arc::new(StubCentralApply::accept_all());
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _meta| {
+                let backend = backend.clone();
+                Box::pin(async move { register_apply_functions(conn, backend).await })
+            })
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for sql in [
+            include_str!("../../goamet_schema.sql"),
+            include_str!("../../migrations/027_activity_commands.sql"),
+            "INSERT INTO users (user_id, name, main_photo_url, row_hash, changed_at) VALUES \
+             ('u1', 'Anna', 'https://goamet.nl/api/v1/images/img-42/thumb', 'h', 't'), \
+             ('u2', 'Bram', 'img-7', 'h', 't'), \
+             ('u3', 'Cor', 'https://cdn.example/cor.jpg', 'h', 't')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    fn command<'a>(
+        id: &'a str,
+        actor: &'a str,
+        activity: &'a str,
+        action: &'a str,
+    ) -> NewActivityCommand<'a> {
+        NewActivityCommand {
+            id,
+            actor_user_id: actor,
+            activity_id: activity,
+            action,
+            title: "Wandelen",
+            description: None,
+            activity_type: "standard",
+            privacy_level: "public",
+            status: "published",
+            scheduled_at: "2030-06-01T10:00:00",
+            duration_minutes: Some(90),
+            max_participants: 8,
+            language: "nl",
+            tags: "[]",
+            category: "{}",
+            location: "{}",
+            city: Some("Utrecht"),
+            latitude: 52.09,
+            longitude: 5.12,
+            main_photo_asset_id: None,
+            note: None,
+        }
+    }
+
+    async fn organizer_photo(pool: &SqlitePool, activity_id: &str) -> Option<String> {
+        sqlx::query_scalar(
+            "SELECT primary_organizer_photo_asset_id FROM activities WHERE activity_id = ?",
+        )
+        .bind(activity_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_stores_organizer_photo_as_image_id() {
+        let pool = pool().await;
+        for (n, actor) in ["u1", "u2", "u3"].into_iter().enumerate() {
+            let (id, activity) = (format!("c{}", n), format!("a{}", n));
+            insert_activity_command(&pool, command(&id, actor, &activity, "create"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            organizer_photo(&pool, "a0").await.as_deref(),
+            Some("img-42")
+        );
+        assert_eq!(organizer_photo(&pool, "a1").await.as_deref(), Some("img-7"));
+        assert_eq!(organizer_photo(&pool, "a2").await, None);
+
+        let organizer: (String, i64) = sqlx::query_as(
+            "SELECT role, (SELECT can_manage_activity FROM activities WHERE activity_id = 'a0') \
+             FROM activity_participants WHERE activity_id = 'a0' AND user_id = 'u1'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(organizer, ("organizer".to_string(), 1));
+    }
+
+    #[tokio::test]
+    async fn cover_options_are_image_ids() {
+        let pool = pool().await;
+        sqlx::query(
+            r#"UPDATE users SET profile_photos_extra = '["https://goamet.nl/api/v1/images/img-43", "img-42", "https://cdn.example/x.jpg", "img-44"]' WHERE user_id = 'u1'"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let mut create = command("c1", "u1", "a1", "create");
+        create.main_photo_asset_id = Some("/api/v1/images/img-50/full");
+        insert_activity_command(&pool, create).await.unwrap();
+
+        let covers = list_cover_options(&pool, "u1", 10).await.unwrap();
+        assert_eq!(covers, ["img-42", "img-43", "img-44", "img-50"]);
+        let covers = list_cover_options(&pool, "u3", 10).await.unwrap();
+        assert_eq!(covers, ["img-50"]);
+    }
+
+    #[tokio::test]
+    async fn update_only_edits_activities_the_actor_manages() {
+        let pool = pool().await;
+        insert_activity_command(&pool, command("c1", "u1", "a1", "create"))
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, \
+             can_manage_activity, row_hash, changed_at) \
+             VALUES ('other', 'Van een ander', '2030-06-01T10:00:00', 4, 0, 'h', 't')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut edit = command("c2", "u1", "a1", "update");
+        edit.title = "Fietsen";
+        edit.max_participants = 12;
+        insert_activity_command(&pool, edit).await.unwrap();
+        let mut foreign = command("c3", "u1", "other", "update");
+        foreign.title = "Overgenomen";
+        insert_activity_command(&pool, foreign).await.unwrap();
+
+        let edited = load_editable_activity(&pool, "a1").await.unwrap().unwrap();
+        assert_eq!(edited.title, "Fietsen");
+        assert_eq!(edited.max_participants, 12);
+        assert_eq!(
+            organizer_photo(&pool, "a1").await.as_deref(),
+            Some("img-42")
+        );
+        let untouched = load_editable_activity(&pool, "other")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(untouched.title, "Van een ander");
+    }
+}
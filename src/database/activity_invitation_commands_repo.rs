@@ -0,0 +1,37 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_INVITATION_COMMAND: &str = r#"
+INSERT INTO activity_invitation_commands (
+  id,
+  actor_user_id,
+  invitation_id,
+  activity_id,
+  action,
+  note
+) VALUES (?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityInvitationCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub invitation_id: &'a str,
+    pub activity_id: &'a str,
+    pub action: &'a str, // accept|decline
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_invitation_command(
+    pool: &SqlitePool,
+    cmd: NewActivityInvitationCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_INVITATION_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.invitation_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.action)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,102 @@
+use sqlx::SqlitePool;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct InvitationRow {
+    pub invitation_id: String,
+    pub activity_id: String,
+    pub activity_summary: Option<String>,
+    pub invited_by: Option<String>,
+    pub status: String,
+    pub message: Option<String>,
+    pub invited_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub is_expired: i64,
+    // Live activity row, when the snapshot has it.
+    pub activity_title: Option<String>,
+    pub activity_scheduled_at: Option<String>,
+    pub activity_city: Option<String>,
+    pub main_photo_asset_id: Option<String>,
+}
+
+const SQL_LIST_PENDING_INVITATIONS: &str = r#"
+SELECT
+  i.invitation_id,
+  i.activity_id,
+  i.activity_summary,
+  i.invited_by,
+  COALESCE(i.status, 'pending') AS status,
+  i.message,
+  i.invited_at,
+  i.expires_at,
+  CASE
+    WHEN i.expires_at IS NOT NULL AND datetime(i.expires_at) <= datetime('now') THEN 1
+    ELSE 0
+  END AS is_expired,
+  a.title AS activity_title,
+  a.scheduled_at AS activity_scheduled_at,
+  a.city AS activity_city,
+  a.main_photo_asset_id
+FROM activity_invitations i
+LEFT JOIN activities a
+  ON a.activity_id = i.activity_id
+  AND a.is_deleted = 0
+WHERE (i.is_deleted = 0 OR i.is_deleted IS NULL)
+  AND (i.invitee_user_id IS NULL OR i.invitee_user_id = ?1)
+  AND COALESCE(i.status, 'pending') = 'pending'
+  AND (i.expires_at IS NULL OR datetime(i.expires_at) > datetime('now'))
+ORDER BY datetime(COALESCE(i.invited_at, i.changed_at)) DESC
+LIMIT ?2
+"#;
+
+const SQL_LOAD_INVITATION: &str = r#"
+SELECT
+  i.invitation_id,
+  i.activity_id,
+  i.activity_summary,
+  i.invited_by,
+  COALESCE(i.status, 'pending') AS status,
+  i.message,
+  i.invited_at,
+  i.expires_at,
+  CASE
+    WHEN i.expires_at IS NOT NULL AND datetime(i.expires_at) <= datetime('now') THEN 1
+    ELSE 0
+  END AS is_expired,
+  a.title AS activity_title,
+  a.scheduled_at AS activity_scheduled_at,
+  a.city AS activity_city,
+  a.main_photo_asset_id
+FROM activity_invitations i
+LEFT JOIN activities a
+  ON a.activity_id = i.activity_id
+  AND a.is_deleted = 0
+WHERE i.invitation_id = ?1
+  AND (i.is_deleted = 0 OR i.is_deleted IS NULL)
+  AND (i.invitee_user_id IS NULL OR i.invitee_user_id = ?2)
+LIMIT 1
+"#;
+
+/// Pending, unexpired invitations addressed to `user_id`, newest first.
+pub async fn list_pending_invitations(
+    pool: &SqlitePool,
+    user_id: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<InvitationRow>> {
+    sqlx::query_as::<_, InvitationRow>(SQL_LIST_PENDING_INVITATIONS)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn load_invitation_for_user(
+    pool: &SqlitePool,
+    invitation_id: &str,
+    user_id: &str,
+) -> sqlx::Result<Option<InvitationRow>> {
+    sqlx::query_as::<_, InvitationRow>(SQL_LOAD_INVITATION)
+        .bind(invitation_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+}
@@ -0,0 +1,335 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_INVITE_COMMAND: &str = r#"
+INSERT INTO activity_invite_commands (
+  id,
+  actor_user_id,
+  activity_id,
+  invitee_user_id,
+  message,
+  note
+) VALUES (?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityInviteCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub activity_id: &'a str,
+    pub invitee_user_id: &'a str,
+    pub message: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_invite_command(
+    pool: &SqlitePool,
+    cmd: NewActivityInviteCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_INVITE_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.invitee_user_id)
+        .bind(cmd.message)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct SentInviteRow {
+    pub invitee_user_id: String,
+    pub name: Option<String>,
+    pub photo_url: Option<String>,
+    pub message: Option<String>,
+    pub invited_at: String,
+    pub status: String,
+}
+
+// Latest invite per invitee. Participation wins over the invitation snapshot,
+// which in turn wins over "pending" (no response synced yet).
+const SQL_LIST_SENT_INVITES: &str = r#"
+SELECT
+  c.invitee_user_id,
+  COALESCE(NULLIF(TRIM(u.name), ''), json_extract(f.friend, '$.name')) AS name,
+  COALESCE(NULLIF(u.main_photo_url, ''), json_extract(f.friend, '$.photo_url')) AS photo_url,
+  c.message,
+  c.created_at AS invited_at,
+  CASE
+    WHEN ap.participation_status = 'waitlisted' THEN 'waitlisted'
+    WHEN ap.participation_status IS NOT NULL THEN 'accepted'
+    ELSE COALESCE(i.status, 'pending')
+  END AS status
+FROM activity_invite_commands c
+LEFT JOIN users u
+  ON u.user_id = c.invitee_user_id
+LEFT JOIN friends f ON (
+  (f.friendship_id = c.actor_user_id || ':' || c.invitee_user_id
+    OR f.friendship_id = c.invitee_user_id || ':' || c.actor_user_id
+    OR json_extract(f.friend, '$.user_id') = c.invitee_user_id)
+  AND (f.is_deleted = 0 OR f.is_deleted IS NULL)
+)
+LEFT JOIN activity_invitations i
+  ON i.activity_id = c.activity_id
+  AND i.invitee_user_id = c.invitee_user_id
+  AND (i.is_deleted = 0 OR i.is_deleted IS NULL)
+LEFT JOIN activity_participants ap
+  ON ap.activity_id = c.activity_id
+  AND ap.user_id = c.invitee_user_id
+  AND ap.is_deleted = 0
+WHERE c.activity_id = ?1
+  AND c.created_at = (
+    SELECT MAX(c2.created_at)
+    FROM activity_invite_commands c2
+    WHERE c2.activity_id = c.activity_id
+      AND c2.invitee_user_id = c.invitee_user_id
+  )
+GROUP BY c.invitee_user_id
+ORDER BY datetime(c.created_at) DESC
+"#;
+
+pub async fn list_sent_invites(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Vec<SentInviteRow>> {
+    sqlx::query_as::<_, SentInviteRow>(SQL_LIST_SENT_INVITES)
+        .bind(activity_id)
+        .fetch_all(pool)
+        .await
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct InviteCandidateRow {
+    pub user_id: String,
+    pub name: Option<String>,
+    pub photo_url: Option<String>,
+    pub is_friend: i64,
+}
+
+// Accepted friends first, then (when allowed and searching) discovered users.
+// Existing participants, blocked users and the actor are left out.
+const SQL_LIST_INVITE_CANDIDATES: &str = r#"
+SELECT
+  user_id,
+  MAX(name) AS name,
+  MAX(photo_url) AS photo_url,
+  MAX(is_friend) AS is_friend
+FROM (
+  SELECT
+    json_extract(f.friend, '$.user_id') AS user_id,
+    COALESCE(NULLIF(TRIM(json_extract(f.friend, '$.name')), ''), u.name) AS name,
+    COALESCE(NULLIF(json_extract(f.friend, '$.photo_url'), ''), u.main_photo_url) AS photo_url,
+    1 AS is_friend
+  FROM friends f
+  LEFT JOIN users u
+    ON u.user_id = json_extract(f.friend, '$.user_id')
+  WHERE f.status = 'accepted'
+    AND (f.is_deleted = 0 OR f.is_deleted IS NULL)
+
+  UNION ALL
+
+  SELECT
+    u.user_id,
+    u.name,
+    u.main_photo_url AS photo_url,
+    0 AS is_friend
+  FROM users u
+  WHERE (u.is_deleted = 0 OR u.is_deleted IS NULL)
+    AND ?3 = 0
+    AND ?2 != ''
+)
+WHERE user_id IS NOT NULL
+  AND user_id != ?1
+  AND (?2 = '' OR lower(COALESCE(name, '')) LIKE ?2)
+  AND user_id NOT IN (
+    SELECT ap.user_id
+    FROM activity_participants ap
+    WHERE ap.activity_id = ?4
+      AND ap.is_deleted = 0
+  )
+  AND user_id NOT IN (
+    SELECT b.blocked_user_id
+    FROM blocks b
+    WHERE b.is_deleted = 0 OR b.is_deleted IS NULL
+  )
+GROUP BY user_id
+ORDER BY MAX(is_friend) DESC, lower(COALESCE(MAX(name), '')) ASC
+LIMIT ?5
+"#;
+
+pub async fn list_invite_candidates(
+    pool: &SqlitePool,
+    auth_user_id: &str,
+    q_like: &str,
+    friends_only: bool,
+    activity_id: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<InviteCandidateRow>> {
+    sqlx::query_as::<_, InviteCandidateRow>(SQL_LIST_INVITE_CANDIDATES)
+        .bind(auth_user_id)
+        .bind(q_like)
+        .bind(friends_only as i64)
+        .bind(activity_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+const SQL_IS_ACCEPTED_FRIEND: &str = r#"
+SELECT COUNT(*)
+FROM friends f
+WHERE (
+    f.friendship_id = ?1 || ':' || ?2
+    OR f.friendship_id = ?2 || ':' || ?1
+    OR json_extract(f.friend, '$.user_id') = ?2
+  )
+  AND f.status = 'accepted'
+  AND (f.is_deleted = 0 OR f.is_deleted IS NULL)
+"#;
+
+pub async fn is_accepted_friend(
+    pool: &SqlitePool,
+    user_id: &str,
+    other_user_id: &str,
+) -> sqlx::Result<bool> {
+    let n: i64 = sqlx::query_scalar(SQL_IS_ACCEPTED_FRIEND)
+        .bind(user_id)
+        .bind(other_user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(n > 0)
+}
+
+const SQL_IS_PARTICIPANT: &str = r#"
+SELECT COUNT(*)
+FROM activity_participants
+WHERE activity_id = ?1
+  AND user_id = ?2
+  AND is_deleted = 0
+"#;
+
+pub async fn is_participant(
+    pool: &SqlitePool,
+    activity_id: &str,
+    user_id: &str,
+) -> sqlx::Result<bool> {
+    let n: i64 = sqlx::query_scalar(SQL_IS_PARTICIPANT)
+        .bind(activity_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+
+    async fn pool() -> SqlitePool {
+        let pool = migrations::schema_test_pool().await;
+        for sql in [
+            "INSERT INTO users (user_id, name, row_hash, changed_at) VALUES \
+             ('me', 'Mira', 'h', 't'), ('anna', 'Anna', 'h', 't'), ('bram', 'Bram', 'h', 't'), \
+             ('cor', 'Cor', 'h', 't'), ('dana', 'Dana', 'h', 't'), ('eva', 'Eva', 'h', 't'), \
+             ('finn', 'Finn', 'h', 't')",
+            "INSERT INTO friends (friendship_id, friend, status, row_hash, changed_at) VALUES \
+             ('me:anna', json_object('user_id', 'anna', 'name', 'Anna'), 'accepted', 'h', 't'), \
+             ('me:bram', json_object('user_id', 'bram', 'name', 'Bram'), 'pending', 'h', 't'), \
+             ('me:cor', json_object('user_id', 'cor', 'name', 'Cor'), 'blocked', 'h', 't'), \
+             ('me:dana', json_object('user_id', 'dana', 'name', 'Dana'), 'accepted', 'h', 't')",
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, \
+             row_hash, changed_at) VALUES ('a1', 'Padel', '2031-05-03T10:30:00', 4, 'h', 't'), \
+             ('a2', 'Yoga', '2031-05-03T10:30:00', 4, 'h', 't')",
+            "INSERT INTO blocks (block_id, blocked_user_id, row_hash, changed_at) \
+             VALUES ('b1', 'eva', 'h', 't')",
+            "INSERT INTO activity_participants (activity_id, user_id, role, participation_status) \
+             VALUES ('a1', 'dana', 'member', 'registered')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    async fn candidates(pool: &SqlitePool, q_like: &str, friends_only: bool) -> Vec<String> {
+        list_invite_candidates(pool, "me", q_like, friends_only, "a1", 20)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.user_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn candidates_are_accepted_friends_and_unblocked_users() {
+        let pool = pool().await;
+        // Dana is an accepted friend but already takes part.
+        assert_eq!(candidates(&pool, "", false).await, ["anna"]);
+        assert_eq!(candidates(&pool, "%", true).await, ["anna"]);
+        // Searching adds everyone else, except blocked users and the actor.
+        assert_eq!(
+            candidates(&pool, "%", false).await,
+            ["anna", "bram", "cor", "finn"]
+        );
+
+        assert!(is_accepted_friend(&pool, "me", "anna").await.unwrap());
+        assert!(!is_accepted_friend(&pool, "me", "bram").await.unwrap());
+        assert!(!is_accepted_friend(&pool, "me", "cor").await.unwrap());
+        assert!(is_participant(&pool, "a1", "dana").await.unwrap());
+        assert!(!is_participant(&pool, "a2", "dana").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn sent_invites_show_the_latest_invite_of_this_activity() {
+        let pool = pool().await;
+        for (id, activity, invitee, at) in [
+            ("c1", "a1", "anna", "2030-01-01 10:00:00"),
+            ("c2", "a1", "anna", "2030-01-02 10:00:00"),
+            ("c3", "a1", "dana", "2030-01-01 11:00:00"),
+            ("c4", "a1", "finn", "2030-01-01 12:00:00"),
+            ("c5", "a2", "bram", "2030-01-03 10:00:00"),
+        ] {
+            insert_invite_command(
+                &pool,
+                NewActivityInviteCommand {
+                    id,
+                    actor_user_id: "me",
+                    activity_id: activity,
+                    invitee_user_id: invitee,
+                    message: Some(id),
+                    note: None,
+                },
+            )
+            .await
+            .unwrap();
+            sqlx::query("UPDATE activity_invite_commands SET created_at = ? WHERE id = ?")
+                .bind(at)
+                .bind(id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO activity_invitations (invitation_id, activity_id, invitee_user_id, \
+             status, row_hash, changed_at) VALUES ('i1', 'a1', 'finn', 'declined', 'h', 't')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let sent: Vec<(String, Option<String>, String)> = list_sent_invites(&pool, "a1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.invitee_user_id, r.message, r.status))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                ("anna".into(), Some("c2".into()), "pending".into()),
+                ("finn".into(), Some("c4".into()), "declined".into()),
+                ("dana".into(), Some("c3".into()), "accepted".into()),
+            ]
+        );
+    }
+}
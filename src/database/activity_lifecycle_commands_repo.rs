@@ -0,0 +1,96 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_LIFECYCLE_COMMAND: &str = r#"
+INSERT INTO activity_lifecycle_commands (
+  id,
+  actor_user_id,
+  activity_id,
+  action,
+  reason,
+  scheduled_at,
+  announcement,
+  note
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityLifecycleCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub activity_id: &'a str,
+    pub action: &'a str, // cancel|reschedule|complete
+    pub reason: Option<&'a str>,
+    pub scheduled_at: Option<&'a str>,
+    pub announcement: &'a str,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_lifecycle_command(
+    pool: &SqlitePool,
+    cmd: NewActivityLifecycleCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_LIFECYCLE_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.action)
+        .bind(cmd.reason)
+        .bind(cmd.scheduled_at)
+        .bind(cmd.announcement)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, Default)]
+pub struct LifecycleActivityRow {
+    pub activity_id: String,
+    pub title: String,
+    pub status: String,
+    pub scheduled_at: String,
+    pub can_manage_activity: i64,
+    pub has_started: i64,
+}
+
+const SQL_LOAD_LIFECYCLE_ACTIVITY: &str = r#"
+SELECT
+  activity_id,
+  title,
+  COALESCE(status, 'published') AS status,
+  scheduled_at,
+  can_manage_activity,
+  CASE WHEN datetime(scheduled_at) <= datetime('now') THEN 1 ELSE 0 END AS has_started
+FROM activities
+WHERE activity_id = ?
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+LIMIT 1
+"#;
+
+pub async fn load_lifecycle_activity(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Option<LifecycleActivityRow>> {
+    sqlx::query_as::<_, LifecycleActivityRow>(SQL_LOAD_LIFECYCLE_ACTIVITY)
+        .bind(activity_id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Everyone who should hear about it: registered and waitlisted participants.
+const SQL_LIST_PARTICIPANT_USER_IDS: &str = r#"
+SELECT DISTINCT user_id
+FROM activity_participants
+WHERE activity_id = ?
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+  AND COALESCE(participation_status, 'registered') IN ('registered', 'waitlisted')
+"#;
+
+pub async fn list_participant_user_ids(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar::<_, String>(SQL_LIST_PARTICIPANT_USER_IDS)
+        .bind(activity_id)
+        .fetch_all(pool)
+        .await
+}
@@ -0,0 +1,146 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_REVIEW_COMMAND: &str = r#"
+INSERT INTO activity_review_commands (
+  id,
+  actor_user_id,
+  activity_id,
+  review_id,
+  action,
+  rating,
+  review_text,
+  note
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewActivityReviewCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub activity_id: &'a str,
+    pub review_id: &'a str,
+    pub action: &'a str, // upsert|helpful
+    pub rating: Option<i64>,
+    pub review_text: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_review_command(
+    pool: &SqlitePool,
+    cmd: NewActivityReviewCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_REVIEW_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.activity_id)
+        .bind(cmd.review_id)
+        .bind(cmd.action)
+        .bind(cmd.rating)
+        .bind(cmd.review_text)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, Default)]
+pub struct ReviewActivityRow {
+    pub activity_id: String,
+    pub status: String,
+    pub has_attended: i64,
+    pub review_count: i64,
+    pub avg_rating: Option<f64>,
+}
+
+// ?2 attended when either the snapshot flag or their participant row says so.
+const SQL_LOAD_REVIEW_ACTIVITY: &str = r#"
+SELECT
+  a.activity_id,
+  COALESCE(a.status, 'published') AS status,
+  CASE
+    WHEN a.my_attendance_status = 'attended' THEN 1
+    WHEN EXISTS (
+      SELECT 1
+      FROM activity_participants p
+      WHERE p.activity_id = a.activity_id
+        AND p.user_id = ?2
+        AND p.attendance_status = 'attended'
+        AND (p.is_deleted = 0 OR p.is_deleted IS NULL)
+    ) THEN 1
+    ELSE 0
+  END AS has_attended,
+  COALESCE(a.review_count, 0) AS review_count,
+  a.avg_rating
+FROM activities a
+WHERE a.activity_id = ?1
+  AND (a.is_deleted = 0 OR a.is_deleted IS NULL)
+LIMIT 1
+"#;
+
+pub async fn load_review_activity(
+    pool: &SqlitePool,
+    activity_id: &str,
+    user_id: &str,
+) -> sqlx::Result<Option<ReviewActivityRow>> {
+    sqlx::query_as::<_, ReviewActivityRow>(SQL_LOAD_REVIEW_ACTIVITY)
+        .bind(activity_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ActivityReviewRow {
+    pub review_id: String,
+    pub reviewer_user_id: Option<String>,
+    pub reviewer_name: Option<String>,
+    pub reviewer_photo_url: Option<String>,
+    pub rating: i64,
+    pub review_text: Option<String>,
+    pub helpful_count: i64,
+    pub created_at: String,
+    pub is_mine: i64,
+    pub has_voted: i64,
+}
+
+// ?2 is the viewer: their own review first, then most helpful, then newest.
+const SQL_LIST_ACTIVITY_REVIEWS: &str = r#"
+SELECT
+  r.review_id,
+  json_extract(r.reviewer, '$.user_id') AS reviewer_user_id,
+  json_extract(r.reviewer, '$.name') AS reviewer_name,
+  json_extract(r.reviewer, '$.photo_url') AS reviewer_photo_url,
+  r.rating,
+  r.review_text,
+  COALESCE(r.helpful_count, 0) AS helpful_count,
+  COALESCE(r.updated_at, r.created_at) AS created_at,
+  CASE
+    WHEN json_extract(r.reviewer, '$.user_id') = ?2 THEN 1
+    ELSE 0
+  END AS is_mine,
+  CASE
+    WHEN EXISTS (
+      SELECT 1
+      FROM activity_review_commands c
+      WHERE c.review_id = r.review_id
+        AND c.actor_user_id = ?2
+        AND c.action = 'helpful'
+    ) THEN 1
+    ELSE 0
+  END AS has_voted
+FROM activity_reviews r
+WHERE r.activity_id = ?1
+  AND (r.is_deleted = 0 OR r.is_deleted IS NULL)
+ORDER BY is_mine DESC, helpful_count DESC, COALESCE(r.updated_at, r.created_at) DESC
+"#;
+
+pub async fn list_activity_reviews(
+    pool: &SqlitePool,
+    activity_id: &str,
+    user_id: &str,
+) -> sqlx::Result<Vec<ActivityReviewRow>> {
+    sqlx::query_as::<_, ActivityReviewRow>(SQL_LIST_ACTIVITY_REVIEWS)
+        .bind(activity_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+}
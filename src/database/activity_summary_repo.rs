@@ -13,6 +13,8 @@ pub struct ActivitySummaryRow {
     pub status: String,
     pub is_joined: i64,
     pub am_on_waitlist: i64,
+    pub can_manage_activity: i64,
+    pub can_manage_attendance: i64,
     pub city: Option<String>,
     pub location: Option<String>,
     pub organizer: String,
@@ -20,6 +22,9 @@ pub struct ActivitySummaryRow {
     pub category: Option<String>,
     pub main_photo_asset_id: Option<String>,
     pub waitlist_enabled: i64,
+    pub cancellation_reason: Option<String>,
+    pub rescheduled_from: Option<String>,
+    pub has_started: i64,
 }
 
 const SQL_LOAD_ACTIVITY_SUMMARY: &str = r#"
@@ -35,13 +40,18 @@ SELECT
   a.status,
   a.is_joined,
   a.am_on_waitlist,
+  a.can_manage_activity,
+  a.can_manage_attendance,
   a.city,
   a.location,
   a.organizer,
   a.tags,
   a.category,
   a.main_photo_asset_id,
-  COALESCE(s.waitlist_enabled, 1) AS waitlist_enabled
+  COALESCE(s.waitlist_enabled, 1) AS waitlist_enabled,
+  a.cancellation_reason,
+  a.rescheduled_from,
+  CASE WHEN datetime(a.scheduled_at) <= datetime('now') THEN 1 ELSE 0 END AS has_started
 FROM activities a
 LEFT JOIN activity_settings s
   ON s.activity_id = a.activity_id
@@ -76,7 +76,12 @@ pub async fn load_discovery_candidates(
     args.add(auth_user_id);
 
     if let Some((min_lat, max_lat, min_lon, max_lon)) = bbox {
-        sql.push_str(" AND latitude BETWEEN ? AND ? AND longitude BETWEEN ? AND ?");
+        // Overlap test rather than containment: the R-tree stores
+        // coordinates as 32-bit floats rounded outwards.
+        sql.push_str(
+            " AND u.rowid IN (SELECT g.user_rowid FROM users_geo_index g \
+             WHERE g.max_lat >= ? AND g.min_lat <= ? AND g.max_lng >= ? AND g.min_lng <= ?)",
+        );
         args.add(min_lat);
         args.add(max_lat);
         args.add(min_lon);
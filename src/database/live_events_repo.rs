@@ -0,0 +1,150 @@
+use sqlx::SqlitePool;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct NewNotificationRow {
+    /// `None` when neither the row nor `current_user` names a recipient.
+    pub recipient_user_id: Option<String>,
+    pub notification_id: String,
+    pub notification_type: String,
+    pub title: String,
+    pub message: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct ConversationUnreadRow {
+    pub conversation_id: String,
+    pub unread_count: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct RecipientUnreadRow {
+    pub recipient_user_id: String,
+    pub unread_count: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct ActivityCapacityRow {
+    pub activity_id: String,
+    pub current_participants_count: i64,
+    pub max_participants: i64,
+    pub waitlist_count: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ActivityCapacityChangeRow {
+    #[sqlx(flatten)]
+    pub capacity: ActivityCapacityRow,
+    /// False once deleted, cancelled or completed.
+    pub is_live: bool,
+    /// `changed_at` as `YYYY-MM-DD HH:MM:SS`.
+    pub changed_at: Option<String>,
+}
+
+const SQL_LATEST_NOTIFICATION_AT: &str = r#"
+SELECT MAX(created_at)
+FROM notifications
+WHERE (is_deleted = 0 OR is_deleted IS NULL)
+"#;
+
+// Unread, non-expired notifications that arrived after the watermark. Rows
+// without a recipient belong to the snapshot owner.
+const SQL_NOTIFICATIONS_SINCE: &str = r#"
+SELECT
+  COALESCE(recipient_user_id, (SELECT user_id FROM current_user LIMIT 1)) AS recipient_user_id,
+  notification_id,
+  notification_type,
+  title,
+  message,
+  created_at
+FROM notifications
+WHERE (is_deleted = 0 OR is_deleted IS NULL)
+  AND COALESCE(status, 'unread') = 'unread'
+  AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'))
+  AND created_at > ?1
+ORDER BY created_at ASC
+LIMIT ?2
+"#;
+
+// Same rows as v_unread_notification_count, per recipient.
+const SQL_UNREAD_PER_RECIPIENT: &str = r#"
+SELECT recipient_user_id, COUNT(*) AS unread_count
+FROM (
+  SELECT COALESCE(recipient_user_id, (SELECT user_id FROM current_user LIMIT 1)) AS recipient_user_id
+  FROM notifications
+  WHERE status = 'unread'
+    AND (is_deleted = 0 OR is_deleted IS NULL)
+    AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'))
+)
+WHERE recipient_user_id IS NOT NULL
+GROUP BY recipient_user_id
+"#;
+
+const SQL_CONVERSATION_UNREAD: &str = r#"
+SELECT
+  conversation_id,
+  COALESCE(unread_count, 0) AS unread_count
+FROM conversations
+WHERE (is_deleted = 0 OR is_deleted IS NULL)
+"#;
+
+// Activities changed since the watermark (all of them without one). Sync
+// writes ISO timestamps (`...T...Z`) and local triggers `datetime('now')`;
+// against a `YYYY-MM-DD HH:MM:SS` watermark the text range over the
+// changed_at index never misses either form, the datetime() check trims the
+// rest of that day's ISO rows.
+const SQL_ACTIVITY_CAPACITY_SINCE: &str = r#"
+SELECT
+  activity_id,
+  COALESCE(current_participants_count, 0) AS current_participants_count,
+  COALESCE(max_participants, 0) AS max_participants,
+  COALESCE(waitlist_count, 0) AS waitlist_count,
+  (is_deleted = 0 OR is_deleted IS NULL)
+    AND COALESCE(status, 'published') NOT IN ('cancelled', 'completed') AS is_live,
+  datetime(changed_at) AS changed_at
+FROM activities
+WHERE changed_at >= COALESCE(?1, '')
+  AND (?1 IS NULL OR datetime(changed_at) >= ?1)
+"#;
+
+pub async fn latest_notification_at(pool: &SqlitePool) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar(SQL_LATEST_NOTIFICATION_AT)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_notifications_since(
+    pool: &SqlitePool,
+    since: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<NewNotificationRow>> {
+    sqlx::query_as::<_, NewNotificationRow>(SQL_NOTIFICATIONS_SINCE)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn list_unread_per_recipient(pool: &SqlitePool) -> sqlx::Result<Vec<RecipientUnreadRow>> {
+    sqlx::query_as::<_, RecipientUnreadRow>(SQL_UNREAD_PER_RECIPIENT)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn list_conversation_unread(
+    pool: &SqlitePool,
+) -> sqlx::Result<Vec<ConversationUnreadRow>> {
+    sqlx::query_as::<_, ConversationUnreadRow>(SQL_CONVERSATION_UNREAD)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn list_activity_capacity_since(
+    pool: &SqlitePool,
+    watermark: Option<&str>,
+) -> sqlx::Result<Vec<ActivityCapacityChangeRow>> {
+    sqlx::query_as::<_, ActivityCapacityChangeRow>(SQL_ACTIVITY_CAPACITY_SINCE)
+        .bind(watermark)
+        .fetch_all(pool)
+        .await
+}
@@ -0,0 +1,341 @@
+//! Embedded runner for `migrations/`, tracked in `_sqlx_migrations`.
+//!
+//! Uses the same table and sha384 checksums as `sqlx migrate` and
+//! `scripts/sqlx-baseline.sh`, so existing baselined databases are picked up
+//! as-is. The script only records 001–021, which `goamet_schema.sql` already
+//! contains; everything after that runs here. An applied migration whose file
+//! has changed since is treated as drift and stops startup.
+
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::SqlitePool;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Versions already in `goamet_schema.sql` that fail when run on top of it;
+/// the other old ones are idempotent. For databases seeded from the schema
+/// file, like the tests and `bench_geo`.
+pub const FOLDED_INTO_SCHEMA: [i64; 4] = [2, 3, 8, 9];
+
+const SQL_HAS_MIGRATIONS_TABLE: &str = r#"
+SELECT COUNT(*)
+FROM sqlite_master
+WHERE type = 'table'
+  AND name = '_sqlx_migrations'
+"#;
+
+const SQL_LIST_APPLIED_MIGRATIONS: &str = r#"
+SELECT version, checksum, success
+FROM _sqlx_migrations
+ORDER BY version
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// Apply pending migrations, then continue starting up.
+    Apply,
+    /// Apply pending migrations and exit (`--migrate-only`).
+    MigrateOnly,
+    /// Print the pending plan without touching the database (`--dry-run`).
+    DryRun,
+}
+
+impl MigrationMode {
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Self {
+        let mut mode = MigrationMode::Apply;
+        for arg in args {
+            match arg.as_str() {
+                "--dry-run" => return MigrationMode::DryRun,
+                "--migrate-only" => mode = MigrationMode::MigrateOnly,
+                _ => {}
+            }
+        }
+        mode
+    }
+
+    /// True when the binary should stop after the migration step.
+    pub fn exits_after_migrations(&self) -> bool {
+        *self != MigrationMode::Apply
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationPlan {
+    pub applied: usize,
+    pub pending: Vec<PendingMigration>,
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// An applied migration no longer matches its file.
+    Drift {
+        version: i64,
+        description: String,
+    },
+    /// A previous run failed halfway through this version.
+    Dirty(i64),
+    /// The database has a version this binary does not know about.
+    Unknown(i64),
+    Database(sqlx::Error),
+    Migrate(MigrateError),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Drift {
+                version,
+                description,
+            } => write!(
+                f,
+                "migration {} ({}) was applied but the file has changed since",
+                version, description
+            ),
+            MigrationError::Dirty(v) => write!(f, "migration {} is partially applied", v),
+            MigrationError::Unknown(v) => write!(
+                f,
+                "database has migration {} which is not in migrations/ (newer binary?)",
+                v
+            ),
+            MigrationError::Database(e) => write!(f, "{}", e),
+            MigrationError::Migrate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+/// Compares the migration source with `_sqlx_migrations` without writing.
+pub async fn plan(pool: &SqlitePool, migrator: &Migrator) -> Result<MigrationPlan, MigrationError> {
+    let has_table: i64 = sqlx::query_scalar(SQL_HAS_MIGRATIONS_TABLE)
+        .fetch_one(pool)
+        .await?;
+    let applied: Vec<(i64, Vec<u8>, bool)> = if has_table > 0 {
+        sqlx::query_as(SQL_LIST_APPLIED_MIGRATIONS)
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    if let Some((version, _, _)) = applied.iter().find(|(_, _, success)| !success) {
+        return Err(MigrationError::Dirty(*version));
+    }
+
+    for (version, checksum, _) in &applied {
+        let Some(m) = migrator.iter().find(|m| m.version == *version) else {
+            return Err(MigrationError::Unknown(*version));
+        };
+        if m.checksum.as_ref() != checksum.as_slice() {
+            return Err(MigrationError::Drift {
+                version: *version,
+                description: m.description.to_string(),
+            });
+        }
+    }
+
+    let pending = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| !applied.iter().any(|(v, _, _)| *v == m.version))
+        .map(|m| PendingMigration {
+            version: m.version,
+            description: m.description.to_string(),
+        })
+        .collect();
+
+    Ok(MigrationPlan {
+        applied: applied.len(),
+        pending,
+    })
+}
+
+/// Validates and (unless dry-run) applies pending migrations.
+/// Returns the plan as it was before applying.
+pub async fn run(
+    pool: &SqlitePool,
+    migrator: &Migrator,
+    mode: MigrationMode,
+) -> Result<MigrationPlan, MigrationError> {
+    let plan = plan(pool, migrator).await?;
+    if mode == MigrationMode::DryRun || plan.pending.is_empty() {
+        return Ok(plan);
+    }
+    migrator.run(pool).await.map_err(MigrationError::Migrate)?;
+    Ok(plan)
+}
+
+/// Startup entry point shared by the server and the maintenance binaries.
+pub async fn apply_on_startup(
+    pool: &SqlitePool,
+    mode: MigrationMode,
+) -> Result<MigrationPlan, MigrationError> {
+    let result = run(pool, &MIGRATOR, mode).await;
+    match &result {
+        Ok(plan) if plan.pending.is_empty() => {
+            println!("🗄️  Migraties: up-to-date ({} toegepast)", plan.applied);
+        }
+        Ok(plan) => {
+            let verb = if mode == MigrationMode::DryRun {
+                "openstaand (dry-run, niets toegepast)"
+            } else {
+                "toegepast"
+            };
+            println!("🗄️  Migraties {}:", verb);
+            for m in &plan.pending {
+                println!("   {:03} {}", m.version, m.description);
+            }
+        }
+        Err(e) => {
+            eprintln!("❌ Migraties geweigerd: {}", e);
+            if matches!(e, MigrationError::Migrate(_)) {
+                eprintln!(
+                    "   Bestaande DB zonder _sqlx_migrations? Draai eerst scripts/sqlx-baseline.sh 21 \
+                     (markeert alleen 001–021 als toegepast; de rest draait bij de volgende start)"
+                );
+            }
+        }
+    }
+    result
+}
+
+/// In-memory database from `goamet_schema.sql` plus every migration not
+/// folded into it, for tests that run the real queries. Commands are
+/// accepted by a stub central apply.
+#[cfg(test)]
+pub(crate) async fn schema_test_pool() -> SqlitePool {
+    let backend = crate::services::central_apply_service::StubCentralApply::accept_all();
+    schema_test_pool_with(std::sync::Arc::new(backend)).await
+}
+
+/// [`schema_test_pool`] with the `sp_apply_*` functions backed by `backend`.
+#[cfg(test)]
+pub(crate) async fn schema_test_pool_with(
+    backend: std::sync::Arc<dyn crate::services::central_apply_service::CentralApply>,
+) -> SqlitePool {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .after_connect(move |conn, _meta| {
+            let backend = backend.clone();
+            Box::pin(async move {
+                crate::database::sp_functions::register_apply_functions(conn, backend).await
+            })
+        })
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::query(include_str!("../../goamet_schema.sql"))
+        .execute(&pool)
+        .await
+        .unwrap();
+    for migration in MIGRATOR.iter() {
+        if !FOLDED_INTO_SCHEMA.contains(&migration.version) {
+            sqlx::query(&migration.sql).execute(&pool).await.unwrap();
+        }
+    }
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::path::PathBuf;
+
+    async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    fn migrations_dir(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("goamet-migrations-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, sql) in files {
+            std::fs::write(dir.join(name), sql).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn parses_mode_flags() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(MigrationMode::from_args(args(&[])), MigrationMode::Apply);
+        assert_eq!(
+            MigrationMode::from_args(args(&["--migrate-only"])),
+            MigrationMode::MigrateOnly
+        );
+        assert_eq!(
+            MigrationMode::from_args(args(&["--migrate-only", "--dry-run"])),
+            MigrationMode::DryRun
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_lists_pending_without_writing() {
+        let dir = migrations_dir(&[
+            ("001_create_a.sql", "CREATE TABLE a (id INTEGER);"),
+            ("002_create_b.sql", "CREATE TABLE b (id INTEGER);"),
+        ]);
+        let migrator = Migrator::new(dir.as_path()).await.unwrap();
+        let pool = memory_pool().await;
+
+        let plan = run(&pool, &migrator, MigrationMode::DryRun).await.unwrap();
+        assert_eq!(plan.applied, 0);
+        assert_eq!(
+            plan.pending.iter().map(|m| m.version).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let tables: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(tables, 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn applies_pending_then_refuses_drift() {
+        let dir = migrations_dir(&[("001_create_a.sql", "CREATE TABLE a (id INTEGER);")]);
+        let migrator = Migrator::new(dir.as_path()).await.unwrap();
+        let pool = memory_pool().await;
+
+        let first = run(&pool, &migrator, MigrationMode::Apply).await.unwrap();
+        assert_eq!(first.pending.len(), 1);
+        let again = plan(&pool, &migrator).await.unwrap();
+        assert_eq!((again.applied, again.pending.len()), (1, 0));
+
+        std::fs::write(
+            dir.join("001_create_a.sql"),
+            "CREATE TABLE a (id INTEGER, x TEXT);",
+        )
+        .unwrap();
+        let edited = Migrator::new(dir.as_path()).await.unwrap();
+        match run(&pool, &edited, MigrationMode::Apply).await {
+            Err(MigrationError::Drift { version, .. }) => assert_eq!(version, 1),
+            other => panic!("expected drift, got {:?}", other.map(|p| p.pending)),
+        }
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn embedded_migrations_have_unique_versions() {
+        let mut versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+        let total = versions.len();
+        versions.dedup();
+        assert_eq!(versions.len(), total);
+        assert!(total >= 22);
+    }
+}
@@ -1,6 +1,13 @@
 pub mod activities_repo;
+pub mod activity_attendance_commands_repo;
+pub mod activity_commands_repo;
+pub mod activity_lifecycle_commands_repo;
 pub mod activity_detail_repo;
+pub mod activity_invitation_commands_repo;
+pub mod activity_invitations_repo;
+pub mod activity_invite_commands_repo;
 pub mod activity_repo;
+pub mod activity_review_commands_repo;
 pub mod activity_signup_commands_repo;
 pub mod activity_summary_repo;
 pub mod activity_waitlist_commands_repo;
@@ -10,6 +17,13 @@ pub mod current_user_repo;
 pub mod discovery_repo;
 pub mod friendship_commands_repo;
 pub mod interests_repo;
+pub mod live_events_repo;
+pub mod migrations;
+pub mod notification_commands_repo;
+pub mod onboarding_repo;
+pub mod notifications_repo;
 pub mod promotion_units_repo;
+pub mod sp_functions;
+pub mod sync_repo;
 pub mod user_repo;
 pub mod user_summary_repo;
@@ -0,0 +1,37 @@
+use sqlx::SqlitePool;
+
+const SQL_INSERT_NOTIFICATION_COMMAND: &str = r#"
+INSERT INTO notification_commands (
+  id,
+  actor_user_id,
+  notification_id,
+  action,
+  action_id,
+  note
+) VALUES (?, ?, ?, ?, ?, ?)
+"#;
+
+pub struct NewNotificationCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub notification_id: Option<&'a str>,
+    pub action: &'a str, // read|archive|read_all|execute
+    pub action_id: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_notification_command(
+    pool: &SqlitePool,
+    cmd: NewNotificationCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_NOTIFICATION_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.notification_id)
+        .bind(cmd.action)
+        .bind(cmd.action_id)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
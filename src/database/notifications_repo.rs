@@ -0,0 +1,155 @@
+use sqlx::SqlitePool;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct NotificationRow {
+    pub notification_id: String,
+    pub notification_type: String,
+    pub title: String,
+    pub message: Option<String>,
+    pub status: String,
+    pub priority: String,
+    pub actor: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub deep_link: Option<String>,
+    pub media_url: Option<String>,
+    pub media_type: Option<String>,
+    pub actions: Option<String>,
+    pub group_id: Option<String>,
+    pub grouped_count: i64,
+    pub created_at: String,
+}
+
+// Group heads (or ungrouped rows) that are not expired; `archived` selects
+// the archive instead of the inbox.
+const SQL_LIST_NOTIFICATIONS: &str = r#"
+SELECT
+  notification_id,
+  notification_type,
+  title,
+  message,
+  COALESCE(status, 'unread') AS status,
+  COALESCE(priority, 'normal') AS priority,
+  actor,
+  target_type,
+  target_id,
+  deep_link,
+  media_url,
+  media_type,
+  actions,
+  group_id,
+  COALESCE(grouped_count, 1) AS grouped_count,
+  created_at
+FROM notifications
+WHERE (is_deleted = 0 OR is_deleted IS NULL)
+  AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'))
+  AND (group_id IS NULL OR COALESCE(is_group_head, 1) = 1)
+  AND (
+    (?1 = 1 AND status = 'archived')
+    OR (?1 = 0 AND COALESCE(status, 'unread') != 'archived')
+  )
+ORDER BY
+  CASE WHEN COALESCE(status, 'unread') = 'unread' THEN 0 ELSE 1 END,
+  CASE COALESCE(priority, 'normal')
+    WHEN 'critical' THEN 0
+    WHEN 'high' THEN 1
+    ELSE 2
+  END,
+  datetime(created_at) DESC
+LIMIT ?2
+"#;
+
+pub async fn list_notifications(
+    pool: &SqlitePool,
+    archived: bool,
+    limit: i64,
+) -> sqlx::Result<Vec<NotificationRow>> {
+    sqlx::query_as::<_, NotificationRow>(SQL_LIST_NOTIFICATIONS)
+        .bind(archived as i64)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+const SQL_LIST_GROUP: &str = r#"
+SELECT
+  notification_id,
+  notification_type,
+  title,
+  message,
+  COALESCE(status, 'unread') AS status,
+  COALESCE(priority, 'normal') AS priority,
+  actor,
+  target_type,
+  target_id,
+  deep_link,
+  media_url,
+  media_type,
+  actions,
+  group_id,
+  COALESCE(grouped_count, 1) AS grouped_count,
+  created_at
+FROM notifications
+WHERE group_id = ?1
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+  AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'))
+ORDER BY COALESCE(is_group_head, 0) DESC, datetime(created_at) DESC
+LIMIT ?2
+"#;
+
+/// All live members of one group, head first.
+pub async fn list_group(
+    pool: &SqlitePool,
+    group_id: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<NotificationRow>> {
+    sqlx::query_as::<_, NotificationRow>(SQL_LIST_GROUP)
+        .bind(group_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+const SQL_LOAD_NOTIFICATION: &str = r#"
+SELECT
+  notification_id,
+  notification_type,
+  title,
+  message,
+  COALESCE(status, 'unread') AS status,
+  COALESCE(priority, 'normal') AS priority,
+  actor,
+  target_type,
+  target_id,
+  deep_link,
+  media_url,
+  media_type,
+  actions,
+  group_id,
+  COALESCE(grouped_count, 1) AS grouped_count,
+  created_at
+FROM notifications
+WHERE notification_id = ?1
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+  AND (expires_at IS NULL OR datetime(expires_at) > datetime('now'))
+LIMIT 1
+"#;
+
+pub async fn load_notification(
+    pool: &SqlitePool,
+    notification_id: &str,
+) -> sqlx::Result<Option<NotificationRow>> {
+    sqlx::query_as::<_, NotificationRow>(SQL_LOAD_NOTIFICATION)
+        .bind(notification_id)
+        .fetch_optional(pool)
+        .await
+}
+
+const SQL_UNREAD_COUNT: &str = r#"
+SELECT count
+FROM v_unread_notification_count
+"#;
+
+pub async fn unread_count(pool: &SqlitePool) -> sqlx::Result<i64> {
+    sqlx::query_scalar(SQL_UNREAD_COUNT).fetch_one(pool).await
+}
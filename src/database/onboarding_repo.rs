@@ -0,0 +1,135 @@
+use sqlx::SqlitePool;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct OnboardingProgressRow {
+    pub current_step: i64,
+    pub selections: String,
+    pub location_name: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+const SQL_LOAD_USER_STATUS: &str = r#"
+SELECT status
+FROM users
+WHERE user_id = ?
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+LIMIT 1
+"#;
+
+const SQL_LOAD_ONBOARDING_DATA: &str = r#"
+SELECT onboarding_data
+FROM interests_catalog
+ORDER BY id
+LIMIT 1
+"#;
+
+const SQL_LOAD_PROGRESS: &str = r#"
+SELECT
+  current_step,
+  selections,
+  location_name,
+  latitude,
+  longitude
+FROM onboarding_progress
+WHERE user_id = ?
+"#;
+
+const SQL_UPSERT_PROGRESS: &str = r#"
+INSERT INTO onboarding_progress (
+  user_id,
+  current_step,
+  selections,
+  location_name,
+  latitude,
+  longitude,
+  updated_at
+) VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
+ON CONFLICT(user_id) DO UPDATE SET
+  current_step = excluded.current_step,
+  selections = excluded.selections,
+  location_name = excluded.location_name,
+  latitude = excluded.latitude,
+  longitude = excluded.longitude,
+  updated_at = excluded.updated_at
+"#;
+
+const SQL_INSERT_ONBOARDING_COMMAND: &str = r#"
+INSERT INTO onboarding_commands (
+  id,
+  actor_user_id,
+  interests,
+  city,
+  latitude,
+  longitude,
+  note
+) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub async fn load_user_status(pool: &SqlitePool, user_id: &str) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar::<_, Option<String>>(SQL_LOAD_USER_STATUS)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+}
+
+pub async fn load_onboarding_data(pool: &SqlitePool) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar::<_, String>(SQL_LOAD_ONBOARDING_DATA)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn load_progress(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> sqlx::Result<Option<OnboardingProgressRow>> {
+    sqlx::query_as::<_, OnboardingProgressRow>(SQL_LOAD_PROGRESS)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn save_progress(
+    pool: &SqlitePool,
+    user_id: &str,
+    progress: &OnboardingProgressRow,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_UPSERT_PROGRESS)
+        .bind(user_id)
+        .bind(progress.current_step)
+        .bind(&progress.selections)
+        .bind(&progress.location_name)
+        .bind(progress.latitude)
+        .bind(progress.longitude)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+pub struct NewOnboardingCommand<'a> {
+    pub id: &'a str,
+    pub actor_user_id: &'a str,
+    pub interests: &'a str, // JSON array, users.interests shape
+    pub city: &'a str,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub note: Option<&'a str>,
+}
+
+pub async fn insert_onboarding_command(
+    pool: &SqlitePool,
+    cmd: NewOnboardingCommand<'_>,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(SQL_INSERT_ONBOARDING_COMMAND)
+        .bind(cmd.id)
+        .bind(cmd.actor_user_id)
+        .bind(cmd.interests)
+        .bind(cmd.city)
+        .bind(cmd.latitude)
+        .bind(cmd.longitude)
+        .bind(cmd.note)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,289 @@
+//! Rust-registered SQLite UDFs behind the command-table apply triggers.
+//!
+//! Each `sp_apply_*_command(command_id)` loads its command row (visible inside
+//! the inserting transaction), forwards it to the central apply backend and
+//! returns its result; the trigger rolls the insert back on anything but 1.
+
+use libsqlite3_sys as ffi;
+use sqlx::SqliteConnection;
+use std::ffi::{c_int, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::services::central_apply_service::{
+    record_outcome, ApplyOutcome, ApplyRequest, CentralApply,
+};
+
+/// UDF name → command table it applies.
+pub const APPLY_FUNCTIONS: &[(&str, &str)] = &[
+    (
+        "sp_apply_activity_signup_command",
+        "activity_signup_commands",
+    ),
+    (
+        "sp_apply_activity_waitlist_command",
+        "activity_waitlist_commands",
+    ),
+    ("sp_apply_friendship_command", "friendship_commands"),
+    (
+        "sp_apply_activity_invitation_command",
+        "activity_invitation_commands",
+    ),
+    (
+        "sp_apply_activity_invite_command",
+        "activity_invite_commands",
+    ),
+    ("sp_apply_notification_command", "notification_commands"),
+    ("sp_apply_onboarding_command", "onboarding_commands"),
+    ("sp_apply_activity_command", "activity_commands"),
+    (
+        "sp_apply_activity_lifecycle_command",
+        "activity_lifecycle_commands",
+    ),
+    (
+        "sp_apply_activity_attendance_command",
+        "activity_attendance_commands",
+    ),
+    (
+        "sp_apply_activity_review_command",
+        "activity_review_commands",
+    ),
+];
+
+struct UdfContext {
+    sp_name: &'static str,
+    command_table: &'static str,
+    backend: Arc<dyn CentralApply>,
+}
+
+/// Registers all apply UDFs on one connection. Use from
+/// `SqlitePoolOptions::after_connect` so every pooled connection has them.
+pub async fn register_apply_functions(
+    conn: &mut SqliteConnection,
+    backend: Arc<dyn CentralApply>,
+) -> sqlx::Result<()> {
+    let mut handle = conn.lock_handle().await?;
+    let db = handle.as_raw_handle().as_ptr();
+
+    for (sp_name, command_table) in APPLY_FUNCTIONS {
+        let name = CString::new(*sp_name).expect("static UDF name");
+        let ctx = Box::into_raw(Box::new(UdfContext {
+            sp_name,
+            command_table,
+            backend: backend.clone(),
+        }));
+
+        // SAFETY: `db` is a live connection held by the lock; on failure
+        // sqlite3_create_function_v2 invokes `drop_context` itself.
+        let rc = unsafe {
+            ffi::sqlite3_create_function_v2(
+                db,
+                name.as_ptr(),
+                1,
+                ffi::SQLITE_UTF8,
+                ctx as *mut c_void,
+                Some(apply_udf),
+                None,
+                None,
+                Some(drop_context),
+            )
+        };
+        if rc != ffi::SQLITE_OK {
+            return Err(sqlx::Error::Protocol(format!(
+                "registering {} failed (sqlite rc={})",
+                sp_name, rc
+            )));
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn drop_context(p: *mut c_void) {
+    drop(Box::from_raw(p as *mut UdfContext));
+}
+
+unsafe extern "C" fn apply_udf(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    let udf = &*(ffi::sqlite3_user_data(ctx) as *const UdfContext);
+    let command_id = if argc == 1 { value_text(*argv) } else { None };
+    let Some(command_id) = command_id else {
+        ffi::sqlite3_result_int64(ctx, 0);
+        return;
+    };
+
+    let db = ffi::sqlite3_context_db_handle(ctx);
+    let outcome = match load_command_row(db, udf.command_table, &command_id) {
+        Some(command) => {
+            let req = ApplyRequest {
+                sp_name: udf.sp_name.to_string(),
+                command_table: udf.command_table.to_string(),
+                command_id: command_id.clone(),
+                command,
+            };
+            // Never unwind into SQLite.
+            catch_unwind(AssertUnwindSafe(|| udf.backend.apply(&req)))
+                .unwrap_or_else(|_| ApplyOutcome::rejected("backend_panicked", None))
+        }
+        None => ApplyOutcome::rejected("command_not_found", None),
+    };
+
+    if !outcome.is_applied() {
+        warn!(
+            sp = udf.sp_name,
+            command_id = %command_id,
+            outcome = ?outcome.outcome,
+            "central_apply_rejected"
+        );
+    }
+    ffi::sqlite3_result_int64(ctx, outcome.result);
+    record_outcome(&command_id, outcome);
+}
+
+unsafe fn value_text(value: *mut ffi::sqlite3_value) -> Option<String> {
+    let text = ffi::sqlite3_value_text(value);
+    if text.is_null() {
+        return None;
+    }
+    Some(
+        CStr::from_ptr(text as *const _)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// Reads the command row as a JSON object (column name → value).
+unsafe fn load_command_row(
+    db: *mut ffi::sqlite3,
+    command_table: &str,
+    command_id: &str,
+) -> Option<serde_json::Value> {
+    let sql = CString::new(format!("SELECT * FROM \"{}\" WHERE id = ?1", command_table)).ok()?;
+    let id = CString::new(command_id).ok()?;
+
+    let mut stmt: *mut ffi::sqlite3_stmt = std::ptr::null_mut();
+    if ffi::sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, std::ptr::null_mut())
+        != ffi::SQLITE_OK
+    {
+        return None;
+    }
+
+    let mut row = None;
+    if ffi::sqlite3_bind_text(stmt, 1, id.as_ptr(), -1, ffi::SQLITE_TRANSIENT()) == ffi::SQLITE_OK
+        && ffi::sqlite3_step(stmt) == ffi::SQLITE_ROW
+    {
+        let mut obj = serde_json::Map::new();
+        for i in 0..ffi::sqlite3_column_count(stmt) {
+            let name = CStr::from_ptr(ffi::sqlite3_column_name(stmt, i))
+                .to_string_lossy()
+                .into_owned();
+            let value = match ffi::sqlite3_column_type(stmt, i) {
+                ffi::SQLITE_INTEGER => serde_json::Value::from(ffi::sqlite3_column_int64(stmt, i)),
+                ffi::SQLITE_FLOAT => serde_json::Value::from(ffi::sqlite3_column_double(stmt, i)),
+                ffi::SQLITE_TEXT => {
+                    let text = ffi::sqlite3_column_text(stmt, i);
+                    serde_json::Value::from(
+                        CStr::from_ptr(text as *const _)
+                            .to_string_lossy()
+                            .into_owned(),
+                    )
+                }
+                _ => serde_json::Value::Null,
+            };
+            obj.insert(name, value);
+        }
+        row = Some(serde_json::Value::Object(obj));
+    }
+
+    ffi::sqlite3_finalize(stmt);
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::central_apply_service::{resolve_command, CommandError, StubCentralApply};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+
+    async fn pool_with(backend: Arc<StubCentralApply>) -> SqlitePool {
+        let backend: Arc<dyn CentralApply> = backend;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _meta| {
+                let backend = backend.clone();
+                Box::pin(async move { register_apply_functions(conn, backend).await })
+            })
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        for sql in [
+            include_str!("../../migrations/004_activity_signup_commands.sql"),
+            include_str!("../../migrations/005_activity_waitlist_commands.sql"),
+            include_str!("../../migrations/006_dev_dummy_sp_triggers.sql"),
+            include_str!("../../migrations/019_friendship_commands.sql"),
+            include_str!("../../migrations/022_restore_sp_apply_triggers.sql"),
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    async fn insert_join(pool: &SqlitePool, id: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            "INSERT INTO activity_signup_commands (id, actor_user_id, activity_id, subject_user_id, action) \
+             VALUES (?, 'u1', 'a1', 'u1', 'join')",
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn count(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM activity_signup_commands")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn applied_command_is_committed_and_forwarded() {
+        let stub = Arc::new(StubCentralApply::with(|_| {
+            ApplyOutcome::applied(Some("waitlisted"))
+        }));
+        let pool = pool_with(stub.clone()).await;
+
+        let res = insert_join(&pool, "cmd-ok").await;
+        let outcome = resolve_command("cmd-ok", res).unwrap();
+        assert_eq!(outcome.outcome.as_deref(), Some("waitlisted"));
+        assert_eq!(count(&pool).await, 1);
+
+        let calls = stub.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].sp_name, "sp_apply_activity_signup_command");
+        assert_eq!(calls[0].command["activity_id"], "a1");
+        assert_eq!(calls[0].command["action"], "join");
+    }
+
+    #[tokio::test]
+    async fn rejected_command_is_rolled_back() {
+        let stub = Arc::new(StubCentralApply::with(|_| {
+            ApplyOutcome::rejected("activity_full", None)
+        }));
+        let pool = pool_with(stub).await;
+
+        let res = insert_join(&pool, "cmd-full").await;
+        match resolve_command("cmd-full", res) {
+            Err(CommandError::Rejected(o)) => {
+                assert_eq!(o.outcome.as_deref(), Some("activity_full"))
+            }
+            other => panic!("expected rejection, got {:?}", other),
+        }
+        assert_eq!(count(&pool).await, 0);
+    }
+}
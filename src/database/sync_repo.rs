@@ -0,0 +1,154 @@
+use sqlx::{sqlite::SqliteArguments, Arguments, Sqlite, SqliteConnection, SqlitePool};
+
+pub const SQL_ENSURE_SYNC_STATE: &str = r#"
+INSERT OR IGNORE INTO sync_state (entity_type)
+VALUES (?)
+"#;
+
+pub const SQL_LOAD_SYNC_STATE: &str = r#"
+SELECT entity_type, last_sync_at, last_range_hash, record_count
+FROM sync_state
+WHERE entity_type = ?
+"#;
+
+pub const SQL_SAVE_SYNC_STATE: &str = r#"
+UPDATE sync_state
+SET last_sync_at = ?,
+    last_range_hash = ?,
+    record_count = ?
+WHERE entity_type = ?
+"#;
+
+pub const SQL_TABLE_EXISTS: &str = r#"
+SELECT COUNT(*)
+FROM sqlite_master
+WHERE type = 'table'
+  AND name = ?
+"#;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SyncStateRow {
+    pub entity_type: String,
+    pub last_sync_at: String,
+    pub last_range_hash: Option<String>,
+    pub record_count: Option<i64>,
+}
+
+pub async fn table_exists(pool: &SqlitePool, table: &str) -> sqlx::Result<bool> {
+    let n: i64 = sqlx::query_scalar(SQL_TABLE_EXISTS)
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+    Ok(n > 0)
+}
+
+pub async fn load_sync_state(pool: &SqlitePool, entity_type: &str) -> sqlx::Result<SyncStateRow> {
+    sqlx::query(SQL_ENSURE_SYNC_STATE)
+        .bind(entity_type)
+        .execute(pool)
+        .await?;
+    sqlx::query_as::<_, SyncStateRow>(SQL_LOAD_SYNC_STATE)
+        .bind(entity_type)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn save_sync_state(
+    conn: &mut SqliteConnection,
+    entity_type: &str,
+    last_sync_at: &str,
+    last_range_hash: Option<&str>,
+    record_count: i64,
+) -> sqlx::Result<()> {
+    sqlx::query(SQL_SAVE_SYNC_STATE)
+        .bind(last_sync_at)
+        .bind(last_range_hash)
+        .bind(record_count)
+        .bind(entity_type)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Column names of a snapshot table (table names come from the static entity list).
+pub async fn table_columns(pool: &SqlitePool, table: &str) -> sqlx::Result<Vec<String>> {
+    let rows: Vec<(String,)> =
+        sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .fetch_all(pool)
+            .await?;
+    Ok(rows.into_iter().map(|r| r.0).collect())
+}
+
+/// `(pk, row_hash)` of all live rows, ordered by pk; input for the range hash.
+pub async fn live_row_hashes(
+    conn: &mut SqliteConnection,
+    table: &str,
+    pk: &str,
+) -> sqlx::Result<Vec<(String, String)>> {
+    sqlx::query_as(&format!(
+        "SELECT CAST({pk} AS TEXT), row_hash FROM {table} \
+         WHERE (is_deleted = 0 OR is_deleted IS NULL) ORDER BY {pk}",
+        pk = pk,
+        table = table
+    ))
+    .fetch_all(conn)
+    .await
+}
+
+/// Upserts one snapshot row. The update only fires when `row_hash` differs,
+/// so the returned row count is 0 for unchanged rows.
+pub async fn upsert_snapshot_row(
+    conn: &mut SqliteConnection,
+    table: &str,
+    pk: &str,
+    columns: &[&str],
+    values: SqliteArguments<'_>,
+) -> sqlx::Result<u64> {
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let updates = columns
+        .iter()
+        .filter(|c| **c != pk)
+        .map(|c| format!("{c} = excluded.{c}", c = c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "INSERT INTO {table} ({cols}) VALUES ({placeholders}) \
+         ON CONFLICT({pk}) DO UPDATE SET {updates} \
+         WHERE {table}.row_hash IS NOT excluded.row_hash",
+        table = table,
+        cols = columns.join(", "),
+        placeholders = placeholders,
+        pk = pk,
+        updates = updates
+    );
+    let res = sqlx::query_with::<Sqlite, _>(&sql, values)
+        .execute(conn)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Applies a tombstone (`changed_at` defaults to now). Returns 0 when the row
+/// is unknown or already deleted.
+pub async fn soft_delete_row(
+    conn: &mut SqliteConnection,
+    table: &str,
+    pk: &str,
+    id: &str,
+    changed_at: Option<&str>,
+    row_hash: Option<&str>,
+) -> sqlx::Result<u64> {
+    let sql = format!(
+        "UPDATE {table} SET is_deleted = 1, changed_at = COALESCE(?, datetime('now')), row_hash = COALESCE(?, row_hash) \
+         WHERE CAST({pk} AS TEXT) = ? AND (is_deleted = 0 OR is_deleted IS NULL)",
+        table = table,
+        pk = pk
+    );
+    let mut args = SqliteArguments::default();
+    args.add(changed_at);
+    args.add(row_hash);
+    args.add(id);
+    let res = sqlx::query_with::<Sqlite, _>(&sql, args)
+        .execute(conn)
+        .await?;
+    Ok(res.rows_affected())
+}
@@ -30,3 +30,55 @@ pub async fn load_user_profile(pool: &SqlitePool, user_id: &str) -> sqlx::Result
         .fetch_optional(pool)
         .await
 }
+
+const SQL_LOAD_USER_TIMEZONE: &str = r#"
+SELECT
+    CASE WHEN json_valid(settings) THEN json_extract(settings, '$.timezone') END
+FROM users
+WHERE user_id = ?1
+  AND (is_deleted = 0 OR is_deleted IS NULL)
+LIMIT 1
+"#;
+
+/// `settings.timezone`, e.g. `Europe/Amsterdam`; `None` when unset.
+pub async fn load_user_timezone(pool: &SqlitePool, user_id: &str) -> sqlx::Result<Option<String>> {
+    let tz: Option<Option<String>> = sqlx::query_scalar(SQL_LOAD_USER_TIMEZONE)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(tz
+        .flatten()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty()))
+}
+
+// `users.interests` holds `{interest_id, name, …}` objects; plain strings
+// are accepted as well.
+const SQL_LIST_USER_INTEREST_NAMES: &str = r#"
+SELECT DISTINCT lower(trim(
+    CASE j.type
+        WHEN 'object' THEN json_extract(j.value, '$.name')
+        ELSE j.value
+    END
+)) AS name
+FROM users u, json_each(CASE WHEN json_valid(u.interests) THEN u.interests ELSE '[]' END) j
+WHERE u.user_id = ?1
+  AND (u.is_deleted = 0 OR u.is_deleted IS NULL)
+  AND j.type IN ('object', 'text')
+"#;
+
+/// Lowercase interest names of the user, for matching activity tags.
+pub async fn load_user_interest_names(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> sqlx::Result<Vec<String>> {
+    let names: Vec<Option<String>> = sqlx::query_scalar(SQL_LIST_USER_INTEREST_NAMES)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(names
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.is_empty())
+        .collect())
+}
@@ -2,20 +2,29 @@ use axum::{
     middleware,
     response::Redirect,
     routing::{get, get_service, post},
-    Router,
+    Extension, Router,
 };
 use dotenvy::dotenv;
 use http::header::{HeaderValue, CACHE_CONTROL};
 use sqlx::sqlite::SqlitePoolOptions;
 use std::env;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::services::ServeDir;
 use tower_http::set_header::SetResponseHeaderLayer;
 
+use website::database::{migrations, sp_functions};
+use website::services::auth_api_service::TokenRefresher;
+use website::services::central_apply_service;
+use website::services::jwt_service::{JwksVerifier, JwtConfig};
+use website::services::live_events_service::{self, LiveEventHub, LiveEventsConfig};
+use website::services::sync_service::{self, SyncConfig};
 use website::web::middleware::auth as auth_middleware;
+use website::web::middleware::csrf::{self as csrf_middleware, CsrfConfig};
 use website::web::routes::{
-    activities, activity, auth, chat_api, chats, discovery, images, location, user,
+    account, activities, activity, auth, chat_api, chats, discovery, events, images, invitations,
+    location, notifications, onboarding, user,
 };
 
 #[tokio::main]
@@ -30,15 +39,92 @@ async fn main() {
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL moet in .env staan");
     println!("Verbinden met database: {}", db_url);
 
+    // Backend voor de sp_apply_* UDFs achter de command-tabellen
+    let central_apply = central_apply_service::from_env();
+    println!("🔁 Central apply backend: {}", central_apply.name());
+
     let pool = SqlitePoolOptions::new()
+        .after_connect(move |conn, _meta| {
+            let backend = central_apply.clone();
+            Box::pin(async move { sp_functions::register_apply_functions(conn, backend).await })
+        })
         .connect(&db_url)
         .await
         .expect("Kan niet verbinden met DB");
 
+    // Openstaande migraties toepassen (--dry-run / --migrate-only stoppen daarna)
+    let migration_mode = migrations::MigrationMode::from_args(env::args().skip(1));
+    if migrations::apply_on_startup(&pool, migration_mode)
+        .await
+        .is_err()
+    {
+        std::process::exit(1);
+    }
+    if migration_mode.exits_after_migrations() {
+        return;
+    }
+
+    // Snapshot sync op de achtergrond (SYNC_INTERVAL_SECS=0 schakelt uit)
+    let sync_config = SyncConfig::from_env();
+    if sync_service::spawn_background(pool.clone(), sync_config.clone()).is_some() {
+        println!(
+            "🔄 Snapshot sync elke {}s via {}",
+            sync_config.interval.as_secs(),
+            sync_config.base_url
+        );
+    } else {
+        println!("🔄 Snapshot sync uitgeschakeld");
+    }
+
+    // Live events (SSE): snapshot watcher publiceert wijzigingen naar open tabs
+    let live_hub = Arc::new(LiveEventHub::new());
+    let live_config = LiveEventsConfig::from_env();
+    if live_events_service::spawn_snapshot_watcher(
+        pool.clone(),
+        live_hub.clone(),
+        live_config.clone(),
+    )
+    .is_some()
+    {
+        println!(
+            "📡 Live events: snapshot check elke {}s",
+            live_config.interval.as_secs()
+        );
+    } else {
+        println!("📡 Live events: snapshot watcher uitgeschakeld");
+    }
+
+    let jwt_config = JwtConfig::from_env();
+    println!("🔑 JWT verificatie via JWKS: {:?}", jwt_config.jwks_source);
+    let auth_mode = auth_middleware::AuthMode::from_env();
+    match auth_mode {
+        auth_middleware::AuthMode::MultiUser => {
+            println!("🔐 Auth mode: multi_user (geldig access_token vereist)")
+        }
+        auth_middleware::AuthMode::SingleUser => println!(
+            "⚠️  Auth mode: single_user (verzoeken zonder cookie draaien als current_user) - alleen voor lokale containers!"
+        ),
+    }
+    let auth_state = auth_middleware::AuthState {
+        mode: auth_mode,
+        pool: pool.clone(),
+        verifier: Arc::new(JwksVerifier::new(jwt_config)),
+        refresher: Arc::new(TokenRefresher::new()),
+        refresh_skew_secs: std::env::var("AUTH_REFRESH_SKEW_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60),
+    };
+
     // 3. Protected routes onder één middleware layer
     let protected_routes = Router::new()
         .route("/discovery", get(discovery::discovery_handler))
         .route("/activities", get(activities::activities_handler))
+        .route("/activities/feed", get(activities::activities_feed_handler))
+        .route(
+            "/api/activities/map",
+            get(activities::activities_map_handler),
+        )
         .route("/chats", get(chats::chats_handler))
         .route("/chats/:conversation_id", get(chats::chat_detail_handler))
         .route("/api/chat/health", get(chat_api::health_handler))
@@ -83,10 +169,43 @@ async fn main() {
             "/api/chat/conversations/:conversation_id/unread",
             get(chat_api::get_unread_count_handler),
         )
+        .route(
+            "/activities/new",
+            get(activity::activity_new_handler).post(activity::activity_create_handler),
+        )
         .route(
             "/activities/:activity_id",
             get(activity::activity_detail_handler),
         )
+        .route(
+            "/activities/:activity_id/edit",
+            get(activity::activity_edit_handler).post(activity::activity_update_handler),
+        )
+        .route(
+            "/activities/:activity_id/attendance",
+            get(activity::activity_attendance_handler)
+                .post(activity::activity_attendance_command_handler),
+        )
+        .route(
+            "/activities/:activity_id/reviews",
+            post(activity::activity_review_handler),
+        )
+        .route(
+            "/activities/:activity_id/reviews/:review_id/helpful",
+            post(activity::activity_review_helpful_handler),
+        )
+        .route(
+            "/activities/:activity_id/cancel",
+            post(activity::activity_cancel_handler),
+        )
+        .route(
+            "/activities/:activity_id/reschedule",
+            post(activity::activity_reschedule_handler),
+        )
+        .route(
+            "/activities/:activity_id/complete",
+            post(activity::activity_complete_handler),
+        )
         .route(
             "/activities/:activity_id/summary",
             get(activity::activity_summary_handler),
@@ -99,6 +218,33 @@ async fn main() {
             "/activities/:activity_id/waitlist",
             post(activity::activity_waitlist_command_handler),
         )
+        .route(
+            "/activities/:activity_id/invites",
+            get(activity::activity_invites_handler).post(activity::activity_invite_command_handler),
+        )
+        .route("/invitations", get(invitations::invitations_handler))
+        .route("/notifications", get(notifications::notifications_handler))
+        .route(
+            "/notifications/read-all",
+            post(notifications::notifications_read_all_handler),
+        )
+        .route(
+            "/notifications/:notification_id",
+            post(notifications::notification_command_handler),
+        )
+        .route(
+            "/notifications/:notification_id/actions/:action_id",
+            post(notifications::notification_action_handler),
+        )
+        .route(
+            "/api/notifications/unread-count",
+            get(notifications::unread_count_handler),
+        )
+        .route("/api/events", get(events::events_handler))
+        .route(
+            "/invitations/:invitation_id/respond",
+            post(invitations::invitation_respond_handler),
+        )
         .route("/users/:user_id", get(user::user_profile_handler))
         .route("/users/:user_id/summary", get(user::user_summary_handler))
         .route(
@@ -107,9 +253,18 @@ async fn main() {
         )
         .route("/images/:image_id", get(images::image_proxy))
         .route("/api/location/search", get(location::search_locations))
+        .route(
+            "/onboarding",
+            get(onboarding::onboarding_handler).post(onboarding::onboarding_step_handler),
+        )
+        .route(
+            "/onboarding/complete",
+            post(onboarding::onboarding_complete_handler),
+        )
         .route("/logout", post(auth::logout_handler))
+        .layer(Extension(live_hub))
         .layer(middleware::from_fn_with_state(
-            pool.clone(),
+            auth_state,
             auth_middleware::require_auth,
         ));
 
@@ -118,6 +273,24 @@ async fn main() {
         // Public routes
         .route("/", get(|| async { Redirect::to("/activities") }))
         .route("/login", get(auth::login_page).post(auth::login_handler))
+        .route(
+            "/login/mfa",
+            get(auth::mfa_page).post(auth::mfa_verify_handler),
+        )
+        .route(
+            "/register",
+            get(account::register_page).post(account::register_handler),
+        )
+        .route("/verify-email", get(account::verify_email_handler))
+        .route(
+            "/forgot-password",
+            get(account::forgot_password_page).post(account::forgot_password_handler),
+        )
+        .route(
+            "/reset-password",
+            get(account::reset_password_page).post(account::reset_password_handler),
+        )
+        .layer(Extension(auth::LoginState::from_env()))
         // Protected routes
         .merge(protected_routes)
         // Static files
@@ -129,6 +302,10 @@ async fn main() {
             )),
         )
         // Layers
+        .layer(middleware::from_fn_with_state(
+            CsrfConfig::from_env(),
+            csrf_middleware::csrf_protect,
+        ))
         .layer(SetResponseHeaderLayer::if_not_present(
             CACHE_CONTROL,
             HeaderValue::from_static("no-store"),
@@ -0,0 +1,210 @@
+//! Self-service account pages (register, verify email, forgot/reset password):
+//! language choice and form validation. The auth-service calls live in
+//! `auth_api_service`.
+
+/// Language for validation and status messages; page copy stays Dutch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Nl,
+    En,
+}
+
+impl Lang {
+    /// An explicit `lang` (query/form) wins, then `Accept-Language`, else Dutch.
+    pub fn detect(explicit: Option<&str>, accept_language: Option<&str>) -> Self {
+        if let Some(lang) = explicit.and_then(Self::parse) {
+            return lang;
+        }
+        accept_language
+            .into_iter()
+            .flat_map(|v| v.split(','))
+            .filter_map(|part| Self::parse(part.split(';').next().unwrap_or("")))
+            .next()
+            .unwrap_or_default()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.split(['-', '_']).next().unwrap_or("") {
+            "nl" => Some(Lang::Nl),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lang::Nl => "nl",
+            Lang::En => "en",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountMessage {
+    NameInvalid,
+    EmailInvalid,
+    PasswordTooShort,
+    PasswordTooWeak,
+    PasswordMismatch,
+    EmailTaken,
+    RegistrationRejected,
+    TokenInvalid,
+    RegisteredCheckEmail,
+    EmailVerified,
+    ResetMailSent,
+    PasswordChanged,
+}
+
+impl AccountMessage {
+    pub fn text(self, lang: Lang) -> &'static str {
+        use AccountMessage::*;
+        match (self, lang) {
+            (NameInvalid, Lang::Nl) => "Vul een naam in van 2 tot 50 tekens.",
+            (NameInvalid, Lang::En) => "Enter a name of 2 to 50 characters.",
+            (EmailInvalid, Lang::Nl) => "Vul een geldig e-mailadres in.",
+            (EmailInvalid, Lang::En) => "Enter a valid email address.",
+            (PasswordTooShort, Lang::Nl) => "Je wachtwoord moet minstens 10 tekens lang zijn.",
+            (PasswordTooShort, Lang::En) => "Your password must be at least 10 characters long.",
+            (PasswordTooWeak, Lang::Nl) => "Gebruik in je wachtwoord zowel letters als cijfers.",
+            (PasswordTooWeak, Lang::En) => "Use both letters and digits in your password.",
+            (PasswordMismatch, Lang::Nl) => "De wachtwoorden komen niet overeen.",
+            (PasswordMismatch, Lang::En) => "The passwords do not match.",
+            (EmailTaken, Lang::Nl) => "Er bestaat al een account met dit e-mailadres.",
+            (EmailTaken, Lang::En) => "An account with this email address already exists.",
+            (RegistrationRejected, Lang::Nl) => "Registratie geweigerd. Controleer je gegevens.",
+            (RegistrationRejected, Lang::En) => {
+                "Registration was rejected. Please check your details."
+            }
+            (TokenInvalid, Lang::Nl) => "Deze link is ongeldig of verlopen.",
+            (TokenInvalid, Lang::En) => "This link is invalid or has expired.",
+            (RegisteredCheckEmail, Lang::Nl) => {
+                "Account aangemaakt. Bevestig je e-mailadres via de link in je mail."
+            }
+            (RegisteredCheckEmail, Lang::En) => {
+                "Account created. Confirm your email address using the link we sent you."
+            }
+            (EmailVerified, Lang::Nl) => "Je e-mailadres is bevestigd. Je kunt nu inloggen.",
+            (EmailVerified, Lang::En) => "Your email address is confirmed. You can now log in.",
+            (ResetMailSent, Lang::Nl) => {
+                "Als er een account bij dit e-mailadres hoort, ontvang je zo een resetlink."
+            }
+            (ResetMailSent, Lang::En) => {
+                "If an account exists for this email address, a reset link is on its way."
+            }
+            (PasswordChanged, Lang::Nl) => "Je wachtwoord is gewijzigd. Je kunt nu inloggen.",
+            (PasswordChanged, Lang::En) => "Your password has been changed. You can now log in.",
+        }
+    }
+}
+
+pub const MIN_PASSWORD_LEN: usize = 10;
+
+pub fn validate_email(email: &str) -> Option<AccountMessage> {
+    let email = email.trim();
+    let valid = email.len() <= 254
+        && !email.chars().any(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        });
+    (!valid).then_some(AccountMessage::EmailInvalid)
+}
+
+pub fn validate_new_password(password: &str, confirm: &str) -> Vec<AccountMessage> {
+    let mut errors = Vec::new();
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        errors.push(AccountMessage::PasswordTooShort);
+    } else if !(password.chars().any(char::is_alphabetic)
+        && password.chars().any(|c| c.is_ascii_digit()))
+    {
+        errors.push(AccountMessage::PasswordTooWeak);
+    }
+    if password != confirm {
+        errors.push(AccountMessage::PasswordMismatch);
+    }
+    errors
+}
+
+pub fn validate_registration(
+    name: &str,
+    email: &str,
+    password: &str,
+    confirm: &str,
+) -> Vec<AccountMessage> {
+    let mut errors = Vec::new();
+    let name_len = name.trim().chars().count();
+    if !(2..=50).contains(&name_len) {
+        errors.push(AccountMessage::NameInvalid);
+    }
+    errors.extend(validate_email(email));
+    errors.extend(validate_new_password(password, confirm));
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_language() {
+        assert_eq!(Lang::detect(Some("en"), Some("nl-NL")), Lang::En);
+        assert_eq!(Lang::detect(Some("fr"), Some("en-GB,en;q=0.8")), Lang::En);
+        assert_eq!(Lang::detect(None, Some("de-DE,nl;q=0.7")), Lang::Nl);
+        assert_eq!(Lang::detect(None, None), Lang::Nl);
+    }
+
+    #[test]
+    fn validates_registration() {
+        assert!(
+            validate_registration("Abbas", "abbas@example.com", "Twente2025!", "Twente2025!")
+                .is_empty()
+        );
+        assert_eq!(
+            validate_registration("A", "abbas@", "short1", "other"),
+            vec![
+                AccountMessage::NameInvalid,
+                AccountMessage::EmailInvalid,
+                AccountMessage::PasswordTooShort,
+                AccountMessage::PasswordMismatch,
+            ]
+        );
+        assert_eq!(
+            validate_new_password("onlyletterslong", "onlyletterslong"),
+            vec![AccountMessage::PasswordTooWeak]
+        );
+        assert_eq!(
+            validate_email("a b@example.com"),
+            Some(AccountMessage::EmailInvalid)
+        );
+        assert_eq!(
+            validate_email("a@example."),
+            Some(AccountMessage::EmailInvalid)
+        );
+    }
+
+    #[test]
+    fn every_message_has_both_languages() {
+        use AccountMessage::*;
+        for m in [
+            NameInvalid,
+            EmailInvalid,
+            PasswordTooShort,
+            PasswordTooWeak,
+            PasswordMismatch,
+            EmailTaken,
+            RegistrationRejected,
+            TokenInvalid,
+            RegisteredCheckEmail,
+            EmailVerified,
+            ResetMailSent,
+            PasswordChanged,
+        ] {
+            assert_ne!(m.text(Lang::Nl), m.text(Lang::En));
+        }
+    }
+}
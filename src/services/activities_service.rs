@@ -1,11 +1,21 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::collections::HashSet;
 
-use crate::database::{activities_repo, discovery_repo, interests_repo, promotion_units_repo};
+use crate::database::activities_repo::{
+    DiscoverRanking, FeedAfter, FeedFilters, MapCellRow, MapOptions, RankWeights,
+};
+use crate::database::{
+    activities_repo, discovery_repo, interests_repo, promotion_units_repo, user_repo,
+};
 use crate::models::PromotionUnitRow;
+use crate::services::activity_review_service;
+use crate::services::timezone_service::{self, LocalDate};
 
-#[derive(Debug, Deserialize, Default)]
+/// Built with [`ActivitiesQuery::from_query_string`]: the filter form sends
+/// `interests`, `category` and `tag` once per checked chip, which a plain
+/// `Query<T>` cannot collect.
+#[derive(Debug, Default)]
 pub struct ActivitiesQuery {
     pub tab: Option<String>, // upcoming|discover|history
     pub q: Option<String>,
@@ -14,15 +24,97 @@ pub struct ActivitiesQuery {
     pub lon: Option<f64>,
     pub loc_label: Option<String>,
     pub interests: Option<Vec<String>>,
+    /// Category slugs (`?category=`).
+    pub categories: Option<Vec<String>>,
+    /// `?tag=`
+    pub tags: Option<Vec<String>>,
+    pub when: Option<String>, // tonight|weekend|week|custom
+    /// `YYYY-MM-DD`, inclusive; used with `when=custom` (or without `when`).
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub evenings: Option<bool>,
+    /// `score` adds the ranking breakdown to Discover cards.
+    pub debug: Option<String>,
     pub hide_full: Option<bool>,
     pub notice: Option<String>,
+    /// Cursor from the previous page (`ActivitiesPageData::next_cursor`).
+    pub after: Option<String>,
 }
 
+impl ActivitiesQuery {
+    /// Parses the raw query string; unknown keys and unparsable values are
+    /// ignored rather than failing the page.
+    pub fn from_query_string(raw: &str) -> Self {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(raw).unwrap_or_default();
+        let mut query = ActivitiesQuery::default();
+        for (key, value) in pairs {
+            let value = value.trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match key.as_str() {
+                "tab" => query.tab = Some(value),
+                "q" => query.q = Some(value),
+                "radius_km" => query.radius_km = value.parse().ok(),
+                "lat" => query.lat = value.parse().ok(),
+                "lon" => query.lon = value.parse().ok(),
+                "loc_label" => query.loc_label = Some(value),
+                "interests" => query.interests.get_or_insert_with(Vec::new).push(value),
+                "category" => query.categories.get_or_insert_with(Vec::new).push(value),
+                "tag" => query.tags.get_or_insert_with(Vec::new).push(value),
+                "when" => query.when = Some(value),
+                "from" => query.from = Some(value),
+                "to" => query.to = Some(value),
+                "evenings" => query.evenings = Some(matches!(value.as_str(), "true" | "1" | "on")),
+                "hide_full" => {
+                    query.hide_full = Some(matches!(value.as_str(), "true" | "1" | "on"))
+                }
+                "debug" => query.debug = Some(value),
+                "notice" => query.notice = Some(value),
+                "after" => query.after = Some(value),
+                _ => {}
+            }
+        }
+        query
+    }
+}
+
+/// Cards per feed page; `/activities/feed` serves the pages after the first.
+pub const PAGE_SIZE: usize = 24;
+/// Rows fetched per query while filling a page.
+const BATCH_SIZE: i64 = 60;
+/// Queries per page at most, so narrow filters cannot scan the whole city.
+const MAX_BATCHES: usize = 8;
+/// Tag chips shown, most used first.
+const MAX_TAG_FACETS: i64 = 20;
+/// Selected values kept per multi-select filter.
+const MAX_SELECTED: usize = 10;
+/// "Vanavond" and "alleen 's avonds" start at this hour.
+const EVENING_FROM_HOUR: u32 = 18;
+/// Discover score weights; tune with `?debug=score`.
+pub const RANK_WEIGHTS: RankWeights = RankWeights {
+    interests: 3.0,
+    distance: 2.0,
+    friends: 2.5,
+    soon: 1.5,
+    capacity: 1.0,
+    distance_scale_km: 10.0,
+    soon_scale_hours: 72.0,
+};
+/// Markers per map response; the rest is reported as `truncated`.
+const MAX_MAP_FEATURES: i64 = 500;
+/// From this zoom level on every activity gets its own marker.
+const MAP_CLUSTER_UNTIL_ZOOM: u8 = 15;
+/// Clusters are cells of a 256 px map tile split this many times per side.
+const MAP_CELLS_PER_TILE: f64 = 4.0;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActivitiesTab {
     Upcoming,
     Discover,
     History,
+    /// Discover's activities on a map, fed by [`build_activities_map`].
+    Map,
 }
 
 impl ActivitiesTab {
@@ -31,6 +123,15 @@ impl ActivitiesTab {
             ActivitiesTab::Upcoming => "upcoming",
             ActivitiesTab::Discover => "discover",
             ActivitiesTab::History => "history",
+            ActivitiesTab::Map => "map",
+        }
+    }
+
+    /// The feed whose activities the tab shows.
+    fn feed(self) -> ActivitiesTab {
+        match self {
+            ActivitiesTab::Map => ActivitiesTab::Discover,
+            tab => tab,
         }
     }
 }
@@ -39,6 +140,7 @@ fn parse_tab(input: Option<&str>) -> ActivitiesTab {
     match input.unwrap_or("discover") {
         "upcoming" => ActivitiesTab::Upcoming,
         "history" => ActivitiesTab::History,
+        "map" => ActivitiesTab::Map,
         _ => ActivitiesTab::Discover,
     }
 }
@@ -53,10 +155,74 @@ pub struct AppliedActivityFilters {
     pub coord_label: Option<String>,
     pub location_label: Option<String>,
     pub selected_interests: Vec<String>,
+    /// Lowercase category slugs.
+    pub selected_categories: Vec<String>,
+    /// Lowercase tags.
+    pub selected_tags: Vec<String>,
+    /// `tonight`, `weekend`, `week`, `custom` or empty for any date.
+    pub when: String,
+    /// `YYYY-MM-DD` bounds of a custom range; empty when open.
+    pub date_from: String,
+    pub date_to: String,
+    pub evenings_only: bool,
+    pub debug_score: bool,
     pub hide_full: bool,
     pub notice: Option<String>,
 }
 
+impl AppliedActivityFilters {
+    pub fn is_when(&self, value: &str) -> bool {
+        self.when == value
+    }
+
+    /// Query string that reproduces these filters (without `notice` and
+    /// `after`); the feed endpoint is called with it for the next pages.
+    pub fn query_string(&self) -> String {
+        let mut pairs: Vec<(&str, String)> = vec![("tab", self.tab.clone())];
+        if !self.search_query.trim().is_empty() {
+            pairs.push(("q", self.search_query.clone()));
+        }
+        pairs.push(("radius_km", self.radius_km.to_string()));
+        if let (Some(lat), Some(lon)) = (self.lat, self.lon) {
+            pairs.push(("lat", lat.to_string()));
+            pairs.push(("lon", lon.to_string()));
+        }
+        if let Some(label) = self.location_label.as_ref() {
+            pairs.push(("loc_label", label.clone()));
+        }
+        pairs.extend(
+            self.selected_interests
+                .iter()
+                .map(|v| ("interests", v.clone())),
+        );
+        pairs.extend(
+            self.selected_categories
+                .iter()
+                .map(|v| ("category", v.clone())),
+        );
+        pairs.extend(self.selected_tags.iter().map(|v| ("tag", v.clone())));
+        if !self.when.is_empty() {
+            pairs.push(("when", self.when.clone()));
+        }
+        if !self.date_from.is_empty() {
+            pairs.push(("from", self.date_from.clone()));
+        }
+        if !self.date_to.is_empty() {
+            pairs.push(("to", self.date_to.clone()));
+        }
+        if self.evenings_only {
+            pairs.push(("evenings", "true".to_string()));
+        }
+        if self.debug_score {
+            pairs.push(("debug", "score".to_string()));
+        }
+        if self.hide_full {
+            pairs.push(("hide_full", "true".to_string()));
+        }
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+}
+
 #[derive(Clone)]
 pub struct InterestOptionView {
     pub name: String,
@@ -81,6 +247,17 @@ pub struct ActivityCardView {
     pub is_full: bool,
     pub waitlist_enabled: bool,
     pub status: String,
+    /// Set when the organizer moved the activity: `za 3 mei • 10:30`.
+    pub rescheduled_from_label: Option<String>,
+    /// e.g. `★ 4,5 (12)`; `None` without reviews.
+    pub rating_label: Option<String>,
+    /// `Vandaag`, `Morgen`, `za 3 mei`; set on the first card of each day
+    /// while the feed is in time order.
+    pub day_header: Option<String>,
+    /// Weighted ranking components, with `?debug=score` on Discover.
+    pub score_breakdown: Option<String>,
+    /// Escaped search snippet with `<mark>` around the hits.
+    pub search_snippet_html: Option<String>,
     pub is_joined: bool,
     pub is_past: bool,
     pub distance_km: Option<f64>,
@@ -104,11 +281,26 @@ pub struct ActivityParticipantPreview {
     pub filler_background_color: Option<String>,
 }
 
+/// Category or tag chip with the number of matching activities.
+#[derive(Clone)]
+pub struct FacetOptionView {
+    pub value: String,
+    pub label: String,
+    pub icon_url: Option<String>,
+    pub count: i64,
+    pub selected: bool,
+}
+
 pub struct ActivitiesPageData {
     pub tab: ActivitiesTab,
     pub activities: Vec<ActivityCardView>,
     pub filters: AppliedActivityFilters,
     pub interest_options: Vec<InterestOptionView>,
+    /// Only on the first page; empty for `/activities/feed`.
+    pub category_options: Vec<FacetOptionView>,
+    pub tag_options: Vec<FacetOptionView>,
+    /// Continue with `?after=`; `None` on the last page.
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Default)]
@@ -116,6 +308,10 @@ struct UserContext {
     lat: Option<f64>,
     lon: Option<f64>,
     default_radius: i64,
+    /// IANA name from `users.settings.timezone`; empty means the default.
+    timezone: String,
+    /// Lowercase names from `users.interests`.
+    interests: Vec<String>,
 }
 
 pub async fn build_activities_page(
@@ -147,29 +343,40 @@ pub async fn build_activities_page(
 
     let tab = parse_tab(query.tab.as_deref());
     let effective = merge_filters(query, &user_ctx, tab);
+    let today = timezone_service::local_now(&user_ctx.timezone).date;
+    let (scheduled_from, scheduled_before) = date_window(&effective, today);
 
     let bbox = effective
         .lat
         .zip(effective.lon)
         .map(|(lat, lon)| bounding_box(lat, lon, effective.radius_km as f64));
 
-    let q_like = if effective.search_query.trim().is_empty() {
-        String::new()
-    } else {
-        format!("%{}%", effective.search_query.trim().to_lowercase())
+    let search = fts_query(&effective.search_query);
+    let categories_json = json_list(&effective.selected_categories);
+    let tags_json = json_list(&effective.selected_tags);
+    let feed_filters = FeedFilters {
+        search: search.as_deref(),
+        bbox,
+        categories_json: categories_json.as_deref(),
+        tags_json: tags_json.as_deref(),
+        scheduled_from: scheduled_from.as_deref(),
+        scheduled_before: scheduled_before.as_deref(),
+        min_hour: effective.evenings_only.then_some(EVENING_FROM_HOUR as i64),
     };
 
-    let limit = 200;
-    let rows = match tab {
-        ActivitiesTab::Upcoming => {
-            activities_repo::list_upcoming(pool, auth_user_id, &q_like, bbox, limit).await?
-        }
-        ActivitiesTab::Discover => {
-            activities_repo::list_discover(pool, auth_user_id, &q_like, bbox, limit).await?
-        }
-        ActivitiesTab::History => {
-            activities_repo::list_history(pool, auth_user_id, &q_like, bbox, limit).await?
-        }
+    let (category_options, tag_options) = if query.after.is_none() {
+        let facets = activities_repo::list_feed_facets(
+            pool,
+            tab.feed().as_str(),
+            auth_user_id,
+            feed_filters,
+            MAX_TAG_FACETS,
+        )
+        .await
+        .unwrap_or_default();
+        build_facet_options(facets, &effective)
+    } else {
+        (Vec::new(), Vec::new())
     };
 
     let interest_rows = interests_repo::list_active(pool, 24)
@@ -207,143 +414,610 @@ pub async fn build_activities_page(
     let filler_emoji = match tab {
         ActivitiesTab::Upcoming => Some("🎉"),
         ActivitiesTab::History => Some("✅"),
-        ActivitiesTab::Discover => None,
+        ActivitiesTab::Discover | ActivitiesTab::Map => None,
     };
 
+    // Discover is ranked unless searching or a date window is picked; in a
+    // date window each day lists the nearest first. The other tabs stay in
+    // time order.
+    let interests_json = json_list(&user_ctx.interests);
+    let ranking = (tab == ActivitiesTab::Discover && search.is_none() && effective.when.is_empty())
+        .then_some(DiscoverRanking {
+            weights: RANK_WEIGHTS,
+            interests_json: interests_json.as_deref(),
+        });
+    let mut after = query.after.as_deref().and_then(parse_cursor);
+    // Unranked, `sort_key` starts with the day of `scheduled_at`, so a
+    // follow-up page knows which day the previous one ended on.
+    let by_time = search.is_none() && ranking.is_none();
+    let mut last_day = after
+        .as_ref()
+        .filter(|_| by_time)
+        .and_then(|a| a.sort_key.get(0..10))
+        .map(str::to_string);
+    let mut next_cursor = None;
     let mut cards = Vec::new();
-    for row in rows {
-        if effective.hide_full
-            && row.current_participants_count >= row.max_participants
-            && row.is_joined == 0
-        {
-            continue;
-        }
+    'batches: for _ in 0..MAX_BATCHES {
+        let rows = match tab {
+            ActivitiesTab::Upcoming => {
+                activities_repo::list_upcoming(
+                    pool,
+                    auth_user_id,
+                    feed_filters,
+                    after.as_ref(),
+                    BATCH_SIZE,
+                )
+                .await?
+            }
+            ActivitiesTab::Discover => {
+                activities_repo::list_discover(
+                    pool,
+                    auth_user_id,
+                    feed_filters,
+                    effective.lat.zip(effective.lon),
+                    ranking,
+                    after.as_ref(),
+                    BATCH_SIZE,
+                )
+                .await?
+            }
+            // The map loads its markers itself; the page only needs the filters.
+            ActivitiesTab::Map => break 'batches,
+            ActivitiesTab::History => {
+                activities_repo::list_history(
+                    pool,
+                    auth_user_id,
+                    feed_filters,
+                    after.as_ref(),
+                    BATCH_SIZE,
+                )
+                .await?
+            }
+        };
+        let exhausted = (rows.len() as i64) < BATCH_SIZE;
 
-        if !selected_interest_set.is_empty() {
-            let tags = parse_string_array_json(row.tags.as_deref());
-            let matches = tags.into_iter().any(|t| {
-                let t = t.trim().to_lowercase();
-                !t.is_empty() && selected_interest_set.contains(&t)
+        for row in rows {
+            // Skipped rows still move the cursor, so the next page resumes after them.
+            after = Some(FeedAfter {
+                sort_key: row.sort_key.clone(),
+                activity_id: row.activity_id.clone(),
             });
-            if !matches {
+
+            if effective.hide_full
+                && row.current_participants_count >= row.max_participants
+                && row.is_joined == 0
+            {
                 continue;
             }
-        }
 
-        let mut distance_km = None;
-        if let (Some(lat0), Some(lon0), Some(lat1), Some(lon1)) =
-            (effective.lat, effective.lon, row.latitude, row.longitude)
-        {
-            let dist = haversine_km(lat0, lon0, lat1, lon1);
-            if dist > effective.radius_km as f64 {
-                continue;
+            if !selected_interest_set.is_empty() {
+                let tags = parse_string_array_json(row.tags.as_deref());
+                let matches = tags.into_iter().any(|t| {
+                    let t = t.trim().to_lowercase();
+                    !t.is_empty() && selected_interest_set.contains(&t)
+                });
+                if !matches {
+                    continue;
+                }
+            }
+
+            let mut distance_km = None;
+            if let (Some(lat0), Some(lon0), Some(lat1), Some(lon1)) =
+                (effective.lat, effective.lon, row.latitude, row.longitude)
+            {
+                let dist = haversine_km(lat0, lon0, lat1, lon1);
+                if dist > effective.radius_km as f64 {
+                    continue;
+                }
+                distance_km = Some(dist);
+            }
+
+            let (date_label, time_label) = format_scheduled_labels(&row.scheduled_at);
+            let day = row.scheduled_at.get(0..10).unwrap_or_default().to_string();
+            let day_header = if by_time && last_day.as_deref() != Some(day.as_str()) {
+                Some(day_header_label(&day, today))
+            } else {
+                None
+            };
+            last_day = Some(day);
+            let score_breakdown = effective
+                .debug_score
+                .then(|| {
+                    score_breakdown(
+                        row.rank_score,
+                        [
+                            row.rank_interests,
+                            row.rank_distance,
+                            row.rank_friends,
+                            row.rank_soon,
+                            row.rank_capacity,
+                        ],
+                        &RANK_WEIGHTS,
+                    )
+                })
+                .flatten();
+            let rescheduled_from_label = row
+                .rescheduled_from
+                .as_deref()
+                .filter(|from| *from != row.scheduled_at)
+                .map(|from| {
+                    let (date, time) = format_scheduled_labels(from);
+                    format!("{} • {}", date, time)
+                });
+            let location_label = row
+                .venue_name
+                .clone()
+                .filter(|s| !s.trim().is_empty())
+                .or_else(|| row.city.clone().filter(|s| !s.trim().is_empty()))
+                .unwrap_or_else(|| "Locatie onbekend".to_string());
+
+            let participants_preview =
+                parse_participants_preview(row.participants_preview_json.as_deref());
+
+            let organizer_name = row
+                .organizer_name
+                .clone()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+            let organizer_photo_image_id = row
+                .organizer_photo_asset_id
+                .clone()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+
+            let participants_preview = prepend_organizer_participant(
+                participants_preview,
+                organizer_name.clone(),
+                row.organizer_user_id.clone(),
+                organizer_photo_image_id.clone(),
+            );
+
+            let participants_preview = reorder_friends_after_organizer(participants_preview);
+
+            let waitlist_enabled = row.waitlist_enabled == 1;
+            let is_closed = matches!(row.status.as_str(), "cancelled" | "completed");
+            let desired_action_kind = if row.current_participants_count >= row.max_participants
+                && row.is_past == 0
+                && !is_closed
+                && row.is_joined == 0
+                && waitlist_enabled
+            {
+                "waitlist"
+            } else if row.is_past == 0 && !is_closed && row.is_joined == 0 {
+                "join"
+            } else {
+                "view"
+            };
+
+            let (participants_preview, participants_page_size) = build_first_page_with_ctas(
+                participants_preview,
+                &promo_units,
+                &info_units,
+                desired_action_kind,
+                &format!("{}:{}", row.activity_id, desired_action_kind),
+                filler_emoji,
+            );
+
+            cards.push(ActivityCardView {
+                activity_id: row.activity_id,
+                title: row.title,
+                scheduled_at: row.scheduled_at.clone(),
+                date_label,
+                time_label,
+                location_label,
+                organizer_name,
+                organizer_photo_image_id,
+                main_photo_asset_id: row.main_photo_asset_id,
+                participants_preview,
+                participants_page_size,
+                max_participants: row.max_participants,
+                participants_count: row.current_participants_count,
+                is_full: row.current_participants_count >= row.max_participants,
+                waitlist_enabled,
+                status: row.status,
+                rescheduled_from_label,
+                rating_label: activity_review_service::rating_label(
+                    row.review_count,
+                    row.avg_rating,
+                ),
+                search_snippet_html: row.search_snippet.as_deref().map(highlight_snippet),
+                day_header,
+                score_breakdown,
+                is_joined: row.is_joined == 1,
+                is_past: row.is_past == 1,
+                distance_km,
+            });
+
+            if cards.len() >= PAGE_SIZE {
+                next_cursor = after.as_ref().map(encode_cursor);
+                break 'batches;
             }
-            distance_km = Some(dist);
         }
 
-        let (date_label, time_label) = format_scheduled_labels(&row.scheduled_at);
-        let location_label = row
-            .venue_name
-            .clone()
-            .filter(|s| !s.trim().is_empty())
-            .or_else(|| row.city.clone().filter(|s| !s.trim().is_empty()))
-            .unwrap_or_else(|| "Locatie onbekend".to_string());
+        if exhausted {
+            next_cursor = None;
+            break;
+        }
+        next_cursor = after.as_ref().map(encode_cursor);
+    }
 
-        let participants_preview =
-            parse_participants_preview(row.participants_preview_json.as_deref());
+    Ok(ActivitiesPageData {
+        tab,
+        activities: cards,
+        filters: effective,
+        interest_options,
+        category_options,
+        tag_options,
+        next_cursor,
+    })
+}
 
-        let organizer_name = row
-            .organizer_name
-            .clone()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty());
-        let organizer_photo_image_id = row
-            .organizer_photo_asset_id
-            .clone()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty());
-
-        let participants_preview = prepend_organizer_participant(
-            participants_preview,
-            organizer_name.clone(),
-            row.organizer_user_id.clone(),
-            organizer_photo_image_id.clone(),
-        );
+/// The visible part of the map, from `bbox=west,south,east,north` (Leaflet's
+/// `toBBoxString()`) and `zoom`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapViewport {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+    pub zoom: u8,
+}
 
-        let participants_preview = reorder_friends_after_organizer(participants_preview);
+impl MapViewport {
+    /// `None` without a usable `bbox`. Longitudes past the antimeridian are
+    /// clamped rather than wrapped.
+    pub fn from_query_string(raw: &str) -> Option<Self> {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(raw).unwrap_or_default();
+        let value = |key: &str| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
 
-        let waitlist_enabled = row.waitlist_enabled == 1;
-        let desired_action_kind = if row.current_participants_count >= row.max_participants
-            && row.is_past == 0
-            && row.status != "cancelled"
-            && row.is_joined == 0
-            && waitlist_enabled
-        {
-            "waitlist"
-        } else if row.is_past == 0 && row.status != "cancelled" && row.is_joined == 0 {
-            "join"
-        } else {
-            "view"
+        let coords = value("bbox")?
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<f64>>>()?;
+        let [west, south, east, north] = coords[..] else {
+            return None;
         };
+        // Leaflet zooms in fractions; the grid steps per whole level.
+        let zoom = value("zoom")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .map(|v| v.floor().clamp(0.0, 22.0) as u8)
+            .unwrap_or(MAP_CLUSTER_UNTIL_ZOOM);
+
+        let viewport = MapViewport {
+            min_lat: south.clamp(-90.0, 90.0),
+            max_lat: north.clamp(-90.0, 90.0),
+            min_lon: west.clamp(-180.0, 180.0),
+            max_lon: east.clamp(-180.0, 180.0),
+            zoom,
+        };
+        (viewport.min_lat <= viewport.max_lat && viewport.min_lon <= viewport.max_lon)
+            .then_some(viewport)
+    }
 
-        let (participants_preview, participants_page_size) = build_first_page_with_ctas(
-            participants_preview,
-            &promo_units,
-            &info_units,
-            desired_action_kind,
-            &format!("{}:{}", row.activity_id, desired_action_kind),
-            filler_emoji,
-        );
+    /// Grid cell `(lon, lat)` in degrees to cluster on, roughly square on
+    /// screen; `None` once zoomed in far enough to show every activity.
+    fn cluster_cell(&self) -> Option<(f64, f64)> {
+        if self.zoom >= MAP_CLUSTER_UNTIL_ZOOM {
+            return None;
+        }
+        let lon = 360.0 / 2f64.powi(self.zoom as i32) / MAP_CELLS_PER_TILE;
+        // Web Mercator stretches latitude by 1/cos, so shrink the cell to match.
+        let mid_lat = ((self.min_lat + self.max_lat) / 2.0).to_radians();
+        Some((lon, lon * mid_lat.cos().max(0.01)))
+    }
+}
 
-        cards.push(ActivityCardView {
-            activity_id: row.activity_id,
-            title: row.title,
-            scheduled_at: row.scheduled_at.clone(),
-            date_label,
-            time_label,
-            location_label,
-            organizer_name,
-            organizer_photo_image_id,
-            main_photo_asset_id: row.main_photo_asset_id,
-            participants_preview,
-            participants_page_size,
-            max_participants: row.max_participants,
-            participants_count: row.current_participants_count,
-            is_full: row.current_participants_count >= row.max_participants,
-            waitlist_enabled,
-            status: row.status,
-            is_joined: row.is_joined == 1,
-            is_past: row.is_past == 1,
-            distance_km,
-        });
+/// GeoJSON `FeatureCollection` for the map tab.
+#[derive(Debug, Serialize)]
+pub struct MapFeatureCollection {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub features: Vec<MapFeature>,
+    /// More markers matched than [`MAX_MAP_FEATURES`]; zoom in for the rest.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MapFeature {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub geometry: MapPoint,
+    /// `[west, south, east, north]` of a cluster's activities.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+    pub properties: MapFeatureProperties,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MapPoint {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// `[lon, lat]`, GeoJSON order.
+    pub coordinates: [f64; 2],
+}
+
+/// A cluster carries only `cluster` and `count`; a single activity the rest.
+#[derive(Debug, Default, Serialize)]
+pub struct MapFeatureProperties {
+    pub cluster: bool,
+    pub count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_full: Option<bool>,
+}
+
+/// Discover's activities inside `viewport` as GeoJSON, with the same
+/// filters as the feed. The viewport takes the place of the radius.
+pub async fn build_activities_map(
+    pool: &SqlitePool,
+    auth_user_id: &str,
+    query: &ActivitiesQuery,
+    viewport: MapViewport,
+) -> sqlx::Result<MapFeatureCollection> {
+    let user_ctx = load_user_context(pool, auth_user_id)
+        .await
+        .unwrap_or_default();
+    let effective = merge_filters(query, &user_ctx, ActivitiesTab::Map);
+    let today = timezone_service::local_now(&user_ctx.timezone).date;
+    let (scheduled_from, scheduled_before) = date_window(&effective, today);
+
+    let search = fts_query(&effective.search_query);
+    let categories_json = json_list(&effective.selected_categories);
+    let tags_json = json_list(&effective.selected_tags);
+    let interests = effective
+        .selected_interests
+        .iter()
+        .map(|s| s.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    let interests_json = json_list(&interests);
+    let filters = FeedFilters {
+        search: search.as_deref(),
+        bbox: Some((
+            viewport.min_lat,
+            viewport.max_lat,
+            viewport.min_lon,
+            viewport.max_lon,
+        )),
+        categories_json: categories_json.as_deref(),
+        tags_json: tags_json.as_deref(),
+        scheduled_from: scheduled_from.as_deref(),
+        scheduled_before: scheduled_before.as_deref(),
+        min_hour: effective.evenings_only.then_some(EVENING_FROM_HOUR as i64),
+    };
+    let options = MapOptions {
+        hide_full: effective.hide_full,
+        interests_json: interests_json.as_deref(),
+        cell_deg: viewport.cluster_cell(),
+    };
+
+    let mut rows =
+        activities_repo::list_map_cells(pool, filters, options, MAX_MAP_FEATURES + 1).await?;
+    let truncated = rows.len() as i64 > MAX_MAP_FEATURES;
+    rows.truncate(MAX_MAP_FEATURES as usize);
+
+    Ok(MapFeatureCollection {
+        kind: "FeatureCollection",
+        features: rows.into_iter().map(map_feature).collect(),
+        truncated,
+    })
+}
+
+fn map_feature(row: MapCellRow) -> MapFeature {
+    let geometry = MapPoint {
+        kind: "Point",
+        coordinates: [row.longitude, row.latitude],
+    };
+    if row.activity_count > 1 {
+        return MapFeature {
+            kind: "Feature",
+            geometry,
+            bbox: Some([row.min_lng, row.min_lat, row.max_lng, row.max_lat]),
+            properties: MapFeatureProperties {
+                cluster: true,
+                count: row.activity_count,
+                ..MapFeatureProperties::default()
+            },
+        };
+    }
+
+    let (date_label, time_label) = format_scheduled_labels(&row.scheduled_at);
+    MapFeature {
+        kind: "Feature",
+        geometry,
+        bbox: None,
+        properties: MapFeatureProperties {
+            cluster: false,
+            count: 1,
+            url: Some(format!("/activities/{}", row.activity_id)),
+            activity_id: Some(row.activity_id),
+            title: Some(row.title),
+            date_label: Some(date_label),
+            time_label: Some(time_label),
+            city: row.city.filter(|c| !c.trim().is_empty()),
+            category: row.category_name.filter(|c| !c.trim().is_empty()),
+            is_full: Some(row.current_participants_count >= row.max_participants),
+        },
+    }
+}
+
+/// Naive `[from, before)` bounds on `scheduled_at` for the date filters,
+/// relative to `today` in the user's timezone.
+fn date_window(
+    filters: &AppliedActivityFilters,
+    today: LocalDate,
+) -> (Option<String>, Option<String>) {
+    match filters.when.as_str() {
+        "tonight" => (
+            Some(today.at(EVENING_FROM_HOUR, 0)),
+            Some(today.add_days(1).at(0, 0)),
+        ),
+        // Saturday and Sunday; during the weekend, what is left of it.
+        "weekend" => {
+            let weekday = today.weekday_mon0();
+            let start = today.add_days((5 - weekday).max(0));
+            (
+                Some(start.at(0, 0)),
+                Some(today.add_days(7 - weekday).at(0, 0)),
+            )
+        }
+        "week" => (Some(today.at(0, 0)), Some(today.add_days(7).at(0, 0))),
+        "custom" => (
+            LocalDate::parse(&filters.date_from).map(|d| d.at(0, 0)),
+            LocalDate::parse(&filters.date_to).map(|d| d.add_days(1).at(0, 0)),
+        ),
+        _ => (None, None),
+    }
+}
+
+/// `score 5,42 = interesses 2,00 + afstand 1,60 + …`, each part weighted.
+/// `parts` are the raw components in `RankWeights` field order.
+fn score_breakdown(score: Option<f64>, parts: [Option<f64>; 5], w: &RankWeights) -> Option<String> {
+    let nl = |v: f64| format!("{:.2}", v).replace('.', ",");
+    let labelled = [
+        ("interesses", w.interests),
+        ("afstand", w.distance),
+        ("vrienden", w.friends),
+        ("binnenkort", w.soon),
+        ("plekken", w.capacity),
+    ]
+    .iter()
+    .zip(parts)
+    .map(|((label, weight), value)| format!("{} {}", label, nl(value.unwrap_or(0.0) * weight)))
+    .collect::<Vec<_>>()
+    .join(" + ");
+    Some(format!("score {} = {}", nl(score?), labelled))
+}
+
+fn day_header_label(day: &str, today: LocalDate) -> String {
+    match LocalDate::parse(day).map(|d| d.days() - today.days()) {
+        Some(0) => "Vandaag".to_string(),
+        Some(1) => "Morgen".to_string(),
+        Some(-1) => "Gisteren".to_string(),
+        _ => format_date_nl_short(day),
     }
+}
 
-    match tab {
-        ActivitiesTab::Discover => {
-            if effective.lat.is_some() && effective.lon.is_some() {
-                cards.sort_by(|a, b| {
-                    a.distance_km
-                        .unwrap_or(f64::MAX)
-                        .partial_cmp(&b.distance_km.unwrap_or(f64::MAX))
-                        .unwrap_or(std::cmp::Ordering::Equal)
+fn json_list(values: &[String]) -> Option<String> {
+    if values.is_empty() {
+        None
+    } else {
+        serde_json::to_string(values).ok()
+    }
+}
+
+/// Splits the facet rows into category and tag chips. Selected tags outside
+/// the top list are kept so they can still be unchecked.
+fn build_facet_options(
+    facets: Vec<activities_repo::FeedFacetRow>,
+    filters: &AppliedActivityFilters,
+) -> (Vec<FacetOptionView>, Vec<FacetOptionView>) {
+    let mut categories = Vec::new();
+    let mut tags = Vec::new();
+    for row in facets {
+        let (list, selected) = match row.facet.as_str() {
+            "category" => (&mut categories, &filters.selected_categories),
+            "tag" => (&mut tags, &filters.selected_tags),
+            _ => continue,
+        };
+        list.push(FacetOptionView {
+            selected: selected.contains(&row.value),
+            value: row.value,
+            label: row.label.trim().to_string(),
+            icon_url: row
+                .icon_url
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            count: row.activity_count,
+        });
+    }
+    // Selected values without a chip (no matches left, or a category missing
+    // from `categories`) stay visible so they can be unchecked.
+    for (list, selected) in [
+        (&mut categories, &filters.selected_categories),
+        (&mut tags, &filters.selected_tags),
+    ] {
+        for value in selected {
+            if !list.iter().any(|o: &FacetOptionView| &o.value == value) {
+                list.push(FacetOptionView {
+                    value: value.clone(),
+                    label: value.clone(),
+                    icon_url: None,
+                    count: 0,
+                    selected: true,
                 });
-            } else {
-                cards.sort_by(|a, b| a.scheduled_at.cmp(&b.scheduled_at));
             }
         }
-        ActivitiesTab::Upcoming => {
-            cards.sort_by(|a, b| a.scheduled_at.cmp(&b.scheduled_at));
-        }
-        ActivitiesTab::History => {
-            cards.sort_by(|a, b| b.scheduled_at.cmp(&a.scheduled_at));
+    }
+    (categories, tags)
+}
+
+/// FTS5 query for the search box: every word as a quoted prefix term, so
+/// `pad caf` finds "Padel in het café" and operators in the input are inert.
+pub fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .take(8)
+        .map(|t| format!("\"{}\"*", t.to_lowercase()))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// The snippet comes back with `\u{2}`/`\u{3}` around hits; escape the text
+/// and turn those markers into `<mark>`.
+pub fn highlight_snippet(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() + 32);
+    for c in raw.chars() {
+        match c {
+            '\u{2}' => out.push_str("<mark class=\"search-hit\">"),
+            '\u{3}' => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
         }
     }
+    out
+}
 
-    Ok(ActivitiesPageData {
-        tab,
-        activities: cards,
-        filters: effective,
-        interest_options,
+/// `sort_key|activity_id`; neither part contains `|`.
+pub fn encode_cursor(after: &FeedAfter) -> String {
+    format!("{}|{}", after.sort_key, after.activity_id)
+}
+
+pub fn parse_cursor(raw: &str) -> Option<FeedAfter> {
+    let (sort_key, activity_id) = raw.trim().split_once('|')?;
+    if sort_key.is_empty() || activity_id.is_empty() || activity_id.contains('|') {
+        return None;
+    }
+    Some(FeedAfter {
+        sort_key: sort_key.to_string(),
+        activity_id: activity_id.to_string(),
     })
 }
 
@@ -368,7 +1042,7 @@ fn parse_participants_preview(raw_json: Option<&str>) -> Vec<ActivityParticipant
 
     let mut out = Vec::new();
     for p in parsed.into_iter().take(30) {
-        let image_id = p.photo_url.as_deref().and_then(|url| extract_image_id(url));
+        let image_id = p.photo_url.as_deref().and_then(extract_image_id);
         let name = p
             .name
             .unwrap_or_else(|| "deelnemer".to_string())
@@ -672,7 +1346,7 @@ fn choose_filler_positions(count: usize, seed_key: &str, target_size: usize) ->
         top.sort_by_key(|p| stable_seed_u64(&format!("{seed_key}:top:{p}")));
         bottom.sort_by_key(|p| stable_seed_u64(&format!("{seed_key}:bottom:{p}")));
 
-        let start_top = (stable_seed_u64(&format!("{seed_key}:start")) % 2) == 0;
+        let start_top = stable_seed_u64(&format!("{seed_key}:start")).is_multiple_of(2);
         for i in 0..remaining {
             let pick_top = if i % 2 == 0 { start_top } else { !start_top };
             let chosen = if pick_top {
@@ -933,6 +1607,10 @@ async fn load_user_context(pool: &SqlitePool, user_id: &str) -> sqlx::Result<Use
         lat: None,
         lon: None,
         default_radius: 25,
+        timezone: user_repo::load_user_timezone(pool, user_id)
+            .await?
+            .unwrap_or_default(),
+        interests: user_repo::load_user_interest_names(pool, user_id).await?,
     };
 
     if let Some(profile) = discovery_repo::load_user_profile_context(pool, user_id).await? {
@@ -971,12 +1649,35 @@ fn merge_filters(
             if !selected_interests.iter().any(|s| s.eq_ignore_ascii_case(t)) {
                 selected_interests.push(t.to_string());
             }
-            if selected_interests.len() >= 10 {
+            if selected_interests.len() >= MAX_SELECTED {
                 break;
             }
         }
     }
 
+    // A preset wins over dates; dates alone mean a custom range.
+    let parse_date = |v: Option<&String>| {
+        v.and_then(|s| LocalDate::parse(s))
+            .map(LocalDate::iso)
+            .unwrap_or_default()
+    };
+    let (when, date_from, date_to) = match query.when.as_deref().map(str::trim) {
+        Some(w @ ("tonight" | "weekend" | "week")) => (w.to_string(), String::new(), String::new()),
+        Some("custom") | None => {
+            let mut from = parse_date(query.from.as_ref());
+            let mut to = parse_date(query.to.as_ref());
+            if !from.is_empty() && !to.is_empty() && to < from {
+                std::mem::swap(&mut from, &mut to);
+            }
+            if from.is_empty() && to.is_empty() {
+                (String::new(), from, to)
+            } else {
+                ("custom".to_string(), from, to)
+            }
+        }
+        Some(_) => (String::new(), String::new(), String::new()),
+    };
+
     AppliedActivityFilters {
         tab: tab.as_str().to_string(),
         search_query: query.q.clone().unwrap_or_default(),
@@ -989,11 +1690,34 @@ fn merge_filters(
             .or_else(|| lat.zip(lon).map(|(a, o)| format!("{:.4}, {:.4}", a, o))),
         location_label: query.loc_label.clone(),
         selected_interests,
+        selected_categories: normalize_values(query.categories.as_deref()),
+        selected_tags: normalize_values(query.tags.as_deref()),
+        when,
+        date_from,
+        date_to,
+        evenings_only: query.evenings.unwrap_or(false),
+        debug_score: query.debug.as_deref() == Some("score"),
         hide_full: query.hide_full.unwrap_or(false),
         notice: query.notice.clone(),
     }
 }
 
+/// Trimmed, lowercase and deduplicated; at most [`MAX_SELECTED`].
+fn normalize_values(values: Option<&[String]>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for raw in values.unwrap_or_default() {
+        let v = raw.trim().to_lowercase();
+        if v.is_empty() || out.contains(&v) {
+            continue;
+        }
+        out.push(v);
+        if out.len() >= MAX_SELECTED {
+            break;
+        }
+    }
+    out
+}
+
 fn parse_string_array_json(raw: Option<&str>) -> Vec<String> {
     let Some(raw) = raw.map(str::trim).filter(|s| !s.is_empty()) else {
         return Vec::new();
@@ -1109,3 +1833,308 @@ fn extract_image_id(value: &str) -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let after = FeedAfter {
+            sort_key: "2031-05-03 10:30:00".to_string(),
+            activity_id: "a1".to_string(),
+        };
+        assert_eq!(parse_cursor(&encode_cursor(&after)), Some(after));
+        assert_eq!(
+            parse_cursor("0000000.000137734631|a2").map(|c| c.sort_key),
+            Some("0000000.000137734631".to_string())
+        );
+        assert_eq!(parse_cursor("no-separator"), None);
+        assert_eq!(parse_cursor("|a1"), None);
+        assert_eq!(parse_cursor("k|a|b"), None);
+    }
+
+    #[test]
+    fn filters_round_trip_through_the_url() {
+        let query = ActivitiesQuery::from_query_string(
+            "tab=history&q=padel+caf%C3%A9&radius_km=40&lat=52.1&lon=4.3&loc_label=Leiden\
+             &category=Sport&category=eten&category=sport&tag=Buiten&tag=&interests=Padel\
+             &hide_full=true&notice=join_ok&after=k%7Ca1&bogus=1",
+        );
+        assert_eq!(query.after.as_deref(), Some("k|a1"));
+        let filters = merge_filters(&query, &UserContext::default(), ActivitiesTab::History);
+        assert_eq!(filters.selected_categories, vec!["sport", "eten"]);
+        assert_eq!(filters.selected_tags, vec!["buiten"]);
+        assert_eq!(filters.search_query, "padel café");
+
+        let again = ActivitiesQuery::from_query_string(&filters.query_string());
+        assert!(again.notice.is_none() && again.after.is_none());
+        let filters2 = merge_filters(&again, &UserContext::default(), ActivitiesTab::History);
+        assert_eq!(filters2.query_string(), filters.query_string());
+        assert_eq!(filters2.selected_categories, filters.selected_categories);
+        assert_eq!(filters2.selected_tags, filters.selected_tags);
+        assert_eq!(filters2.selected_interests, vec!["Padel"]);
+        assert_eq!(filters2.radius_km, 40);
+        assert_eq!(filters2.lat, Some(52.1));
+        assert!(filters2.hide_full);
+    }
+
+    #[test]
+    fn date_filters_resolve_in_local_days() {
+        let query = ActivitiesQuery::from_query_string("when=custom&from=2031-05-10&to=2031-05-03");
+        let filters = merge_filters(&query, &UserContext::default(), ActivitiesTab::Discover);
+        assert_eq!(
+            (filters.date_from.as_str(), filters.date_to.as_str()),
+            ("2031-05-03", "2031-05-10")
+        );
+        assert_eq!(
+            ActivitiesQuery::from_query_string(&filters.query_string())
+                .from
+                .as_deref(),
+            Some("2031-05-03")
+        );
+
+        // Thursday 2031-05-01.
+        let thursday = LocalDate::parse("2031-05-01").unwrap();
+        let window = |when: &str, today| {
+            let query = ActivitiesQuery::from_query_string(&format!("when={}", when));
+            date_window(
+                &merge_filters(&query, &UserContext::default(), ActivitiesTab::Discover),
+                today,
+            )
+        };
+        assert_eq!(
+            window("tonight", thursday),
+            (
+                Some("2031-05-01 18:00:00".to_string()),
+                Some("2031-05-02 00:00:00".to_string())
+            )
+        );
+        assert_eq!(
+            window("weekend", thursday),
+            (
+                Some("2031-05-03 00:00:00".to_string()),
+                Some("2031-05-05 00:00:00".to_string())
+            )
+        );
+        assert_eq!(
+            window("weekend", thursday.add_days(3)),
+            (
+                Some("2031-05-04 00:00:00".to_string()),
+                Some("2031-05-05 00:00:00".to_string())
+            )
+        );
+        assert_eq!(
+            window("week", thursday).1.as_deref(),
+            Some("2031-05-08 00:00:00")
+        );
+        assert_eq!(window("someday", thursday), (None, None));
+        assert_eq!(day_header_label("2031-05-02", thursday), "Morgen");
+    }
+
+    #[test]
+    fn breaks_down_weighted_scores() {
+        assert_eq!(
+            score_breakdown(Some(4.25), [Some(1.0 / 3.0), Some(0.5), None, Some(1.0), Some(0.25)], &RANK_WEIGHTS)
+                .as_deref(),
+            Some("score 4,25 = interesses 1,00 + afstand 1,00 + vrienden 0,00 + binnenkort 1,50 + plekken 0,25")
+        );
+        assert_eq!(score_breakdown(None, [Some(1.0); 5], &RANK_WEIGHTS), None);
+    }
+
+    #[test]
+    fn builds_prefix_fts_queries() {
+        assert_eq!(
+            fts_query("  Padel, café!").as_deref(),
+            Some("\"padel\"* \"café\"*")
+        );
+        assert_eq!(
+            fts_query("\"a\" OR NEAR(b"),
+            Some("\"a\"* \"or\"* \"near\"* \"b\"*".to_string())
+        );
+        assert_eq!(fts_query(" -*- "), None);
+    }
+
+    #[test]
+    fn keeps_selected_values_without_a_chip() {
+        let query = ActivitiesQuery::from_query_string("category=Muziek&category=sport&tag=padel");
+        let filters = merge_filters(&query, &UserContext::default(), ActivitiesTab::Discover);
+        let facets = vec![activities_repo::FeedFacetRow {
+            facet: "category".to_string(),
+            value: "sport".to_string(),
+            label: "Sport".to_string(),
+            icon_url: None,
+            activity_count: 4,
+        }];
+
+        let (categories, tags) = build_facet_options(facets, &filters);
+        let chips = |list: &[FacetOptionView]| {
+            list.iter()
+                .map(|o| (o.value.clone(), o.count, o.selected))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            chips(&categories),
+            [
+                ("sport".to_string(), 4, true),
+                ("muziek".to_string(), 0, true)
+            ]
+        );
+        assert_eq!(chips(&tags), [("padel".to_string(), 0, true)]);
+    }
+
+    #[test]
+    fn highlights_escaped_snippets() {
+        assert_eq!(
+            highlight_snippet("<b>\u{2}Padel\u{3}</b> & co"),
+            "&lt;b&gt;<mark class=\"search-hit\">Padel</mark>&lt;/b&gt; &amp; co"
+        );
+    }
+
+    #[test]
+    fn map_viewport_clusters_until_zoomed_in() {
+        let v =
+            MapViewport::from_query_string("bbox=3.3%2C50.7%2C7.3%2C53.6&zoom=7&tab=map").unwrap();
+        assert_eq!(
+            (v.min_lon, v.min_lat, v.max_lon, v.max_lat),
+            (3.3, 50.7, 7.3, 53.6)
+        );
+        let (lon, lat) = v.cluster_cell().unwrap();
+        assert!((lon - 360.0 / 128.0 / 4.0).abs() < 1e-9);
+        assert!(lat < lon && lat > lon * 0.6);
+
+        let close = MapViewport::from_query_string("bbox=4.8,52.3,4.9,52.4&zoom=16").unwrap();
+        assert_eq!(close.cluster_cell(), None);
+        let wrapped = MapViewport::from_query_string("bbox=-190,-10,200,10&zoom=2").unwrap();
+        assert_eq!((wrapped.min_lon, wrapped.max_lon), (-180.0, 180.0));
+
+        let fractional = MapViewport::from_query_string("bbox=4,52,5,53&zoom=8.5").unwrap();
+        assert_eq!(fractional.zoom, 8);
+        let far = MapViewport::from_query_string("bbox=4,52,5,53&zoom=-1").unwrap();
+        assert_eq!(far.zoom, 0);
+
+        assert_eq!(MapViewport::from_query_string("zoom=7"), None);
+        assert_eq!(MapViewport::from_query_string("bbox=1,2,3"), None);
+        assert_eq!(MapViewport::from_query_string("bbox=5,52,4,53"), None);
+        assert_eq!(MapViewport::from_query_string("bbox=4,NaN,5,53"), None);
+    }
+
+    // 1200 Discover activities, 20 per day on the same hour so `sort_key`
+    // ties. The first 800 carry no selected interest, more than one page's
+    // batches can scan; every fifth is full; the grid reaches past the
+    // radius, into the bounding box's corners and beyond it.
+    const SQL_SEED_PAGING: &str = r#"
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1200)
+INSERT INTO activities (
+    activity_id, title, scheduled_at, max_participants, current_participants_count,
+    status, is_joined, is_deleted, latitude, longitude, tags, row_hash, changed_at
+)
+SELECT
+    printf('a%04d', i), 'Activiteit ' || i,
+    strftime('%Y-%m-%dT%H:00:00', 'now', '+' || (1 + i / 20) || ' days'),
+    10, CASE WHEN i % 5 = 0 THEN 10 ELSE 3 END,
+    'published', 0, 0,
+    52.09 + ((i * 37) % 41 - 20) * 0.004,
+    5.12 + ((i * 53) % 43 - 21) * 0.0065,
+    CASE WHEN i <= 800 THEN '["yoga"]' ELSE '["padel"]' END,
+    'h', 't'
+FROM n
+"#;
+
+    #[tokio::test]
+    async fn feed_pages_cover_every_visible_activity_once() {
+        let pool = crate::database::migrations::schema_test_pool().await;
+        sqlx::query(SQL_SEED_PAGING).execute(&pool).await.unwrap();
+
+        let (lat, lon, radius_km) = (52.09, 5.12, 8.0);
+        let (min_lat, max_lat, min_lon, max_lon) = bounding_box(lat, lon, radius_km);
+        let seeded: Vec<(String, f64, f64, String, i64)> = sqlx::query_as(
+            "SELECT activity_id, latitude, longitude, tags, current_participants_count \
+             FROM activities",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let mut expected: Vec<String> = seeded
+            .into_iter()
+            .filter(|(_, la, lo, tags, count)| {
+                tags.contains("padel")
+                    && *count < 10
+                    && (min_lat..=max_lat).contains(la)
+                    && (min_lon..=max_lon).contains(lo)
+                    && haversine_km(lat, lon, *la, *lo) <= radius_km
+            })
+            .map(|(id, ..)| id)
+            .collect();
+        expected.sort();
+        assert!(expected.len() > 3 * PAGE_SIZE);
+
+        let filters = "tab=discover&lat=52.09&lon=5.12&radius_km=8&interests=padel&hide_full=1";
+        // A date window keeps Discover in time order; without one it is ranked.
+        for (order, raw) in [
+            ("time", format!("{}&from=2000-01-01", filters)),
+            ("score", filters.to_string()),
+        ] {
+            let mut query = ActivitiesQuery::from_query_string(&raw);
+            let mut seen = Vec::new();
+            let mut short_pages = 0;
+            for _ in 0..100 {
+                let page = build_activities_page(&pool, "me", &query).await.unwrap();
+                seen.extend(page.activities.iter().map(|c| c.activity_id.clone()));
+                match page.next_cursor {
+                    Some(cursor) => {
+                        short_pages += usize::from(page.activities.len() < PAGE_SIZE);
+                        query.after = Some(cursor);
+                    }
+                    None => break,
+                }
+            }
+            assert!(query.after.is_some(), "{}: a single page", order);
+
+            let mut unique = seen.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), seen.len(), "{}: repeated ids", order);
+            assert_eq!(unique, expected, "{}: missing or extra ids", order);
+            if order == "time" {
+                // The skipped yoga block alone fills a page's batches.
+                assert!(short_pages > 0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn map_clusters_and_truncates_the_viewport() {
+        let pool = crate::database::migrations::schema_test_pool().await;
+        sqlx::query(SQL_SEED_PAGING).execute(&pool).await.unwrap();
+        let map = |raw: &str| {
+            let viewport = MapViewport::from_query_string(raw).unwrap();
+            let query = ActivitiesQuery::from_query_string(raw);
+            let pool = pool.clone();
+            async move {
+                build_activities_map(&pool, "me", &query, viewport)
+                    .await
+                    .unwrap()
+            }
+        };
+        let count = |map: &MapFeatureCollection| -> i64 {
+            map.features.iter().map(|f| f.properties.count).sum()
+        };
+        let bbox = "tab=map&bbox=4.9,51.9,5.4,52.3";
+
+        let close = map(&format!("{}&zoom=16", bbox)).await;
+        assert!(close.truncated);
+        assert_eq!(close.features.len() as i64, MAX_MAP_FEATURES);
+        assert!(close.features.iter().all(|f| !f.properties.cluster));
+
+        let grid = map(&format!("{}&zoom=10.7", bbox)).await;
+        assert!(!grid.truncated);
+        assert!(grid.features.len() < 50);
+        assert!(grid.features.iter().any(|f| f.properties.cluster));
+        assert_eq!(count(&grid), 1200);
+
+        // Interests and "hide full" narrow the clusters themselves.
+        let padel = map(&format!("{}&zoom=10&interests=padel&hide_full=1", bbox)).await;
+        assert_eq!(count(&padel), 320);
+    }
+}
@@ -0,0 +1,295 @@
+//! Attendance check-in for activity managers.
+//!
+//! Registered participants are marked `attended` or `no_show` in bulk, one
+//! `activity_attendance_commands` row per submit, and only inside the
+//! check-in window around the activity.
+
+use serde_json::json;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::activity_attendance_commands_repo::{
+    self, AttendanceActivityRow, NewActivityAttendanceCommand,
+};
+use crate::database::activity_detail_repo;
+use crate::models::ActivityParticipantsRow;
+use crate::services::activity_summary_service::{extract_image_id, format_scheduled_labels};
+use crate::services::central_apply_service::{self, ApplyOutcome, DomainError, ServiceError};
+
+/// Check-in opens this long before `scheduled_at`.
+pub const CHECK_IN_OPENS_BEFORE_MINUTES: i64 = 30;
+/// End of the activity when `duration_minutes` is unknown.
+pub const DEFAULT_DURATION_MINUTES: i64 = 120;
+/// Check-in stays open this long after the activity ended.
+pub const CHECK_IN_CLOSES_AFTER_MINUTES: i64 = 24 * 60;
+
+/// `registered` clears an earlier mark.
+pub const ATTENDANCE_STATUSES: &[&str] = &["attended", "no_show", "registered"];
+
+pub struct AttendeeView {
+    pub user_id: String,
+    pub name: String,
+    pub photo_image_id: Option<String>,
+    pub role: Option<String>,
+    /// `registered` | `attended` | `no_show`
+    pub attendance_status: String,
+}
+
+pub struct AttendanceView {
+    pub activity_id: String,
+    pub title: String,
+    pub status: String,
+    pub scheduled_date_label: String,
+    pub scheduled_time_label: String,
+    /// `before` | `open` | `closed`
+    pub window_state: String,
+    /// e.g. `za 3 mei • 10:00`
+    pub opens_label: String,
+    pub closes_label: String,
+    pub can_manage_attendance: bool,
+    pub attendees: Vec<AttendeeView>,
+    pub attended_count: usize,
+    pub no_show_count: usize,
+}
+
+impl AttendanceView {
+    pub fn can_check_in(&self) -> bool {
+        self.window_state == "open" && self.status != "cancelled"
+    }
+}
+
+#[derive(Debug)]
+pub enum AttendanceError {
+    NotFound,
+    Forbidden,
+    Cancelled,
+    NotOpenYet,
+    WindowClosed,
+    NoSelection,
+    InvalidStatus,
+    /// Selected user is not a registered participant.
+    UnknownParticipant,
+}
+
+impl DomainError for AttendanceError {
+    /// Notice code for the attendance page.
+    fn notice(&self) -> &'static str {
+        match self {
+            AttendanceError::NotFound | AttendanceError::Forbidden => "not_allowed",
+            AttendanceError::Cancelled => "activity_cancelled",
+            AttendanceError::NotOpenYet => "check_in_not_open",
+            AttendanceError::WindowClosed => "check_in_closed",
+            AttendanceError::NoSelection => "no_selection",
+            AttendanceError::InvalidStatus | AttendanceError::UnknownParticipant => "rejected",
+        }
+    }
+}
+
+async fn load_activity(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Option<AttendanceActivityRow>> {
+    activity_attendance_commands_repo::load_attendance_activity(
+        pool,
+        activity_id,
+        CHECK_IN_OPENS_BEFORE_MINUTES,
+        DEFAULT_DURATION_MINUTES,
+        CHECK_IN_CLOSES_AFTER_MINUTES,
+    )
+    .await
+}
+
+/// Only registered participants take part in check-in.
+fn registered(participants: Vec<ActivityParticipantsRow>) -> Vec<ActivityParticipantsRow> {
+    participants
+        .into_iter()
+        .filter(|p| p.participation_status.as_deref().unwrap_or("registered") == "registered")
+        .collect()
+}
+
+fn label(timestamp: &str) -> String {
+    let (date, time) = format_scheduled_labels(timestamp);
+    format!("{} • {}", date, time)
+}
+
+pub async fn load_attendance_view(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> sqlx::Result<Option<AttendanceView>> {
+    let Some(row) = load_activity(pool, activity_id).await? else {
+        return Ok(None);
+    };
+    let participants =
+        registered(activity_detail_repo::list_activity_participants(pool, activity_id).await?);
+
+    let attendees: Vec<AttendeeView> = participants
+        .into_iter()
+        .map(|p| AttendeeView {
+            name: p
+                .name
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "Onbekend".to_string()),
+            photo_image_id: p.photo_url.as_deref().and_then(extract_image_id),
+            role: p.role,
+            attendance_status: p
+                .attendance_status
+                .filter(|s| s == "attended" || s == "no_show")
+                .unwrap_or_else(|| "registered".to_string()),
+            user_id: p.user_id,
+        })
+        .collect();
+    let count = |status: &str| {
+        attendees
+            .iter()
+            .filter(|a| a.attendance_status == status)
+            .count()
+    };
+
+    let (scheduled_date_label, scheduled_time_label) = format_scheduled_labels(&row.scheduled_at);
+    Ok(Some(AttendanceView {
+        attended_count: count("attended"),
+        no_show_count: count("no_show"),
+        activity_id: row.activity_id,
+        title: row.title,
+        status: row.status,
+        scheduled_date_label,
+        scheduled_time_label,
+        window_state: row.window_state,
+        opens_label: label(&row.opens_at),
+        closes_label: label(&row.closes_at),
+        can_manage_attendance: row.can_manage_attendance == 1,
+        attendees,
+    }))
+}
+
+/// Who may mark attendance right now.
+pub fn check_window(row: &AttendanceActivityRow) -> Result<(), AttendanceError> {
+    if row.can_manage_attendance != 1 {
+        return Err(AttendanceError::Forbidden);
+    }
+    if row.status == "cancelled" {
+        return Err(AttendanceError::Cancelled);
+    }
+    match row.window_state.as_str() {
+        "open" => Ok(()),
+        "before" => Err(AttendanceError::NotOpenYet),
+        _ => Err(AttendanceError::WindowClosed),
+    }
+}
+
+/// The `marks` JSON for one bulk submit; every selected user must be one of
+/// `registered_ids`.
+pub fn build_marks(
+    user_ids: &[String],
+    registered_ids: &[&str],
+    status: &str,
+) -> Result<String, AttendanceError> {
+    if !ATTENDANCE_STATUSES.contains(&status) {
+        return Err(AttendanceError::InvalidStatus);
+    }
+    let mut selected: Vec<&str> = Vec::new();
+    for user_id in user_ids.iter().map(|u| u.trim()).filter(|u| !u.is_empty()) {
+        if !registered_ids.contains(&user_id) {
+            return Err(AttendanceError::UnknownParticipant);
+        }
+        if !selected.contains(&user_id) {
+            selected.push(user_id);
+        }
+    }
+    if selected.is_empty() {
+        return Err(AttendanceError::NoSelection);
+    }
+    let marks: Vec<_> = selected
+        .iter()
+        .map(|user_id| json!({ "user_id": user_id, "status": status }))
+        .collect();
+    Ok(serde_json::Value::from(marks).to_string())
+}
+
+pub async fn mark_attendance(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    activity_id: &str,
+    user_ids: &[String],
+    status: &str,
+) -> Result<ApplyOutcome, ServiceError<AttendanceError>> {
+    let row = load_activity(pool, activity_id)
+        .await?
+        .ok_or(AttendanceError::NotFound)?;
+    check_window(&row)?;
+
+    let participants =
+        registered(activity_detail_repo::list_activity_participants(pool, activity_id).await?);
+    let registered_ids: Vec<&str> = participants.iter().map(|p| p.user_id.as_str()).collect();
+    let marks = build_marks(user_ids, &registered_ids, status)?;
+
+    let id = Uuid::new_v4().to_string();
+    let res = activity_attendance_commands_repo::insert_attendance_command(
+        pool,
+        NewActivityAttendanceCommand {
+            id: &id,
+            actor_user_id,
+            activity_id,
+            marks: &marks,
+            note: Some("website"),
+        },
+    )
+    .await;
+    Ok(central_apply_service::resolve_command(&id, res)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(status: &str, window_state: &str) -> AttendanceActivityRow {
+        AttendanceActivityRow {
+            status: status.to_string(),
+            can_manage_attendance: 1,
+            window_state: window_state.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn enforces_the_check_in_window() {
+        assert!(check_window(&row("published", "open")).is_ok());
+        assert!(check_window(&row("completed", "open")).is_ok());
+        assert!(matches!(
+            check_window(&row("published", "before")),
+            Err(AttendanceError::NotOpenYet)
+        ));
+        assert!(matches!(
+            check_window(&row("published", "closed")),
+            Err(AttendanceError::WindowClosed)
+        ));
+        assert!(matches!(
+            check_window(&row("cancelled", "open")),
+            Err(AttendanceError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn builds_bulk_marks_for_registered_participants() {
+        let ids = vec!["u1".to_string(), "u2".to_string(), "u1".to_string()];
+        let marks = build_marks(&ids, &["u1", "u2", "u3"], "no_show").unwrap();
+        assert_eq!(
+            marks,
+            r#"[{"status":"no_show","user_id":"u1"},{"status":"no_show","user_id":"u2"}]"#
+        );
+
+        assert!(matches!(
+            build_marks(&ids, &["u1"], "attended"),
+            Err(AttendanceError::UnknownParticipant)
+        ));
+        assert!(matches!(
+            build_marks(&[], &["u1"], "attended"),
+            Err(AttendanceError::NoSelection)
+        ));
+        assert!(matches!(
+            build_marks(&ids, &["u1", "u2"], "late"),
+            Err(AttendanceError::InvalidStatus)
+        ));
+    }
+}
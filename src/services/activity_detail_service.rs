@@ -6,6 +6,7 @@ use crate::database::{
     activity_detail_repo, activity_signup_commands_repo, activity_waitlist_commands_repo,
 };
 use crate::models::{ActivitiesRow, ActivityParticipantsRow};
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
 
 #[derive(Debug, Deserialize, Default)]
 pub struct ActivityDetailQuery {
@@ -145,9 +146,9 @@ pub async fn create_signup_command(
     activity_id: &str,
     subject_user_id: &str,
     action: &str,
-) -> sqlx::Result<()> {
+) -> Result<ApplyOutcome, CommandError> {
     let id = Uuid::new_v4().to_string();
-    activity_signup_commands_repo::insert_signup_command(
+    let res = activity_signup_commands_repo::insert_signup_command(
         pool,
         activity_signup_commands_repo::NewActivitySignupCommand {
             id: &id,
@@ -158,8 +159,8 @@ pub async fn create_signup_command(
             note: Some("website"),
         },
     )
-    .await?;
-    Ok(())
+    .await;
+    central_apply_service::resolve_command(&id, res)
 }
 
 pub async fn create_waitlist_command(
@@ -169,9 +170,9 @@ pub async fn create_waitlist_command(
     subject_user_id: &str,
     action: &str,
     priority: Option<i64>,
-) -> sqlx::Result<()> {
+) -> Result<ApplyOutcome, CommandError> {
     let id = Uuid::new_v4().to_string();
-    activity_waitlist_commands_repo::insert_waitlist_command(
+    let res = activity_waitlist_commands_repo::insert_waitlist_command(
         pool,
         activity_waitlist_commands_repo::NewActivityWaitlistCommand {
             id: &id,
@@ -183,8 +184,8 @@ pub async fn create_waitlist_command(
             note: Some("website"),
         },
     )
-    .await?;
-    Ok(())
+    .await;
+    central_apply_service::resolve_command(&id, res)
 }
 
 fn format_scheduled_labels(scheduled_at: &str) -> (String, String) {
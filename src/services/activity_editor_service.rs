@@ -0,0 +1,698 @@
+//! Create/edit form for activities. Submissions become `activity_commands`
+//! rows; only users with `can_manage_activity` may edit an existing one.
+
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::activity_commands_repo::{
+    self, CategoryOptionRow, EditableActivityRow, NewActivityCommand,
+};
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
+use crate::services::location_service;
+
+pub const ACTIVITY_TYPES: &[(&str, &str)] = &[
+    ("standard", "Standaard"),
+    ("xxl", "XXL"),
+    ("womens_only", "Alleen vrouwen"),
+    ("mens_only", "Alleen mannen"),
+];
+
+pub const PRIVACY_LEVELS: &[(&str, &str)] = &[
+    ("public", "Openbaar"),
+    ("friends_only", "Alleen vrienden"),
+    ("invite_only", "Alleen op uitnodiging"),
+];
+
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("nl", "Nederlands"),
+    ("en", "Engels"),
+    ("de", "Duits"),
+    ("fr", "Frans"),
+    ("es", "Spaans"),
+];
+
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 30;
+const COVER_OPTIONS_LIMIT: i64 = 24;
+
+/// Raw form fields; also used to prefill the form.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ActivityForm {
+    pub title: String,
+    pub description: String,
+    pub activity_type: String,
+    pub privacy_level: String,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// `HH:MM`
+    pub time: String,
+    pub duration_minutes: String,
+    pub max_participants: String,
+    pub language: String,
+    /// Comma separated.
+    pub tags: String,
+    pub category_id: String,
+    pub venue_name: String,
+    pub venue_city: String,
+    pub latitude: String,
+    pub longitude: String,
+    pub place_id: String,
+    pub cover_image_id: String,
+    /// `draft` | `published`
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Venue {
+    pub name: String,
+    pub city: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub place_id: Option<String>,
+}
+
+/// A form that passed validation. `venue` is `None` when only a venue name
+/// was typed; it is then looked up before submitting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidActivity {
+    pub title: String,
+    pub description: Option<String>,
+    pub activity_type: &'static str,
+    pub privacy_level: &'static str,
+    pub status: &'static str,
+    pub scheduled_at: String,
+    pub duration_minutes: Option<i64>,
+    pub max_participants: i64,
+    pub language: &'static str,
+    pub tags: Vec<String>,
+    pub category_id: Option<String>,
+    pub venue_name: String,
+    pub venue: Option<Venue>,
+    pub cover_image_id: Option<String>,
+}
+
+/// What the form may choose from, plus the state it may not undo.
+pub struct FormContext<'a> {
+    pub category_ids: Vec<&'a str>,
+    pub cover_ids: Vec<&'a str>,
+    /// Status of the activity being edited; published cannot go back to draft.
+    pub current_status: Option<&'a str>,
+    pub current_participants: i64,
+}
+
+pub struct ActivityFormView {
+    /// `None` for a new activity.
+    pub activity_id: Option<String>,
+    pub form: ActivityForm,
+    pub is_published: bool,
+    pub categories: Vec<CategoryOptionRow>,
+    pub covers: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl ActivityFormView {
+    pub fn action_url(&self) -> String {
+        match &self.activity_id {
+            Some(id) => format!("/activities/{}/edit", id),
+            None => "/activities/new".to_string(),
+        }
+    }
+
+    pub fn type_options(&self) -> Vec<SelectOption> {
+        select_options(ACTIVITY_TYPES, &self.form.activity_type)
+    }
+
+    pub fn privacy_options(&self) -> Vec<SelectOption> {
+        select_options(PRIVACY_LEVELS, &self.form.privacy_level)
+    }
+
+    pub fn language_options(&self) -> Vec<SelectOption> {
+        select_options(LANGUAGES, &self.form.language)
+    }
+
+    pub fn is_cover(&self, image_id: &str) -> bool {
+        self.form.cover_image_id.trim() == image_id
+    }
+}
+
+pub struct SelectOption {
+    pub value: &'static str,
+    pub label: &'static str,
+    pub selected: bool,
+}
+
+fn select_options(options: &[(&'static str, &'static str)], current: &str) -> Vec<SelectOption> {
+    options
+        .iter()
+        .map(|(value, label)| SelectOption {
+            value,
+            label,
+            selected: *value == current.trim(),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum EditorError {
+    NotFound,
+    Forbidden,
+    /// Validation messages, in form order.
+    Invalid(Vec<String>),
+    Command(CommandError),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for EditorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditorError::NotFound => write!(f, "activity not found"),
+            EditorError::Forbidden => write!(f, "not allowed to manage this activity"),
+            EditorError::Invalid(errors) => write!(f, "invalid form: {}", errors.join(" ")),
+            EditorError::Command(e) => write!(f, "{}", e),
+            EditorError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for EditorError {
+    fn from(e: sqlx::Error) -> Self {
+        EditorError::Database(e)
+    }
+}
+
+impl From<CommandError> for EditorError {
+    fn from(e: CommandError) -> Self {
+        EditorError::Command(e)
+    }
+}
+
+fn pick(options: &[(&'static str, &'static str)], value: &str) -> Option<&'static str> {
+    options.iter().map(|(v, _)| *v).find(|v| *v == value.trim())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let v = value.trim();
+    (!v.is_empty()).then(|| v.to_string())
+}
+
+fn valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    match parts.as_slice() {
+        [y, m, d] if y.len() == 4 && m.len() == 2 && d.len() == 2 => {
+            let (Ok(_), Ok(m), Ok(d)) = (y.parse::<u32>(), m.parse::<u32>(), d.parse::<u32>())
+            else {
+                return false;
+            };
+            (1..=12).contains(&m) && (1..=31).contains(&d)
+        }
+        _ => false,
+    }
+}
+
+fn valid_time(time: &str) -> bool {
+    match time.split_once(':') {
+        Some((h, m)) if h.len() == 2 && m.len() == 2 => {
+            matches!((h.parse::<u32>(), m.parse::<u32>()), (Ok(h), Ok(m)) if h < 24 && m < 60)
+        }
+        _ => false,
+    }
+}
+
+/// `YYYY-MM-DD` and `HH:MM` as the naive timestamp stored in `scheduled_at`.
+pub fn parse_schedule(date: &str, time: &str) -> Option<String> {
+    let (date, time) = (date.trim(), time.trim());
+    (valid_date(date) && valid_time(time)).then(|| format!("{}T{}:00", date, time))
+}
+
+/// Comma separated tags: trimmed, without leading `#`, deduplicated
+/// case-insensitively.
+pub fn parse_tags(value: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in value
+        .split(',')
+        .map(|t| t.trim().trim_start_matches('#').trim())
+    {
+        if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Checks the form without touching the database. Whether the moment lies in
+/// the future is checked on submit.
+pub fn validate(form: &ActivityForm, ctx: &FormContext) -> Result<ValidActivity, Vec<String>> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut error = |msg: &str| errors.push(msg.to_string());
+
+    let title = form.title.trim().to_string();
+    if !(3..=100).contains(&title.chars().count()) {
+        error("Geef een titel van 3 tot 100 tekens.");
+    }
+    let description = non_empty(&form.description);
+    if description
+        .as_ref()
+        .is_some_and(|d| d.chars().count() > 2000)
+    {
+        error("De beschrijving mag maximaal 2000 tekens zijn.");
+    }
+
+    let activity_type = pick(ACTIVITY_TYPES, &form.activity_type);
+    if activity_type.is_none() {
+        error("Kies een type activiteit.");
+    }
+    let privacy_level = pick(PRIVACY_LEVELS, &form.privacy_level);
+    if privacy_level.is_none() {
+        error("Kies wie de activiteit kan zien.");
+    }
+    let language = pick(LANGUAGES, &form.language);
+    if language.is_none() {
+        error("Kies een taal.");
+    }
+
+    let scheduled_at = parse_schedule(&form.date, &form.time);
+    if scheduled_at.is_none() {
+        error("Kies een datum en tijd.");
+    }
+
+    let duration_minutes = match form.duration_minutes.trim() {
+        "" => None,
+        v => match v.parse::<i64>() {
+            Ok(m) if (15..=24 * 60).contains(&m) => Some(m),
+            _ => {
+                error("De duur moet tussen 15 minuten en 24 uur liggen.");
+                None
+            }
+        },
+    };
+
+    let max_participants = match form.max_participants.trim().parse::<i64>() {
+        Ok(n) if (2..=500).contains(&n) => {
+            if n < ctx.current_participants {
+                error("Er doen al meer mensen mee dan het nieuwe maximum.");
+            }
+            n
+        }
+        _ => {
+            error("Het aantal deelnemers moet tussen 2 en 500 liggen.");
+            0
+        }
+    };
+
+    let tags = parse_tags(&form.tags);
+    if tags.len() > MAX_TAGS || tags.iter().any(|t| t.chars().count() > MAX_TAG_LEN) {
+        error("Gebruik maximaal 10 tags van elk hooguit 30 tekens.");
+    }
+
+    let category_id = non_empty(&form.category_id);
+    if category_id
+        .as_deref()
+        .is_some_and(|c| !ctx.category_ids.contains(&c))
+    {
+        error("Kies een categorie uit de lijst.");
+    }
+
+    let venue_name = form.venue_name.trim().to_string();
+    let coords = (
+        form.latitude.trim().parse::<f64>().ok(),
+        form.longitude.trim().parse::<f64>().ok(),
+    );
+    let venue = match coords {
+        (Some(latitude), Some(longitude))
+            if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+        {
+            Some(Venue {
+                name: venue_name.clone(),
+                city: non_empty(&form.venue_city),
+                latitude,
+                longitude,
+                place_id: non_empty(&form.place_id),
+            })
+        }
+        _ => None,
+    };
+    if venue_name.is_empty() {
+        error("Kies een locatie.");
+    }
+
+    let cover_image_id = non_empty(&form.cover_image_id);
+    if cover_image_id
+        .as_deref()
+        .is_some_and(|c| !ctx.cover_ids.contains(&c))
+    {
+        error("Kies een omslagfoto uit de lijst.");
+    }
+
+    let status = match (form.status.trim(), ctx.current_status) {
+        ("published", _) => "published",
+        (_, Some("published")) => {
+            error("Een gepubliceerde activiteit kan geen concept meer worden.");
+            "published"
+        }
+        _ => "draft",
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ValidActivity {
+        title,
+        description,
+        activity_type: activity_type.unwrap_or("standard"),
+        privacy_level: privacy_level.unwrap_or("public"),
+        status,
+        scheduled_at: scheduled_at.unwrap_or_default(),
+        duration_minutes,
+        max_participants,
+        language: language.unwrap_or("nl"),
+        tags,
+        category_id,
+        venue_name,
+        venue,
+        cover_image_id,
+    })
+}
+
+fn form_from_row(row: &EditableActivityRow) -> ActivityForm {
+    let location: serde_json::Value = row
+        .location
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    let category: serde_json::Value = row
+        .category
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    let tags: Vec<String> = row
+        .tags
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    let text = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    };
+    let coord = |v: Option<f64>, key: &str| {
+        v.or_else(|| location[key].as_f64())
+            .map(|c| c.to_string())
+            .unwrap_or_default()
+    };
+
+    ActivityForm {
+        title: row.title.clone(),
+        description: row.description.clone().unwrap_or_default(),
+        activity_type: row.activity_type.clone().unwrap_or_default(),
+        privacy_level: row.privacy_level.clone().unwrap_or_default(),
+        date: row.scheduled_at.get(0..10).unwrap_or("").to_string(),
+        time: row.scheduled_at.get(11..16).unwrap_or("").to_string(),
+        duration_minutes: row
+            .duration_minutes
+            .map(|d| d.to_string())
+            .unwrap_or_default(),
+        max_participants: row.max_participants.to_string(),
+        language: row.language.clone().unwrap_or_default(),
+        tags: tags.join(", "),
+        category_id: text(&category["category_id"]),
+        venue_name: text(&location["venue_name"]),
+        venue_city: row.city.clone().unwrap_or_else(|| text(&location["city"])),
+        latitude: coord(row.latitude, "latitude"),
+        longitude: coord(row.longitude, "longitude"),
+        place_id: text(&location["place_id"]),
+        cover_image_id: row.main_photo_asset_id.clone().unwrap_or_default(),
+        status: row.status.clone().unwrap_or_default(),
+    }
+}
+
+fn new_form() -> ActivityForm {
+    ActivityForm {
+        activity_type: "standard".to_string(),
+        privacy_level: "public".to_string(),
+        duration_minutes: "120".to_string(),
+        max_participants: "10".to_string(),
+        language: "nl".to_string(),
+        status: "draft".to_string(),
+        ..Default::default()
+    }
+}
+
+async fn load_managed(
+    pool: &SqlitePool,
+    activity_id: &str,
+) -> Result<EditableActivityRow, EditorError> {
+    let row = activity_commands_repo::load_editable_activity(pool, activity_id)
+        .await?
+        .ok_or(EditorError::NotFound)?;
+    // Cancelled and completed activities are closed; an update would reopen them.
+    let closed = matches!(row.status.as_deref(), Some("cancelled" | "completed"));
+    if row.can_manage_activity != 1 || closed {
+        return Err(EditorError::Forbidden);
+    }
+    Ok(row)
+}
+
+/// Empty form for `/activities/new`, or the activity's current values.
+pub async fn load_form(
+    pool: &SqlitePool,
+    user_id: &str,
+    activity_id: Option<&str>,
+) -> Result<ActivityFormView, EditorError> {
+    let form = match activity_id {
+        Some(id) => form_from_row(&load_managed(pool, id).await?),
+        None => new_form(),
+    };
+    form_view(pool, user_id, activity_id, form, vec![]).await
+}
+
+/// Re-renders a submitted form with its validation messages.
+pub async fn form_view(
+    pool: &SqlitePool,
+    user_id: &str,
+    activity_id: Option<&str>,
+    form: ActivityForm,
+    errors: Vec<String>,
+) -> Result<ActivityFormView, EditorError> {
+    let is_published = match activity_id {
+        Some(id) => load_managed(pool, id).await?.status.as_deref() == Some("published"),
+        None => false,
+    };
+    let mut covers =
+        activity_commands_repo::list_cover_options(pool, user_id, COVER_OPTIONS_LIMIT).await?;
+    // Keep the current cover selectable even when it is not one of "ours".
+    if let Some(current) = non_empty(&form.cover_image_id) {
+        if !covers.contains(&current) {
+            covers.insert(0, current);
+        }
+    }
+    Ok(ActivityFormView {
+        activity_id: activity_id.map(|s| s.to_string()),
+        form,
+        is_published,
+        categories: activity_commands_repo::list_category_options(pool).await?,
+        covers,
+        errors,
+    })
+}
+
+/// Validates and submits the form as a `create` (no `activity_id`) or
+/// `update` command. Returns the activity id and the apply outcome.
+pub async fn submit(
+    pool: &SqlitePool,
+    user_id: &str,
+    activity_id: Option<&str>,
+    form: &ActivityForm,
+) -> Result<(String, ApplyOutcome), EditorError> {
+    let existing = match activity_id {
+        Some(id) => Some(load_managed(pool, id).await?),
+        None => None,
+    };
+    let categories = activity_commands_repo::list_category_options(pool).await?;
+    let mut covers =
+        activity_commands_repo::list_cover_options(pool, user_id, COVER_OPTIONS_LIMIT).await?;
+    if let Some(current) = existing
+        .as_ref()
+        .and_then(|r| r.main_photo_asset_id.clone())
+    {
+        covers.push(current);
+    }
+    let ctx = FormContext {
+        category_ids: categories.iter().map(|c| c.category_id.as_str()).collect(),
+        cover_ids: covers.iter().map(String::as_str).collect(),
+        current_status: existing.as_ref().and_then(|r| r.status.as_deref()),
+        current_participants: existing
+            .as_ref()
+            .and_then(|r| r.current_participants_count)
+            .unwrap_or(0),
+    };
+
+    let mut valid = validate(form, &ctx).map_err(EditorError::Invalid)?;
+    if !activity_commands_repo::is_future(pool, &valid.scheduled_at).await? {
+        return Err(EditorError::Invalid(vec![
+            "Kies een moment in de toekomst.".to_string(),
+        ]));
+    }
+    if valid.venue.is_none() {
+        valid.venue = lookup_venue(&valid.venue_name).await;
+    }
+    let Some(venue) = valid.venue.clone() else {
+        return Err(EditorError::Invalid(vec![
+            "We konden deze locatie niet vinden. Kies een suggestie uit de lijst.".to_string(),
+        ]));
+    };
+
+    let category = categories
+        .iter()
+        .find(|c| Some(&c.category_id) == valid.category_id.as_ref())
+        .map(|c| {
+            json!({
+                "category_id": c.category_id,
+                "name": c.name,
+                "slug": c.slug,
+                "icon_url": c.icon_url,
+            })
+        })
+        .unwrap_or_else(|| json!({}));
+    let location = json!({
+        "venue_name": venue.name,
+        "city": venue.city,
+        "latitude": venue.latitude,
+        "longitude": venue.longitude,
+        "place_id": venue.place_id,
+    });
+
+    let new_id = Uuid::new_v4().to_string();
+    let activity_id = activity_id.unwrap_or(&new_id).to_string();
+    let id = Uuid::new_v4().to_string();
+    let res = activity_commands_repo::insert_activity_command(
+        pool,
+        NewActivityCommand {
+            id: &id,
+            actor_user_id: user_id,
+            activity_id: &activity_id,
+            action: if existing.is_some() {
+                "update"
+            } else {
+                "create"
+            },
+            title: &valid.title,
+            description: valid.description.as_deref(),
+            activity_type: valid.activity_type,
+            privacy_level: valid.privacy_level,
+            status: valid.status,
+            scheduled_at: &valid.scheduled_at,
+            duration_minutes: valid.duration_minutes,
+            max_participants: valid.max_participants,
+            language: valid.language,
+            tags: &serde_json::Value::from(valid.tags.clone()).to_string(),
+            category: &category.to_string(),
+            location: &location.to_string(),
+            city: venue.city.as_deref(),
+            latitude: venue.latitude,
+            longitude: venue.longitude,
+            main_photo_asset_id: valid.cover_image_id.as_deref(),
+            note: Some("website"),
+        },
+    )
+    .await;
+    let outcome = central_apply_service::resolve_command(&id, res)?;
+    Ok((activity_id, outcome))
+}
+
+/// Venue typed without picking a suggestion: take the best upstream match.
+async fn lookup_venue(name: &str) -> Option<Venue> {
+    let hit = location_service::search_locations_upstream(name, 1)
+        .await
+        .ok()?
+        .into_iter()
+        .next()?;
+    Some(Venue {
+        name: name.to_string(),
+        city: non_empty(&hit.name),
+        latitude: hit.latitude,
+        longitude: hit.longitude,
+        place_id: non_empty(&hit.id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled() -> ActivityForm {
+        ActivityForm {
+            title: "Wandelen op de Lemelerberg".to_string(),
+            date: "2031-05-04".to_string(),
+            time: "10:30".to_string(),
+            venue_name: "Lemelerberg".to_string(),
+            venue_city: "Lemele".to_string(),
+            latitude: "52.47".to_string(),
+            longitude: "6.42".to_string(),
+            tags: "wandelen, #Natuur, natuur, ".to_string(),
+            category_id: "7".to_string(),
+            status: "published".to_string(),
+            ..new_form()
+        }
+    }
+
+    fn ctx<'a>(current_status: Option<&'a str>, current_participants: i64) -> FormContext<'a> {
+        FormContext {
+            category_ids: vec!["7"],
+            cover_ids: vec!["img-1"],
+            current_status,
+            current_participants,
+        }
+    }
+
+    #[test]
+    fn validates_a_complete_form() {
+        let valid = validate(&filled(), &ctx(None, 0)).unwrap();
+        assert_eq!(valid.scheduled_at, "2031-05-04T10:30:00");
+        assert_eq!(valid.tags, vec!["wandelen", "Natuur"]);
+        assert_eq!(valid.status, "published");
+        assert_eq!(valid.duration_minutes, Some(120));
+        assert_eq!(valid.venue.unwrap().city.as_deref(), Some("Lemele"));
+
+        // Typed venue without coordinates is looked up on submit.
+        let typed = ActivityForm {
+            latitude: String::new(),
+            longitude: String::new(),
+            ..filled()
+        };
+        assert_eq!(validate(&typed, &ctx(None, 0)).unwrap().venue, None);
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let form = ActivityForm {
+            title: "x".to_string(),
+            activity_type: "party".to_string(),
+            time: "25:00".to_string(),
+            max_participants: "1".to_string(),
+            category_id: "99".to_string(),
+            cover_image_id: "someone-elses".to_string(),
+            venue_name: " ".to_string(),
+            ..filled()
+        };
+        let errors = validate(&form, &ctx(None, 0)).unwrap_err();
+        assert_eq!(errors.len(), 7, "{:?}", errors);
+    }
+
+    #[test]
+    fn edits_keep_published_and_capacity_constraints() {
+        let draft = ActivityForm {
+            status: "draft".to_string(),
+            max_participants: "4".to_string(),
+            ..filled()
+        };
+        let errors = validate(&draft, &ctx(Some("published"), 6)).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(
+            validate(&draft, &ctx(Some("draft"), 3)).unwrap().status,
+            "draft"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Organizer lifecycle actions: cancel, reschedule and complete.
+//!
+//! Each action is one `activity_lifecycle_commands` row. Its `announcement`
+//! is what the central side posts to the activity chat and sends to every
+//! participant as a notification; open tabs of participants also get an
+//! `activity_status` live event right away.
+
+use serde_json::json;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::activity_commands_repo;
+use crate::database::activity_lifecycle_commands_repo::{
+    self, LifecycleActivityRow, NewActivityLifecycleCommand,
+};
+use crate::services::activity_editor_service;
+use crate::services::activity_summary_service::format_scheduled_labels;
+use crate::services::central_apply_service::{self, ApplyOutcome, DomainError, ServiceError};
+use crate::services::live_events_service::LiveEventHub;
+
+const MAX_REASON_LEN: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleAction {
+    Cancel {
+        reason: String,
+    },
+    /// New naive `scheduled_at` (`YYYY-MM-DDTHH:MM:SS`).
+    Reschedule {
+        scheduled_at: String,
+    },
+    Complete,
+}
+
+impl LifecycleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleAction::Cancel { .. } => "cancel",
+            LifecycleAction::Reschedule { .. } => "reschedule",
+            LifecycleAction::Complete => "complete",
+        }
+    }
+
+    /// Notice shown after the action went through.
+    pub fn done_notice(&self) -> &'static str {
+        match self {
+            LifecycleAction::Cancel { .. } => "activity_cancelled",
+            LifecycleAction::Reschedule { .. } => "activity_rescheduled",
+            LifecycleAction::Complete => "activity_completed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LifecycleError {
+    NotFound,
+    Forbidden,
+    /// Already cancelled or completed.
+    Closed,
+    ReasonMissing,
+    /// Missing, malformed, past or unchanged moment.
+    InvalidSchedule,
+    NotStarted,
+}
+
+impl DomainError for LifecycleError {
+    /// Notice code for the activities page.
+    fn notice(&self) -> &'static str {
+        match self {
+            LifecycleError::NotFound | LifecycleError::Forbidden => "not_allowed",
+            LifecycleError::Closed => "activity_closed",
+            LifecycleError::ReasonMissing => "cancel_reason_missing",
+            LifecycleError::InvalidSchedule => "reschedule_invalid",
+            LifecycleError::NotStarted => "activity_not_started",
+        }
+    }
+}
+
+pub fn cancel_action(reason: &str) -> Result<LifecycleAction, LifecycleError> {
+    let reason = reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+        return Err(LifecycleError::ReasonMissing);
+    }
+    Ok(LifecycleAction::Cancel {
+        reason: reason.to_string(),
+    })
+}
+
+pub fn reschedule_action(date: &str, time: &str) -> Result<LifecycleAction, LifecycleError> {
+    activity_editor_service::parse_schedule(date, time)
+        .map(|scheduled_at| LifecycleAction::Reschedule { scheduled_at })
+        .ok_or(LifecycleError::InvalidSchedule)
+}
+
+/// Checks the action against the activity's current state. Whether a new
+/// moment lies in the future is checked against SQLite's clock on apply.
+pub fn check_transition(
+    row: &LifecycleActivityRow,
+    action: &LifecycleAction,
+) -> Result<(), LifecycleError> {
+    if row.can_manage_activity != 1 {
+        return Err(LifecycleError::Forbidden);
+    }
+    if matches!(row.status.as_str(), "cancelled" | "completed") {
+        return Err(LifecycleError::Closed);
+    }
+    match action {
+        LifecycleAction::Reschedule { scheduled_at } if *scheduled_at == row.scheduled_at => {
+            Err(LifecycleError::InvalidSchedule)
+        }
+        LifecycleAction::Complete if row.status != "published" || row.has_started != 1 => {
+            Err(LifecycleError::NotStarted)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The system message / notification text, in Dutch like the rest of the UI.
+pub fn announcement(row: &LifecycleActivityRow, action: &LifecycleAction) -> String {
+    let when = |scheduled_at: &str| {
+        let (date, time) = format_scheduled_labels(scheduled_at);
+        format!("{} om {}", date, time)
+    };
+    match action {
+        LifecycleAction::Cancel { reason } => {
+            format!("{} is geannuleerd. Reden: {}", row.title, reason)
+        }
+        LifecycleAction::Reschedule { scheduled_at } => format!(
+            "{} is verplaatst van {} naar {}.",
+            row.title,
+            when(&row.scheduled_at),
+            when(scheduled_at)
+        ),
+        LifecycleAction::Complete => {
+            format!("{} is afgerond. Bedankt voor het meedoen!", row.title)
+        }
+    }
+}
+
+pub async fn apply(
+    pool: &SqlitePool,
+    hub: &LiveEventHub,
+    actor_user_id: &str,
+    activity_id: &str,
+    action: LifecycleAction,
+) -> Result<ApplyOutcome, ServiceError<LifecycleError>> {
+    let row = activity_lifecycle_commands_repo::load_lifecycle_activity(pool, activity_id)
+        .await?
+        .ok_or(LifecycleError::NotFound)?;
+    check_transition(&row, &action)?;
+    if let LifecycleAction::Reschedule { scheduled_at } = &action {
+        if !activity_commands_repo::is_future(pool, scheduled_at).await? {
+            return Err(LifecycleError::InvalidSchedule.into());
+        }
+    }
+
+    let announcement = announcement(&row, &action);
+    let (reason, scheduled_at) = match &action {
+        LifecycleAction::Cancel { reason } => (Some(reason.as_str()), None),
+        LifecycleAction::Reschedule { scheduled_at } => (None, Some(scheduled_at.as_str())),
+        LifecycleAction::Complete => (None, None),
+    };
+    let id = Uuid::new_v4().to_string();
+    let res = activity_lifecycle_commands_repo::insert_lifecycle_command(
+        pool,
+        NewActivityLifecycleCommand {
+            id: &id,
+            actor_user_id,
+            activity_id,
+            action: action.as_str(),
+            reason,
+            scheduled_at,
+            announcement: &announcement,
+            note: Some("website"),
+        },
+    )
+    .await;
+    let outcome = central_apply_service::resolve_command(&id, res)?;
+
+    let status = match action {
+        LifecycleAction::Cancel { .. } => "cancelled",
+        LifecycleAction::Complete => "completed",
+        LifecycleAction::Reschedule { .. } => row.status.as_str(),
+    };
+    let data = json!({
+        "activity_id": activity_id,
+        "action": action.as_str(),
+        "status": status,
+        "scheduled_at": scheduled_at.unwrap_or(&row.scheduled_at),
+        "message": announcement,
+    });
+    for user_id in activity_lifecycle_commands_repo::list_participant_user_ids(pool, activity_id)
+        .await?
+        .iter()
+        .filter(|u| *u != actor_user_id)
+    {
+        hub.publish(Some(user_id), "activity_status", data.clone());
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::central_apply_service::CommandError;
+
+    fn row(status: &str, has_started: i64) -> LifecycleActivityRow {
+        LifecycleActivityRow {
+            title: "Padel".to_string(),
+            status: status.to_string(),
+            scheduled_at: "2031-05-03T10:30:00".to_string(),
+            can_manage_activity: 1,
+            has_started,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_cancel_and_reschedule_input() {
+        assert!(matches!(
+            cancel_action("  "),
+            Err(LifecycleError::ReasonMissing)
+        ));
+        assert_eq!(
+            cancel_action(" Regen ").unwrap(),
+            LifecycleAction::Cancel {
+                reason: "Regen".to_string()
+            }
+        );
+        assert_eq!(
+            reschedule_action("2031-05-10", "19:00").unwrap(),
+            LifecycleAction::Reschedule {
+                scheduled_at: "2031-05-10T19:00:00".to_string()
+            }
+        );
+        assert!(reschedule_action("10-05-2031", "19:00").is_err());
+    }
+
+    #[test]
+    fn checks_transitions_against_current_state() {
+        let cancel = cancel_action("Regen").unwrap();
+        assert!(check_transition(&row("published", 0), &cancel).is_ok());
+        assert!(check_transition(&row("draft", 0), &cancel).is_ok());
+        assert!(matches!(
+            check_transition(&row("cancelled", 0), &cancel),
+            Err(LifecycleError::Closed)
+        ));
+
+        let same = reschedule_action("2031-05-03", "10:30").unwrap();
+        assert!(matches!(
+            check_transition(&row("published", 0), &same),
+            Err(LifecycleError::InvalidSchedule)
+        ));
+
+        let complete = LifecycleAction::Complete;
+        assert!(check_transition(&row("published", 1), &complete).is_ok());
+        assert!(matches!(
+            check_transition(&row("published", 0), &complete),
+            Err(LifecycleError::NotStarted)
+        ));
+
+        let mut not_mine = row("published", 0);
+        not_mine.can_manage_activity = 0;
+        assert!(matches!(
+            check_transition(&not_mine, &cancel),
+            Err(LifecycleError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn maps_errors_to_notices() {
+        let closed: ServiceError<LifecycleError> = LifecycleError::Closed.into();
+        assert_eq!(closed.notice(), "activity_closed");
+        let rejected: ServiceError<LifecycleError> =
+            CommandError::Rejected(ApplyOutcome::rejected("activity_full", None)).into();
+        assert_eq!(rejected.notice(), "rejected");
+        let failed: ServiceError<LifecycleError> = sqlx::Error::RowNotFound.into();
+        assert_eq!(failed.notice(), "error");
+    }
+
+    #[test]
+    fn announces_old_and_new_moment() {
+        let action = reschedule_action("2031-05-10", "19:00").unwrap();
+        assert_eq!(
+            announcement(&row("published", 0), &action),
+            "Padel is verplaatst van za 3 mei om 10:30 naar za 10 mei om 19:00."
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Reviews and ratings for completed activities.
+//!
+//! Participants who attended write one review each and can edit it later;
+//! everyone else's reviews can be marked helpful once. Both go through
+//! `activity_review_commands`, which also keeps `activities.review_count`
+//! and `avg_rating` in step for the feed cards.
+
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::activity_review_commands_repo::{
+    self, ActivityReviewRow, NewActivityReviewCommand, ReviewActivityRow,
+};
+use crate::services::activity_summary_service::{extract_image_id, format_scheduled_labels};
+use crate::services::central_apply_service::{self, ApplyOutcome, DomainError, ServiceError};
+
+const MAX_REVIEW_LEN: usize = 1000;
+
+pub struct ReviewView {
+    pub review_id: String,
+    pub reviewer_name: String,
+    pub reviewer_photo_image_id: Option<String>,
+    pub rating: i64,
+    /// e.g. `★★★★☆`
+    pub stars: String,
+    pub review_text: Option<String>,
+    pub helpful_count: i64,
+    pub date_label: String,
+    pub is_mine: bool,
+    pub can_vote: bool,
+}
+
+pub struct ReviewsView {
+    pub rating_label: Option<String>,
+    pub can_review: bool,
+    /// Prefills the form when editing.
+    pub my_rating: Option<i64>,
+    pub my_review_text: String,
+    pub reviews: Vec<ReviewView>,
+}
+
+impl ReviewsView {
+    pub fn is_my_rating(&self, rating: &i64) -> bool {
+        self.my_rating == Some(*rating)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReviewError {
+    NotFound,
+    NotCompleted,
+    NotAttended,
+    InvalidRating,
+    TooLong,
+    OwnReview,
+    AlreadyVoted,
+}
+
+impl DomainError for ReviewError {
+    /// Notice code for the activity page.
+    fn notice(&self) -> &'static str {
+        match self {
+            ReviewError::NotFound => "not_allowed",
+            ReviewError::NotCompleted | ReviewError::NotAttended => "review_not_allowed",
+            ReviewError::InvalidRating | ReviewError::TooLong => "review_invalid",
+            ReviewError::OwnReview => "own_review",
+            ReviewError::AlreadyVoted => "already_voted",
+        }
+    }
+}
+
+/// Feed and detail label, e.g. `★ 4,5 (12)`.
+pub fn rating_label(review_count: i64, avg_rating: Option<f64>) -> Option<String> {
+    let avg = avg_rating.filter(|_| review_count > 0)?;
+    Some(format!(
+        "★ {} ({})",
+        format!("{:.1}", avg).replace('.', ","),
+        review_count
+    ))
+}
+
+fn stars(rating: i64) -> String {
+    let full = rating.clamp(0, 5) as usize;
+    format!("{}{}", "★".repeat(full), "☆".repeat(5 - full))
+}
+
+/// Rating and optional text from the form.
+pub fn parse_review(rating: &str, text: &str) -> Result<(i64, Option<String>), ReviewError> {
+    let rating = rating
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|r| (1..=5).contains(r))
+        .ok_or(ReviewError::InvalidRating)?;
+    let text = text.trim();
+    if text.chars().count() > MAX_REVIEW_LEN {
+        return Err(ReviewError::TooLong);
+    }
+    Ok((rating, Some(text.to_string()).filter(|s| !s.is_empty())))
+}
+
+/// Only attendees of a completed activity may write a review.
+pub fn check_can_review(row: &ReviewActivityRow) -> Result<(), ReviewError> {
+    if row.status != "completed" {
+        return Err(ReviewError::NotCompleted);
+    }
+    if row.has_attended != 1 {
+        return Err(ReviewError::NotAttended);
+    }
+    Ok(())
+}
+
+fn review_view(row: ActivityReviewRow) -> ReviewView {
+    let (date_label, _) = format_scheduled_labels(&row.created_at);
+    let is_mine = row.is_mine == 1;
+    ReviewView {
+        reviewer_name: row
+            .reviewer_name
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "Onbekend".to_string()),
+        reviewer_photo_image_id: row.reviewer_photo_url.as_deref().and_then(extract_image_id),
+        stars: stars(row.rating),
+        rating: row.rating,
+        review_text: row
+            .review_text
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()),
+        helpful_count: row.helpful_count,
+        date_label,
+        can_vote: !is_mine && row.has_voted == 0,
+        is_mine,
+        review_id: row.review_id,
+    }
+}
+
+pub async fn load_reviews_view(
+    pool: &SqlitePool,
+    user_id: &str,
+    activity_id: &str,
+) -> sqlx::Result<Option<ReviewsView>> {
+    let Some(row) =
+        activity_review_commands_repo::load_review_activity(pool, activity_id, user_id).await?
+    else {
+        return Ok(None);
+    };
+    let reviews: Vec<ReviewView> =
+        activity_review_commands_repo::list_activity_reviews(pool, activity_id, user_id)
+            .await?
+            .into_iter()
+            .map(review_view)
+            .collect();
+    let mine = reviews.iter().find(|r| r.is_mine);
+
+    Ok(Some(ReviewsView {
+        rating_label: rating_label(row.review_count, row.avg_rating),
+        can_review: check_can_review(&row).is_ok(),
+        my_rating: mine.map(|r| r.rating),
+        my_review_text: mine.and_then(|r| r.review_text.clone()).unwrap_or_default(),
+        reviews,
+    }))
+}
+
+/// Creates the actor's review, or edits it when they already wrote one.
+pub async fn save_review(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    activity_id: &str,
+    rating: &str,
+    review_text: &str,
+) -> Result<ApplyOutcome, ServiceError<ReviewError>> {
+    let (rating, review_text) = parse_review(rating, review_text)?;
+    let row = activity_review_commands_repo::load_review_activity(pool, activity_id, actor_user_id)
+        .await?
+        .ok_or(ReviewError::NotFound)?;
+    check_can_review(&row)?;
+
+    let review_id =
+        activity_review_commands_repo::list_activity_reviews(pool, activity_id, actor_user_id)
+            .await?
+            .into_iter()
+            .find(|r| r.is_mine == 1)
+            .map(|r| r.review_id)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let id = Uuid::new_v4().to_string();
+    let res = activity_review_commands_repo::insert_review_command(
+        pool,
+        NewActivityReviewCommand {
+            id: &id,
+            actor_user_id,
+            activity_id,
+            review_id: &review_id,
+            action: "upsert",
+            rating: Some(rating),
+            review_text: review_text.as_deref(),
+            note: Some("website"),
+        },
+    )
+    .await;
+    Ok(central_apply_service::resolve_command(&id, res)?)
+}
+
+pub async fn vote_helpful(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    activity_id: &str,
+    review_id: &str,
+) -> Result<ApplyOutcome, ServiceError<ReviewError>> {
+    let review =
+        activity_review_commands_repo::list_activity_reviews(pool, activity_id, actor_user_id)
+            .await?
+            .into_iter()
+            .find(|r| r.review_id == review_id)
+            .ok_or(ReviewError::NotFound)?;
+    if review.is_mine == 1 {
+        return Err(ReviewError::OwnReview.into());
+    }
+    if review.has_voted == 1 {
+        return Err(ReviewError::AlreadyVoted.into());
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let res = activity_review_commands_repo::insert_review_command(
+        pool,
+        NewActivityReviewCommand {
+            id: &id,
+            actor_user_id,
+            activity_id,
+            review_id,
+            action: "helpful",
+            rating: None,
+            review_text: None,
+            note: Some("website"),
+        },
+    )
+    .await;
+    Ok(central_apply_service::resolve_command(&id, res)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(status: &str, has_attended: i64) -> ReviewActivityRow {
+        ReviewActivityRow {
+            status: status.to_string(),
+            has_attended,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_rating_and_text() {
+        assert_eq!(
+            parse_review("4", "  Top middag ").unwrap(),
+            (4, Some("Top middag".to_string()))
+        );
+        assert_eq!(parse_review("5", "   ").unwrap(), (5, None));
+        assert!(matches!(
+            parse_review("6", ""),
+            Err(ReviewError::InvalidRating)
+        ));
+        assert!(matches!(
+            parse_review("", ""),
+            Err(ReviewError::InvalidRating)
+        ));
+        assert!(matches!(
+            parse_review("3", &"x".repeat(MAX_REVIEW_LEN + 1)),
+            Err(ReviewError::TooLong)
+        ));
+    }
+
+    #[test]
+    fn only_attendees_of_completed_activities_review() {
+        assert!(check_can_review(&row("completed", 1)).is_ok());
+        assert!(matches!(
+            check_can_review(&row("published", 1)),
+            Err(ReviewError::NotCompleted)
+        ));
+        assert!(matches!(
+            check_can_review(&row("completed", 0)),
+            Err(ReviewError::NotAttended)
+        ));
+    }
+
+    #[test]
+    fn formats_rating_labels() {
+        assert_eq!(rating_label(12, Some(4.46)).as_deref(), Some("★ 4,5 (12)"));
+        assert_eq!(rating_label(0, Some(4.0)), None);
+        assert_eq!(rating_label(3, None), None);
+        assert_eq!(stars(3), "★★★☆☆");
+    }
+}
@@ -22,7 +22,13 @@ pub struct ActivitySummaryView {
     pub status: String,
     pub is_joined: bool,
     pub am_on_waitlist: bool,
+    pub can_manage_activity: bool,
+    pub can_manage_attendance: bool,
     pub waitlist_enabled: bool,
+    pub cancellation_reason: Option<String>,
+    /// Previous moment, e.g. `za 3 mei • 10:30`, when the activity was moved.
+    pub rescheduled_from_label: Option<String>,
+    pub has_started: bool,
     pub tab: Option<String>,
     pub return_to: Option<String>,
 }
@@ -52,6 +58,20 @@ fn build_view(
     let (organizer_name, organizer_photo_image_id) = parse_organizer(row.organizer.as_str());
 
     let duration_label = row.duration_minutes.map(|m| format!("{} min", m));
+    let rescheduled_from_label = row
+        .rescheduled_from
+        .as_deref()
+        .filter(|from| *from != row.scheduled_at)
+        .map(|from| {
+            let (date, time) = format_scheduled_labels(from);
+            format!("{} • {}", date, time)
+        });
+    let cancellation_reason = row
+        .cancellation_reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
     let description = row
         .description
         .as_deref()
@@ -78,13 +98,18 @@ fn build_view(
         status: row.status,
         is_joined: row.is_joined == 1,
         am_on_waitlist: row.am_on_waitlist == 1,
+        can_manage_activity: row.can_manage_activity == 1,
+        can_manage_attendance: row.can_manage_attendance == 1,
         waitlist_enabled: row.waitlist_enabled == 1,
+        cancellation_reason,
+        rescheduled_from_label,
+        has_started: row.has_started == 1,
         tab,
         return_to,
     }
 }
 
-fn format_scheduled_labels(scheduled_at: &str) -> (String, String) {
+pub(crate) fn format_scheduled_labels(scheduled_at: &str) -> (String, String) {
     let date = scheduled_at.get(0..10).unwrap_or(scheduled_at);
     let time = scheduled_at.get(11..16).unwrap_or("");
     (format_date_nl_short(date), time.to_string())
@@ -247,7 +272,7 @@ fn parse_organizer(json: &str) -> (Option<String>, Option<String>) {
     (name, photo_id)
 }
 
-fn extract_image_id(value: &str) -> Option<String> {
+pub(crate) fn extract_image_id(value: &str) -> Option<String> {
     let v = value.trim();
     if v.is_empty() {
         return None;
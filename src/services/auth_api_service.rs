@@ -0,0 +1,414 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{info, warn};
+
+pub fn auth_service_base_url() -> String {
+    std::env::var("AUTH_SERVICE_URL").unwrap_or_else(|_| "http://auth.localhost:8080".to_string())
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenPairResponse {
+    data: TokenPair,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefreshError {
+    Rejected(u16),
+    Upstream(String),
+}
+
+impl std::fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefreshError::Rejected(status) => write!(f, "refresh rejected ({})", status),
+            RefreshError::Upstream(e) => write!(f, "refresh failed: {}", e),
+        }
+    }
+}
+
+/// Exchanges a refresh token for a new access/refresh pair at the auth-service.
+pub async fn exchange_refresh_token(
+    client: &reqwest::Client,
+    refresh_token: &str,
+) -> Result<TokenPair, RefreshError> {
+    let url = format!(
+        "{}/api/v1/auth/refresh",
+        auth_service_base_url().trim_end_matches('/')
+    );
+    let resp = client
+        .post(&url)
+        .timeout(Duration::from_secs(10))
+        .json(&json!({ "refresh_token": refresh_token }))
+        .send()
+        .await
+        .map_err(|e| RefreshError::Upstream(e.to_string()))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(RefreshError::Rejected(status.as_u16()));
+    }
+
+    resp.json::<TokenPairResponse>()
+        .await
+        .map(|r| r.data)
+        .map_err(|e| RefreshError::Upstream(e.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResponseData {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    #[serde(default)]
+    mfa_required: bool,
+    mfa_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    data: LoginResponseData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginError {
+    Rejected(u16),
+    Upstream(String),
+}
+
+impl std::fmt::Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoginError::Rejected(status) => write!(f, "login rejected ({})", status),
+            LoginError::Upstream(e) => write!(f, "login failed: {}", e),
+        }
+    }
+}
+
+/// First login step: either a session or a second-factor challenge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginOutcome {
+    Tokens(TokenPair),
+    /// The auth-service wants a TOTP/code; `mfa_token` identifies the
+    /// half-finished login at `/api/v1/auth/mfa/verify`.
+    MfaRequired {
+        mfa_token: String,
+    },
+}
+
+/// `POST {AUTH_SERVICE_URL}/api/v1/auth/login`.
+pub async fn login(
+    client: &reqwest::Client,
+    base_url: &str,
+    email: &str,
+    password: &str,
+) -> Result<LoginOutcome, LoginError> {
+    let url = format!("{}/api/v1/auth/login", base_url.trim_end_matches('/'));
+    let resp = client
+        .post(&url)
+        .timeout(Duration::from_secs(10))
+        .json(&json!({ "email": email, "password": password }))
+        .send()
+        .await
+        .map_err(|e| LoginError::Upstream(e.to_string()))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(LoginError::Rejected(status.as_u16()));
+    }
+
+    let data = resp
+        .json::<LoginResponse>()
+        .await
+        .map_err(|e| LoginError::Upstream(e.to_string()))?
+        .data;
+    match data {
+        LoginResponseData {
+            mfa_required: true,
+            mfa_token: Some(mfa_token),
+            ..
+        } => Ok(LoginOutcome::MfaRequired { mfa_token }),
+        LoginResponseData {
+            mfa_required: true, ..
+        } => Err(LoginError::Upstream(
+            "mfa_required without mfa_token".to_string(),
+        )),
+        LoginResponseData {
+            access_token: Some(access_token),
+            refresh_token: Some(refresh_token),
+            ..
+        } => Ok(LoginOutcome::Tokens(TokenPair {
+            access_token,
+            refresh_token,
+        })),
+        _ => Err(LoginError::Upstream(
+            "login response without tokens".to_string(),
+        )),
+    }
+}
+
+/// Second login step: `POST {AUTH_SERVICE_URL}/api/v1/auth/mfa/verify`.
+/// A wrong code comes back as `Rejected(400|401)`.
+pub async fn verify_mfa_code(
+    client: &reqwest::Client,
+    base_url: &str,
+    mfa_token: &str,
+    code: &str,
+) -> Result<TokenPair, LoginError> {
+    let url = format!("{}/api/v1/auth/mfa/verify", base_url.trim_end_matches('/'));
+    let resp = client
+        .post(&url)
+        .timeout(Duration::from_secs(10))
+        .json(&json!({ "mfa_token": mfa_token, "code": code }))
+        .send()
+        .await
+        .map_err(|e| LoginError::Upstream(e.to_string()))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(LoginError::Rejected(status.as_u16()));
+    }
+
+    resp.json::<TokenPairResponse>()
+        .await
+        .map(|r| r.data)
+        .map_err(|e| LoginError::Upstream(e.to_string()))
+}
+
+/// Account endpoints next to `/api/v1/auth/login`. They answer 2xx on
+/// success; the status code is all the website needs on failure (409 = email
+/// taken, 400/404/410/422 = invalid input or token).
+async fn post_account(
+    client: &reqwest::Client,
+    base_url: &str,
+    path: &str,
+    body: serde_json::Value,
+) -> Result<(), LoginError> {
+    let url = format!("{}{}", base_url.trim_end_matches('/'), path);
+    let resp = client
+        .post(&url)
+        .timeout(Duration::from_secs(10))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| LoginError::Upstream(e.to_string()))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(LoginError::Rejected(status.as_u16()));
+    }
+    Ok(())
+}
+
+/// `POST /api/v1/auth/register`; the auth-service mails a verification link.
+pub async fn register(
+    client: &reqwest::Client,
+    base_url: &str,
+    name: &str,
+    email: &str,
+    password: &str,
+) -> Result<(), LoginError> {
+    post_account(
+        client,
+        base_url,
+        "/api/v1/auth/register",
+        json!({ "name": name, "email": email, "password": password }),
+    )
+    .await
+}
+
+/// `POST /api/v1/auth/verify-email` with the token from the mailed link.
+pub async fn verify_email(
+    client: &reqwest::Client,
+    base_url: &str,
+    token: &str,
+) -> Result<(), LoginError> {
+    post_account(
+        client,
+        base_url,
+        "/api/v1/auth/verify-email",
+        json!({ "token": token }),
+    )
+    .await
+}
+
+/// `POST /api/v1/auth/forgot-password`; mails a reset link if the account exists.
+pub async fn request_password_reset(
+    client: &reqwest::Client,
+    base_url: &str,
+    email: &str,
+) -> Result<(), LoginError> {
+    post_account(
+        client,
+        base_url,
+        "/api/v1/auth/forgot-password",
+        json!({ "email": email }),
+    )
+    .await
+}
+
+/// `POST /api/v1/auth/reset-password` with the token from the mailed link.
+pub async fn reset_password(
+    client: &reqwest::Client,
+    base_url: &str,
+    token: &str,
+    password: &str,
+) -> Result<(), LoginError> {
+    post_account(
+        client,
+        base_url,
+        "/api/v1/auth/reset-password",
+        json!({ "token": token, "password": password }),
+    )
+    .await
+}
+
+type RefreshSlot = Arc<OnceCell<Result<TokenPair, RefreshError>>>;
+
+/// Single-flight token refresh.
+///
+/// The browser fires many requests at once (page + images + chat calls), all
+/// carrying the same expired access token. Only the first one talks to the
+/// auth-service; the others wait for and reuse its result. Results are kept for
+/// a short grace period because the auth-service rotates refresh tokens, so a
+/// late request with the old cookie could otherwise never refresh again.
+pub struct TokenRefresher {
+    client: reqwest::Client,
+    slots: Mutex<HashMap<String, (Instant, RefreshSlot)>>,
+    grace: Duration,
+}
+
+impl TokenRefresher {
+    pub fn new() -> Self {
+        Self::with_grace(Duration::from_secs(30))
+    }
+
+    pub fn with_grace(grace: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            slots: Mutex::new(HashMap::new()),
+            grace,
+        }
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenPair, RefreshError> {
+        let client = self.client.clone();
+        let token = refresh_token.to_string();
+        self.refresh_with(refresh_token, || async move {
+            exchange_refresh_token(&client, &token).await
+        })
+        .await
+    }
+
+    pub async fn refresh_with<F, Fut>(
+        &self,
+        refresh_token: &str,
+        exchange: F,
+    ) -> Result<TokenPair, RefreshError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<TokenPair, RefreshError>>,
+    {
+        let slot = {
+            let mut slots = self.slots.lock().await;
+            slots.retain(|_, (created, _)| created.elapsed() < self.grace);
+            slots
+                .entry(refresh_token.to_string())
+                .or_insert_with(|| (Instant::now(), Arc::new(OnceCell::new())))
+                .1
+                .clone()
+        };
+
+        let result = slot
+            .get_or_init(|| async {
+                let res = exchange().await;
+                match &res {
+                    Ok(_) => info!("access_token_refreshed"),
+                    Err(e) => warn!(error = %e, "access_token_refresh_failed"),
+                }
+                res
+            })
+            .await
+            .clone();
+
+        // Failures are not cached beyond the in-flight window so a transient
+        // auth-service error does not lock the user out for the grace period.
+        if result.is_err() {
+            let mut slots = self.slots.lock().await;
+            if slots
+                .get(refresh_token)
+                .is_some_and(|(_, s)| Arc::ptr_eq(s, &slot))
+            {
+                slots.remove(refresh_token);
+            }
+        }
+
+        result
+    }
+}
+
+impl Default for TokenRefresher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn pair(n: usize) -> TokenPair {
+        TokenPair {
+            access_token: format!("access-{}", n),
+            refresh_token: format!("refresh-{}", n),
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_share_one_exchange() {
+        let refresher = Arc::new(TokenRefresher::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..16 {
+            let refresher = refresher.clone();
+            let calls = calls.clone();
+            handles.push(tokio::spawn(async move {
+                refresher
+                    .refresh_with("old-refresh", || async move {
+                        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok(pair(n))
+                    })
+                    .await
+            }));
+        }
+
+        for h in handles {
+            assert_eq!(h.await.unwrap(), Ok(pair(1)));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_is_retried() {
+        let refresher = TokenRefresher::new();
+        let first = refresher
+            .refresh_with("rt", || async { Err(RefreshError::Rejected(401)) })
+            .await;
+        assert_eq!(first, Err(RefreshError::Rejected(401)));
+
+        let second = refresher.refresh_with("rt", || async { Ok(pair(2)) }).await;
+        assert_eq!(second, Ok(pair(2)));
+    }
+}
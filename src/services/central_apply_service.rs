@@ -0,0 +1,354 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+
+/// One command row handed to the central stored procedure of the same name.
+#[derive(Debug, Clone)]
+pub struct ApplyRequest {
+    pub sp_name: String,
+    pub command_table: String,
+    pub command_id: String,
+    pub command: serde_json::Value,
+}
+
+/// Result of a central apply. `result == 1` commits the command row; anything
+/// else makes the trigger roll the insert back.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApplyOutcome {
+    pub result: i64,
+    /// Machine-readable outcome, e.g. `joined`, `waitlisted`, `activity_full`.
+    #[serde(default)]
+    pub outcome: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl ApplyOutcome {
+    pub fn applied(outcome: Option<&str>) -> Self {
+        Self {
+            result: 1,
+            outcome: outcome.map(|s| s.to_string()),
+            message: None,
+        }
+    }
+
+    pub fn rejected(outcome: &str, message: Option<String>) -> Self {
+        Self {
+            result: 0,
+            outcome: Some(outcome.to_string()),
+            message,
+        }
+    }
+
+    pub fn is_applied(&self) -> bool {
+        self.result == 1
+    }
+}
+
+/// Backend behind the `sp_apply_*` SQLite UDFs.
+///
+/// Called synchronously from the SQLite worker thread while the command insert
+/// is still inside its transaction.
+pub trait CentralApply: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn apply(&self, req: &ApplyRequest) -> ApplyOutcome;
+}
+
+/// Picks the backend from `CENTRAL_APPLY_BACKEND` (`http` | `stub`, default `http`).
+/// Must be called inside the Tokio runtime (the HTTP backend captures its handle).
+pub fn from_env() -> Arc<dyn CentralApply> {
+    match std::env::var("CENTRAL_APPLY_BACKEND")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "stub" => Arc::new(StubCentralApply::accept_all()),
+        _ => Arc::new(HttpCentralApply::new(central_apply_base_url())),
+    }
+}
+
+fn central_apply_base_url() -> String {
+    std::env::var("CENTRAL_APPLY_URL").unwrap_or_else(|_| "http://api.localhost:8080".to_string())
+}
+
+/// Forwards the command to `POST {base}/api/v1/commands/{sp_name}`.
+pub struct HttpCentralApply {
+    base_url: String,
+    client: reqwest::Client,
+    runtime: tokio::runtime::Handle,
+}
+
+impl HttpCentralApply {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            runtime: tokio::runtime::Handle::current(),
+        }
+    }
+
+    async fn post(&self, req: &ApplyRequest) -> ApplyOutcome {
+        let url = format!("{}/api/v1/commands/{}", self.base_url, req.sp_name);
+        let resp = match self
+            .client
+            .post(&url)
+            .timeout(Duration::from_secs(10))
+            .json(&json!({
+                "command_table": req.command_table,
+                "command_id": req.command_id,
+                "command": req.command,
+            }))
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                warn!(sp = %req.sp_name, error = %e, "central_apply_unreachable");
+                return ApplyOutcome::rejected("unavailable", Some(e.to_string()));
+            }
+        };
+
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        match serde_json::from_str::<ApplyOutcome>(&body) {
+            Ok(outcome) if status.is_success() || !outcome.is_applied() => outcome,
+            _ => {
+                warn!(sp = %req.sp_name, status = %status, "central_apply_bad_response");
+                ApplyOutcome::rejected(&format!("http_{}", status.as_u16()), None)
+            }
+        }
+    }
+}
+
+impl CentralApply for HttpCentralApply {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn apply(&self, req: &ApplyRequest) -> ApplyOutcome {
+        // UDFs run on sqlx's SQLite worker thread, outside the runtime.
+        self.runtime.block_on(self.post(req))
+    }
+}
+
+type StubResponder = Box<dyn Fn(&ApplyRequest) -> ApplyOutcome + Send + Sync>;
+
+/// In-process backend for tests and offline containers. Records every call.
+pub struct StubCentralApply {
+    responder: StubResponder,
+    calls: Mutex<Vec<ApplyRequest>>,
+}
+
+impl StubCentralApply {
+    pub fn accept_all() -> Self {
+        Self::with(|_| ApplyOutcome::applied(None))
+    }
+
+    pub fn with(responder: impl Fn(&ApplyRequest) -> ApplyOutcome + Send + Sync + 'static) -> Self {
+        Self {
+            responder: Box::new(responder),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn calls(&self) -> Vec<ApplyRequest> {
+        self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+}
+
+impl CentralApply for StubCentralApply {
+    fn name(&self) -> &'static str {
+        "stub"
+    }
+
+    fn apply(&self, req: &ApplyRequest) -> ApplyOutcome {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(req.clone());
+        }
+        (self.responder)(req)
+    }
+}
+
+// Outcomes by command id. The UDF cannot return more than an integer to the
+// trigger, and anything it writes to the DB is lost on rollback, so the
+// service layer picks the full outcome up here after the insert returns.
+const MAX_PENDING_OUTCOMES: usize = 1024;
+
+/// Outcomes not taken yet, oldest first. When full, only the oldest are
+/// dropped so requests still in flight keep theirs.
+struct PendingOutcomes {
+    capacity: usize,
+    entries: VecDeque<(String, ApplyOutcome)>,
+}
+
+impl PendingOutcomes {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, command_id: &str, outcome: ApplyOutcome) {
+        self.entries.retain(|(id, _)| id != command_id);
+        while self.entries.len() >= self.capacity {
+            if let Some((id, _)) = self.entries.pop_front() {
+                warn!(command_id = %id, "central_apply_outcome_evicted");
+            }
+        }
+        self.entries.push_back((command_id.to_string(), outcome));
+    }
+
+    fn take(&mut self, command_id: &str) -> Option<ApplyOutcome> {
+        let pos = self.entries.iter().position(|(id, _)| id == command_id)?;
+        self.entries.remove(pos).map(|(_, outcome)| outcome)
+    }
+}
+
+fn outcomes() -> &'static Mutex<PendingOutcomes> {
+    static OUTCOMES: OnceLock<Mutex<PendingOutcomes>> = OnceLock::new();
+    OUTCOMES.get_or_init(|| Mutex::new(PendingOutcomes::new(MAX_PENDING_OUTCOMES)))
+}
+
+pub fn record_outcome(command_id: &str, outcome: ApplyOutcome) {
+    if let Ok(mut pending) = outcomes().lock() {
+        pending.record(command_id, outcome);
+    }
+}
+
+pub fn take_outcome(command_id: &str) -> Option<ApplyOutcome> {
+    outcomes().lock().ok()?.take(command_id)
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// The central apply said no; the command row was rolled back.
+    Rejected(ApplyOutcome),
+    /// The command row was stored but no outcome was recorded for it, so
+    /// whether it was e.g. waitlisted rather than joined is unknown.
+    OutcomeMissing,
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Rejected(o) => write!(
+                f,
+                "command rejected: {}",
+                o.outcome.as_deref().unwrap_or("unknown")
+            ),
+            CommandError::OutcomeMissing => write!(f, "command outcome missing"),
+            CommandError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for CommandError {
+    fn from(e: sqlx::Error) -> Self {
+        CommandError::Database(e)
+    }
+}
+
+/// A service's own reasons to refuse a command before it reaches the apply.
+pub trait DomainError: std::fmt::Debug {
+    /// Notice code shown on the page the user is sent back to.
+    fn notice(&self) -> &'static str;
+}
+
+/// Error of a command service: either one of its own checks failed or the
+/// command itself did (rejected by the central apply, or a database error).
+#[derive(Debug)]
+pub enum ServiceError<E> {
+    Domain(E),
+    Command(CommandError),
+}
+
+impl<E: DomainError> ServiceError<E> {
+    pub fn notice(&self) -> &'static str {
+        match self {
+            ServiceError::Domain(e) => e.notice(),
+            ServiceError::Command(CommandError::Rejected(_)) => "rejected",
+            ServiceError::Command(_) => "error",
+        }
+    }
+}
+
+impl<E: DomainError> std::fmt::Display for ServiceError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceError::Domain(e) => write!(f, "{}", e.notice()),
+            ServiceError::Command(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: DomainError> From<E> for ServiceError<E> {
+    fn from(e: E) -> Self {
+        ServiceError::Domain(e)
+    }
+}
+
+impl<E> From<CommandError> for ServiceError<E> {
+    fn from(e: CommandError) -> Self {
+        ServiceError::Command(e)
+    }
+}
+
+impl<E> From<sqlx::Error> for ServiceError<E> {
+    fn from(e: sqlx::Error) -> Self {
+        ServiceError::Command(CommandError::Database(e))
+    }
+}
+
+/// Combines the insert result with the outcome recorded by the UDF.
+pub fn resolve_command<T>(
+    command_id: &str,
+    insert: sqlx::Result<T>,
+) -> Result<ApplyOutcome, CommandError> {
+    let outcome = take_outcome(command_id);
+    match (insert, outcome) {
+        (Ok(_), Some(outcome)) => Ok(outcome),
+        (Ok(_), None) => Err(CommandError::OutcomeMissing),
+        (Err(_), Some(outcome)) if !outcome.is_applied() => Err(CommandError::Rejected(outcome)),
+        (Err(e), _) => Err(CommandError::Database(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_only_the_oldest_pending_outcomes() {
+        let mut pending = PendingOutcomes::new(3);
+        for id in ["c1", "c2", "c3", "c4"] {
+            pending.record(id, ApplyOutcome::applied(Some("waitlisted")));
+        }
+        assert_eq!(pending.take("c1"), None);
+        for id in ["c2", "c3", "c4"] {
+            assert_eq!(
+                pending.take(id),
+                Some(ApplyOutcome::applied(Some("waitlisted")))
+            );
+        }
+        assert_eq!(pending.take("c4"), None);
+    }
+
+    #[test]
+    fn a_missing_outcome_is_not_reported_as_applied() {
+        assert!(matches!(
+            resolve_command("never-recorded", Ok(())),
+            Err(CommandError::OutcomeMissing)
+        ));
+        record_outcome("recorded", ApplyOutcome::applied(Some("joined")));
+        assert_eq!(
+            resolve_command("recorded", Ok(())).unwrap(),
+            ApplyOutcome::applied(Some("joined"))
+        );
+    }
+}
@@ -2,20 +2,21 @@ use sqlx::SqlitePool;
 use uuid::Uuid;
 
 use crate::database::friendship_commands_repo;
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
 
 pub async fn create_friendship_command(
     pool: &SqlitePool,
     actor_user_id: &str,
     target_user_id: &str,
     action: &str,
-) -> sqlx::Result<()> {
+) -> Result<ApplyOutcome, CommandError> {
     let action = action.trim();
     if action != "request" && action != "cancel" && action != "accept" && action != "decline" {
-        return Err(sqlx::Error::Protocol("invalid action".into()));
+        return Err(sqlx::Error::Protocol("invalid action".into()).into());
     }
 
     let id = Uuid::new_v4().to_string();
-    friendship_commands_repo::insert_friendship_command(
+    let res = friendship_commands_repo::insert_friendship_command(
         pool,
         friendship_commands_repo::NewFriendshipCommand {
             id: &id,
@@ -25,6 +26,6 @@ pub async fn create_friendship_command(
             note: Some("website"),
         },
     )
-    .await?;
-    Ok(())
+    .await;
+    central_apply_service::resolve_command(&id, res)
 }
@@ -0,0 +1,504 @@
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::{
+    activity_detail_repo, activity_invitation_commands_repo, activity_invitations_repo,
+    activity_invite_commands_repo,
+};
+use crate::services::activity_summary_service::{extract_image_id, format_scheduled_labels};
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
+
+const INBOX_LIMIT: i64 = 100;
+const CANDIDATE_LIMIT: i64 = 50;
+const MESSAGE_MAX_CHARS: usize = 500;
+
+pub struct InvitationView {
+    pub invitation_id: String,
+    pub activity_id: String,
+    pub title: String,
+    pub scheduled_date_label: String,
+    pub scheduled_time_label: String,
+    pub city: Option<String>,
+    pub organizer_name: Option<String>,
+    pub main_photo_asset_id: Option<String>,
+    pub inviter_user_id: Option<String>,
+    pub inviter_name: String,
+    pub inviter_photo_image_id: Option<String>,
+    pub message: Option<String>,
+    pub expires_label: Option<String>,
+}
+
+pub async fn load_pending_invitations(
+    pool: &SqlitePool,
+    auth_user_id: &str,
+) -> sqlx::Result<Vec<InvitationView>> {
+    let rows = activity_invitations_repo::list_pending_invitations(pool, auth_user_id, INBOX_LIMIT)
+        .await?;
+    Ok(rows.into_iter().map(build_view).collect())
+}
+
+/// Accepts or declines a pending invitation addressed to `auth_user_id`.
+/// Unknown, answered or expired invitations are rejected before any command
+/// row is written.
+pub async fn respond_to_invitation(
+    pool: &SqlitePool,
+    auth_user_id: &str,
+    invitation_id: &str,
+    action: &str,
+) -> Result<ApplyOutcome, CommandError> {
+    let action = action.trim();
+    if action != "accept" && action != "decline" {
+        return Err(sqlx::Error::Protocol("invalid action".into()).into());
+    }
+
+    let Some(invitation) =
+        activity_invitations_repo::load_invitation_for_user(pool, invitation_id, auth_user_id)
+            .await?
+    else {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "not_found",
+            None,
+        )));
+    };
+    if invitation.status != "pending" {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "already_responded",
+            None,
+        )));
+    }
+    if invitation.is_expired != 0 {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "expired", None,
+        )));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let res = activity_invitation_commands_repo::insert_invitation_command(
+        pool,
+        activity_invitation_commands_repo::NewActivityInvitationCommand {
+            id: &id,
+            actor_user_id: auth_user_id,
+            invitation_id: &invitation.invitation_id,
+            activity_id: &invitation.activity_id,
+            action,
+            note: Some("website"),
+        },
+    )
+    .await;
+    central_apply_service::resolve_command(&id, res)
+}
+
+pub struct SentInviteView {
+    pub user_id: String,
+    pub name: String,
+    pub photo_image_id: Option<String>,
+    pub message: Option<String>,
+    pub invited_label: String,
+    pub status: String,
+}
+
+pub struct InviteCandidateView {
+    pub user_id: String,
+    pub name: String,
+    pub photo_image_id: Option<String>,
+    pub is_friend: bool,
+}
+
+pub struct ActivityInvitesView {
+    pub activity_id: String,
+    pub title: String,
+    pub scheduled_date_label: String,
+    pub scheduled_time_label: String,
+    pub privacy_level: String,
+    pub can_manage_activity: bool,
+    pub sent: Vec<SentInviteView>,
+    pub candidates: Vec<InviteCandidateView>,
+    pub q: String,
+}
+
+/// Who may invite whom, per `privacy_level`:
+/// - only activity managers send invites (the only way into `invite_only`);
+/// - `friends_only` invitees must be an accepted friend of the inviter.
+pub fn check_invite_allowed(
+    privacy_level: &str,
+    can_manage_activity: bool,
+    is_friend: bool,
+) -> Result<(), &'static str> {
+    if !can_manage_activity {
+        return Err("not_allowed");
+    }
+    if privacy_level == "friends_only" && !is_friend {
+        return Err("not_friends");
+    }
+    Ok(())
+}
+
+/// Organizer view of one activity: invites sent so far plus people to invite.
+pub async fn load_activity_invites_view(
+    pool: &SqlitePool,
+    auth_user_id: &str,
+    activity_id: &str,
+    q: Option<&str>,
+) -> sqlx::Result<Option<ActivityInvitesView>> {
+    let Some(activity) = activity_detail_repo::load_activity_by_id(pool, activity_id).await? else {
+        return Ok(None);
+    };
+
+    let q = q.map(str::trim).unwrap_or("").to_string();
+    let q_like = if q.is_empty() {
+        String::new()
+    } else {
+        format!("%{}%", q.to_lowercase())
+    };
+
+    let sent = activity_invite_commands_repo::list_sent_invites(pool, activity_id)
+        .await?
+        .into_iter()
+        .map(|r| SentInviteView {
+            name: non_empty(r.name).unwrap_or_else(|| "Onbekend".to_string()),
+            photo_image_id: r.photo_url.as_deref().and_then(extract_image_id),
+            message: non_empty(r.message),
+            invited_label: format_scheduled_labels(&r.invited_at).0,
+            status: r.status,
+            user_id: r.invitee_user_id,
+        })
+        .collect::<Vec<_>>();
+
+    let candidates = activity_invite_commands_repo::list_invite_candidates(
+        pool,
+        auth_user_id,
+        &q_like,
+        activity.privacy_level == "friends_only",
+        activity_id,
+        CANDIDATE_LIMIT,
+    )
+    .await?
+    .into_iter()
+    .filter(|c| {
+        !sent
+            .iter()
+            .any(|s| s.user_id == c.user_id && s.status == "pending")
+    })
+    .map(|c| InviteCandidateView {
+        name: non_empty(c.name).unwrap_or_else(|| "Onbekend".to_string()),
+        photo_image_id: c.photo_url.as_deref().and_then(extract_image_id),
+        is_friend: c.is_friend == 1,
+        user_id: c.user_id,
+    })
+    .collect();
+
+    let (scheduled_date_label, scheduled_time_label) =
+        format_scheduled_labels(&activity.scheduled_at);
+    Ok(Some(ActivityInvitesView {
+        activity_id: activity.activity_id,
+        title: activity.title,
+        scheduled_date_label,
+        scheduled_time_label,
+        privacy_level: activity.privacy_level,
+        can_manage_activity: activity.can_manage_activity == 1,
+        sent,
+        candidates,
+        q,
+    }))
+}
+
+/// Sends an invite from an activity manager. Privacy and duplicate checks run
+/// locally first; the central apply has the final say.
+pub async fn create_invite_command(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    activity_id: &str,
+    invitee_user_id: &str,
+    message: Option<&str>,
+) -> Result<ApplyOutcome, CommandError> {
+    let invitee_user_id = invitee_user_id.trim();
+    if invitee_user_id.is_empty() || invitee_user_id == actor_user_id {
+        return Err(sqlx::Error::Protocol("invalid invitee".into()).into());
+    }
+    let message = message
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.chars().take(MESSAGE_MAX_CHARS).collect::<String>());
+
+    let Some(activity) = activity_detail_repo::load_activity_by_id(pool, activity_id).await? else {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "not_found",
+            None,
+        )));
+    };
+    if activity.status == "cancelled" || activity.status == "completed" {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "unavailable",
+            None,
+        )));
+    }
+
+    let is_friend =
+        activity_invite_commands_repo::is_accepted_friend(pool, actor_user_id, invitee_user_id)
+            .await?;
+    if let Err(reason) = check_invite_allowed(
+        &activity.privacy_level,
+        activity.can_manage_activity == 1,
+        is_friend,
+    ) {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(reason, None)));
+    }
+
+    if activity_invite_commands_repo::is_participant(pool, activity_id, invitee_user_id).await? {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "already_joined",
+            None,
+        )));
+    }
+    let already_pending = activity_invite_commands_repo::list_sent_invites(pool, activity_id)
+        .await?
+        .iter()
+        .any(|r| r.invitee_user_id == invitee_user_id && r.status == "pending");
+    if already_pending {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "already_invited",
+            None,
+        )));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let res = activity_invite_commands_repo::insert_invite_command(
+        pool,
+        activity_invite_commands_repo::NewActivityInviteCommand {
+            id: &id,
+            actor_user_id,
+            activity_id,
+            invitee_user_id,
+            message: message.as_deref(),
+            note: Some("website"),
+        },
+    )
+    .await;
+    central_apply_service::resolve_command(&id, res)
+}
+
+#[derive(Deserialize, Default)]
+struct EmbeddedActivity {
+    title: Option<String>,
+    scheduled_at: Option<String>,
+    city: Option<String>,
+    organizer_name: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct EmbeddedInviter {
+    user_id: Option<String>,
+    name: Option<String>,
+    photo_url: Option<String>,
+}
+
+fn build_view(row: activity_invitations_repo::InvitationRow) -> InvitationView {
+    let embedded: EmbeddedActivity = row
+        .activity_summary
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    let inviter: EmbeddedInviter = row
+        .invited_by
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+
+    // The live activity row wins over the summary embedded at invite time.
+    let title = non_empty(row.activity_title)
+        .or_else(|| non_empty(embedded.title))
+        .unwrap_or_else(|| "Activiteit".to_string());
+    let scheduled_at = non_empty(row.activity_scheduled_at)
+        .or_else(|| non_empty(embedded.scheduled_at))
+        .unwrap_or_default();
+    let (scheduled_date_label, scheduled_time_label) = format_scheduled_labels(&scheduled_at);
+
+    InvitationView {
+        invitation_id: row.invitation_id,
+        activity_id: row.activity_id,
+        title,
+        scheduled_date_label,
+        scheduled_time_label,
+        city: non_empty(row.activity_city).or_else(|| non_empty(embedded.city)),
+        organizer_name: non_empty(embedded.organizer_name),
+        main_photo_asset_id: non_empty(row.main_photo_asset_id),
+        inviter_user_id: non_empty(inviter.user_id),
+        inviter_name: non_empty(inviter.name).unwrap_or_else(|| "Iemand".to_string()),
+        inviter_photo_image_id: inviter.photo_url.as_deref().and_then(extract_image_id),
+        message: non_empty(row.message),
+        expires_label: row.expires_at.as_deref().map(|s| {
+            let (date, time) = format_scheduled_labels(s);
+            if time.is_empty() {
+                date
+            } else {
+                format!("{} {}", date, time)
+            }
+        }),
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations::{schema_test_pool, schema_test_pool_with};
+    use crate::services::central_apply_service::StubCentralApply;
+    use std::sync::Arc;
+
+    async fn pool_with(backend: Arc<StubCentralApply>) -> SqlitePool {
+        let pool = schema_test_pool_with(backend).await;
+        for sql in [
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, row_hash, changed_at) \
+             VALUES ('a1', 'Padel', '2030-06-01T18:30:00Z', 4, 'h', '2030-01-01T00:00:00Z')",
+            "INSERT INTO activity_invitations (invitation_id, activity_id, activity_summary, invited_by, row_hash, changed_at) \
+             VALUES ('inv1', 'a1', '{\"title\":\"Padel\",\"scheduled_at\":\"2030-06-01T18:30:00Z\"}', \
+             '{\"user_id\":\"u2\",\"name\":\"Sanne\"}', 'h1', '2030-01-01T00:00:00Z')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    async fn response(pool: &SqlitePool) -> (String, Option<String>) {
+        sqlx::query_as(
+            "SELECT status, responded_at FROM activity_invitations WHERE invitation_id = 'inv1'",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn invite_rules_follow_privacy_level() {
+        assert_eq!(
+            check_invite_allowed("public", false, true),
+            Err("not_allowed")
+        );
+        assert_eq!(
+            check_invite_allowed("invite_only", false, true),
+            Err("not_allowed")
+        );
+        assert_eq!(check_invite_allowed("invite_only", true, false), Ok(()));
+        assert_eq!(
+            check_invite_allowed("friends_only", true, false),
+            Err("not_friends")
+        );
+        assert_eq!(check_invite_allowed("friends_only", true, true), Ok(()));
+        assert_eq!(check_invite_allowed("public", true, false), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn accept_marks_invitation_and_leaves_inbox() {
+        let stub = Arc::new(StubCentralApply::accept_all());
+        let pool = pool_with(stub.clone()).await;
+
+        let pending = load_pending_invitations(&pool, "u1").await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].title, "Padel");
+        assert_eq!(pending[0].inviter_name, "Sanne");
+
+        respond_to_invitation(&pool, "u1", "inv1", "accept")
+            .await
+            .unwrap();
+        let (status, responded_at) = response(&pool).await;
+        assert_eq!(status, "accepted");
+        assert!(responded_at.is_some());
+        assert!(load_pending_invitations(&pool, "u1")
+            .await
+            .unwrap()
+            .is_empty());
+
+        let calls = stub.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].sp_name, "sp_apply_activity_invitation_command");
+        assert_eq!(calls[0].command["invitation_id"], "inv1");
+
+        match respond_to_invitation(&pool, "u1", "inv1", "decline").await {
+            Err(CommandError::Rejected(o)) => {
+                assert_eq!(o.outcome.as_deref(), Some("already_responded"))
+            }
+            other => panic!("expected rejection, got {:?}", other.map(|o| o.outcome)),
+        }
+    }
+
+    #[tokio::test]
+    async fn decline_marks_invitation_declined() {
+        let pool = pool_with(Arc::new(StubCentralApply::accept_all())).await;
+
+        respond_to_invitation(&pool, "u1", "inv1", "decline")
+            .await
+            .unwrap();
+        let (status, responded_at) = response(&pool).await;
+        assert_eq!(status, "declined");
+        assert!(responded_at.is_some());
+        assert!(load_pending_invitations(&pool, "u1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_response_keeps_invitation_pending() {
+        let stub = Arc::new(StubCentralApply::with(|_| {
+            ApplyOutcome::rejected("activity_full", None)
+        }));
+        let pool = pool_with(stub).await;
+
+        let res = respond_to_invitation(&pool, "u1", "inv1", "accept").await;
+        assert!(matches!(res, Err(CommandError::Rejected(_))));
+        assert_eq!(response(&pool).await, ("pending".to_string(), None));
+        let commands: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM activity_invitation_commands")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(commands, 0);
+    }
+
+    #[tokio::test]
+    async fn invited_friends_leave_the_candidates() {
+        let pool = schema_test_pool().await;
+        for sql in [
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, \
+             privacy_level, status, can_manage_activity, row_hash, changed_at) \
+             VALUES ('a1', 'Padel', '2031-05-03T10:30:00', 4, 'friends_only', 'published', 1, 'h', 't')",
+            "INSERT INTO friends (friendship_id, friend, status, row_hash, changed_at) VALUES \
+             ('me:anna', json_object('user_id', 'anna', 'name', 'Anna'), 'accepted', 'h', 't'), \
+             ('me:gus', json_object('user_id', 'gus', 'name', 'Gus'), 'accepted', 'h', 't')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        create_invite_command(&pool, "me", "a1", "anna", Some("Doe je mee?"))
+            .await
+            .unwrap();
+        let view = load_activity_invites_view(&pool, "me", "a1", None)
+            .await
+            .unwrap()
+            .unwrap();
+        let sent: Vec<(&str, &str)> = view
+            .sent
+            .iter()
+            .map(|s| (s.user_id.as_str(), s.status.as_str()))
+            .collect();
+        assert_eq!(sent, [("anna", "pending")]);
+        let candidates: Vec<&str> = view.candidates.iter().map(|c| c.user_id.as_str()).collect();
+        assert_eq!(candidates, ["gus"]);
+
+        match create_invite_command(&pool, "me", "a1", "anna", None).await {
+            Err(CommandError::Rejected(o)) => {
+                assert_eq!(o.outcome.as_deref(), Some("already_invited"))
+            }
+            other => panic!("expected rejection, got {:?}", other.map(|o| o.outcome)),
+        }
+        match create_invite_command(&pool, "me", "a1", "stranger", None).await {
+            Err(CommandError::Rejected(o)) => assert_eq!(o.outcome.as_deref(), Some("not_friends")),
+            other => panic!("expected rejection, got {:?}", other.map(|o| o.outcome)),
+        }
+    }
+}
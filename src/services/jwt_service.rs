@@ -0,0 +1,400 @@
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Where the signing keys come from: the auth-service JWKS endpoint, or a local
+/// JWKS file (offline containers and tests).
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    Url(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    pub jwks_source: JwksSource,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub leeway_secs: u64,
+    /// How long a fetched key set is trusted before it is reloaded.
+    pub refresh_interval: Duration,
+    /// Minimum time between forced reloads for an unknown `kid`.
+    pub min_refetch_interval: Duration,
+}
+
+impl JwtConfig {
+    pub fn from_env() -> Self {
+        let jwks_source = match std::env::var("AUTH_JWKS_PATH") {
+            Ok(path) if !path.trim().is_empty() => JwksSource::File(PathBuf::from(path.trim())),
+            _ => JwksSource::Url(std::env::var("AUTH_JWKS_URL").unwrap_or_else(|_| {
+                "http://auth.localhost:8080/.well-known/jwks.json".to_string()
+            })),
+        };
+
+        Self {
+            jwks_source,
+            issuer: non_empty_env("AUTH_JWT_ISSUER"),
+            audience: non_empty_env("AUTH_JWT_AUDIENCE"),
+            leeway_secs: std::env::var("AUTH_JWT_LEEWAY_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            refresh_interval: Duration::from_secs(
+                std::env::var("AUTH_JWKS_REFRESH_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(600),
+            ),
+            min_refetch_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    Malformed,
+    MissingKid,
+    UnknownKey,
+    UnsupportedAlgorithm,
+    BadSignature,
+    Expired,
+    NotYetValid,
+    InvalidIssuer,
+    InvalidAudience,
+    MissingClaim(String),
+    KeysUnavailable(String),
+}
+
+impl TokenError {
+    /// Short machine-readable reason, used in the 401 response.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            TokenError::Malformed => "token_malformed",
+            TokenError::MissingKid => "token_missing_kid",
+            TokenError::UnknownKey => "token_unknown_key",
+            TokenError::UnsupportedAlgorithm => "token_unsupported_algorithm",
+            TokenError::BadSignature => "token_bad_signature",
+            TokenError::Expired => "token_expired",
+            TokenError::NotYetValid => "token_not_yet_valid",
+            TokenError::InvalidIssuer => "token_invalid_issuer",
+            TokenError::InvalidAudience => "token_invalid_audience",
+            TokenError::MissingClaim(_) => "token_missing_claim",
+            TokenError::KeysUnavailable(_) => "jwks_unavailable",
+        }
+    }
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::MissingClaim(c) => write!(f, "{} ({})", self.reason(), c),
+            TokenError::KeysUnavailable(e) => write!(f, "{} ({})", self.reason(), e),
+            _ => f.write_str(self.reason()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifiedClaims {
+    pub sub: String,
+    pub exp: i64,
+    #[serde(default)]
+    pub iat: Option<i64>,
+}
+
+struct CachedKey {
+    key: DecodingKey,
+    algorithm: Algorithm,
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: HashMap<String, Arc<CachedKey>>,
+    fetched_at: Option<Instant>,
+}
+
+pub struct JwksVerifier {
+    config: JwtConfig,
+    cache: RwLock<KeyCache>,
+    client: reqwest::Client,
+}
+
+impl JwksVerifier {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            cache: RwLock::new(KeyCache::default()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn config(&self) -> &JwtConfig {
+        &self.config
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<VerifiedClaims, TokenError> {
+        let header = decode_header(token).map_err(|_| TokenError::Malformed)?;
+        let kid = header.kid.ok_or(TokenError::MissingKid)?;
+        let cached = self.key_for(&kid).await?;
+
+        // Never let the token pick its own algorithm: it must match the key.
+        if header.alg != cached.algorithm {
+            return Err(TokenError::UnsupportedAlgorithm);
+        }
+
+        let mut validation = Validation::new(cached.algorithm);
+        validation.leeway = self.config.leeway_secs;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        match self.config.issuer.as_deref() {
+            Some(iss) => validation.set_issuer(&[iss]),
+            None => validation.iss = None,
+        }
+        match self.config.audience.as_deref() {
+            Some(aud) => validation.set_audience(&[aud]),
+            None => validation.validate_aud = false,
+        }
+
+        decode::<VerifiedClaims>(token, &cached.key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => TokenError::Expired,
+                ErrorKind::ImmatureSignature => TokenError::NotYetValid,
+                ErrorKind::InvalidSignature => TokenError::BadSignature,
+                ErrorKind::InvalidIssuer => TokenError::InvalidIssuer,
+                ErrorKind::InvalidAudience => TokenError::InvalidAudience,
+                ErrorKind::InvalidAlgorithm => TokenError::UnsupportedAlgorithm,
+                ErrorKind::MissingRequiredClaim(c) => TokenError::MissingClaim(c.clone()),
+                _ => TokenError::Malformed,
+            })
+    }
+
+    async fn key_for(&self, kid: &str) -> Result<Arc<CachedKey>, TokenError> {
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache
+                .fetched_at
+                .is_some_and(|t| t.elapsed() < self.config.refresh_interval);
+            if fresh {
+                if let Some(k) = cache.keys.get(kid) {
+                    return Ok(k.clone());
+                }
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+
+        // Another request may have refreshed while we waited for the lock.
+        let stale = cache
+            .fetched_at
+            .is_none_or(|t| t.elapsed() >= self.config.refresh_interval);
+        let may_refetch = cache
+            .fetched_at
+            .is_none_or(|t| t.elapsed() >= self.config.min_refetch_interval);
+
+        if stale || (!cache.keys.contains_key(kid) && may_refetch) {
+            match self.load_jwks().await {
+                Ok(set) => {
+                    cache.keys = build_key_map(&set);
+                    cache.fetched_at = Some(Instant::now());
+                    info!(keys = cache.keys.len(), "jwks_refreshed");
+                }
+                Err(e) => {
+                    warn!(error = %e, "jwks_refresh_failed");
+                    // Keep serving the previous keys if we have any.
+                    if cache.keys.is_empty() {
+                        return Err(TokenError::KeysUnavailable(e));
+                    }
+                }
+            }
+        }
+
+        cache.keys.get(kid).cloned().ok_or(TokenError::UnknownKey)
+    }
+
+    async fn load_jwks(&self) -> Result<JwkSet, String> {
+        match &self.config.jwks_source {
+            JwksSource::File(path) => {
+                let raw = tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                serde_json::from_str(&raw).map_err(|e| e.to_string())
+            }
+            JwksSource::Url(url) => {
+                let resp = self
+                    .client
+                    .get(url)
+                    .timeout(Duration::from_secs(5))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if !resp.status().is_success() {
+                    return Err(format!("{} returned {}", url, resp.status()));
+                }
+                resp.json::<JwkSet>().await.map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+fn build_key_map(set: &JwkSet) -> HashMap<String, Arc<CachedKey>> {
+    let mut out = HashMap::new();
+    for jwk in &set.keys {
+        let Some(kid) = jwk.common.key_id.clone() else {
+            continue;
+        };
+        let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+            (Some(alg), _) => match alg.to_string().parse::<Algorithm>() {
+                Ok(a) => a,
+                Err(_) => continue,
+            },
+            (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+            (None, AlgorithmParameters::EllipticCurve(_)) => Algorithm::ES256,
+            (None, AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+            (None, AlgorithmParameters::OctetKey(_)) => continue,
+        };
+        let Ok(key) = DecodingKey::from_jwk(jwk) else {
+            continue;
+        };
+        out.insert(kid, Arc::new(CachedKey { key, algorithm }));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const SIGNING_KEY: &str = include_str!("../../tests/fixtures/auth/signing_key.pem");
+    const FORGED_KEY: &str = include_str!("../../tests/fixtures/auth/forged_key.pem");
+    const KID: &str = "goamet-test-1";
+
+    fn verifier() -> JwksVerifier {
+        JwksVerifier::new(JwtConfig {
+            jwks_source: JwksSource::File(PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/auth/jwks.json"
+            ))),
+            issuer: Some("goamet-auth".to_string()),
+            audience: Some("goamet-website".to_string()),
+            leeway_secs: 0,
+            refresh_interval: Duration::from_secs(600),
+            min_refetch_interval: Duration::from_secs(30),
+        })
+    }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn sign(pem: &str, kid: Option<&str>, claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = kid.map(|k| k.to_string());
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn claims(exp_offset: i64) -> serde_json::Value {
+        json!({
+            "sub": "user-123",
+            "iss": "goamet-auth",
+            "aud": "goamet-website",
+            "iat": now(),
+            "exp": now() + exp_offset,
+        })
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_token() {
+        let token = sign(SIGNING_KEY, Some(KID), claims(300));
+        let verified = verifier().verify(&token).await.unwrap();
+        assert_eq!(verified.sub, "user-123");
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_token() {
+        let token = sign(SIGNING_KEY, Some(KID), claims(-60));
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::Expired
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_forged_signature() {
+        let token = sign(FORGED_KEY, Some(KID), claims(300));
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::BadSignature
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_kid() {
+        let token = sign(SIGNING_KEY, Some("rotated-away"), claims(300));
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::UnknownKey
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_kid() {
+        let token = sign(SIGNING_KEY, None, claims(300));
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::MissingKid
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_issuer_and_audience() {
+        let mut c = claims(300);
+        c["iss"] = json!("someone-else");
+        let token = sign(SIGNING_KEY, Some(KID), c);
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::InvalidIssuer
+        );
+
+        let mut c = claims(300);
+        c["aud"] = json!("other-app");
+        let token = sign(SIGNING_KEY, Some(KID), c);
+        assert_eq!(
+            verifier().verify(&token).await.unwrap_err(),
+            TokenError::InvalidAudience
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unsigned_payload() {
+        // The old middleware trusted any base64 payload; make sure that is gone.
+        let token = "eyJhbGciOiJub25lIn0.eyJzdWIiOiJ1c2VyLTEyMyJ9.";
+        assert_eq!(
+            verifier().verify(token).await.unwrap_err(),
+            TokenError::Malformed
+        );
+    }
+}
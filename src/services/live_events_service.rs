@@ -0,0 +1,552 @@
+//! Live events for open tabs (`GET /api/events`, server-sent events).
+//!
+//! The hub keeps one broadcast channel per connected user plus a short
+//! backlog so a reconnecting `EventSource` can resume from `Last-Event-ID`.
+//! The snapshot watcher polls the snapshot tables and publishes what changed:
+//! new notifications and unread totals for their recipient, chat unread
+//! totals for the snapshot owner, capacity changes for everyone.
+
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::database::current_user_repo;
+use crate::database::live_events_repo::{self, ActivityCapacityRow};
+
+const BACKLOG_LEN: usize = 256;
+const CHANNEL_CAPACITY: usize = 64;
+const NEW_NOTIFICATIONS_LIMIT: i64 = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveEvent {
+    pub id: u64,
+    /// `None` fans out to every connected user.
+    pub target_user_id: Option<String>,
+    pub name: &'static str,
+    pub data: Value,
+}
+
+impl LiveEvent {
+    fn is_visible_to(&self, user_id: &str) -> bool {
+        self.target_user_id.as_deref().is_none_or(|t| t == user_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveItem {
+    Event(Arc<LiveEvent>),
+    /// Events were dropped (backlog too short or a slow reader); the client
+    /// should refetch its state instead of relying on deltas.
+    Missed,
+}
+
+struct HubState {
+    next_id: u64,
+    backlog: VecDeque<Arc<LiveEvent>>,
+    channels: HashMap<String, broadcast::Sender<Arc<LiveEvent>>>,
+}
+
+pub struct LiveEventHub {
+    state: Mutex<HubState>,
+}
+
+impl Default for LiveEventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveEventHub {
+    pub fn new() -> Self {
+        // Ids start at the current time so a `Last-Event-ID` from before a
+        // restart is always older than the backlog and triggers a resync.
+        let next_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(1);
+        Self::starting_at(next_id)
+    }
+
+    fn starting_at(next_id: u64) -> Self {
+        Self {
+            state: Mutex::new(HubState {
+                next_id,
+                backlog: VecDeque::with_capacity(BACKLOG_LEN),
+                channels: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn publish(&self, target_user_id: Option<&str>, name: &'static str, data: Value) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let event = Arc::new(LiveEvent {
+            id,
+            target_user_id: target_user_id.map(str::to_string),
+            name,
+            data,
+        });
+        if state.backlog.len() == BACKLOG_LEN {
+            state.backlog.pop_front();
+        }
+        state.backlog.push_back(event.clone());
+
+        state.channels.retain(|_, tx| tx.receiver_count() > 0);
+        match target_user_id {
+            Some(user_id) => {
+                if let Some(tx) = state.channels.get(user_id) {
+                    let _ = tx.send(event);
+                }
+            }
+            None => {
+                for tx in state.channels.values() {
+                    let _ = tx.send(event.clone());
+                }
+            }
+        }
+        id
+    }
+
+    /// Subscribes `user_id`; with `last_event_id` the missed events that are
+    /// still in the backlog are replayed first.
+    pub fn subscribe(&self, user_id: &str, last_event_id: Option<u64>) -> LiveSubscription {
+        let mut state = self.state.lock().unwrap();
+        let receiver = state
+            .channels
+            .entry(user_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+
+        let mut pending = VecDeque::new();
+        if let Some(last_id) = last_event_id {
+            let oldest = state.backlog.front().map_or(state.next_id, |e| e.id);
+            if last_id + 1 < oldest || last_id >= state.next_id {
+                pending.push_back(LiveItem::Missed);
+            } else {
+                pending.extend(
+                    state
+                        .backlog
+                        .iter()
+                        .filter(|e| e.id > last_id && e.is_visible_to(user_id))
+                        .cloned()
+                        .map(LiveItem::Event),
+                );
+            }
+        }
+
+        LiveSubscription { pending, receiver }
+    }
+
+    pub fn connected_users(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .channels
+            .values()
+            .filter(|tx| tx.receiver_count() > 0)
+            .count()
+    }
+}
+
+pub struct LiveSubscription {
+    pending: VecDeque<LiveItem>,
+    receiver: broadcast::Receiver<Arc<LiveEvent>>,
+}
+
+impl LiveSubscription {
+    /// Next item for this tab; `None` once the hub is gone.
+    pub async fn next(&mut self) -> Option<LiveItem> {
+        if let Some(item) = self.pending.pop_front() {
+            return Some(item);
+        }
+        match self.receiver.recv().await {
+            Ok(event) => Some(LiveItem::Event(event)),
+            Err(broadcast::error::RecvError::Lagged(_)) => Some(LiveItem::Missed),
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LiveEventsConfig {
+    /// Snapshot poll interval; zero disables the watcher.
+    pub interval: Duration,
+}
+
+impl LiveEventsConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("LIVE_EVENTS_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        Self {
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotState {
+    /// Unread notifications per recipient.
+    pub notifications_unread: HashMap<String, i64>,
+    pub latest_notification_at: Option<String>,
+    pub chat_unread: HashMap<String, i64>,
+    pub capacity: HashMap<String, ActivityCapacityRow>,
+    /// Latest `activities.changed_at` seen; the next pass only reads newer rows.
+    pub capacity_changed_at: Option<String>,
+}
+
+/// The current state; with `prev`, capacity is only re-read for activities
+/// changed since its watermark.
+pub async fn load_snapshot_state(
+    pool: &SqlitePool,
+    prev: Option<&SnapshotState>,
+) -> sqlx::Result<SnapshotState> {
+    let watermark = prev.and_then(|p| p.capacity_changed_at.clone());
+    let mut capacity = prev.map(|p| p.capacity.clone()).unwrap_or_default();
+    let mut capacity_changed_at = watermark.clone();
+    for row in live_events_repo::list_activity_capacity_since(pool, watermark.as_deref()).await? {
+        if row.changed_at > capacity_changed_at {
+            capacity_changed_at = row.changed_at;
+        }
+        if row.is_live {
+            capacity.insert(row.capacity.activity_id.clone(), row.capacity);
+        } else {
+            capacity.remove(&row.capacity.activity_id);
+        }
+    }
+
+    Ok(SnapshotState {
+        notifications_unread: live_events_repo::list_unread_per_recipient(pool)
+            .await?
+            .into_iter()
+            .map(|r| (r.recipient_user_id, r.unread_count))
+            .collect(),
+        latest_notification_at: live_events_repo::latest_notification_at(pool).await?,
+        chat_unread: live_events_repo::list_conversation_unread(pool)
+            .await?
+            .into_iter()
+            .map(|r| (r.conversation_id, r.unread_count))
+            .collect(),
+        capacity,
+        capacity_changed_at,
+    })
+}
+
+/// `(recipient, count)` for every unread total that changed, zero for
+/// recipients with nothing unread left.
+pub fn diff_notifications_unread(prev: &SnapshotState, next: &SnapshotState) -> Vec<(String, i64)> {
+    let mut changed: Vec<(String, i64)> = next
+        .notifications_unread
+        .iter()
+        .filter(|(user, count)| prev.notifications_unread.get(*user) != Some(*count))
+        .map(|(user, count)| (user.clone(), *count))
+        .chain(
+            prev.notifications_unread
+                .keys()
+                .filter(|user| !next.notifications_unread.contains_key(*user))
+                .map(|user| (user.clone(), 0)),
+        )
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// `(name, data)` for every per-conversation unread total that changed.
+/// Conversations that disappeared report zero.
+pub fn diff_chat_unread(prev: &SnapshotState, next: &SnapshotState) -> Vec<(&'static str, Value)> {
+    let total: i64 = next.chat_unread.values().sum();
+    let mut changed: Vec<(&String, i64)> = next
+        .chat_unread
+        .iter()
+        .filter(|(id, count)| prev.chat_unread.get(*id).copied().unwrap_or(0) != **count)
+        .map(|(id, count)| (id, *count))
+        .chain(
+            prev.chat_unread
+                .iter()
+                .filter(|(id, count)| **count != 0 && !next.chat_unread.contains_key(*id))
+                .map(|(id, _)| (id, 0)),
+        )
+        .collect();
+    changed.sort();
+
+    changed
+        .into_iter()
+        .map(|(conversation_id, unread_count)| {
+            (
+                "chat_unread",
+                json!({
+                    "conversation_id": conversation_id,
+                    "unread_count": unread_count,
+                    "total_unread": total,
+                }),
+            )
+        })
+        .collect()
+}
+
+pub fn diff_capacity(prev: &SnapshotState, next: &SnapshotState) -> Vec<(&'static str, Value)> {
+    let mut changed: Vec<&ActivityCapacityRow> = next
+        .capacity
+        .iter()
+        .filter(|(id, row)| prev.capacity.get(*id).is_some_and(|p| p != *row))
+        .map(|(_, row)| row)
+        .collect();
+    changed.sort_by(|a, b| a.activity_id.cmp(&b.activity_id));
+
+    changed
+        .into_iter()
+        .map(|row| {
+            (
+                "activity_capacity",
+                json!({
+                    "activity_id": row.activity_id,
+                    "current_participants_count": row.current_participants_count,
+                    "max_participants": row.max_participants,
+                    "waitlist_count": row.waitlist_count,
+                    "is_full": row.max_participants > 0
+                        && row.current_participants_count >= row.max_participants,
+                }),
+            )
+        })
+        .collect()
+}
+
+/// One watcher pass: publishes everything that changed since `prev`.
+pub async fn publish_snapshot_changes(
+    pool: &SqlitePool,
+    hub: &LiveEventHub,
+    prev: &SnapshotState,
+) -> sqlx::Result<SnapshotState> {
+    let next = load_snapshot_state(pool, Some(prev)).await?;
+
+    if let Some(since) = prev.latest_notification_at.as_deref() {
+        if next.latest_notification_at.as_deref() > Some(since) {
+            for n in
+                live_events_repo::list_notifications_since(pool, since, NEW_NOTIFICATIONS_LIMIT)
+                    .await?
+            {
+                let Some(recipient) = n.recipient_user_id.as_deref() else {
+                    continue;
+                };
+                hub.publish(
+                    Some(recipient),
+                    "notification",
+                    json!({
+                        "notification_id": n.notification_id,
+                        "notification_type": n.notification_type,
+                        "title": n.title,
+                        "message": n.message,
+                        "created_at": n.created_at,
+                    }),
+                );
+            }
+        }
+    }
+    for (recipient, count) in diff_notifications_unread(prev, &next) {
+        hub.publish(
+            Some(&recipient),
+            "notifications_unread",
+            json!({ "count": count }),
+        );
+    }
+
+    // Conversations carry no owner column; their totals are the owner's.
+    let owner = current_user_repo::load_current_user_id(pool).await?;
+    if let Some(owner) = owner.as_deref() {
+        for (name, data) in diff_chat_unread(prev, &next) {
+            hub.publish(Some(owner), name, data);
+        }
+    }
+    for (name, data) in diff_capacity(prev, &next) {
+        hub.publish(None, name, data);
+    }
+
+    Ok(next)
+}
+
+pub fn spawn_snapshot_watcher(
+    pool: SqlitePool,
+    hub: Arc<LiveEventHub>,
+    config: LiveEventsConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if config.interval.is_zero() {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(config.interval);
+        let mut state: Option<SnapshotState> = None;
+        loop {
+            ticker.tick().await;
+            let result = match &state {
+                // First pass only records the baseline.
+                None => load_snapshot_state(&pool, None).await,
+                Some(prev) => publish_snapshot_changes(&pool, &hub, prev).await,
+            };
+            match result {
+                Ok(next) => state = Some(next),
+                Err(e) => warn!("Live events snapshot poll failed: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_ids(items: &[LiveItem]) -> Vec<u64> {
+        items
+            .iter()
+            .filter_map(|i| match i {
+                LiveItem::Event(e) => Some(e.id),
+                LiveItem::Missed => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fans_out_per_user_and_to_everyone() {
+        let hub = LiveEventHub::starting_at(1);
+        let mut alice = hub.subscribe("alice", None);
+        let mut bob = hub.subscribe("bob", None);
+
+        hub.publish(Some("alice"), "notifications_unread", json!({ "count": 2 }));
+        hub.publish(None, "activity_capacity", json!({ "activity_id": "a1" }));
+
+        let a1 = alice.next().await.unwrap();
+        let a2 = alice.next().await.unwrap();
+        assert_eq!(event_ids(&[a1, a2]), vec![1, 2]);
+
+        match bob.next().await.unwrap() {
+            LiveItem::Event(e) => assert_eq!((e.id, e.name), (2, "activity_capacity")),
+            LiveItem::Missed => panic!("unexpected resync"),
+        }
+        assert_eq!(hub.connected_users(), 2);
+    }
+
+    #[tokio::test]
+    async fn replays_backlog_after_last_event_id() {
+        let hub = LiveEventHub::starting_at(10);
+        hub.publish(Some("alice"), "notifications_unread", json!({ "count": 1 }));
+        hub.publish(Some("bob"), "notifications_unread", json!({ "count": 1 }));
+        hub.publish(None, "activity_capacity", json!({ "activity_id": "a1" }));
+
+        let mut sub = hub.subscribe("alice", Some(10));
+        let replayed = vec![sub.next().await.unwrap()];
+        assert_eq!(event_ids(&replayed), vec![12]);
+
+        // Ids from before a restart (or from the future) force a resync.
+        let mut stale = hub.subscribe("alice", Some(3));
+        assert_eq!(stale.next().await.unwrap(), LiveItem::Missed);
+        let mut future = hub.subscribe("alice", Some(99));
+        assert_eq!(future.next().await.unwrap(), LiveItem::Missed);
+    }
+
+    #[test]
+    fn diffs_chat_unread_and_capacity() {
+        let capacity = |current| ActivityCapacityRow {
+            activity_id: "a1".to_string(),
+            current_participants_count: current,
+            max_participants: 4,
+            waitlist_count: 0,
+        };
+        let prev = SnapshotState {
+            chat_unread: HashMap::from([("c1".to_string(), 1), ("c2".to_string(), 3)]),
+            capacity: HashMap::from([("a1".to_string(), capacity(3))]),
+            ..Default::default()
+        };
+        let next = SnapshotState {
+            chat_unread: HashMap::from([("c1".to_string(), 2)]),
+            capacity: HashMap::from([
+                ("a1".to_string(), capacity(4)),
+                ("a2".to_string(), capacity(1)),
+            ]),
+            ..Default::default()
+        };
+
+        let chat = diff_chat_unread(&prev, &next);
+        assert_eq!(chat.len(), 2);
+        assert_eq!(chat[0].1["conversation_id"], "c1");
+        assert_eq!(chat[0].1["unread_count"], 2);
+        assert_eq!(chat[1].1["conversation_id"], "c2");
+        assert_eq!(chat[1].1["unread_count"], 0);
+        assert_eq!(chat[1].1["total_unread"], 2);
+
+        let cap = diff_capacity(&prev, &next);
+        assert_eq!(cap.len(), 1);
+        assert_eq!(cap[0].1["is_full"], true);
+    }
+
+    #[tokio::test]
+    async fn routes_notifications_and_rereads_only_changed_activities() {
+        let pool = crate::database::migrations::schema_test_pool().await;
+        for sql in [
+            "INSERT INTO current_user (user_id) VALUES ('owner')",
+            "INSERT INTO activities (activity_id, title, scheduled_at, max_participants, \
+             current_participants_count, row_hash, changed_at) VALUES \
+             ('a1', 'Padel', '2031-05-03T10:30:00', 4, 1, 'h', '2020-01-01T08:00:00Z'), \
+             ('a2', 'Yoga', '2031-05-03T10:30:00', 10, 1, 'h', '2020-01-01T00:00:00Z')",
+            "INSERT INTO notifications (notification_id, notification_type, title, created_at, \
+             row_hash, changed_at) VALUES ('n0', 'system', 'Welkom', '2030-01-01 00:00:00', 'h', 't')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        let prev = load_snapshot_state(&pool, None).await.unwrap();
+        assert_eq!(
+            prev.notifications_unread,
+            HashMap::from([("owner".to_string(), 1)])
+        );
+        assert_eq!(prev.capacity.len(), 2);
+        assert_eq!(
+            prev.capacity_changed_at.as_deref(),
+            Some("2020-01-01 08:00:00")
+        );
+
+        for sql in [
+            "INSERT INTO notifications (notification_id, notification_type, title, created_at, \
+             recipient_user_id, row_hash, changed_at) \
+             VALUES ('n1', 'friend_request', 'Nieuw verzoek', '2030-01-02 00:00:00', 'bob', 'h', 't')",
+            "UPDATE notifications SET status = 'read' WHERE notification_id = 'n0'",
+            "UPDATE activities SET current_participants_count = 4, changed_at = datetime('now') \
+             WHERE activity_id = 'a1'",
+            // Earlier that day, so trimmed from the re-read.
+            "UPDATE activities SET current_participants_count = 9 WHERE activity_id = 'a2'",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        let hub = LiveEventHub::starting_at(1);
+        let next = publish_snapshot_changes(&pool, &hub, &prev).await.unwrap();
+        assert_eq!(next.capacity["a2"].current_participants_count, 1);
+        assert!(next.capacity_changed_at > prev.capacity_changed_at);
+
+        let received = |user: &str| -> Vec<(&'static str, Value)> {
+            hub.subscribe(user, Some(0))
+                .pending
+                .into_iter()
+                .filter_map(|i| match i {
+                    LiveItem::Event(e) => Some((e.name, e.data.clone())),
+                    LiveItem::Missed => None,
+                })
+                .collect()
+        };
+        let bob = received("bob");
+        let names: Vec<&str> = bob.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            ["notification", "notifications_unread", "activity_capacity"]
+        );
+        assert_eq!(bob[0].1["notification_id"], "n1");
+        assert_eq!(bob[1].1["count"], 1);
+        assert_eq!(bob[2].1["activity_id"], "a1");
+
+        let owner = received("owner");
+        assert_eq!(owner[0], ("notifications_unread", json!({ "count": 0 })));
+        assert_eq!(owner.len(), 2);
+    }
+}
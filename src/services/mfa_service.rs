@@ -0,0 +1,279 @@
+//! Server-side state for the second login step.
+//!
+//! When the auth-service answers a login with `mfa_required`, the
+//! half-finished login (its `mfa_token`) stays here under a random id that
+//! only travels in the short-lived `mfa_pending` cookie. Failed codes are
+//! counted per email: too many attempts in a short window are throttled,
+//! too many failures lock the email out for a while.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MfaPolicy {
+    /// How long a pending login waits for its code.
+    pub pending_ttl: Duration,
+    /// Failed codes before the email is locked out.
+    pub max_failures: u32,
+    pub lockout: Duration,
+    /// At most `max_attempts_per_window` code submissions per `window`.
+    pub max_attempts_per_window: usize,
+    pub window: Duration,
+}
+
+impl Default for MfaPolicy {
+    fn default() -> Self {
+        Self {
+            pending_ttl: Duration::from_secs(5 * 60),
+            max_failures: 5,
+            lockout: Duration::from_secs(15 * 60),
+            max_attempts_per_window: 3,
+            window: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingLogin {
+    pub email: String,
+    pub mfa_token: String,
+    pub return_to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MfaError {
+    /// Unknown or expired pending login; start over at `/login`.
+    Expired,
+    RateLimited {
+        retry_after: Duration,
+    },
+    LockedOut {
+        retry_after: Duration,
+    },
+}
+
+struct PendingEntry {
+    login: PendingLogin,
+    created: Instant,
+}
+
+#[derive(Default)]
+struct EmailState {
+    failures: u32,
+    attempts: Vec<Instant>,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct StoreState {
+    pending: HashMap<String, PendingEntry>,
+    emails: HashMap<String, EmailState>,
+}
+
+#[derive(Default)]
+pub struct MfaStore {
+    policy: MfaPolicy,
+    state: Mutex<StoreState>,
+}
+
+fn email_key(email: &str) -> String {
+    email.trim().to_ascii_lowercase()
+}
+
+impl MfaStore {
+    pub fn new(policy: MfaPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(StoreState::default()),
+        }
+    }
+
+    /// Remaining lockout for `email`, checked before the password step too.
+    pub fn locked_out(&self, email: &str) -> Option<Duration> {
+        self.locked_out_at(email, Instant::now())
+    }
+
+    fn locked_out_at(&self, email: &str, now: Instant) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state
+            .emails
+            .get(&email_key(email))
+            .and_then(|e| e.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Parks a login that still needs its second factor; returns the pending id.
+    pub fn start(&self, login: PendingLogin) -> String {
+        self.start_at(login, Instant::now())
+    }
+
+    fn start_at(&self, login: PendingLogin, now: Instant) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut state = self.state.lock().unwrap();
+        let ttl = self.policy.pending_ttl;
+        state
+            .pending
+            .retain(|_, p| now.duration_since(p.created) < ttl);
+        state.pending.insert(
+            id.clone(),
+            PendingEntry {
+                login,
+                created: now,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, pending_id: &str) -> Option<PendingLogin> {
+        self.get_at(pending_id, Instant::now())
+    }
+
+    fn get_at(&self, pending_id: &str, now: Instant) -> Option<PendingLogin> {
+        let state = self.state.lock().unwrap();
+        state
+            .pending
+            .get(pending_id)
+            .filter(|p| now.duration_since(p.created) < self.policy.pending_ttl)
+            .map(|p| p.login.clone())
+    }
+
+    /// Registers a code submission; call before asking the auth-service.
+    pub fn begin_attempt(&self, pending_id: &str) -> Result<PendingLogin, MfaError> {
+        self.begin_attempt_at(pending_id, Instant::now())
+    }
+
+    fn begin_attempt_at(&self, pending_id: &str, now: Instant) -> Result<PendingLogin, MfaError> {
+        let login = self.get_at(pending_id, now).ok_or(MfaError::Expired)?;
+        if let Some(retry_after) = self.locked_out_at(&login.email, now) {
+            return Err(MfaError::LockedOut { retry_after });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let email = state.emails.entry(email_key(&login.email)).or_default();
+        let window = self.policy.window;
+        email.attempts.retain(|t| now.duration_since(*t) < window);
+        if email.attempts.len() >= self.policy.max_attempts_per_window {
+            let oldest = email.attempts[0];
+            return Err(MfaError::RateLimited {
+                retry_after: window.saturating_sub(now.duration_since(oldest)),
+            });
+        }
+        email.attempts.push(now);
+        Ok(login)
+    }
+
+    /// Counts a wrong code. Returns the lockout once `max_failures` is hit;
+    /// the pending login is dropped then.
+    pub fn record_failure(&self, pending_id: &str) -> Option<MfaError> {
+        self.record_failure_at(pending_id, Instant::now())
+    }
+
+    fn record_failure_at(&self, pending_id: &str, now: Instant) -> Option<MfaError> {
+        let mut state = self.state.lock().unwrap();
+        let key = email_key(&state.pending.get(pending_id)?.login.email);
+        let email = state.emails.entry(key).or_default();
+        email.failures += 1;
+        if email.failures < self.policy.max_failures {
+            return None;
+        }
+
+        email.failures = 0;
+        email.attempts.clear();
+        email.locked_until = Some(now + self.policy.lockout);
+        state.pending.remove(pending_id);
+        Some(MfaError::LockedOut {
+            retry_after: self.policy.lockout,
+        })
+    }
+
+    /// Successful second factor: forget the pending login and the failures.
+    pub fn finish(&self, pending_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.pending.remove(pending_id) {
+            state.emails.remove(&email_key(&entry.login.email));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(email: &str) -> PendingLogin {
+        PendingLogin {
+            email: email.to_string(),
+            mfa_token: "mfa-1".to_string(),
+            return_to: None,
+        }
+    }
+
+    #[test]
+    fn pending_login_expires() {
+        let store = MfaStore::default();
+        let t0 = Instant::now();
+        let id = store.start_at(pending("a@example.com"), t0);
+
+        assert!(store.get_at(&id, t0 + Duration::from_secs(60)).is_some());
+        assert_eq!(
+            store.begin_attempt_at(&id, t0 + Duration::from_secs(301)),
+            Err(MfaError::Expired)
+        );
+        assert_eq!(
+            store.begin_attempt_at("unknown", t0),
+            Err(MfaError::Expired)
+        );
+    }
+
+    #[test]
+    fn throttles_then_locks_out_after_failures() {
+        let store = MfaStore::default();
+        let t0 = Instant::now();
+        let id = store.start_at(pending("A@example.com"), t0);
+
+        for _ in 0..3 {
+            assert!(store.begin_attempt_at(&id, t0).is_ok());
+            assert_eq!(store.record_failure_at(&id, t0), None);
+        }
+        assert!(matches!(
+            store.begin_attempt_at(&id, t0 + Duration::from_secs(10)),
+            Err(MfaError::RateLimited { retry_after }) if retry_after == Duration::from_secs(20)
+        ));
+
+        let t1 = t0 + Duration::from_secs(31);
+        assert!(store.begin_attempt_at(&id, t1).is_ok());
+        assert_eq!(store.record_failure_at(&id, t1), None);
+        assert!(store.begin_attempt_at(&id, t1).is_ok());
+        assert!(matches!(
+            store.record_failure_at(&id, t1),
+            Some(MfaError::LockedOut { .. })
+        ));
+
+        // The pending login is gone and the email stays locked.
+        assert_eq!(store.get_at(&id, t1), None);
+        assert!(store.locked_out_at("a@example.com", t1).is_some());
+        let again = store.start_at(pending("a@example.com"), t1);
+        assert!(matches!(
+            store.begin_attempt_at(&again, t1),
+            Err(MfaError::LockedOut { .. })
+        ));
+        assert_eq!(
+            store.locked_out_at("a@example.com", t1 + Duration::from_secs(15 * 60)),
+            None
+        );
+    }
+
+    #[test]
+    fn success_clears_failures() {
+        let store = MfaStore::default();
+        let id = store.start(pending("a@example.com"));
+        assert!(store.begin_attempt(&id).is_ok());
+        store.record_failure(&id);
+        store.finish(&id);
+
+        assert_eq!(store.get(&id), None);
+        let state = store.state.lock().unwrap();
+        assert!(state.emails.is_empty());
+    }
+}
@@ -1,11 +1,27 @@
+pub mod account_service;
 pub mod activities_service;
+pub mod activity_attendance_service;
 pub mod activity_detail_service;
+pub mod activity_editor_service;
+pub mod activity_lifecycle_service;
 pub mod activity_geo_service;
+pub mod activity_review_service;
 pub mod activity_summary_service;
+pub mod auth_api_service;
+pub mod central_apply_service;
 pub mod chat_api_service;
 pub mod chat_inbox_service;
 pub mod discovery_service;
 pub mod friendship_service;
+pub mod invitation_service;
+pub mod jwt_service;
+pub mod live_events_service;
 pub mod location_service;
+pub mod mfa_service;
+pub mod notification_service;
+pub mod onboarding_service;
+pub mod redirect_service;
+pub mod sync_service;
+pub mod timezone_service;
 pub mod user_service;
 pub mod user_summary_service;
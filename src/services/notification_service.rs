@@ -0,0 +1,433 @@
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::database::{notification_commands_repo, notifications_repo};
+use crate::services::activity_summary_service::{extract_image_id, format_scheduled_labels};
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
+use crate::services::redirect_service;
+
+const INBOX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct NotificationsQuery {
+    pub view: Option<String>, // inbox|archived
+    pub group: Option<String>,
+    pub notice: Option<String>,
+}
+
+pub struct NotificationActionView {
+    pub id: String,
+    pub label: String,
+    /// GET actions navigate to their url after being recorded.
+    pub is_link: bool,
+    pub style: String,
+    pub confirm: Option<String>,
+}
+
+pub struct NotificationView {
+    pub notification_id: String,
+    pub notification_type: String,
+    pub icon: &'static str,
+    pub title: String,
+    pub message: Option<String>,
+    pub is_unread: bool,
+    pub is_archived: bool,
+    pub is_important: bool,
+    pub actor_name: Option<String>,
+    pub actor_photo_image_id: Option<String>,
+    pub media_image_id: Option<String>,
+    pub link: Option<String>,
+    pub actions: Vec<NotificationActionView>,
+    pub group_id: Option<String>,
+    pub grouped_more: i64,
+    pub created_label: String,
+}
+
+pub struct NotificationsPageData {
+    pub view: String,
+    pub group_id: Option<String>,
+    /// Current page, used as `return_to` by the command forms.
+    pub self_url: String,
+    pub notifications: Vec<NotificationView>,
+    pub unread_count: i64,
+    pub notice: Option<String>,
+}
+
+pub async fn build_notifications_page(
+    pool: &SqlitePool,
+    query: &NotificationsQuery,
+) -> sqlx::Result<NotificationsPageData> {
+    let archived = query.view.as_deref() == Some("archived");
+    let group_id = query
+        .group
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    let rows = match group_id.as_deref() {
+        Some(g) => notifications_repo::list_group(pool, g, INBOX_LIMIT).await?,
+        None => notifications_repo::list_notifications(pool, archived, INBOX_LIMIT).await?,
+    };
+    let in_group = group_id.is_some();
+    let notifications = rows.into_iter().map(|r| build_view(r, in_group)).collect();
+
+    let self_url = match (group_id.as_deref(), archived) {
+        (Some(g), _) => format!(
+            "/notifications?{}",
+            serde_urlencoded::to_string([("group", g)]).unwrap_or_default()
+        ),
+        (None, true) => "/notifications?view=archived".to_string(),
+        (None, false) => "/notifications".to_string(),
+    };
+
+    Ok(NotificationsPageData {
+        view: if archived { "archived" } else { "inbox" }.to_string(),
+        group_id,
+        self_url,
+        notifications,
+        unread_count: notifications_repo::unread_count(pool).await?,
+        notice: query.notice.clone(),
+    })
+}
+
+pub async fn unread_count(pool: &SqlitePool) -> sqlx::Result<i64> {
+    notifications_repo::unread_count(pool).await
+}
+
+/// `read`, `archive` or `read_all` (no notification id).
+pub async fn create_notification_command(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    notification_id: Option<&str>,
+    action: &str,
+) -> Result<ApplyOutcome, CommandError> {
+    let action = action.trim();
+    let valid = match action {
+        "read" | "archive" => notification_id.is_some(),
+        "read_all" => notification_id.is_none(),
+        _ => false,
+    };
+    if !valid {
+        return Err(sqlx::Error::Protocol("invalid action".into()).into());
+    }
+    if let Some(id) = notification_id {
+        if notifications_repo::load_notification(pool, id)
+            .await?
+            .is_none()
+        {
+            return Err(CommandError::Rejected(ApplyOutcome::rejected(
+                "not_found",
+                None,
+            )));
+        }
+    }
+    insert_command(pool, actor_user_id, notification_id, action, None).await
+}
+
+/// Runs one of the notification's embedded action buttons through the
+/// central apply. Returns the outcome plus where the user should land next.
+pub async fn execute_notification_action(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    notification_id: &str,
+    action_id: &str,
+) -> Result<(ApplyOutcome, Option<String>), CommandError> {
+    let Some(row) = notifications_repo::load_notification(pool, notification_id).await? else {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "not_found",
+            None,
+        )));
+    };
+    let Some(action) = parse_actions(row.actions.as_deref())
+        .into_iter()
+        .find(|a| a.id.as_deref() == Some(action_id))
+    else {
+        return Err(CommandError::Rejected(ApplyOutcome::rejected(
+            "unknown_action",
+            None,
+        )));
+    };
+
+    let outcome = insert_command(
+        pool,
+        actor_user_id,
+        Some(notification_id),
+        "execute",
+        Some(action_id),
+    )
+    .await?;
+    let next = action
+        .url
+        .as_deref()
+        .filter(|_| is_link_method(action.method.as_deref()))
+        .and_then(local_link)
+        .map(|s| s.to_string());
+    Ok((outcome, next))
+}
+
+async fn insert_command(
+    pool: &SqlitePool,
+    actor_user_id: &str,
+    notification_id: Option<&str>,
+    action: &str,
+    action_id: Option<&str>,
+) -> Result<ApplyOutcome, CommandError> {
+    let id = Uuid::new_v4().to_string();
+    let res = notification_commands_repo::insert_notification_command(
+        pool,
+        notification_commands_repo::NewNotificationCommand {
+            id: &id,
+            actor_user_id,
+            notification_id,
+            action,
+            action_id,
+            note: Some("website"),
+        },
+    )
+    .await;
+    central_apply_service::resolve_command(&id, res)
+}
+
+#[derive(Deserialize, Default)]
+struct EmbeddedActor {
+    name: Option<String>,
+    photo_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct EmbeddedAction {
+    id: Option<String>,
+    label: Option<String>,
+    url: Option<String>,
+    method: Option<String>,
+    style: Option<String>,
+    confirm: Option<serde_json::Value>,
+}
+
+fn parse_actions(json: Option<&str>) -> Vec<EmbeddedAction> {
+    json.and_then(|s| serde_json::from_str::<Vec<EmbeddedAction>>(s).ok())
+        .unwrap_or_default()
+}
+
+fn is_link_method(method: Option<&str>) -> bool {
+    method
+        .map(|m| m.trim().eq_ignore_ascii_case("get"))
+        .unwrap_or(true)
+}
+
+/// Only same-site paths are followed; upstream API urls are executed centrally.
+fn local_link(url: &str) -> Option<&str> {
+    redirect_service::local_path(url).filter(|v| !v.starts_with("/api/"))
+}
+
+fn build_view(row: notifications_repo::NotificationRow, in_group: bool) -> NotificationView {
+    let actor: EmbeddedActor = row
+        .actor
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+
+    let actions = parse_actions(row.actions.as_deref())
+        .into_iter()
+        .filter_map(|a| {
+            let id = a.id.filter(|s| !s.trim().is_empty())?;
+            let label = a.label.filter(|s| !s.trim().is_empty())?;
+            let is_link = is_link_method(a.method.as_deref());
+            // A link without a usable local url has nothing to do.
+            if is_link && a.url.as_deref().and_then(local_link).is_none() {
+                return None;
+            }
+            let confirm = match a.confirm {
+                Some(serde_json::Value::String(s)) if !s.trim().is_empty() => Some(s),
+                Some(serde_json::Value::Bool(true)) => Some("Weet je het zeker?".to_string()),
+                _ => None,
+            };
+            Some(NotificationActionView {
+                id,
+                label,
+                is_link,
+                style: a.style.unwrap_or_else(|| "secondary".to_string()),
+                confirm,
+            })
+        })
+        .collect();
+
+    let link = row
+        .deep_link
+        .as_deref()
+        .and_then(local_link)
+        .map(|s| s.to_string())
+        .or_else(|| {
+            if row.notification_type == "activity_invite" {
+                Some("/invitations".to_string())
+            } else {
+                target_link(row.target_type.as_deref(), row.target_id.as_deref())
+            }
+        });
+    let media_image_id = if row.media_type.as_deref() == Some("video") {
+        None
+    } else {
+        row.media_url.as_deref().and_then(extract_image_id)
+    };
+    let (date, time) = format_scheduled_labels(&row.created_at);
+
+    NotificationView {
+        icon: type_icon(&row.notification_type),
+        notification_id: row.notification_id,
+        notification_type: row.notification_type,
+        title: row.title,
+        message: row
+            .message
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()),
+        is_unread: row.status == "unread",
+        is_archived: row.status == "archived",
+        is_important: row.priority == "high" || row.priority == "critical",
+        actor_name: actor
+            .name
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()),
+        actor_photo_image_id: actor.photo_url.as_deref().and_then(extract_image_id),
+        media_image_id,
+        link,
+        actions,
+        grouped_more: if in_group {
+            0
+        } else {
+            (row.grouped_count - 1).max(0)
+        },
+        group_id: row.group_id,
+        created_label: if time.is_empty() {
+            date
+        } else {
+            format!("{} {}", date, time)
+        },
+    }
+}
+
+fn target_link(target_type: Option<&str>, target_id: Option<&str>) -> Option<String> {
+    let id = target_id.map(str::trim).filter(|s| !s.is_empty())?;
+    match target_type? {
+        "activity" => Some(format!("/activities?tab=upcoming#activity-card-{}", id)),
+        "user" => Some(format!("/users/{}", id)),
+        "chat" => Some(format!("/chats/{}", id)),
+        _ => None,
+    }
+}
+
+fn type_icon(notification_type: &str) -> &'static str {
+    match notification_type {
+        "activity_invite" => "✉️",
+        "activity_reminder" => "⏰",
+        "activity_update" => "📅",
+        "friend_request" | "friend_accepted" => "🤝",
+        "chat_request" => "💬",
+        "comment" | "mention" => "💬",
+        "reaction" | "new_favorite" => "❤️",
+        "profile_view" => "👀",
+        "community_invite" | "community_announcement" | "new_member" | "new_post" => "👥",
+        _ => "🔔",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations::schema_test_pool_with;
+    use crate::services::central_apply_service::StubCentralApply;
+    use std::sync::Arc;
+
+    async fn pool_with(backend: Arc<StubCentralApply>) -> SqlitePool {
+        let pool = schema_test_pool_with(backend).await;
+        for sql in [
+            "INSERT INTO notifications (notification_id, notification_type, title, group_id, grouped_count, is_group_head, created_at, actions, row_hash, changed_at) VALUES \
+             ('n1', 'reaction', 'Sanne en 1 ander vinden je post leuk', 'g1', 2, 1, '2030-01-02T10:00:00', \
+              '[{\"id\":\"open\",\"label\":\"Bekijk\",\"url\":\"/users/u2\",\"method\":\"GET\"},{\"id\":\"thanks\",\"label\":\"Bedank\",\"url\":\"/api/v1/thanks\",\"method\":\"POST\"}]', 'h', 't'), \
+             ('n2', 'reaction', 'Sanne vindt je post leuk', 'g1', 1, 0, '2030-01-01T10:00:00', '[]', 'h', 't'), \
+             ('n3', 'system', 'Verlopen', NULL, 1, 1, '2030-01-01T10:00:00', '[]', 'h', 't')",
+            "UPDATE notifications SET expires_at = '2000-01-01T00:00:00' WHERE notification_id = 'n3'",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn inbox_shows_group_heads_and_hides_expired() {
+        let pool = pool_with(Arc::new(StubCentralApply::accept_all())).await;
+
+        let page = build_notifications_page(&pool, &NotificationsQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.unread_count, 2);
+        assert_eq!(page.notifications.len(), 1);
+        let head = &page.notifications[0];
+        assert_eq!(head.grouped_more, 1);
+        assert_eq!(head.actions.len(), 2);
+        assert!(head.actions[0].is_link);
+        assert!(!head.actions[1].is_link);
+
+        let group = build_notifications_page(
+            &pool,
+            &NotificationsQuery {
+                group: Some("g1".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(group.notifications.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn commands_update_the_whole_group() {
+        let stub = Arc::new(StubCentralApply::accept_all());
+        let pool = pool_with(stub.clone()).await;
+
+        sqlx::query(
+            "INSERT INTO notifications (notification_id, notification_type, title, created_at, row_hash, changed_at) VALUES \
+             ('n4', 'friend_request', 'Bram wil vrienden worden', '2030-01-03T10:00:00', 'h', 't'), \
+             ('n5', 'system', 'Welkom', '2030-01-03T11:00:00', 'h', 't')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(unread_count(&pool).await.unwrap(), 4);
+
+        let (_, next) = execute_notification_action(&pool, "u1", "n1", "thanks")
+            .await
+            .unwrap();
+        assert_eq!(next, None);
+        assert_eq!(unread_count(&pool).await.unwrap(), 2);
+        assert_eq!(stub.calls()[0].command["action_id"], "thanks");
+
+        create_notification_command(&pool, "u1", Some("n1"), "archive")
+            .await
+            .unwrap();
+        assert_eq!(unread_count(&pool).await.unwrap(), 2);
+        let archived = build_notifications_page(
+            &pool,
+            &NotificationsQuery {
+                view: Some("archived".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(archived.notifications.len(), 1);
+
+        create_notification_command(&pool, "u1", Some("n4"), "read")
+            .await
+            .unwrap();
+        assert_eq!(unread_count(&pool).await.unwrap(), 1);
+        create_notification_command(&pool, "u1", None, "read_all")
+            .await
+            .unwrap();
+        assert_eq!(unread_count(&pool).await.unwrap(), 0);
+
+        let res = execute_notification_action(&pool, "u1", "n1", "nope").await;
+        assert!(matches!(res, Err(CommandError::Rejected(_))));
+    }
+}
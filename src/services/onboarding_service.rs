@@ -0,0 +1,589 @@
+//! Onboarding wizard for users with status `pending_onboarding`.
+//!
+//! The pages come from `interests_catalog.onboarding_data`; a final step asks
+//! for the user's location. Answers are saved in `onboarding_progress` after
+//! every page, so the wizard resumes where the user left off. Finishing
+//! inserts an `onboarding_commands` row, which activates the user.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::database::onboarding_repo::{self, OnboardingProgressRow};
+use crate::services::central_apply_service::{self, ApplyOutcome, CommandError};
+
+pub const PENDING_ONBOARDING: &str = "pending_onboarding";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingCatalog {
+    #[serde(default)]
+    pub pages: Vec<CatalogPage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogPage {
+    #[serde(deserialize_with = "id_string")]
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub subtitle: Option<String>,
+    /// `None` (or 0) means no limit.
+    #[serde(default)]
+    pub max_selections: Option<u32>,
+    #[serde(default)]
+    pub sort_order: i64,
+    #[serde(default)]
+    pub categories: Vec<CatalogCategory>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogCategory {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub subtitle: Option<String>,
+    #[serde(default)]
+    pub theme_color: Option<String>,
+    #[serde(default)]
+    pub sort_order: i64,
+    #[serde(default)]
+    pub interests: Vec<CatalogInterest>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogInterest {
+    #[serde(deserialize_with = "id_string")]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub sort_order: i64,
+}
+
+/// Catalog ids arrive as numbers or strings depending on the exporter.
+fn id_string<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(d)? {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!("invalid id: {}", other))),
+    }
+}
+
+impl OnboardingCatalog {
+    /// Parses `onboarding_data`; pages, categories and interests come out in
+    /// `sortOrder`. Pages without interests are dropped.
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        let mut catalog: OnboardingCatalog = serde_json::from_str(json)?;
+        catalog
+            .pages
+            .retain(|p| p.categories.iter().any(|c| !c.interests.is_empty()));
+        catalog.pages.sort_by_key(|p| p.sort_order);
+        for page in &mut catalog.pages {
+            page.categories.sort_by_key(|c| c.sort_order);
+            for category in &mut page.categories {
+                category.interests.sort_by_key(|i| i.sort_order);
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Catalog pages plus the location step.
+    pub fn total_steps(&self) -> usize {
+        self.pages.len() + 1
+    }
+
+    pub fn location_step(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl CatalogPage {
+    fn limit(&self) -> Option<usize> {
+        self.max_selections.filter(|m| *m > 0).map(|m| m as usize)
+    }
+
+    fn find_interest(&self, id: &str) -> Option<(&CatalogCategory, &CatalogInterest)> {
+        self.categories
+            .iter()
+            .find_map(|c| c.interests.iter().find(|i| i.id == id).map(|i| (c, i)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnboardingLocation {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Saved wizard state: furthest step reached and the answers so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WizardProgress {
+    pub step: usize,
+    /// Page id → chosen interest ids.
+    pub selections: BTreeMap<String, Vec<String>>,
+    pub location: Option<OnboardingLocation>,
+}
+
+impl WizardProgress {
+    fn from_row(row: OnboardingProgressRow) -> Self {
+        let location = match (row.location_name, row.latitude, row.longitude) {
+            (Some(name), Some(latitude), Some(longitude)) => Some(OnboardingLocation {
+                name,
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        };
+        Self {
+            step: row.current_step.max(0) as usize,
+            selections: serde_json::from_str(&row.selections).unwrap_or_default(),
+            location,
+        }
+    }
+
+    fn to_row(&self) -> OnboardingProgressRow {
+        OnboardingProgressRow {
+            current_step: self.step as i64,
+            selections: serde_json::to_string(&self.selections).unwrap_or_else(|_| "{}".into()),
+            location_name: self.location.as_ref().map(|l| l.name.clone()),
+            latitude: self.location.as_ref().map(|l| l.latitude),
+            longitude: self.location.as_ref().map(|l| l.longitude),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OnboardingError {
+    TooManySelections {
+        max: usize,
+    },
+    UnknownInterest,
+    /// The location step was submitted before all pages were answered.
+    Incomplete,
+    LocationMissing,
+    Command(CommandError),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for OnboardingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnboardingError::TooManySelections { max } => {
+                write!(f, "Kies maximaal {} interesses op deze pagina.", max)
+            }
+            OnboardingError::UnknownInterest => {
+                write!(f, "Deze keuze hoort niet bij deze pagina.")
+            }
+            OnboardingError::Incomplete => {
+                write!(f, "Rond eerst de vorige stappen af.")
+            }
+            OnboardingError::LocationMissing => {
+                write!(f, "Kies je woonplaats uit de lijst.")
+            }
+            OnboardingError::Command(e) => write!(f, "{}", e),
+            OnboardingError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OnboardingError {
+    fn from(e: sqlx::Error) -> Self {
+        OnboardingError::Database(e)
+    }
+}
+
+impl From<CommandError> for OnboardingError {
+    fn from(e: CommandError) -> Self {
+        OnboardingError::Command(e)
+    }
+}
+
+pub struct InterestOption {
+    pub id: String,
+    pub name: String,
+    pub emoji: Option<String>,
+    pub selected: bool,
+}
+
+pub struct CategoryView {
+    pub name: String,
+    pub subtitle: Option<String>,
+    pub theme_color: Option<String>,
+    pub interests: Vec<InterestOption>,
+}
+
+pub struct WizardStepView {
+    pub step: usize,
+    pub total_steps: usize,
+    /// Highest step the user may jump to (progress dots).
+    pub reached_step: usize,
+    pub is_location_step: bool,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub max_selections: Option<usize>,
+    pub categories: Vec<CategoryView>,
+    pub location: Option<OnboardingLocation>,
+}
+
+impl WizardStepView {
+    pub fn step_number(&self) -> usize {
+        self.step + 1
+    }
+}
+
+pub async fn needs_onboarding(pool: &SqlitePool, user_id: &str) -> sqlx::Result<bool> {
+    Ok(onboarding_repo::load_user_status(pool, user_id)
+        .await?
+        .is_some_and(|s| s == PENDING_ONBOARDING))
+}
+
+async fn load_catalog(pool: &SqlitePool) -> sqlx::Result<OnboardingCatalog> {
+    let json = onboarding_repo::load_onboarding_data(pool).await?;
+    Ok(json
+        .and_then(|j| match OnboardingCatalog::parse(&j) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                tracing::warn!("interests_catalog.onboarding_data unreadable: {}", e);
+                None
+            }
+        })
+        .unwrap_or_default())
+}
+
+async fn load_progress(pool: &SqlitePool, user_id: &str) -> sqlx::Result<WizardProgress> {
+    Ok(onboarding_repo::load_progress(pool, user_id)
+        .await?
+        .map(WizardProgress::from_row)
+        .unwrap_or_default())
+}
+
+/// Renders `step`, or the saved step when none is given. Steps beyond the
+/// furthest one reached fall back to it, so pages cannot be skipped.
+pub async fn load_step(
+    pool: &SqlitePool,
+    user_id: &str,
+    step: Option<usize>,
+) -> sqlx::Result<WizardStepView> {
+    let catalog = load_catalog(pool).await?;
+    let progress = load_progress(pool, user_id).await?;
+    Ok(build_step(&catalog, &progress, step))
+}
+
+fn build_step(
+    catalog: &OnboardingCatalog,
+    progress: &WizardProgress,
+    step: Option<usize>,
+) -> WizardStepView {
+    let reached_step = progress.step.min(catalog.location_step());
+    let step = step.unwrap_or(reached_step).min(reached_step);
+
+    let Some(page) = catalog.pages.get(step) else {
+        return WizardStepView {
+            step,
+            total_steps: catalog.total_steps(),
+            reached_step,
+            is_location_step: true,
+            title: "Waar woon je?".to_string(),
+            subtitle: Some("We laten je activiteiten en mensen in de buurt zien.".to_string()),
+            max_selections: None,
+            categories: vec![],
+            location: progress.location.clone(),
+        };
+    };
+
+    let chosen = progress.selections.get(&page.id);
+    let categories = page
+        .categories
+        .iter()
+        .filter(|c| !c.interests.is_empty())
+        .map(|c| CategoryView {
+            name: c.name.clone(),
+            subtitle: c.subtitle.clone(),
+            theme_color: c.theme_color.clone(),
+            interests: c
+                .interests
+                .iter()
+                .map(|i| InterestOption {
+                    id: i.id.clone(),
+                    name: i.name.clone(),
+                    emoji: i.emoji.clone(),
+                    selected: chosen.is_some_and(|ids| ids.contains(&i.id)),
+                })
+                .collect(),
+        })
+        .collect();
+
+    WizardStepView {
+        step,
+        total_steps: catalog.total_steps(),
+        reached_step,
+        is_location_step: false,
+        title: page.title.clone(),
+        subtitle: page.subtitle.clone(),
+        max_selections: page.limit(),
+        categories,
+        location: None,
+    }
+}
+
+/// Checks one page's answer against the catalog: known ids only, within
+/// `maxSelections`. Returns the ids deduplicated, in submission order.
+pub fn validate_selection(
+    page: &CatalogPage,
+    interest_ids: &[String],
+) -> Result<Vec<String>, OnboardingError> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in interest_ids.iter().map(|s| s.trim()) {
+        if page.find_interest(id).is_none() {
+            return Err(OnboardingError::UnknownInterest);
+        }
+        if !ids.iter().any(|s| s == id) {
+            ids.push(id.to_string());
+        }
+    }
+    match page.limit() {
+        Some(max) if ids.len() > max => Err(OnboardingError::TooManySelections { max }),
+        _ => Ok(ids),
+    }
+}
+
+/// Saves the answer for a catalog page and returns the step to show next:
+/// the following one, or the previous one when going `back`.
+pub async fn save_page(
+    pool: &SqlitePool,
+    user_id: &str,
+    step: usize,
+    interest_ids: &[String],
+    back: bool,
+) -> Result<usize, OnboardingError> {
+    let catalog = load_catalog(pool).await?;
+    let mut progress = load_progress(pool, user_id).await?;
+    let step = step.min(progress.step);
+    let Some(page) = catalog.pages.get(step) else {
+        return Ok(catalog.location_step());
+    };
+
+    let ids = validate_selection(page, interest_ids)?;
+    progress.selections.insert(page.id.clone(), ids);
+    let next = if back {
+        step.saturating_sub(1)
+    } else {
+        step + 1
+    };
+    progress.step = progress.step.max(next);
+    onboarding_repo::save_progress(pool, user_id, &progress.to_row()).await?;
+    Ok(next)
+}
+
+/// Stores the location and submits the whole wizard as an onboarding
+/// command. The location stays saved when the command is rejected.
+pub async fn complete(
+    pool: &SqlitePool,
+    user_id: &str,
+    location: Option<OnboardingLocation>,
+) -> Result<ApplyOutcome, OnboardingError> {
+    let location = location
+        .filter(|l| {
+            !l.name.trim().is_empty()
+                && (-90.0..=90.0).contains(&l.latitude)
+                && (-180.0..=180.0).contains(&l.longitude)
+        })
+        .ok_or(OnboardingError::LocationMissing)?;
+
+    let catalog = load_catalog(pool).await?;
+    let mut progress = load_progress(pool, user_id).await?;
+    if progress.step < catalog.location_step() {
+        return Err(OnboardingError::Incomplete);
+    }
+    progress.location = Some(location.clone());
+    onboarding_repo::save_progress(pool, user_id, &progress.to_row()).await?;
+
+    let interests = interests_json(&catalog, &progress);
+    let id = Uuid::new_v4().to_string();
+    let res = onboarding_repo::insert_onboarding_command(
+        pool,
+        onboarding_repo::NewOnboardingCommand {
+            id: &id,
+            actor_user_id: user_id,
+            interests: &interests,
+            city: location.name.trim(),
+            latitude: location.latitude,
+            longitude: location.longitude,
+            note: Some("website"),
+        },
+    )
+    .await;
+    Ok(central_apply_service::resolve_command(&id, res)?)
+}
+
+/// The chosen interests in `users.interests` shape, in catalog order.
+fn interests_json(catalog: &OnboardingCatalog, progress: &WizardProgress) -> String {
+    let interests: Vec<serde_json::Value> = catalog
+        .pages
+        .iter()
+        .filter_map(|page| Some((page, progress.selections.get(&page.id)?)))
+        .flat_map(|(page, chosen)| {
+            page.categories.iter().flat_map(move |c| {
+                c.interests
+                    .iter()
+                    .filter(|i| chosen.contains(&i.id))
+                    .map(move |i| {
+                        json!({
+                            "interest_id": i.id,
+                            "name": i.name,
+                            "emoji": i.emoji,
+                            "category_name": c.name,
+                        })
+                    })
+            })
+        })
+        .collect();
+    serde_json::Value::from(interests).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations::schema_test_pool;
+
+    const CATALOG: &str = r#"{
+      "totalPages": 3,
+      "pages": [
+        {"id": 2, "slug": "food", "title": "Eten", "maxSelections": 1, "sortOrder": 2,
+         "categories": [{"id": 20, "name": "Keuken", "sortOrder": 1,
+           "interests": [{"id": "i-sushi", "name": "Sushi", "emoji": "🍣", "sortOrder": 1}]}]},
+        {"id": 1, "slug": "sport", "title": "Sport", "maxSelections": 2, "sortOrder": 1,
+         "categories": [
+           {"id": 11, "name": "Water", "sortOrder": 2,
+            "interests": [{"id": 3, "name": "Zwemmen", "sortOrder": 1}]},
+           {"id": 10, "name": "Buiten", "sortOrder": 1,
+            "interests": [{"id": 2, "name": "Wandelen", "sortOrder": 2},
+                          {"id": 1, "name": "Hardlopen", "sortOrder": 1}]}]},
+        {"id": 9, "title": "Leeg", "sortOrder": 3, "categories": []}
+      ]
+    }"#;
+
+    fn ids(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_and_orders_catalog() {
+        let catalog = OnboardingCatalog::parse(CATALOG).unwrap();
+        let titles: Vec<&str> = catalog.pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Sport", "Eten"]);
+        assert_eq!(catalog.total_steps(), 3);
+        let sport = &catalog.pages[0];
+        assert_eq!(sport.id, "1");
+        assert_eq!(sport.categories[0].name, "Buiten");
+        assert_eq!(sport.categories[0].interests[0].name, "Hardlopen");
+    }
+
+    #[test]
+    fn enforces_max_selections_per_page() {
+        let catalog = OnboardingCatalog::parse(CATALOG).unwrap();
+        let sport = &catalog.pages[0];
+        assert_eq!(
+            validate_selection(sport, &ids(&["1", "3", "1"])).unwrap(),
+            ids(&["1", "3"])
+        );
+        assert!(matches!(
+            validate_selection(sport, &ids(&["1", "2", "3"])),
+            Err(OnboardingError::TooManySelections { max: 2 })
+        ));
+        assert!(matches!(
+            validate_selection(sport, &ids(&["i-sushi"])),
+            Err(OnboardingError::UnknownInterest)
+        ));
+    }
+
+    #[tokio::test]
+    async fn resumes_saved_progress_and_builds_interests() {
+        let pool = schema_test_pool().await;
+        sqlx::query(
+            "INSERT INTO interests_catalog (id, onboarding_data, row_hash, changed_at) VALUES (1, ?, 'h', '2026-01-01T00:00:00Z')",
+        )
+        .bind(CATALOG)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO users (user_id, name, status, row_hash, changed_at) VALUES ('u1', 'Anna', 'pending_onboarding', 'h', '2026-01-01T00:00:00Z')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(needs_onboarding(&pool, "u1").await.unwrap());
+
+        // Fresh user starts at the first page and cannot skip ahead.
+        let view = load_step(&pool, "u1", Some(2)).await.unwrap();
+        assert_eq!((view.step, view.total_steps), (0, 3));
+        assert_eq!(view.max_selections, Some(2));
+
+        assert_eq!(
+            save_page(&pool, "u1", 0, &ids(&["2"]), false)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Dropping off and coming back lands on the second page.
+        let view = load_step(&pool, "u1", None).await.unwrap();
+        assert_eq!(view.title, "Eten");
+        let back = load_step(&pool, "u1", Some(0)).await.unwrap();
+        assert!(back.categories[0].interests[1].selected, "Wandelen kept");
+
+        save_page(&pool, "u1", 1, &ids(&["i-sushi"]), false)
+            .await
+            .unwrap();
+        let view = load_step(&pool, "u1", None).await.unwrap();
+        assert!(view.is_location_step);
+
+        let catalog = OnboardingCatalog::parse(CATALOG).unwrap();
+        let progress = load_progress(&pool, "u1").await.unwrap();
+        let interests: serde_json::Value =
+            serde_json::from_str(&interests_json(&catalog, &progress)).unwrap();
+        assert_eq!(interests[0]["name"], "Wandelen");
+        assert_eq!(interests[0]["category_name"], "Buiten");
+        assert_eq!(interests[1]["interest_id"], "i-sushi");
+
+        let outcome = complete(
+            &pool,
+            "u1",
+            Some(OnboardingLocation {
+                name: " Utrecht ".into(),
+                latitude: 52.09,
+                longitude: 5.12,
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(outcome.is_applied());
+
+        // The 026 trigger activates the snapshot user and drops the progress.
+        let (status, city, stored): (String, String, String) =
+            sqlx::query_as("SELECT status, city, interests FROM users WHERE user_id = 'u1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((status.as_str(), city.as_str()), ("active", "Utrecht"));
+        let stored: serde_json::Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored, interests);
+        let (left,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM onboarding_progress WHERE user_id = 'u1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(left, 0);
+        assert!(!needs_onboarding(&pool, "u1").await.unwrap());
+    }
+}
@@ -0,0 +1,47 @@
+//! Same-site redirect targets taken from forms, query strings and
+//! notification links.
+
+/// `value` trimmed if it is a path on this site, `None` otherwise.
+///
+/// Browsers read `\` as `/` and drop tabs and newlines from urls, so
+/// `/\evil.com` and `/<tab>/evil.com` both end up at `//evil.com`; any
+/// backslash or control character is refused along with `//` and schemes.
+pub fn local_path(value: &str) -> Option<&str> {
+    let v = value.trim();
+    if !v.starts_with('/') || v.starts_with("//") || v.contains("://") {
+        return None;
+    }
+    if v.chars().any(|c| c == '\\' || c.is_control()) {
+        return None;
+    }
+    Some(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_paths_on_this_site() {
+        assert_eq!(
+            local_path(" /activities?tab=map "),
+            Some("/activities?tab=map")
+        );
+        assert_eq!(local_path("/"), Some("/"));
+
+        for unsafe_target in [
+            "",
+            "activities",
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "/activities\\..\\",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "/redirect?to=https://evil.com",
+        ] {
+            assert_eq!(local_path(unsafe_target), None, "{:?}", unsafe_target);
+        }
+    }
+}
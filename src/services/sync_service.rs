@@ -0,0 +1,715 @@
+//! Snapshot sync: pulls incremental changes per entity type from the central
+//! API into the local snapshot tables and keeps `sync_state` up to date.
+//!
+//! Upstream contract (`GET {SYNC_API_URL}/api/v1/sync/{entity_type}?since=&limit=`):
+//!
+//! ```json
+//! { "records": [{ "<pk>": "...", "row_hash": "...", "changed_at": "...", "is_deleted": 0, ... }],
+//!   "next_since": "2025-12-17T11:33:44Z", "has_more": false, "range_hash": "<sha256 hex>" }
+//! ```
+//!
+//! `range_hash` is the sha256 over `"{pk}:{row_hash}\n"` for every live row,
+//! ordered by pk. It is sent on the last page and compared with the local
+//! snapshot; the local value is stored in `sync_state.last_range_hash` so a
+//! snapshot changed behind our back is detected on the next run.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteArguments, Arguments, SqlitePool};
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::database::sync_repo;
+
+pub const EPOCH: &str = "1970-01-01T00:00:00Z";
+
+/// A snapshot table that follows the `row_hash` / `changed_at` / `is_deleted` layout.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncEntity {
+    pub entity_type: &'static str,
+    pub table: &'static str,
+    pub pk: &'static str,
+}
+
+const fn entity(entity_type: &'static str, pk: &'static str) -> SyncEntity {
+    SyncEntity {
+        entity_type,
+        table: entity_type,
+        pk,
+    }
+}
+
+pub const SYNC_ENTITIES: &[SyncEntity] = &[
+    entity("users", "user_id"),
+    entity("activities", "activity_id"),
+    entity("categories", "category_id"),
+    entity("interests", "interest_id"),
+    entity("communities", "community_id"),
+    entity("conversations", "conversation_id"),
+    entity("messages", "message_id"),
+    entity("friends", "friendship_id"),
+    entity("notifications", "notification_id"),
+    entity("favorites", "favorite_id"),
+    entity("blocks", "block_id"),
+    entity("mutes", "mute_id"),
+    entity("activity_invitations", "invitation_id"),
+    entity("community_posts", "post_id"),
+    entity("profile_views", "view_id"),
+    entity("activity_reviews", "review_id"),
+    entity("comments", "comment_id"),
+];
+
+pub fn find_entity(entity_type: &str) -> Option<&'static SyncEntity> {
+    SYNC_ENTITIES.iter().find(|e| e.entity_type == entity_type)
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub base_url: String,
+    pub api_token: Option<String>,
+    pub page_size: i64,
+    /// Background interval; zero disables the background task.
+    pub interval: Duration,
+}
+
+impl SyncConfig {
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("SYNC_API_URL")
+            .unwrap_or_else(|_| "http://api.localhost:8080".to_string());
+        let page_size = std::env::var("SYNC_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i64| *v > 0)
+            .unwrap_or(500);
+        let interval_secs = std::env::var("SYNC_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_token: std::env::var("SYNC_API_TOKEN")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            page_size,
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncPage {
+    #[serde(default)]
+    records: Vec<Map<String, Value>>,
+    next_since: Option<String>,
+    #[serde(default)]
+    has_more: bool,
+    range_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntitySyncReport {
+    pub entity_type: String,
+    pub pages: usize,
+    pub upserted: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+    pub full_resync: bool,
+    pub skipped: bool,
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    Upstream(String),
+    Database(sqlx::Error),
+    InvalidRecord(String),
+    /// Local snapshot does not match upstream even after a full resync.
+    Drift {
+        entity_type: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Upstream(e) => write!(f, "upstream: {}", e),
+            SyncError::Database(e) => write!(f, "database: {}", e),
+            SyncError::InvalidRecord(e) => write!(f, "invalid record: {}", e),
+            SyncError::Drift {
+                entity_type,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: range hash mismatch (upstream {}, local {})",
+                entity_type, expected, actual
+            ),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SyncError {
+    fn from(e: sqlx::Error) -> Self {
+        SyncError::Database(e)
+    }
+}
+
+pub fn range_hash(rows: &[(String, String)]) -> String {
+    let mut hasher = Sha256::new();
+    for (pk, row_hash) in rows {
+        hasher.update(pk.as_bytes());
+        hasher.update(b":");
+        hasher.update(row_hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+pub struct SyncEngine {
+    pool: SqlitePool,
+    client: reqwest::Client,
+    config: SyncConfig,
+}
+
+impl SyncEngine {
+    pub fn new(pool: SqlitePool, config: SyncConfig) -> Self {
+        Self {
+            pool,
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+
+    pub async fn sync_all(&self) -> Vec<(&'static str, Result<EntitySyncReport, SyncError>)> {
+        let mut out = Vec::with_capacity(SYNC_ENTITIES.len());
+        for entity in SYNC_ENTITIES {
+            out.push((entity.entity_type, self.sync_entity(entity).await));
+        }
+        out
+    }
+
+    pub async fn sync_entity(&self, entity: &SyncEntity) -> Result<EntitySyncReport, SyncError> {
+        if !sync_repo::table_exists(&self.pool, entity.table).await? {
+            return Ok(EntitySyncReport {
+                entity_type: entity.entity_type.to_string(),
+                skipped: true,
+                ..Default::default()
+            });
+        }
+
+        let state = sync_repo::load_sync_state(&self.pool, entity.entity_type).await?;
+        let local_hash = {
+            let mut conn = self.pool.acquire().await?;
+            range_hash(&sync_repo::live_row_hashes(&mut conn, entity.table, entity.pk).await?)
+        };
+        let local_drift = state
+            .last_range_hash
+            .as_deref()
+            .is_some_and(|stored| stored != local_hash);
+        if local_drift {
+            warn!(
+                entity = entity.entity_type,
+                "sync_local_drift_detected, full resync"
+            );
+        }
+
+        match self
+            .sync_pass(entity, &state.last_sync_at, local_drift)
+            .await
+        {
+            Err(SyncError::Drift { .. }) if !local_drift => {
+                warn!(
+                    entity = entity.entity_type,
+                    "sync_upstream_drift_detected, full resync"
+                );
+                self.sync_pass(entity, EPOCH, true).await
+            }
+            other => other,
+        }
+    }
+
+    /// One pass over all upstream pages. A full pass starts at the epoch and
+    /// soft-deletes local rows that upstream no longer has.
+    async fn sync_pass(
+        &self,
+        entity: &SyncEntity,
+        since: &str,
+        full: bool,
+    ) -> Result<EntitySyncReport, SyncError> {
+        let columns = sync_repo::table_columns(&self.pool, entity.table).await?;
+        let mut report = EntitySyncReport {
+            entity_type: entity.entity_type.to_string(),
+            full_resync: full,
+            ..Default::default()
+        };
+        let mut since = if full {
+            EPOCH.to_string()
+        } else {
+            since.to_string()
+        };
+        let mut seen: HashSet<String> = HashSet::new();
+
+        loop {
+            let page = self.fetch_page(entity.entity_type, &since).await?;
+            report.pages += 1;
+
+            let mut tx = self.pool.begin().await?;
+            for record in &page.records {
+                let id = record_id(record, entity.pk)?;
+                if is_deleted(record) {
+                    let changed_at = record.get("changed_at").and_then(|v| v.as_str());
+                    let row_hash = record.get("row_hash").and_then(|v| v.as_str());
+                    let n = sync_repo::soft_delete_row(
+                        &mut tx,
+                        entity.table,
+                        entity.pk,
+                        &id,
+                        changed_at,
+                        row_hash,
+                    )
+                    .await?;
+                    report.deleted += n as usize;
+                    continue;
+                }
+
+                if !record.contains_key("row_hash") {
+                    return Err(SyncError::InvalidRecord(format!(
+                        "{} {} has no row_hash",
+                        entity.entity_type, id
+                    )));
+                }
+                let cols: Vec<&str> = columns
+                    .iter()
+                    .map(|c| c.as_str())
+                    .filter(|c| record.contains_key(*c))
+                    .collect();
+                let mut args = SqliteArguments::default();
+                for c in &cols {
+                    add_json_arg(&mut args, &record[*c]);
+                }
+                let n =
+                    sync_repo::upsert_snapshot_row(&mut tx, entity.table, entity.pk, &cols, args)
+                        .await?;
+                if n > 0 {
+                    report.upserted += 1;
+                } else {
+                    report.unchanged += 1;
+                }
+                if full {
+                    seen.insert(id);
+                }
+            }
+
+            let last_page = !page.has_more || page.records.is_empty();
+            if last_page && full {
+                let stale: Vec<String> =
+                    sync_repo::live_row_hashes(&mut tx, entity.table, entity.pk)
+                        .await?
+                        .into_iter()
+                        .map(|(pk, _)| pk)
+                        .filter(|pk| !seen.contains(pk))
+                        .collect();
+                for pk in stale {
+                    report.deleted += sync_repo::soft_delete_row(
+                        &mut tx,
+                        entity.table,
+                        entity.pk,
+                        &pk,
+                        None,
+                        None,
+                    )
+                    .await? as usize;
+                }
+            }
+
+            let live = sync_repo::live_row_hashes(&mut tx, entity.table, entity.pk).await?;
+            let local_hash = range_hash(&live);
+            if last_page {
+                if let Some(expected) = page.range_hash.as_deref() {
+                    if expected != local_hash {
+                        tx.rollback().await?;
+                        return Err(SyncError::Drift {
+                            entity_type: entity.entity_type.to_string(),
+                            expected: expected.to_string(),
+                            actual: local_hash,
+                        });
+                    }
+                }
+            }
+
+            let next_since = page.next_since.clone().unwrap_or_else(|| since.clone());
+            sync_repo::save_sync_state(
+                &mut tx,
+                entity.entity_type,
+                &next_since,
+                Some(&local_hash),
+                live.len() as i64,
+            )
+            .await?;
+            tx.commit().await?;
+
+            if last_page {
+                return Ok(report);
+            }
+            since = next_since;
+        }
+    }
+
+    async fn fetch_page(&self, entity_type: &str, since: &str) -> Result<SyncPage, SyncError> {
+        let url = format!("{}/api/v1/sync/{}", self.config.base_url, entity_type);
+        let mut req = self
+            .client
+            .get(&url)
+            .timeout(Duration::from_secs(30))
+            .query(&[
+                ("since", since.to_string()),
+                ("limit", self.config.page_size.to_string()),
+            ]);
+        if let Some(token) = &self.config.api_token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| SyncError::Upstream(e.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(SyncError::Upstream(format!(
+                "{} returned {}",
+                url,
+                resp.status()
+            )));
+        }
+        resp.json::<SyncPage>()
+            .await
+            .map_err(|e| SyncError::Upstream(e.to_string()))
+    }
+}
+
+/// Runs `sync_all` every `config.interval` in the background. Returns `None`
+/// when the interval is zero (disabled).
+pub fn spawn_background(
+    pool: SqlitePool,
+    config: SyncConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if config.interval.is_zero() {
+        return None;
+    }
+    let interval = config.interval;
+    let engine = SyncEngine::new(pool, config);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            log_results(&engine.sync_all().await);
+        }
+    }))
+}
+
+pub fn log_results(results: &[(&'static str, Result<EntitySyncReport, SyncError>)]) {
+    for (entity_type, result) in results {
+        match result {
+            Ok(r) if r.skipped => {}
+            Ok(r) => info!(
+                entity = entity_type,
+                pages = r.pages,
+                upserted = r.upserted,
+                unchanged = r.unchanged,
+                deleted = r.deleted,
+                full_resync = r.full_resync,
+                "sync_entity_done"
+            ),
+            Err(e) => warn!(entity = entity_type, error = %e, "sync_entity_failed"),
+        }
+    }
+}
+
+fn record_id(record: &Map<String, Value>, pk: &str) -> Result<String, SyncError> {
+    match record.get(pk) {
+        Some(Value::String(s)) if !s.is_empty() => Ok(s.clone()),
+        Some(Value::Number(n)) => Ok(n.to_string()),
+        _ => Err(SyncError::InvalidRecord(format!("missing {}", pk))),
+    }
+}
+
+fn is_deleted(record: &Map<String, Value>) -> bool {
+    match record.get("is_deleted") {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
+        _ => false,
+    }
+}
+
+fn add_json_arg(args: &mut SqliteArguments<'_>, value: &Value) {
+    match value {
+        Value::Null => args.add(Option::<String>::None),
+        Value::Bool(b) => args.add(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => args.add(i),
+            None => args.add(n.as_f64()),
+        },
+        Value::String(s) => args.add(s.clone()),
+        // JSON columns (settings, interests, photo_urls, ...) are stored as text.
+        other => args.add(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::{Query, State},
+        routing::get,
+        Json, Router,
+    };
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct FakeUpstream {
+        rows: Vec<Value>,
+        range_hash_override: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct FakeQuery {
+        since: String,
+        limit: usize,
+    }
+
+    async fn fake_sync(
+        State(upstream): State<Arc<Mutex<FakeUpstream>>>,
+        Query(q): Query<FakeQuery>,
+    ) -> Json<Value> {
+        let upstream = upstream.lock().unwrap();
+        let mut changed: Vec<&Value> = upstream
+            .rows
+            .iter()
+            .filter(|r| r["changed_at"].as_str().unwrap() > q.since.as_str())
+            .collect();
+        changed.sort_by_key(|r| r["changed_at"].as_str().unwrap().to_string());
+        let has_more = changed.len() > q.limit;
+        let page: Vec<&Value> = changed.into_iter().take(q.limit).collect();
+
+        let mut live: Vec<(String, String)> = upstream
+            .rows
+            .iter()
+            .filter(|r| r["is_deleted"] != json!(1))
+            .map(|r| {
+                (
+                    r["category_id"].as_str().unwrap().to_string(),
+                    r["row_hash"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        live.sort();
+        let range = upstream
+            .range_hash_override
+            .clone()
+            .unwrap_or_else(|| range_hash(&live));
+
+        Json(json!({
+            "records": page,
+            "next_since": page.last().map(|r| r["changed_at"].clone()).unwrap_or(json!(q.since)),
+            "has_more": has_more,
+            "range_hash": if has_more { Value::Null } else { json!(range) },
+        }))
+    }
+
+    async fn start_upstream(rows: Vec<Value>) -> (String, Arc<Mutex<FakeUpstream>>) {
+        let upstream = Arc::new(Mutex::new(FakeUpstream {
+            rows,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/api/v1/sync/categories", get(fake_sync))
+            .with_state(upstream.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), upstream)
+    }
+
+    async fn local_db() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+CREATE TABLE sync_state (
+    entity_type TEXT PRIMARY KEY,
+    last_sync_at TEXT NOT NULL DEFAULT '1970-01-01T00:00:00Z',
+    last_range_hash TEXT,
+    record_count INTEGER DEFAULT 0,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+CREATE TABLE categories (
+    category_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    display_order INTEGER DEFAULT 0,
+    row_hash TEXT NOT NULL,
+    changed_at TEXT NOT NULL,
+    is_deleted INTEGER DEFAULT 0
+);
+"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    fn category(id: &str, hash: &str, changed_at: &str) -> Value {
+        json!({
+            "category_id": id,
+            "name": format!("Categorie {}", id),
+            "slug": id,
+            "display_order": 1,
+            "row_hash": hash,
+            "changed_at": changed_at,
+            "is_deleted": 0,
+            "unknown_upstream_field": "ignored",
+        })
+    }
+
+    fn engine(pool: &SqlitePool, base_url: String) -> SyncEngine {
+        SyncEngine::new(
+            pool.clone(),
+            SyncConfig {
+                base_url,
+                api_token: None,
+                page_size: 2,
+                interval: Duration::ZERO,
+            },
+        )
+    }
+
+    fn categories() -> &'static SyncEntity {
+        find_entity("categories").unwrap()
+    }
+
+    async fn state(pool: &SqlitePool) -> sync_repo::SyncStateRow {
+        sync_repo::load_sync_state(pool, "categories")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn initial_sync_pages_upserts_and_advances_state() {
+        let (url, _) = start_upstream(vec![
+            category("c1", "h1", "2025-01-01T00:00:01Z"),
+            category("c2", "h2", "2025-01-01T00:00:02Z"),
+            category("c3", "h3", "2025-01-01T00:00:03Z"),
+        ])
+        .await;
+        let pool = local_db().await;
+
+        let report = engine(&pool, url).sync_entity(categories()).await.unwrap();
+        assert_eq!((report.pages, report.upserted), (2, 3));
+
+        let st = state(&pool).await;
+        assert_eq!(st.last_sync_at, "2025-01-01T00:00:03Z");
+        assert_eq!(st.record_count, Some(3));
+        let expected = range_hash(&[
+            ("c1".into(), "h1".into()),
+            ("c2".into(), "h2".into()),
+            ("c3".into(), "h3".into()),
+        ]);
+        assert_eq!(st.last_range_hash.as_deref(), Some(expected.as_str()));
+    }
+
+    #[tokio::test]
+    async fn incremental_sync_skips_unchanged_rows_and_soft_deletes() {
+        let (url, upstream) = start_upstream(vec![
+            category("c1", "h1", "2025-01-01T00:00:01Z"),
+            category("c2", "h2", "2025-01-01T00:00:02Z"),
+            category("c3", "h3", "2025-01-01T00:00:03Z"),
+        ])
+        .await;
+        let pool = local_db().await;
+        let engine = engine(&pool, url);
+        engine.sync_entity(categories()).await.unwrap();
+
+        {
+            let mut up = upstream.lock().unwrap();
+            up.rows[0] = category("c1", "h1-v2", "2025-01-02T00:00:01Z");
+            up.rows[1] = json!({
+                "category_id": "c2",
+                "row_hash": "h2-deleted",
+                "changed_at": "2025-01-02T00:00:02Z",
+                "is_deleted": 1,
+            });
+            // Re-sent without a content change.
+            up.rows[2] = category("c3", "h3", "2025-01-02T00:00:03Z");
+        }
+
+        let report = engine.sync_entity(categories()).await.unwrap();
+        assert_eq!(
+            (report.upserted, report.unchanged, report.deleted),
+            (1, 1, 1)
+        );
+        assert!(!report.full_resync);
+
+        let deleted: i64 =
+            sqlx::query_scalar("SELECT is_deleted FROM categories WHERE category_id = 'c2'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(state(&pool).await.record_count, Some(2));
+    }
+
+    #[tokio::test]
+    async fn local_tampering_triggers_full_resync() {
+        let (url, _) = start_upstream(vec![
+            category("c1", "h1", "2025-01-01T00:00:01Z"),
+            category("c2", "h2", "2025-01-01T00:00:02Z"),
+        ])
+        .await;
+        let pool = local_db().await;
+        let engine = engine(&pool, url);
+        engine.sync_entity(categories()).await.unwrap();
+
+        sqlx::query(
+            "INSERT INTO categories (category_id, name, slug, row_hash, changed_at) \
+             VALUES ('rogue', 'Rogue', 'rogue', 'x', '2025-01-01')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let report = engine.sync_entity(categories()).await.unwrap();
+        assert!(report.full_resync);
+        assert_eq!(report.deleted, 1);
+        assert_eq!(state(&pool).await.record_count, Some(2));
+    }
+
+    #[tokio::test]
+    async fn upstream_drift_is_reported_and_state_not_advanced() {
+        let (url, upstream) =
+            start_upstream(vec![category("c1", "h1", "2025-01-01T00:00:01Z")]).await;
+        upstream.lock().unwrap().range_hash_override = Some("bogus".to_string());
+        let pool = local_db().await;
+
+        let err = engine(&pool, url)
+            .sync_entity(categories())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SyncError::Drift { .. }));
+
+        let st = state(&pool).await;
+        assert_eq!(st.last_sync_at, EPOCH);
+        assert_eq!(st.last_range_hash, None);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM categories")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+}
@@ -0,0 +1,421 @@
+//! Wall-clock time in a user's `settings.timezone`.
+//!
+//! There is no tz database in the build, so this covers the zones our users
+//! actually pick: a table of standard offsets with the EU, US, Australian and
+//! New Zealand daylight saving rules, plus literal offsets like `+02:00`.
+//! Anything else falls back to [`DEFAULT_TIMEZONE`], with a warning the first
+//! time each unknown zone comes by.
+
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+pub const DEFAULT_TIMEZONE: &str = "Europe/Amsterdam";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DstRule {
+    None,
+    /// Last Sunday of March 01:00 UTC until last Sunday of October 01:00 UTC.
+    Eu,
+    /// Second Sunday of March 02:00 until first Sunday of November 02:00 local.
+    Us,
+    /// First Sunday of October until first Sunday of April, 02:00 standard
+    /// time; summer spans the new year.
+    Au,
+    /// Last Sunday of September until first Sunday of April, 02:00 standard
+    /// time.
+    Nz,
+}
+
+// (name, standard offset in minutes, rule)
+const ZONES: &[(&str, i64, DstRule)] = &[
+    ("Europe/Amsterdam", 60, DstRule::Eu),
+    ("Europe/Brussels", 60, DstRule::Eu),
+    ("Europe/Luxembourg", 60, DstRule::Eu),
+    ("Europe/Berlin", 60, DstRule::Eu),
+    ("Europe/Paris", 60, DstRule::Eu),
+    ("Europe/Madrid", 60, DstRule::Eu),
+    ("Europe/Rome", 60, DstRule::Eu),
+    ("Europe/Vienna", 60, DstRule::Eu),
+    ("Europe/Zurich", 60, DstRule::Eu),
+    ("Europe/Copenhagen", 60, DstRule::Eu),
+    ("Europe/Stockholm", 60, DstRule::Eu),
+    ("Europe/Oslo", 60, DstRule::Eu),
+    ("Europe/Prague", 60, DstRule::Eu),
+    ("Europe/Warsaw", 60, DstRule::Eu),
+    ("Europe/Budapest", 60, DstRule::Eu),
+    ("Europe/London", 0, DstRule::Eu),
+    ("Europe/Dublin", 0, DstRule::Eu),
+    ("Europe/Lisbon", 0, DstRule::Eu),
+    ("Atlantic/Canary", 0, DstRule::Eu),
+    ("Europe/Athens", 120, DstRule::Eu),
+    ("Europe/Helsinki", 120, DstRule::Eu),
+    ("Europe/Riga", 120, DstRule::Eu),
+    ("Europe/Tallinn", 120, DstRule::Eu),
+    ("Europe/Vilnius", 120, DstRule::Eu),
+    ("Europe/Sofia", 120, DstRule::Eu),
+    ("Europe/Bucharest", 120, DstRule::Eu),
+    ("Europe/Kyiv", 120, DstRule::Eu),
+    ("Europe/Kiev", 120, DstRule::Eu),
+    ("Europe/Istanbul", 180, DstRule::None),
+    ("America/Curacao", -240, DstRule::None),
+    ("America/Aruba", -240, DstRule::None),
+    ("America/Kralendijk", -240, DstRule::None),
+    ("America/Paramaribo", -180, DstRule::None),
+    ("America/New_York", -300, DstRule::Us),
+    ("America/Chicago", -360, DstRule::Us),
+    ("America/Denver", -420, DstRule::Us),
+    ("America/Phoenix", -420, DstRule::None),
+    ("America/Los_Angeles", -480, DstRule::Us),
+    ("Asia/Dubai", 240, DstRule::None),
+    ("Asia/Kolkata", 330, DstRule::None),
+    ("Asia/Jakarta", 420, DstRule::None),
+    ("Asia/Singapore", 480, DstRule::None),
+    ("Asia/Tokyo", 540, DstRule::None),
+    ("Australia/Perth", 480, DstRule::None),
+    ("Australia/Adelaide", 570, DstRule::Au),
+    ("Australia/Brisbane", 600, DstRule::None),
+    ("Australia/Sydney", 600, DstRule::Au),
+    ("Australia/Melbourne", 600, DstRule::Au),
+    ("Australia/Hobart", 600, DstRule::Au),
+    ("Pacific/Auckland", 720, DstRule::Nz),
+    ("America/Sao_Paulo", -180, DstRule::None),
+    ("Africa/Johannesburg", 120, DstRule::None),
+    ("UTC", 0, DstRule::None),
+    ("Etc/UTC", 0, DstRule::None),
+    ("GMT", 0, DstRule::None),
+];
+
+/// A calendar date; ordering follows the calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LocalDate {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl LocalDate {
+    /// `YYYY-MM-DD`, rejecting impossible dates.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parts = input.trim().splitn(3, '-');
+        let year: i64 = parts.next()?.parse().ok()?;
+        let month: u32 = parts.next()?.parse().ok()?;
+        let day: u32 = parts.next()?.parse().ok()?;
+        let date = LocalDate { year, month, day };
+        (LocalDate::from_days(date.days()) == date).then_some(date)
+    }
+
+    /// Days since 1970-01-01 (Howard Hinnant's `days_from_civil`).
+    pub fn days(self) -> i64 {
+        let y = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        LocalDate { year, month, day }
+    }
+
+    pub fn add_days(self, n: i64) -> Self {
+        LocalDate::from_days(self.days() + n)
+    }
+
+    /// Monday = 0 .. Sunday = 6.
+    pub fn weekday_mon0(self) -> i64 {
+        (self.days() + 3).rem_euclid(7)
+    }
+
+    /// `YYYY-MM-DD`
+    pub fn iso(self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// Naive `YYYY-MM-DD HH:MM:00`, the shape `datetime(scheduled_at)` has.
+    pub fn at(self, hour: u32, minute: u32) -> String {
+        format!("{} {:02}:{:02}:00", self.iso(), hour, minute)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalNow {
+    pub date: LocalDate,
+    pub minute_of_day: u32,
+}
+
+/// Last `weekday_mon0` in the month, or the `nth` one counting from 1.
+fn nth_weekday(year: i64, month: u32, weekday_mon0: i64, nth: Option<i64>) -> LocalDate {
+    match nth {
+        Some(n) => {
+            let first = LocalDate {
+                year,
+                month,
+                day: 1,
+            };
+            let offset = (weekday_mon0 - first.weekday_mon0()).rem_euclid(7);
+            first.add_days(offset + (n - 1) * 7)
+        }
+        None => {
+            let next_month = if month == 12 {
+                LocalDate {
+                    year: year + 1,
+                    month: 1,
+                    day: 1,
+                }
+            } else {
+                LocalDate {
+                    year,
+                    month: month + 1,
+                    day: 1,
+                }
+            };
+            let last = next_month.add_days(-1);
+            last.add_days(-(last.weekday_mon0() - weekday_mon0).rem_euclid(7))
+        }
+    }
+}
+
+fn is_dst(rule: DstRule, std_offset: i64, unix_secs: i64) -> bool {
+    const SUNDAY: i64 = 6;
+    let year = LocalDate::from_days(unix_secs.div_euclid(86_400)).year;
+    // Southern rules switch at 02:00 standard time on both ends.
+    let southern = |start: LocalDate, end: LocalDate| {
+        let at = |d: LocalDate| d.days() * 86_400 + 7_200 - std_offset * 60;
+        unix_secs >= at(start) || unix_secs < at(end)
+    };
+    let (start, end) = match rule {
+        DstRule::None => return false,
+        DstRule::Au => {
+            return southern(
+                nth_weekday(year, 10, SUNDAY, Some(1)),
+                nth_weekday(year, 4, SUNDAY, Some(1)),
+            )
+        }
+        DstRule::Nz => {
+            return southern(
+                nth_weekday(year, 9, SUNDAY, None),
+                nth_weekday(year, 4, SUNDAY, Some(1)),
+            )
+        }
+        DstRule::Eu => (
+            nth_weekday(year, 3, SUNDAY, None).days() * 86_400 + 3_600,
+            nth_weekday(year, 10, SUNDAY, None).days() * 86_400 + 3_600,
+        ),
+        DstRule::Us => (
+            nth_weekday(year, 3, SUNDAY, Some(2)).days() * 86_400 + 7_200 - std_offset * 60,
+            nth_weekday(year, 11, SUNDAY, Some(1)).days() * 86_400 + 7_200 - (std_offset + 60) * 60,
+        ),
+    };
+    (start..end).contains(&unix_secs)
+}
+
+/// `+02:00`, `-0530`, `+1`, optionally prefixed with `UTC`/`GMT`.
+fn parse_fixed_offset(tz: &str) -> Option<i64> {
+    let rest = tz
+        .strip_prefix("UTC")
+        .or_else(|| tz.strip_prefix("GMT"))
+        .unwrap_or(tz);
+    let (sign, digits) = match (rest.strip_prefix('+'), rest.strip_prefix('-')) {
+        (Some(digits), _) => (1, digits),
+        (_, Some(digits)) => (-1, digits),
+        _ => return None,
+    };
+    // ASCII only from here, so the byte split below stays on a char boundary.
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (h, m) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    let h: i64 = h.parse().ok()?;
+    let m: i64 = m.parse().ok()?;
+    (h <= 14 && m < 60).then_some(sign * (h * 60 + m))
+}
+
+/// Standard offset and daylight saving rule, if `tz` is a zone we know.
+fn lookup(tz: &str) -> Option<(i64, DstRule)> {
+    let tz = tz.trim();
+    if let Some(fixed) = parse_fixed_offset(tz) {
+        return Some((fixed, DstRule::None));
+    }
+    ZONES
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(tz))
+        .map(|&(_, offset, rule)| (offset, rule))
+}
+
+/// False for zones that get [`DEFAULT_TIMEZONE`] instead.
+pub fn is_known_timezone(tz: &str) -> bool {
+    lookup(tz).is_some()
+}
+
+/// Logs the fallback for `tz`, once per zone; true when it did.
+fn report_unknown(tz: &str) -> bool {
+    static REPORTED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    let first = REPORTED
+        .get_or_init(Default::default)
+        .lock()
+        .map(|mut seen| seen.insert(tz.to_string()))
+        .unwrap_or(false);
+    if first {
+        warn!(
+            "Unknown timezone {:?}, falling back to {}",
+            tz, DEFAULT_TIMEZONE
+        );
+    }
+    first
+}
+
+/// Offset from UTC in minutes for `tz` at `unix_secs`.
+pub fn utc_offset_minutes(tz: &str, unix_secs: i64) -> i64 {
+    let (std_offset, rule) = lookup(tz).unwrap_or_else(|| {
+        // Unset is not unknown; only report zones someone picked.
+        if !tz.trim().is_empty() {
+            report_unknown(tz.trim());
+        }
+        lookup(DEFAULT_TIMEZONE).unwrap_or((60, DstRule::Eu))
+    });
+    if is_dst(rule, std_offset, unix_secs) {
+        std_offset + 60
+    } else {
+        std_offset
+    }
+}
+
+pub fn local_at(tz: &str, unix_secs: i64) -> LocalNow {
+    let local = unix_secs + utc_offset_minutes(tz, unix_secs) * 60;
+    LocalNow {
+        date: LocalDate::from_days(local.div_euclid(86_400)),
+        minute_of_day: (local.rem_euclid(86_400) / 60) as u32,
+    }
+}
+
+pub fn local_now(tz: &str) -> LocalNow {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    local_at(tz, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix(date: &str, hour: i64, minute: i64) -> i64 {
+        LocalDate::parse(date).unwrap().days() * 86_400 + hour * 3_600 + minute * 60
+    }
+
+    #[test]
+    fn converts_dates_and_weekdays() {
+        let d = LocalDate::parse("2024-02-29").unwrap();
+        assert_eq!(d.add_days(1).iso(), "2024-03-01");
+        assert_eq!(LocalDate::from_days(0).iso(), "1970-01-01");
+        // 2031-05-03 is a Saturday.
+        assert_eq!(LocalDate::parse("2031-05-03").unwrap().weekday_mon0(), 5);
+        assert_eq!(LocalDate::parse("2023-02-29"), None);
+        assert_eq!(LocalDate::parse("2031-13-01"), None);
+    }
+
+    #[test]
+    fn applies_daylight_saving_rules() {
+        // EU switches at 01:00 UTC on 2026-03-29 and 2026-10-25.
+        assert_eq!(
+            utc_offset_minutes("Europe/Amsterdam", unix("2026-03-29", 0, 59)),
+            60
+        );
+        assert_eq!(
+            utc_offset_minutes("Europe/Amsterdam", unix("2026-03-29", 1, 0)),
+            120
+        );
+        assert_eq!(
+            utc_offset_minutes("Europe/Amsterdam", unix("2026-10-25", 0, 59)),
+            120
+        );
+        assert_eq!(
+            utc_offset_minutes("Europe/Amsterdam", unix("2026-10-25", 1, 0)),
+            60
+        );
+        // US: 2026-03-08 02:00 EST is 07:00 UTC.
+        assert_eq!(
+            utc_offset_minutes("America/New_York", unix("2026-03-08", 6, 59)),
+            -300
+        );
+        assert_eq!(
+            utc_offset_minutes("America/New_York", unix("2026-03-08", 7, 0)),
+            -240
+        );
+        assert_eq!(utc_offset_minutes("+05:30", 0), 330);
+        assert_eq!(utc_offset_minutes("UTC-3", 0), -180);
+        assert_eq!(
+            utc_offset_minutes("Mars/Olympus", unix("2026-07-01", 12, 0)),
+            120
+        );
+
+        // Sydney: AEDT from 2026-10-04 02:00 AEST (16:00 UTC the day
+        // before) until 2027-04-04 03:00 AEDT (16:00 UTC the day before).
+        assert_eq!(
+            utc_offset_minutes("Australia/Sydney", unix("2026-10-03", 15, 59)),
+            600
+        );
+        assert_eq!(
+            utc_offset_minutes("Australia/Sydney", unix("2026-10-03", 16, 0)),
+            660
+        );
+        assert_eq!(
+            utc_offset_minutes("Australia/Sydney", unix("2027-01-15", 12, 0)),
+            660
+        );
+        assert_eq!(
+            utc_offset_minutes("Australia/Sydney", unix("2027-04-03", 16, 0)),
+            600
+        );
+        // Auckland: NZDT from 2026-09-27 02:00 NZST (14:00 UTC the day before).
+        assert_eq!(
+            utc_offset_minutes("Pacific/Auckland", unix("2026-09-26", 14, 0)),
+            780
+        );
+        assert_eq!(
+            utc_offset_minutes("Pacific/Auckland", unix("2026-07-01", 0, 0)),
+            720
+        );
+
+        let now = local_at("Europe/Amsterdam", unix("2026-12-31", 23, 30));
+        assert_eq!(now.date.iso(), "2027-01-01");
+        assert_eq!(now.minute_of_day, 30);
+    }
+
+    #[test]
+    fn reports_unknown_zones_and_odd_offsets() {
+        assert!(is_known_timezone("europe/amsterdam"));
+        assert!(is_known_timezone("GMT+2"));
+        assert!(!is_known_timezone("Mars/Arsia"));
+        assert!(report_unknown("Mars/Arsia"));
+        assert!(!report_unknown("Mars/Arsia"));
+
+        for odd in ["+aé1", "+é", "UTC+", "-12:7é", "+1:30:00"] {
+            assert!(!is_known_timezone(odd), "{:?}", odd);
+            assert_eq!(utc_offset_minutes(odd, 0), 60);
+        }
+    }
+}
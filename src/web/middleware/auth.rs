@@ -1,70 +1,484 @@
 use axum::{
     extract::{Request, State},
-    http::header,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Redirect, Response},
 };
-use base64::{engine::general_purpose, Engine as _};
-use serde::Deserialize;
+use cookie::Cookie;
 use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
 
 use crate::database::current_user_repo;
+use crate::services::auth_api_service::{TokenPair, TokenRefresher};
+use crate::services::jwt_service::{JwksVerifier, TokenError, VerifiedClaims};
+use crate::services::onboarding_service;
 
 #[derive(Clone, Debug)]
 pub struct AuthenticatedUser {
     pub id: String,
 }
 
-#[derive(Deserialize)]
-struct JwtPayload {
-    sub: String,
+/// How requests without session cookies are treated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMode {
+    /// Every protected request needs a valid access token (default).
+    MultiUser,
+    /// Local single-user container: requests without cookies run as the user
+    /// in the `current_user` table. Never enable this on a shared deployment.
+    SingleUser,
+}
+
+impl AuthMode {
+    /// Reads `GOAMET_AUTH_MODE` (`multi_user` | `single_user`).
+    pub fn from_env() -> Self {
+        match std::env::var("GOAMET_AUTH_MODE") {
+            Ok(v) => Self::parse(&v).unwrap_or_else(|| {
+                warn!(value = %v, "unknown GOAMET_AUTH_MODE, falling back to multi_user");
+                AuthMode::MultiUser
+            }),
+            Err(_) => AuthMode::MultiUser,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "multi_user" | "multi-user" => Some(AuthMode::MultiUser),
+            "single_user" | "single-user" => Some(AuthMode::SingleUser),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMode::MultiUser => "multi_user",
+            AuthMode::SingleUser => "single_user",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthState {
+    pub mode: AuthMode,
+    pub pool: SqlitePool,
+    pub verifier: Arc<JwksVerifier>,
+    pub refresher: Arc<TokenRefresher>,
+    /// Access tokens expiring within this many seconds are refreshed proactively.
+    pub refresh_skew_secs: i64,
+}
+
+/// Reads a single cookie value; handles both "; " and ";" separators.
+pub fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    headers
+        .get(header::COOKIE)
+        .and_then(|hv| hv.to_str().ok())?
+        .split(';')
+        .map(|part| part.trim())
+        .find_map(|part| part.strip_prefix(prefix.as_str()))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Session cookie as set by login and token refresh.
+pub fn session_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    let mut c = Cookie::new(name, value);
+    c.set_path("/");
+    c.set_http_only(true);
+    c.set_same_site(cookie::SameSite::Lax);
+    c
+}
+
+fn removal_cookie(name: &'static str) -> Cookie<'static> {
+    let mut c = session_cookie(name, String::new());
+    c.make_removal();
+    c
 }
 
 pub async fn require_auth(
-    State(pool): State<SqlitePool>,
+    State(state): State<AuthState>,
     mut request: Request,
     next: Next,
 ) -> Response {
-    // Extract cookies from request
-    let token = request
-        .headers()
-        .get(header::COOKIE)
-        .and_then(|hv| hv.to_str().ok())
-        .and_then(|cookies| {
-            cookies
-                .split("; ")
-                .find(|c| c.starts_with("access_token="))
-                .and_then(|c| c.strip_prefix("access_token="))
-        });
-
-    if let Some(token) = token {
-        // Parse JWT payload (middle part)
-        let parts: Vec<&str> = token.split('.').collect();
-        if parts.len() == 3 {
-            if let Ok(payload_bytes) = general_purpose::URL_SAFE_NO_PAD.decode(parts[1]) {
-                if let Ok(payload) = serde_json::from_slice::<JwtPayload>(&payload_bytes) {
-                    // Inject user id into request extensions
-                    request
-                        .extensions_mut()
-                        .insert(AuthenticatedUser { id: payload.sub });
-
-                    return next.run(request).await;
+    let access_token = cookie_value(request.headers(), "access_token");
+    let has_refresh_token = cookie_value(request.headers(), "refresh_token").is_some();
+
+    if access_token.is_some() || has_refresh_token {
+        let verified = match access_token {
+            Some(token) => state.verifier.verify(&token).await,
+            None => Err(TokenError::Expired),
+        };
+
+        return match verified {
+            Ok(claims) if !expires_within(&claims, state.refresh_skew_secs) => {
+                authenticate(&mut request, claims.sub);
+                proceed(&state, request, next).await
+            }
+            Ok(claims) => match refresh_session(&state, &mut request).await {
+                Some((pair, refreshed)) => {
+                    authenticate(&mut request, refreshed.sub);
+                    let response = proceed(&state, request, next).await;
+                    with_rotated_cookies(response, pair)
                 }
+                // Still valid for a moment: serve this request and retry on the next one.
+                None => {
+                    authenticate(&mut request, claims.sub);
+                    proceed(&state, request, next).await
+                }
+            },
+            Err(TokenError::Expired) => match refresh_session(&state, &mut request).await {
+                Some((pair, refreshed)) => {
+                    authenticate(&mut request, refreshed.sub);
+                    let response = proceed(&state, request, next).await;
+                    with_rotated_cookies(response, pair)
+                }
+                None => session_expired(&request),
+            },
+            Err(e) => {
+                debug!(reason = %e, "access_token_rejected");
+                unauthorized(e.reason())
+            }
+        };
+    }
+
+    // Single-user container: no cookies means "the local user"
+    if state.mode == AuthMode::SingleUser {
+        if let Ok(Some(user_id)) = current_user_repo::load_current_user_id(&state.pool).await {
+            authenticate(&mut request, user_id);
+            return proceed(&state, request, next).await;
+        }
+    }
+
+    unauthorized("missing_token")
+}
+
+/// Users with status `pending_onboarding` only reach the wizard and what it
+/// needs; everything else sends them to `/onboarding`.
+async fn proceed(state: &AuthState, request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if !onboarding_allowed(path) {
+        if let Some(user) = request.extensions().get::<AuthenticatedUser>() {
+            match onboarding_service::needs_onboarding(&state.pool, &user.id).await {
+                Ok(true) => {
+                    return if request.method() == Method::GET && !path.starts_with("/api/") {
+                        Redirect::to("/onboarding").into_response()
+                    } else {
+                        (StatusCode::FORBIDDEN, "Onboarding required").into_response()
+                    };
+                }
+                Ok(false) => {}
+                Err(e) => debug!(error = %e, "onboarding_status_unavailable"),
             }
         }
     }
+    next.run(request).await
+}
+
+fn onboarding_allowed(path: &str) -> bool {
+    path == "/onboarding"
+        || path.starts_with("/onboarding/")
+        || path.starts_with("/images/")
+        || path == "/api/location/search"
+        || path == "/logout"
+}
+
+fn authenticate(request: &mut Request, user_id: String) {
+    request
+        .extensions_mut()
+        .insert(AuthenticatedUser { id: user_id });
+}
+
+fn expires_within(claims: &VerifiedClaims, skew_secs: i64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    claims.exp - now <= skew_secs
+}
+
+/// Exchanges the refresh_token cookie for a new token pair and rewrites the
+/// request's Cookie header, so handlers that forward the access token
+/// (chat API, image proxy) already use the new one.
+async fn refresh_session(
+    state: &AuthState,
+    request: &mut Request,
+) -> Option<(TokenPair, VerifiedClaims)> {
+    let refresh_token = cookie_value(request.headers(), "refresh_token")?;
+    let pair = state.refresher.refresh(&refresh_token).await.ok()?;
+    let claims = match state.verifier.verify(&pair.access_token).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            warn!(reason = %e, "refreshed_access_token_rejected");
+            return None;
+        }
+    };
+
+    let cookie_header = rewrite_cookie_header(request.headers(), &pair);
+    if let Ok(value) = HeaderValue::from_str(&cookie_header) {
+        request.headers_mut().insert(header::COOKIE, value);
+    }
+
+    Some((pair, claims))
+}
+
+fn rewrite_cookie_header(headers: &HeaderMap, pair: &TokenPair) -> String {
+    let mut parts: Vec<String> = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|hv| hv.to_str().ok())
+        .flat_map(|hv| hv.split(';'))
+        .map(|part| part.trim())
+        .filter(|part| {
+            !part.is_empty()
+                && !part.starts_with("access_token=")
+                && !part.starts_with("refresh_token=")
+        })
+        .map(|part| part.to_string())
+        .collect();
+    parts.push(format!("access_token={}", pair.access_token));
+    parts.push(format!("refresh_token={}", pair.refresh_token));
+    parts.join("; ")
+}
+
+fn with_rotated_cookies(mut response: Response, pair: TokenPair) -> Response {
+    for c in [
+        session_cookie("access_token", pair.access_token),
+        session_cookie("refresh_token", pair.refresh_token),
+    ] {
+        if let Ok(value) = c.to_string().parse() {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+/// Refresh failed: page navigations go back to /login (and return here after
+/// logging in); API and form calls get a plain 401.
+fn session_expired(request: &Request) -> Response {
+    let path = request.uri().path();
+    let mut response = if request.method() == Method::GET && !path.starts_with("/api/") {
+        let return_to = request
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let query = serde_urlencoded::to_string([("return_to", return_to)]).unwrap_or_default();
+        Redirect::to(&format!("/login?{}", query)).into_response()
+    } else {
+        unauthorized("session_expired")
+    };
 
-    // Fallback for offline/local usage: use the current_user table
-    if let Ok(Some(user_id)) = current_user_repo::load_current_user_id(&pool).await {
-        request
-            .extensions_mut()
-            .insert(AuthenticatedUser { id: user_id });
-        return next.run(request).await;
+    for c in [
+        removal_cookie("access_token"),
+        removal_cookie("refresh_token"),
+    ] {
+        if let Ok(value) = c.to_string().parse() {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
     }
+    response
+}
 
-    // No valid token or parse error, return 401
+fn unauthorized(reason: &str) -> Response {
     Response::builder()
-        .status(401)
-        .body(axum::body::Body::from("Unauthorized - Please login"))
+        .status(StatusCode::UNAUTHORIZED)
+        .header(
+            header::WWW_AUTHENTICATE,
+            format!(
+                "Bearer error=\"invalid_token\", error_description=\"{}\"",
+                reason
+            ),
+        )
+        .body(axum::body::Body::from(format!(
+            "Unauthorized - Please login ({})",
+            reason
+        )))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::jwt_service::{JwksSource, JwtConfig};
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Extension, Router};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    const SIGNING_KEY: &str = include_str!("../../../tests/fixtures/auth/signing_key.pem");
+
+    async fn state(mode: AuthMode, current_user: Option<&str>) -> AuthState {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE current_user (id INTEGER PRIMARY KEY, user_id TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        if let Some(user_id) = current_user {
+            sqlx::query("INSERT INTO current_user (id, user_id) VALUES (1, ?)")
+                .bind(user_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        AuthState {
+            mode,
+            pool,
+            verifier: Arc::new(JwksVerifier::new(JwtConfig {
+                jwks_source: JwksSource::File(PathBuf::from(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/auth/jwks.json"
+                ))),
+                issuer: None,
+                audience: None,
+                leeway_secs: 0,
+                refresh_interval: Duration::from_secs(600),
+                min_refetch_interval: Duration::from_secs(30),
+            })),
+            refresher: Arc::new(TokenRefresher::new()),
+            refresh_skew_secs: 60,
+        }
+    }
+
+    fn valid_token(sub: &str) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("goamet-test-1".to_string());
+        encode(
+            &header,
+            &json!({ "sub": sub, "iat": now, "exp": now + 3600 }),
+            &EncodingKey::from_rsa_pem(SIGNING_KEY.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    async fn call(state: AuthState, cookie: Option<String>) -> (StatusCode, String) {
+        let app = Router::new()
+            .route(
+                "/me",
+                get(|Extension(user): Extension<AuthenticatedUser>| async move { user.id }),
+            )
+            .layer(from_fn_with_state(state, require_auth));
+
+        let mut req = Request::builder().uri("/me");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let resp = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn parses_auth_mode() {
+        assert_eq!(AuthMode::parse("single_user"), Some(AuthMode::SingleUser));
+        assert_eq!(AuthMode::parse(" Single-User "), Some(AuthMode::SingleUser));
+        assert_eq!(AuthMode::parse("multi_user"), Some(AuthMode::MultiUser));
+        assert_eq!(AuthMode::parse(""), Some(AuthMode::MultiUser));
+        assert_eq!(AuthMode::parse("yes"), None);
+    }
+
+    #[tokio::test]
+    async fn multi_user_with_cookie_uses_token_subject() {
+        let cookie = format!("access_token={}", valid_token("user-123"));
+        let (status, body) = call(
+            state(AuthMode::MultiUser, Some("local")).await,
+            Some(cookie),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "user-123");
+    }
+
+    #[tokio::test]
+    async fn multi_user_without_cookie_is_rejected_even_with_current_user() {
+        let (status, body) = call(state(AuthMode::MultiUser, Some("local")).await, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("missing_token"));
+    }
+
+    #[tokio::test]
+    async fn single_user_with_cookie_still_uses_token_subject() {
+        let cookie = format!("access_token={}", valid_token("user-123"));
+        let (status, body) = call(
+            state(AuthMode::SingleUser, Some("local")).await,
+            Some(cookie),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "user-123");
+    }
+
+    #[tokio::test]
+    async fn single_user_without_cookie_uses_current_user() {
+        let (status, body) = call(state(AuthMode::SingleUser, Some("local")).await, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "local");
+    }
+
+    #[tokio::test]
+    async fn single_user_with_invalid_cookie_does_not_fall_back() {
+        let cookie = "access_token=not-a-jwt".to_string();
+        let (status, body) = call(
+            state(AuthMode::SingleUser, Some("local")).await,
+            Some(cookie),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("malformed"));
+    }
+
+    #[tokio::test]
+    async fn single_user_without_current_user_row_is_rejected() {
+        let (status, _) = call(state(AuthMode::SingleUser, None).await, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn pending_onboarding_users_are_sent_to_the_wizard() {
+        let state = state(AuthMode::SingleUser, Some("local")).await;
+        sqlx::query(
+            "CREATE TABLE users (user_id TEXT PRIMARY KEY, status TEXT, is_deleted INTEGER)",
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO users (user_id, status) VALUES ('local', 'pending_onboarding')")
+            .execute(&state.pool)
+            .await
+            .unwrap();
+
+        let app = Router::new()
+            .route("/me", get(|| async { "me" }))
+            .route("/onboarding", get(|| async { "wizard" }))
+            .layer(from_fn_with_state(state.clone(), require_auth));
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let resp = app.clone().oneshot(get("/me")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()[header::LOCATION], "/onboarding");
+        let resp = app.clone().oneshot(get("/onboarding")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        sqlx::query("UPDATE users SET status = 'active'")
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        let resp = app.oneshot(get("/me")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,359 @@
+use askama::Template;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use cookie::Cookie;
+use tracing::warn;
+
+use crate::web::middleware::auth::cookie_value;
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_FIELD: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Form bodies are read to find the token; ours are a few hundred bytes.
+const MAX_FORM_BYTES: usize = 64 * 1024;
+
+/// Per-session CSRF token, available to handlers for their templates
+/// (`<input type="hidden" name="csrf_token" value="{{ csrf_token }}">`).
+#[derive(Clone, Debug)]
+pub struct CsrfToken(pub String);
+
+#[derive(Clone, Debug, Default)]
+pub struct CsrfConfig {
+    /// Extra origins (`https://app.example.com`) accepted besides the Host
+    /// header, e.g. behind a reverse proxy that rewrites Host.
+    pub trusted_origins: Vec<String>,
+}
+
+impl CsrfConfig {
+    /// Reads `CSRF_TRUSTED_ORIGINS` (comma separated).
+    pub fn from_env() -> Self {
+        Self {
+            trusted_origins: std::env::var("CSRF_TRUSTED_ORIGINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_ascii_lowercase())
+                .filter(|o| !o.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct CsrfErrorTemplate {
+    title: String,
+    message: String,
+}
+
+/// Removal cookie for logout, so the next session starts with a fresh token.
+pub fn csrf_removal_cookie() -> Cookie<'static> {
+    let mut c = csrf_cookie(String::new());
+    c.make_removal();
+    c
+}
+
+fn csrf_cookie(token: String) -> Cookie<'static> {
+    let mut c = Cookie::new(CSRF_COOKIE, token);
+    c.set_path("/");
+    c.set_http_only(true);
+    c.set_same_site(cookie::SameSite::Lax);
+    c
+}
+
+fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn is_valid_token(token: &str) -> bool {
+    token.len() == 64 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Form posts must echo the session token (`csrf_token` field or
+/// `X-CSRF-Token` header); every other state-changing request (JSON) must come
+/// from our own origin according to Origin, or Referer when Origin is absent.
+pub async fn csrf_protect(
+    State(config): State<CsrfConfig>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let existing = cookie_value(request.headers(), CSRF_COOKIE).filter(|t| is_valid_token(t));
+    let is_new = existing.is_none();
+    let token = existing.unwrap_or_else(new_token);
+
+    if !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        let path = request.uri().path().to_string();
+        if !same_origin(request.headers(), &config) {
+            warn!(path = %path, "csrf_origin_rejected");
+            return rejected();
+        }
+
+        if is_form(request.headers()) {
+            let header_token = request
+                .headers()
+                .get(CSRF_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let (parts, body) = request.into_parts();
+            let bytes = match to_bytes(body, MAX_FORM_BYTES).await {
+                Ok(b) => b,
+                Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            };
+            let submitted = header_token.or_else(|| form_token(&bytes));
+            if is_new || !submitted.is_some_and(|s| constant_time_eq(&s, &token)) {
+                warn!(path = %path, "csrf_token_rejected");
+                return rejected();
+            }
+            request = Request::from_parts(parts, Body::from(bytes));
+        } else if !has_origin_or_referer(request.headers()) {
+            warn!(path = %path, "csrf_origin_missing");
+            return rejected();
+        }
+    }
+
+    request.extensions_mut().insert(CsrfToken(token.clone()));
+    let mut response = next.run(request).await;
+    if is_new {
+        if let Ok(value) = csrf_cookie(token).to_string().parse() {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            let v = v.to_ascii_lowercase();
+            v.starts_with("application/x-www-form-urlencoded")
+                || v.starts_with("multipart/form-data")
+        })
+        .unwrap_or(false)
+}
+
+fn form_token(body: &[u8]) -> Option<String> {
+    serde_urlencoded::from_bytes::<Vec<(String, String)>>(body)
+        .ok()?
+        .into_iter()
+        .find(|(k, _)| k == CSRF_FIELD)
+        .map(|(_, v)| v)
+}
+
+fn has_origin_or_referer(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::ORIGIN) || headers.contains_key(header::REFERER)
+}
+
+/// True when Origin (or else Referer) is absent or points at this host.
+/// Absence is only acceptable for forms, which carry the token instead.
+fn same_origin(headers: &HeaderMap, config: &CsrfConfig) -> bool {
+    let source = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|v| v.to_str().ok());
+    let Some(source) = source else {
+        return true;
+    };
+    let Some(origin) = origin_of(source) else {
+        return false;
+    };
+
+    if config.trusted_origins.contains(&origin) {
+        return true;
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(|h| h.trim().to_ascii_lowercase());
+    host.is_some_and(|h| {
+        origin
+            .split_once("://")
+            .is_some_and(|(_, authority)| authority == h)
+    })
+}
+
+/// `scheme://host[:port]` of an Origin or Referer value; `null` origins fail.
+fn origin_of(value: &str) -> Option<String> {
+    let value = value.trim().to_ascii_lowercase();
+    let (scheme, rest) = value.split_once("://")?;
+    if scheme != "http" && scheme != "https" {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    if authority.is_empty() {
+        return None;
+    }
+    Some(format!("{}://{}", scheme, authority))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn rejected() -> Response {
+    let template = CsrfErrorTemplate {
+        title: "Formulier verlopen".to_string(),
+        message: "Deze actie kon niet worden geverifieerd. Ga terug, herlaad de pagina en probeer het opnieuw.".to_string(),
+    };
+    (StatusCode::FORBIDDEN, Html(template.render().unwrap())).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        middleware::from_fn_with_state,
+        routing::{get, post},
+        Extension, Router,
+    };
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/form",
+                get(|Extension(t): Extension<CsrfToken>| async move { t.0 })
+                    .post(|body: String| async move { body }),
+            )
+            .route("/api/json", post(|| async { "ok" }))
+            .layer(from_fn_with_state(CsrfConfig::default(), csrf_protect))
+    }
+
+    async fn send(
+        req: axum::http::request::Builder,
+        body: &str,
+    ) -> (StatusCode, String, Vec<String>) {
+        let resp = app()
+            .oneshot(req.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = resp.status();
+        let cookies = resp
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).to_string(), cookies)
+    }
+
+    fn form_post(cookie: Option<&str>) -> axum::http::request::Builder {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/form")
+            .header(header::HOST, "goamet.test")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        req
+    }
+
+    #[tokio::test]
+    async fn get_issues_token_cookie_once() {
+        let (status, body, cookies) = send(Request::builder().uri("/form"), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(is_valid_token(&body));
+        assert!(cookies[0].starts_with(&format!("csrf_token={}", body)));
+
+        let cookie = format!("csrf_token={}", TOKEN);
+        let (_, body, cookies) = send(
+            Request::builder()
+                .uri("/form")
+                .header(header::COOKIE, cookie),
+            "",
+        )
+        .await;
+        assert_eq!(body, TOKEN);
+        assert!(cookies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn form_post_needs_matching_token() {
+        let cookie = format!("csrf_token={}", TOKEN);
+        let body = format!("action=signup&csrf_token={}", TOKEN);
+        let (status, echoed, _) = send(form_post(Some(&cookie)), &body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echoed, body, "handler still sees the full form");
+
+        let (status, page, _) = send(form_post(Some(&cookie)), "action=signup").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(page.contains("Formulier verlopen"));
+
+        let wrong = format!("action=signup&csrf_token={}", TOKEN.replace('0', "1"));
+        let (status, _, _) = send(form_post(Some(&cookie)), &wrong).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // No cookie: a token copied from somewhere else is worthless.
+        let (status, _, _) = send(form_post(None), &body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let cross_site = form_post(Some(&cookie)).header(header::ORIGIN, "https://evil.test");
+        let (status, _, _) = send(cross_site, &body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn json_requests_need_same_origin() {
+        let json = |origin: Option<(&'static str, &'static str)>| {
+            let mut req = Request::builder()
+                .method("POST")
+                .uri("/api/json")
+                .header(header::HOST, "goamet.test:3000")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some((name, value)) = origin {
+                req = req.header(name, value);
+            }
+            req
+        };
+
+        let (status, _, _) = send(json(Some(("origin", "http://goamet.test:3000"))), "{}").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = send(
+            json(Some(("referer", "http://goamet.test:3000/chats/c1"))),
+            "{}",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        for origin in [
+            None,
+            Some(("origin", "http://evil.test")),
+            Some(("origin", "null")),
+            Some(("referer", "http://goamet.test.evil.test/")),
+        ] {
+            let (status, _, _) = send(json(origin), "{}").await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{:?}", origin);
+        }
+    }
+
+    #[test]
+    fn parses_origins() {
+        assert_eq!(
+            origin_of("https://Example.com:8443/path?q=1").as_deref(),
+            Some("https://example.com:8443")
+        );
+        assert_eq!(origin_of("null"), None);
+        assert_eq!(origin_of("javascript://x"), None);
+    }
+}
@@ -0,0 +1,538 @@
+use askama::Template;
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Extension, Form,
+};
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::services::account_service::{self, AccountMessage, Lang};
+use crate::services::auth_api_service::{self, LoginError};
+use crate::web::middleware::csrf::CsrfToken;
+use crate::web::routes::auth::{render_error, LoginState};
+
+#[derive(Template)]
+#[template(path = "register.html")]
+pub struct RegisterTemplate {
+    csrf_token: String,
+    lang: &'static str,
+    name: String,
+    email: String,
+    errors: Vec<&'static str>,
+    done: Option<&'static str>,
+}
+
+#[derive(Template)]
+#[template(path = "verify_email.html")]
+pub struct VerifyEmailTemplate {
+    lang: &'static str,
+    verified: bool,
+    message: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "forgot_password.html")]
+pub struct ForgotPasswordTemplate {
+    csrf_token: String,
+    lang: &'static str,
+    email: String,
+    errors: Vec<&'static str>,
+    done: Option<&'static str>,
+}
+
+#[derive(Template)]
+#[template(path = "reset_password.html")]
+pub struct ResetPasswordTemplate {
+    csrf_token: String,
+    lang: &'static str,
+    token: String,
+    errors: Vec<&'static str>,
+    done: Option<&'static str>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AccountQuery {
+    lang: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterForm {
+    name: String,
+    email: String,
+    password: String,
+    password_confirm: String,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordForm {
+    email: String,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordForm {
+    token: String,
+    password: String,
+    password_confirm: String,
+    lang: Option<String>,
+}
+
+fn lang_of(explicit: Option<&str>, headers: &HeaderMap) -> Lang {
+    Lang::detect(
+        explicit,
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok()),
+    )
+}
+
+fn texts(messages: &[AccountMessage], lang: Lang) -> Vec<&'static str> {
+    messages.iter().map(|m| m.text(lang)).collect()
+}
+
+fn page(status: StatusCode, template: impl Template) -> Response {
+    (status, Html(template.render().unwrap())).into_response()
+}
+
+fn upstream_failed(what: &str, e: LoginError) -> Html<String> {
+    match e {
+        LoginError::Rejected(status) => {
+            error!("Auth service error ({}): {}", what, status);
+            render_error("Er ging iets mis", format!("{} failed: {}", what, status))
+        }
+        LoginError::Upstream(e) => {
+            error!("Request naar auth-service failed ({}): {}", what, e);
+            render_error("Er ging iets mis", format!("Connection error: {}", e))
+        }
+    }
+}
+
+pub async fn register_page(
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let lang = lang_of(query.lang.as_deref(), &headers);
+    page(
+        StatusCode::OK,
+        RegisterTemplate {
+            csrf_token: csrf.0,
+            lang: lang.code(),
+            name: String::new(),
+            email: String::new(),
+            errors: vec![],
+            done: None,
+        },
+    )
+}
+
+pub async fn register_handler(
+    Extension(login): Extension<LoginState>,
+    Extension(csrf): Extension<CsrfToken>,
+    headers: HeaderMap,
+    Form(form): Form<RegisterForm>,
+) -> Result<Response, Html<String>> {
+    let lang = lang_of(form.lang.as_deref(), &headers);
+    let mut template = RegisterTemplate {
+        csrf_token: csrf.0,
+        lang: lang.code(),
+        name: form.name.trim().to_string(),
+        email: form.email.trim().to_string(),
+        errors: vec![],
+        done: None,
+    };
+
+    let errors = account_service::validate_registration(
+        &form.name,
+        &form.email,
+        &form.password,
+        &form.password_confirm,
+    );
+    if !errors.is_empty() {
+        template.errors = texts(&errors, lang);
+        return Ok(page(StatusCode::UNPROCESSABLE_ENTITY, template));
+    }
+
+    println!("📝 REGISTRATION: email={}", template.email);
+    match auth_api_service::register(
+        &login.client,
+        &login.auth_base_url,
+        &template.name,
+        &template.email,
+        &form.password,
+    )
+    .await
+    {
+        Ok(()) => {
+            template.done = Some(AccountMessage::RegisteredCheckEmail.text(lang));
+            Ok(page(StatusCode::OK, template))
+        }
+        Err(LoginError::Rejected(409)) => {
+            template.errors = texts(&[AccountMessage::EmailTaken], lang);
+            Ok(page(StatusCode::CONFLICT, template))
+        }
+        Err(LoginError::Rejected(status @ (400 | 422))) => {
+            warn!(status, "registration_rejected");
+            template.errors = texts(&[AccountMessage::RegistrationRejected], lang);
+            Ok(page(StatusCode::UNPROCESSABLE_ENTITY, template))
+        }
+        Err(e) => Err(upstream_failed("Registration", e)),
+    }
+}
+
+/// Landing page for the link in the verification mail.
+pub async fn verify_email_handler(
+    Extension(login): Extension<LoginState>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Html<String>> {
+    let lang = lang_of(query.lang.as_deref(), &headers);
+    let token = query.token.as_deref().map(str::trim).unwrap_or("");
+
+    let result = if token.is_empty() {
+        Err(LoginError::Rejected(400))
+    } else {
+        auth_api_service::verify_email(&login.client, &login.auth_base_url, token).await
+    };
+
+    let (status, verified, message) = match result {
+        Ok(()) => (StatusCode::OK, true, AccountMessage::EmailVerified),
+        Err(LoginError::Rejected(400 | 404 | 410 | 422)) => {
+            (StatusCode::BAD_REQUEST, false, AccountMessage::TokenInvalid)
+        }
+        Err(e) => return Err(upstream_failed("Email verification", e)),
+    };
+    Ok(page(
+        status,
+        VerifyEmailTemplate {
+            lang: lang.code(),
+            verified,
+            message: message.text(lang),
+        },
+    ))
+}
+
+pub async fn forgot_password_page(
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let lang = lang_of(query.lang.as_deref(), &headers);
+    page(
+        StatusCode::OK,
+        ForgotPasswordTemplate {
+            csrf_token: csrf.0,
+            lang: lang.code(),
+            email: String::new(),
+            errors: vec![],
+            done: None,
+        },
+    )
+}
+
+pub async fn forgot_password_handler(
+    Extension(login): Extension<LoginState>,
+    Extension(csrf): Extension<CsrfToken>,
+    headers: HeaderMap,
+    Form(form): Form<ForgotPasswordForm>,
+) -> Result<Response, Html<String>> {
+    let lang = lang_of(form.lang.as_deref(), &headers);
+    let mut template = ForgotPasswordTemplate {
+        csrf_token: csrf.0,
+        lang: lang.code(),
+        email: form.email.trim().to_string(),
+        errors: vec![],
+        done: None,
+    };
+
+    if let Some(e) = account_service::validate_email(&form.email) {
+        template.errors = texts(&[e], lang);
+        return Ok(page(StatusCode::UNPROCESSABLE_ENTITY, template));
+    }
+
+    // Unknown addresses get the same answer, so the page does not reveal
+    // which emails have an account.
+    match auth_api_service::request_password_reset(
+        &login.client,
+        &login.auth_base_url,
+        &template.email,
+    )
+    .await
+    {
+        Ok(()) | Err(LoginError::Rejected(404)) => {
+            template.done = Some(AccountMessage::ResetMailSent.text(lang));
+            Ok(page(StatusCode::OK, template))
+        }
+        Err(e) => Err(upstream_failed("Password reset", e)),
+    }
+}
+
+pub async fn reset_password_page(
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let lang = lang_of(query.lang.as_deref(), &headers);
+    let token = query.token.unwrap_or_default().trim().to_string();
+    let errors = if token.is_empty() {
+        texts(&[AccountMessage::TokenInvalid], lang)
+    } else {
+        vec![]
+    };
+    page(
+        StatusCode::OK,
+        ResetPasswordTemplate {
+            csrf_token: csrf.0,
+            lang: lang.code(),
+            token,
+            errors,
+            done: None,
+        },
+    )
+}
+
+pub async fn reset_password_handler(
+    Extension(login): Extension<LoginState>,
+    Extension(csrf): Extension<CsrfToken>,
+    headers: HeaderMap,
+    Form(form): Form<ResetPasswordForm>,
+) -> Result<Response, Html<String>> {
+    let lang = lang_of(form.lang.as_deref(), &headers);
+    let mut template = ResetPasswordTemplate {
+        csrf_token: csrf.0,
+        lang: lang.code(),
+        token: form.token.trim().to_string(),
+        errors: vec![],
+        done: None,
+    };
+
+    let mut errors = account_service::validate_new_password(&form.password, &form.password_confirm);
+    if template.token.is_empty() {
+        errors.insert(0, AccountMessage::TokenInvalid);
+    }
+    if !errors.is_empty() {
+        template.errors = texts(&errors, lang);
+        return Ok(page(StatusCode::UNPROCESSABLE_ENTITY, template));
+    }
+
+    match auth_api_service::reset_password(
+        &login.client,
+        &login.auth_base_url,
+        &template.token,
+        &form.password,
+    )
+    .await
+    {
+        Ok(()) => {
+            template.done = Some(AccountMessage::PasswordChanged.text(lang));
+            Ok(page(StatusCode::OK, template))
+        }
+        Err(LoginError::Rejected(400 | 404 | 410 | 422)) => {
+            template.errors = texts(&[AccountMessage::TokenInvalid], lang);
+            Ok(page(StatusCode::BAD_REQUEST, template))
+        }
+        Err(e) => Err(upstream_failed("Password reset", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::Request,
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    use crate::services::mfa_service::MfaPolicy;
+    use crate::web::middleware::csrf::{csrf_protect, CsrfConfig};
+
+    const CSRF: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    /// Local stub of the auth-service account endpoints; records the calls.
+    async fn start_auth_service() -> (String, Arc<Mutex<Vec<(String, Value)>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |path: &'static str, calls: Arc<Mutex<Vec<(String, Value)>>>| {
+            post(move |Json(body): Json<Value>| {
+                let calls = calls.clone();
+                async move {
+                    calls.lock().unwrap().push((path.to_string(), body.clone()));
+                    let status = match path {
+                        "/register" if body["email"] == "taken@example.com" => StatusCode::CONFLICT,
+                        "/verify-email" | "/reset-password" if body["token"] != "good-token" => {
+                            StatusCode::GONE
+                        }
+                        "/forgot-password" if body["email"] == "nobody@example.com" => {
+                            StatusCode::NOT_FOUND
+                        }
+                        _ => StatusCode::OK,
+                    };
+                    (
+                        status,
+                        Json(json!({ "success": status.is_success(), "data": {} })),
+                    )
+                }
+            })
+        };
+        let app = Router::new()
+            .route("/api/v1/auth/register", record("/register", calls.clone()))
+            .route(
+                "/api/v1/auth/verify-email",
+                record("/verify-email", calls.clone()),
+            )
+            .route(
+                "/api/v1/auth/forgot-password",
+                record("/forgot-password", calls.clone()),
+            )
+            .route(
+                "/api/v1/auth/reset-password",
+                record("/reset-password", calls.clone()),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), calls)
+    }
+
+    fn app(base_url: String) -> Router {
+        Router::new()
+            .route("/register", get(register_page).post(register_handler))
+            .route("/verify-email", get(verify_email_handler))
+            .route(
+                "/forgot-password",
+                get(forgot_password_page).post(forgot_password_handler),
+            )
+            .route(
+                "/reset-password",
+                get(reset_password_page).post(reset_password_handler),
+            )
+            .layer(Extension(LoginState::new(base_url, MfaPolicy::default())))
+            .layer(axum::middleware::from_fn_with_state(
+                CsrfConfig::default(),
+                csrf_protect,
+            ))
+    }
+
+    async fn call(base_url: &str, req: Request<Body>) -> (StatusCode, String) {
+        let resp = app(base_url.to_string()).oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    fn form(uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::COOKIE, format!("csrf_token={}", CSRF))
+            .body(Body::from(format!("{}&csrf_token={}", body, CSRF)))
+            .unwrap()
+    }
+
+    fn get_req(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn registration_validates_locally_then_calls_auth_service() {
+        let (base_url, calls) = start_auth_service().await;
+
+        let (status, body) = call(
+            &base_url,
+            form(
+                "/register",
+                "name=A&email=nope&password=short&password_confirm=x&lang=en",
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("Enter a valid email address."));
+        assert!(body.contains("at least 10 characters"));
+        assert!(calls.lock().unwrap().is_empty());
+
+        let ok =
+            "name=Abbas&email=new%40example.com&password=Twente2025x&password_confirm=Twente2025x";
+        let (status, body) = call(&base_url, form("/register", ok)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Account aangemaakt"));
+        assert_eq!(calls.lock().unwrap()[0].1["email"], "new@example.com");
+
+        let taken = ok.replace("new%40", "taken%40");
+        let (status, body) = call(&base_url, form("/register", &taken)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("Er bestaat al een account"));
+    }
+
+    #[tokio::test]
+    async fn verify_email_landing() {
+        let (base_url, _) = start_auth_service().await;
+
+        let (status, body) = call(&base_url, get_req("/verify-email?token=good-token")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Je e-mailadres is bevestigd"));
+
+        let (status, body) = call(&base_url, get_req("/verify-email?token=stale&lang=en")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("invalid or has expired"));
+    }
+
+    #[tokio::test]
+    async fn forgot_and_reset_password() {
+        let (base_url, calls) = start_auth_service().await;
+
+        // Known and unknown addresses look the same.
+        for email in ["abbas%40example.com", "nobody%40example.com"] {
+            let (status, body) = call(
+                &base_url,
+                form("/forgot-password", &format!("email={}", email)),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body.contains("ontvang je zo een resetlink"));
+        }
+
+        let (status, body) = call(
+            &base_url,
+            form(
+                "/reset-password",
+                "token=good-token&password=Nieuw2026pass&password_confirm=Other2026pass",
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("komen niet overeen"));
+        assert_eq!(calls.lock().unwrap().len(), 2);
+
+        let (status, body) = call(
+            &base_url,
+            form(
+                "/reset-password",
+                "token=good-token&password=Nieuw2026pass&password_confirm=Nieuw2026pass",
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Je wachtwoord is gewijzigd"));
+
+        let (status, _) = call(
+            &base_url,
+            form(
+                "/reset-password",
+                "token=used&password=Nieuw2026pass&password_confirm=Nieuw2026pass",
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
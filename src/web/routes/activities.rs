@@ -1,13 +1,16 @@
 use askama::Template;
 use axum::{
-    extract::{Query, State},
-    response::Html,
-    Extension,
+    extract::{RawQuery, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Extension, Json,
 };
 use sqlx::SqlitePool;
+use tracing::warn;
 
-use crate::services::activities_service::{self, ActivitiesQuery};
+use crate::services::activities_service::{self, ActivitiesQuery, MapViewport};
 use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
 
 #[derive(Template)]
 #[template(path = "activities.html")]
@@ -15,13 +18,29 @@ pub struct ActivitiesTemplate {
     pub activities: Vec<activities_service::ActivityCardView>,
     pub filters: activities_service::AppliedActivityFilters,
     pub interest_options: Vec<activities_service::InterestOptionView>,
+    pub category_options: Vec<activities_service::FacetOptionView>,
+    pub tag_options: Vec<activities_service::FacetOptionView>,
+    pub next_cursor: Option<String>,
+    pub csrf_token: String,
+}
+
+/// One page of cards for infinite scroll, same filters as the full page.
+#[derive(Template)]
+#[template(path = "activity_cards.html")]
+pub struct ActivityCardsTemplate {
+    pub activities: Vec<activities_service::ActivityCardView>,
+    pub filters: activities_service::AppliedActivityFilters,
+    pub next_cursor: Option<String>,
+    pub csrf_token: String,
 }
 
 pub async fn activities_handler(
     Extension(auth_user): Extension<AuthenticatedUser>,
-    Query(query): Query<ActivitiesQuery>,
+    Extension(csrf): Extension<CsrfToken>,
+    RawQuery(raw): RawQuery,
     State(pool): State<SqlitePool>,
 ) -> Html<String> {
+    let query = ActivitiesQuery::from_query_string(raw.as_deref().unwrap_or_default());
     let data = activities_service::build_activities_page(&pool, &auth_user.id, &query)
         .await
         .unwrap_or(activities_service::ActivitiesPageData {
@@ -33,12 +52,68 @@ pub async fn activities_handler(
                 ..activities_service::AppliedActivityFilters::default()
             },
             interest_options: vec![],
+            category_options: vec![],
+            tag_options: vec![],
+            next_cursor: None,
         });
 
     let template = ActivitiesTemplate {
         activities: data.activities,
         filters: data.filters,
         interest_options: data.interest_options,
+        category_options: data.category_options,
+        tag_options: data.tag_options,
+        next_cursor: data.next_cursor,
+        csrf_token: csrf.0,
     };
     Html(template.render().unwrap())
 }
+
+pub async fn activities_feed_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    RawQuery(raw): RawQuery,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let query = ActivitiesQuery::from_query_string(raw.as_deref().unwrap_or_default());
+    let data = match activities_service::build_activities_page(&pool, &auth_user.id, &query).await {
+        Ok(d) => d,
+        Err(e) => {
+            warn!("Activities feed page failed: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let template = ActivityCardsTemplate {
+        activities: data.activities,
+        filters: data.filters,
+        next_cursor: data.next_cursor,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// GeoJSON markers for the map tab: `bbox=west,south,east,north&zoom=` plus
+/// the usual activity filters.
+pub async fn activities_map_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    RawQuery(raw): RawQuery,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    let raw = raw.unwrap_or_default();
+    let Some(viewport) = MapViewport::from_query_string(&raw) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid_bbox" })),
+        )
+            .into_response();
+    };
+    let query = ActivitiesQuery::from_query_string(&raw);
+    match activities_service::build_activities_map(&pool, &auth_user.id, &query, viewport).await {
+        Ok(collection) => Json(collection).into_response(),
+        Err(e) => {
+            warn!("Activities map failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -7,34 +7,81 @@ use axum::{
 };
 use serde::Deserialize;
 use sqlx::SqlitePool;
+use std::sync::Arc;
 use tracing::warn;
 
+use crate::services::activity_attendance_service::{self, AttendanceView};
 use crate::services::activity_detail_service::{self, ActivityDetailQuery};
+use crate::services::activity_editor_service::{self, ActivityForm, ActivityFormView, EditorError};
+use crate::services::activity_lifecycle_service::{self, LifecycleAction, LifecycleError};
+use crate::services::activity_review_service::{self, ReviewError, ReviewsView};
 use crate::services::activity_summary_service;
+use crate::services::central_apply_service::{
+    ApplyOutcome, CommandError, DomainError, ServiceError,
+};
+use crate::services::invitation_service;
+use crate::services::live_events_service::LiveEventHub;
+use crate::services::redirect_service::local_path;
 use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
 
 #[derive(Template)]
 #[template(path = "activity.html")]
 pub struct ActivityDetailTemplate {
     pub activity: activity_detail_service::ActivityDetailView,
+    pub reviews: ReviewsView,
     pub can_manage_activity: bool,
+    pub csrf_token: String,
 }
 
 pub async fn activity_detail_handler(
-    Extension(_auth_user): Extension<AuthenticatedUser>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
     Path(activity_id): Path<String>,
     Query(query): Query<ActivityDetailQuery>,
     State(pool): State<SqlitePool>,
 ) -> impl IntoResponse {
-    // Detail page is retired; keep the route for backwards compatibility and deep links.
-    let _ = (activity_id, query, pool);
-    Redirect::to("/activities").into_response()
+    // The summary sheet replaced the detail page; it only lives on for
+    // completed activities, where it carries the reviews.
+    let view = match activity_detail_service::load_activity_detail_view(&pool, &activity_id, &query)
+        .await
+    {
+        Ok(Some(v)) if v.status == "completed" => v,
+        Ok(_) => return Redirect::to("/activities").into_response(),
+        Err(e) => {
+            warn!("Activity detail load failed for {}: {}", activity_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let reviews = match activity_review_service::load_reviews_view(
+        &pool,
+        &auth_user.id,
+        &activity_id,
+    )
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Redirect::to("/activities").into_response(),
+        Err(e) => {
+            warn!("Activity reviews load failed for {}: {}", activity_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let template = ActivityDetailTemplate {
+        can_manage_activity: view.can_manage_activity,
+        activity: view,
+        reviews,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
 }
 
 #[derive(Template)]
 #[template(path = "activity_summary.html")]
 pub struct ActivitySummaryTemplate {
     pub summary: activity_summary_service::ActivitySummaryView,
+    pub csrf_token: String,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -45,6 +92,7 @@ pub struct ActivitySummaryQuery {
 
 pub async fn activity_summary_handler(
     Extension(_auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
     Path(activity_id): Path<String>,
     Query(query): Query<ActivitySummaryQuery>,
     State(pool): State<SqlitePool>,
@@ -68,7 +116,10 @@ pub async fn activity_summary_handler(
         return StatusCode::NOT_FOUND.into_response();
     };
 
-    let template = ActivitySummaryTemplate { summary: view };
+    let template = ActivitySummaryTemplate {
+        summary: view,
+        csrf_token: csrf.0,
+    };
     Html(template.render().unwrap()).into_response()
 }
 
@@ -101,7 +152,14 @@ pub async fn activity_signup_command_handler(
     )
     .await
     {
-        Ok(_) => match action {
+        Ok(outcome) => match action {
+            "join" if outcome.outcome.as_deref() == Some("waitlisted") => {
+                if subject == auth_user.id {
+                    "join_waitlisted"
+                } else {
+                    "owner_join_waitlisted"
+                }
+            }
             "join" => {
                 if subject == auth_user.id {
                     "join_ok"
@@ -118,13 +176,17 @@ pub async fn activity_signup_command_handler(
             }
             _ => "ok",
         },
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Signup command rejected: {:?}", outcome.outcome);
+            rejected_notice(&outcome)
+        }
         Err(e) => {
             warn!("Signup command failed: {}", e);
             "error"
         }
     };
 
-    if let Some(target) = form.return_to.as_deref().and_then(sanitize_return_to) {
+    if let Some(target) = form.return_to.as_deref().and_then(local_path) {
         let sep = if target.contains('?') { "&" } else { "?" };
         return Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response();
     }
@@ -171,13 +233,17 @@ pub async fn activity_waitlist_command_handler(
             "set_priority" => "waitlist_priority_ok",
             _ => "ok",
         },
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Waitlist command rejected: {:?}", outcome.outcome);
+            rejected_notice(&outcome)
+        }
         Err(e) => {
             warn!("Waitlist command failed: {}", e);
             "error"
         }
     };
 
-    if let Some(target) = form.return_to.as_deref().and_then(sanitize_return_to) {
+    if let Some(target) = form.return_to.as_deref().and_then(local_path) {
         let sep = if target.contains('?') { "&" } else { "?" };
         return Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response();
     }
@@ -185,13 +251,430 @@ pub async fn activity_waitlist_command_handler(
     Redirect::to(&format!("/activities/{}?notice={}", activity_id, notice)).into_response()
 }
 
-fn sanitize_return_to(value: &str) -> Option<&str> {
-    let v = value.trim();
-    if !v.starts_with('/') {
-        return None;
+#[derive(Template)]
+#[template(path = "activity_invites.html")]
+pub struct ActivityInvitesTemplate {
+    pub view: invitation_service::ActivityInvitesView,
+    pub notice: Option<String>,
+    pub csrf_token: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ActivityInvitesQuery {
+    pub q: Option<String>,
+    pub notice: Option<String>,
+}
+
+pub async fn activity_invites_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Path(activity_id): Path<String>,
+    Query(query): Query<ActivityInvitesQuery>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let view = match invitation_service::load_activity_invites_view(
+        &pool,
+        &auth_user.id,
+        &activity_id,
+        query.q.as_deref(),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Activity invites load failed for {}: {}", activity_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(view) = view else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !view.can_manage_activity {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let template = ActivityInvitesTemplate {
+        view,
+        notice: query.notice,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InviteCommandForm {
+    pub invitee_user_id: String,
+    pub message: Option<String>,
+    pub return_to: Option<String>,
+}
+
+pub async fn activity_invite_command_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<InviteCommandForm>,
+) -> impl IntoResponse {
+    let notice = match invitation_service::create_invite_command(
+        &pool,
+        &auth_user.id,
+        &activity_id,
+        &form.invitee_user_id,
+        form.message.as_deref(),
+    )
+    .await
+    {
+        Ok(_) => "invite_sent",
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Invite command rejected: {:?}", outcome.outcome);
+            match outcome.outcome.as_deref() {
+                Some("not_friends") => "not_friends",
+                Some("already_invited") => "already_invited",
+                Some("already_joined") => "already_joined",
+                _ => rejected_notice(&outcome),
+            }
+        }
+        Err(e) => {
+            warn!("Invite command failed: {}", e);
+            "error"
+        }
+    };
+
+    if let Some(target) = form.return_to.as_deref().and_then(local_path) {
+        let sep = if target.contains('?') { "&" } else { "?" };
+        return Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response();
     }
-    if v.starts_with("//") || v.contains("://") {
-        return None;
+
+    Redirect::to(&format!(
+        "/activities/{}/invites?notice={}",
+        activity_id, notice
+    ))
+    .into_response()
+}
+
+#[derive(Template)]
+#[template(path = "activity_form.html")]
+pub struct ActivityFormTemplate {
+    pub view: ActivityFormView,
+    pub csrf_token: String,
+}
+
+pub async fn activity_new_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let view = activity_editor_service::load_form(&pool, &auth_user.id, None).await;
+    render_activity_form(StatusCode::OK, view, csrf)
+}
+
+pub async fn activity_create_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<ActivityForm>,
+) -> impl IntoResponse {
+    submit_activity_form(&pool, &auth_user.id, None, form, csrf).await
+}
+
+pub async fn activity_edit_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let view = activity_editor_service::load_form(&pool, &auth_user.id, Some(&activity_id)).await;
+    render_activity_form(StatusCode::OK, view, csrf)
+}
+
+pub async fn activity_update_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<ActivityForm>,
+) -> impl IntoResponse {
+    submit_activity_form(&pool, &auth_user.id, Some(&activity_id), form, csrf).await
+}
+
+async fn submit_activity_form(
+    pool: &SqlitePool,
+    user_id: &str,
+    activity_id: Option<&str>,
+    form: ActivityForm,
+    csrf: CsrfToken,
+) -> axum::response::Response {
+    let errors = match activity_editor_service::submit(pool, user_id, activity_id, &form).await {
+        Ok(_) => {
+            let notice = match (activity_id, form.status.as_str()) {
+                (Some(_), _) => "activity_updated",
+                (None, "published") => "activity_created",
+                _ => "activity_draft_saved",
+            };
+            return Redirect::to(&format!("/activities?tab=upcoming&notice={}", notice))
+                .into_response();
+        }
+        Err(EditorError::Invalid(errors)) => errors,
+        Err(EditorError::Command(CommandError::Rejected(outcome))) => {
+            warn!("Activity command rejected: {:?}", outcome.outcome);
+            vec![match rejected_notice(&outcome) {
+                "not_allowed" => "Je mag deze activiteit niet bewerken.",
+                "error" => "Opslaan mislukt. Probeer het zo nog eens.",
+                _ => "De activiteit is geweigerd.",
+            }
+            .to_string()]
+        }
+        Err(e) => {
+            return render_activity_form(StatusCode::OK, Err(e), csrf);
+        }
+    };
+
+    let view = activity_editor_service::form_view(pool, user_id, activity_id, form, errors).await;
+    render_activity_form(StatusCode::UNPROCESSABLE_ENTITY, view, csrf)
+}
+
+fn render_activity_form(
+    status: StatusCode,
+    view: Result<ActivityFormView, EditorError>,
+    csrf: CsrfToken,
+) -> axum::response::Response {
+    match view {
+        Ok(view) => {
+            let template = ActivityFormTemplate {
+                view,
+                csrf_token: csrf.0,
+            };
+            (status, Html(template.render().unwrap())).into_response()
+        }
+        Err(EditorError::NotFound) => StatusCode::NOT_FOUND.into_response(),
+        Err(EditorError::Forbidden) => StatusCode::FORBIDDEN.into_response(),
+        Err(e) => {
+            warn!("Activity form failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "activity_attendance.html")]
+pub struct ActivityAttendanceTemplate {
+    pub view: AttendanceView,
+    pub notice: Option<String>,
+    pub csrf_token: String,
+}
+
+pub async fn activity_attendance_handler(
+    Extension(_auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Path(activity_id): Path<String>,
+    Query(query): Query<ActivityDetailQuery>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let view = match activity_attendance_service::load_attendance_view(&pool, &activity_id).await {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Attendance load failed for {}: {}", activity_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(view) = view else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !view.can_manage_attendance {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let template = ActivityAttendanceTemplate {
+        view,
+        notice: query.notice,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Bulk submit: repeated `user_id` plus one `status`.
+pub async fn activity_attendance_command_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let status = fields
+        .iter()
+        .find(|(k, _)| k == "status")
+        .map(|(_, v)| v.trim())
+        .unwrap_or("");
+    let user_ids: Vec<String> = fields
+        .iter()
+        .filter(|(k, _)| k == "user_id")
+        .map(|(_, v)| v.clone())
+        .collect();
+
+    let notice = match activity_attendance_service::mark_attendance(
+        &pool,
+        &auth_user.id,
+        &activity_id,
+        &user_ids,
+        status,
+    )
+    .await
+    {
+        Ok(_) => "attendance_saved",
+        Err(e) => service_error_notice("Attendance", e),
+    };
+
+    Redirect::to(&format!(
+        "/activities/{}/attendance?notice={}",
+        activity_id, notice
+    ))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct ReviewCommandForm {
+    pub rating: String,
+    pub review_text: String,
+}
+
+pub async fn activity_review_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<ReviewCommandForm>,
+) -> impl IntoResponse {
+    let result = activity_review_service::save_review(
+        &pool,
+        &auth_user.id,
+        &activity_id,
+        &form.rating,
+        &form.review_text,
+    )
+    .await;
+    review_redirect(&activity_id, result, "review_saved")
+}
+
+pub async fn activity_review_helpful_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path((activity_id, review_id)): Path<(String, String)>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let result =
+        activity_review_service::vote_helpful(&pool, &auth_user.id, &activity_id, &review_id).await;
+    review_redirect(&activity_id, result, "helpful_saved")
+}
+
+fn review_redirect(
+    activity_id: &str,
+    result: Result<ApplyOutcome, ServiceError<ReviewError>>,
+    done_notice: &str,
+) -> axum::response::Response {
+    let notice = match result {
+        Ok(_) => done_notice,
+        Err(e) => service_error_notice("Review", e),
+    };
+    Redirect::to(&format!(
+        "/activities/{}?notice={}#reviews",
+        activity_id, notice
+    ))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct LifecycleCommandForm {
+    pub reason: String,
+    pub date: String,
+    pub time: String,
+    pub return_to: Option<String>,
+}
+
+pub async fn activity_cancel_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(hub): Extension<Arc<LiveEventHub>>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<LifecycleCommandForm>,
+) -> impl IntoResponse {
+    let action = activity_lifecycle_service::cancel_action(&form.reason);
+    lifecycle_command(&pool, &hub, &auth_user.id, &activity_id, action, &form).await
+}
+
+pub async fn activity_reschedule_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(hub): Extension<Arc<LiveEventHub>>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<LifecycleCommandForm>,
+) -> impl IntoResponse {
+    let action = activity_lifecycle_service::reschedule_action(&form.date, &form.time);
+    lifecycle_command(&pool, &hub, &auth_user.id, &activity_id, action, &form).await
+}
+
+pub async fn activity_complete_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(hub): Extension<Arc<LiveEventHub>>,
+    Path(activity_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<LifecycleCommandForm>,
+) -> impl IntoResponse {
+    let action = Ok(LifecycleAction::Complete);
+    lifecycle_command(&pool, &hub, &auth_user.id, &activity_id, action, &form).await
+}
+
+async fn lifecycle_command(
+    pool: &SqlitePool,
+    hub: &LiveEventHub,
+    user_id: &str,
+    activity_id: &str,
+    action: Result<LifecycleAction, LifecycleError>,
+    form: &LifecycleCommandForm,
+) -> axum::response::Response {
+    let result = match action {
+        Ok(action) => {
+            let notice = action.done_notice();
+            activity_lifecycle_service::apply(pool, hub, user_id, activity_id, action)
+                .await
+                .map(|_| notice)
+        }
+        Err(e) => Err(e.into()),
+    };
+    let notice = match result {
+        Ok(notice) => notice,
+        Err(e) => service_error_notice("Lifecycle", e),
+    };
+
+    let target = form
+        .return_to
+        .as_deref()
+        .and_then(local_path)
+        .unwrap_or("/activities?tab=upcoming");
+    let sep = if target.contains('?') { "&" } else { "?" };
+    Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response()
+}
+
+/// Notice for a failed lifecycle, attendance or review command; only
+/// failures of the command itself are logged.
+fn service_error_notice<E: DomainError>(what: &str, err: ServiceError<E>) -> &'static str {
+    match err {
+        ServiceError::Command(CommandError::Rejected(outcome)) => {
+            warn!("{} command rejected: {:?}", what, outcome.outcome);
+            rejected_notice(&outcome)
+        }
+        ServiceError::Command(e) => {
+            warn!("{} command failed: {}", what, e);
+            "error"
+        }
+        ServiceError::Domain(e) => e.notice(),
+    }
+}
+
+fn rejected_notice(outcome: &ApplyOutcome) -> &'static str {
+    match outcome.outcome.as_deref() {
+        Some("activity_full") => "activity_full",
+        Some("not_allowed") => "not_allowed",
+        Some("unavailable") => "error",
+        _ => "rejected",
     }
-    Some(v)
 }
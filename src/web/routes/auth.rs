@@ -1,141 +1,350 @@
 use askama::Template;
 use axum::{
-    http::header,
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
-    Form,
+    Extension, Form,
 };
 use cookie::Cookie;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use tracing::error;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::services::auth_api_service::{self, LoginError, LoginOutcome, TokenPair};
+use crate::services::mfa_service::{MfaError, MfaPolicy, MfaStore, PendingLogin};
+use crate::services::redirect_service::local_path;
+use crate::web::middleware::auth::{cookie_value, session_cookie};
+use crate::web::middleware::csrf::{csrf_removal_cookie, CsrfToken};
+
+const MFA_PENDING_COOKIE: &str = "mfa_pending";
+
+/// Shared by the login routes: where the auth-service lives and the pending
+/// second-factor logins.
+#[derive(Clone)]
+pub struct LoginState {
+    pub auth_base_url: String,
+    pub client: reqwest::Client,
+    pub mfa: Arc<MfaStore>,
+}
+
+impl LoginState {
+    pub fn from_env() -> Self {
+        Self::new(
+            auth_api_service::auth_service_base_url(),
+            MfaPolicy::default(),
+        )
+    }
+
+    pub fn new(auth_base_url: String, policy: MfaPolicy) -> Self {
+        Self {
+            auth_base_url,
+            client: reqwest::Client::new(),
+            mfa: Arc::new(MfaStore::new(policy)),
+        }
+    }
+}
 
 #[derive(Template)]
 #[template(path = "login.html")]
-pub struct LoginTemplate;
+pub struct LoginTemplate {
+    return_to: Option<String>,
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    return_to: Option<String>,
+}
 
 #[derive(Deserialize)]
 pub struct LoginForm {
     email: String,
     password: String,
+    return_to: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
-struct AuthResponse {
-    access_token: String,
-    refresh_token: String,
-    #[serde(default)]
-    mfa_required: bool,
+#[derive(Deserialize)]
+pub struct MfaForm {
+    code: String,
 }
 
-#[derive(Deserialize)]
-struct AuthServiceResponse {
-    #[serde(rename = "success")]
-    _success: bool,
-    data: AuthResponse,
+#[derive(Template)]
+#[template(path = "login_mfa.html")]
+pub struct MfaTemplate {
+    email: String,
+    error: Option<String>,
+    csrf_token: String,
 }
 
 #[derive(Template)]
 #[template(path = "error.html")]
 struct ErrorTemplate {
+    title: String,
     message: String,
 }
 
-pub async fn login_page() -> Html<String> {
-    let template = LoginTemplate;
+pub async fn login_page(
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<LoginQuery>,
+) -> Html<String> {
+    let template = LoginTemplate {
+        csrf_token: csrf.0,
+        return_to: query
+            .return_to
+            .as_deref()
+            .and_then(local_path)
+            .map(|s| s.to_string()),
+    };
     Html(template.render().unwrap())
 }
 
-pub async fn login_handler(Form(form): Form<LoginForm>) -> Result<Response, Html<String>> {
+pub async fn login_handler(
+    Extension(login): Extension<LoginState>,
+    Form(form): Form<LoginForm>,
+) -> Result<Response, Html<String>> {
     println!("📝 LOGIN ATTEMPT: email={}", form.email);
 
-    // HTTP client maken
-    let client = reqwest::Client::new();
-
-    // POST request naar auth-service
-    println!("🔐 Sending auth request to http://auth.localhost:8080/api/v1/auth/login");
-    let response = client
-        .post("http://auth.localhost:8080/api/v1/auth/login")
-        .json(&json!({
-            "email": form.email,
-            "password": form.password,
-        }))
-        .send()
-        .await;
+    if let Some(retry_after) = login.mfa.locked_out(&form.email) {
+        println!("🔒 Login blocked: too many failed codes");
+        return Err(error_page(lockout_message(retry_after)));
+    }
 
-    match response {
-        Ok(resp) => {
-            let status = resp.status();
-            println!("✅ Got response from auth-service: status={}", status);
-
-            if resp.status().is_success() {
-                // Read body as text first for debugging
-                let body_text = resp.text().await.unwrap_or_default();
-                println!("📦 Raw auth response: {}", body_text);
-
-                // Parse tokens from text
-                println!("📦 Parsing auth response...");
-                let auth_resp: AuthResponse =
-                    match serde_json::from_str::<AuthServiceResponse>(&body_text) {
-                        Ok(wrapper) => {
-                            println!("✅ Successfully parsed response!");
-                            wrapper.data
-                        }
-                        Err(e) => {
-                            println!("❌ PARSE ERROR: {}", e);
-                            error!("Kan auth response niet parsen: {}", e);
-                            let template = ErrorTemplate {
-                                message: format!("Parse error: {}", e),
-                            };
-                            return Err(Html(template.render().unwrap()));
-                        }
-                    };
-
-                // Maak cookies
-                println!("🍪 Creating cookies...");
-                let mut access_cookie = Cookie::new("access_token", auth_resp.access_token.clone());
-                access_cookie.set_path("/");
-                access_cookie.set_http_only(true);
-                access_cookie.set_same_site(cookie::SameSite::Lax);
-
-                let mut refresh_cookie = Cookie::new("refresh_token", auth_resp.refresh_token);
-                refresh_cookie.set_path("/");
-                refresh_cookie.set_http_only(true);
-                refresh_cookie.set_same_site(cookie::SameSite::Lax);
-
-                // Build response with cookies
-                println!("➡️  Redirecting to /activities...");
-                let mut response = Redirect::to("/activities").into_response();
-                response.headers_mut().append(
-                    header::SET_COOKIE,
-                    access_cookie.to_string().parse().unwrap(),
-                );
-                response.headers_mut().append(
-                    header::SET_COOKIE,
-                    refresh_cookie.to_string().parse().unwrap(),
-                );
-
-                println!("✅ LOGIN SUCCESS!");
-                Ok(response)
-            } else {
-                println!("❌ Auth service returned error status: {}", status);
-                error!("Auth service error: {}", status);
-                let template = ErrorTemplate {
-                    message: format!("Login failed: {}", status),
-                };
-                Err(Html(template.render().unwrap()))
-            }
+    let return_to = form
+        .return_to
+        .as_deref()
+        .and_then(local_path)
+        .map(|s| s.to_string());
+
+    println!(
+        "🔐 Sending auth request to {}/api/v1/auth/login",
+        login.auth_base_url
+    );
+    match auth_api_service::login(
+        &login.client,
+        &login.auth_base_url,
+        &form.email,
+        &form.password,
+    )
+    .await
+    {
+        Ok(LoginOutcome::Tokens(pair)) => {
+            println!("✅ LOGIN SUCCESS!");
+            Ok(session_response(
+                return_to.as_deref().unwrap_or("/activities"),
+                pair,
+            ))
         }
-        Err(e) => {
+        Ok(LoginOutcome::MfaRequired { mfa_token }) => {
+            println!("🔢 Second factor required, waiting for code...");
+            let pending_id = login.mfa.start(PendingLogin {
+                email: form.email,
+                mfa_token,
+                return_to,
+            });
+            let mut response = Redirect::to("/login/mfa").into_response();
+            response.headers_mut().append(
+                header::SET_COOKIE,
+                pending_cookie(pending_id).to_string().parse().unwrap(),
+            );
+            Ok(response)
+        }
+        Err(LoginError::Rejected(status)) => {
+            println!("❌ Auth service returned error status: {}", status);
+            error!("Auth service error: {}", status);
+            Err(error_page(format!("Login failed: {}", status)))
+        }
+        Err(LoginError::Upstream(e)) => {
             println!("❌ CONNECTION ERROR: {}", e);
             error!("Request naar auth-service failed: {}", e);
-            let template = ErrorTemplate {
-                message: format!("Connection error: {}", e),
-            };
-            Err(Html(template.render().unwrap()))
+            Err(error_page(format!("Connection error: {}", e)))
+        }
+    }
+}
+
+pub async fn mfa_page(
+    Extension(login): Extension<LoginState>,
+    Extension(csrf): Extension<CsrfToken>,
+    headers: HeaderMap,
+) -> Response {
+    match cookie_value(&headers, MFA_PENDING_COOKIE).and_then(|id| login.mfa.get(&id)) {
+        Some(pending) => mfa_form(StatusCode::OK, &pending.email, None, csrf),
+        None => Redirect::to("/login").into_response(),
+    }
+}
+
+pub async fn mfa_verify_handler(
+    Extension(login): Extension<LoginState>,
+    Extension(csrf): Extension<CsrfToken>,
+    headers: HeaderMap,
+    Form(form): Form<MfaForm>,
+) -> Response {
+    let Some(pending_id) = cookie_value(&headers, MFA_PENDING_COOKIE) else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let code: String = form.code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.is_empty() || code.len() > 12 || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        let email = login
+            .mfa
+            .get(&pending_id)
+            .map(|p| p.email)
+            .unwrap_or_default();
+        return mfa_form(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            &email,
+            Some("Vul de code uit je authenticator-app in.".to_string()),
+            csrf,
+        );
+    }
+
+    let pending = match login.mfa.begin_attempt(&pending_id) {
+        Ok(p) => p,
+        Err(MfaError::RateLimited { retry_after }) => {
+            let email = login
+                .mfa
+                .get(&pending_id)
+                .map(|p| p.email)
+                .unwrap_or_default();
+            return mfa_form(
+                StatusCode::TOO_MANY_REQUESTS,
+                &email,
+                Some(format!(
+                    "Te veel pogingen. Probeer het over {} seconden opnieuw.",
+                    retry_after.as_secs().max(1)
+                )),
+                csrf,
+            );
+        }
+        Err(MfaError::LockedOut { retry_after }) => {
+            return abandon_mfa(lockout_message(retry_after));
+        }
+        Err(MfaError::Expired) => {
+            return abandon_mfa("Je inlogpoging is verlopen. Log opnieuw in.".to_string());
+        }
+    };
+
+    match auth_api_service::verify_mfa_code(
+        &login.client,
+        &login.auth_base_url,
+        &pending.mfa_token,
+        &code,
+    )
+    .await
+    {
+        Ok(pair) => {
+            login.mfa.finish(&pending_id);
+            println!("✅ LOGIN SUCCESS (MFA)!");
+            let mut response =
+                session_response(pending.return_to.as_deref().unwrap_or("/activities"), pair);
+            response.headers_mut().append(
+                header::SET_COOKIE,
+                pending_removal_cookie().to_string().parse().unwrap(),
+            );
+            response
+        }
+        Err(LoginError::Rejected(status)) if matches!(status, 400 | 401 | 403 | 422) => {
+            warn!(status, "mfa_code_rejected");
+            match login.mfa.record_failure(&pending_id) {
+                Some(MfaError::LockedOut { retry_after }) => {
+                    abandon_mfa(lockout_message(retry_after))
+                }
+                _ => mfa_form(
+                    StatusCode::UNAUTHORIZED,
+                    &pending.email,
+                    Some("Onjuiste code. Probeer het opnieuw.".to_string()),
+                    csrf,
+                ),
+            }
+        }
+        Err(e) => {
+            error!("MFA verificatie bij auth-service mislukt: {}", e);
+            mfa_form(
+                StatusCode::BAD_GATEWAY,
+                &pending.email,
+                Some("Verificatie mislukt. Probeer het opnieuw.".to_string()),
+                csrf,
+            )
         }
     }
 }
 
+fn mfa_form(status: StatusCode, email: &str, error: Option<String>, csrf: CsrfToken) -> Response {
+    let template = MfaTemplate {
+        email: email.to_string(),
+        error,
+        csrf_token: csrf.0,
+    };
+    (status, Html(template.render().unwrap())).into_response()
+}
+
+/// Error page that also drops the `mfa_pending` cookie.
+fn abandon_mfa(message: String) -> Response {
+    let mut response = (StatusCode::UNAUTHORIZED, error_page(message)).into_response();
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        pending_removal_cookie().to_string().parse().unwrap(),
+    );
+    response
+}
+
+fn error_page(message: String) -> Html<String> {
+    render_error("Login Mislukt", message)
+}
+
+/// `error.html` for the public auth pages (login, MFA, account).
+pub(crate) fn render_error(title: &str, message: String) -> Html<String> {
+    let template = ErrorTemplate {
+        title: title.to_string(),
+        message,
+    };
+    Html(template.render().unwrap())
+}
+
+fn lockout_message(retry_after: Duration) -> String {
+    format!(
+        "Te veel onjuiste codes. Probeer het over {} minuten opnieuw.",
+        retry_after.as_secs().div_ceil(60).max(1)
+    )
+}
+
+fn session_response(target: &str, pair: TokenPair) -> Response {
+    let access_cookie = session_cookie("access_token", pair.access_token);
+    let refresh_cookie = session_cookie("refresh_token", pair.refresh_token);
+
+    println!("➡️  Redirecting to {}...", target);
+    let mut response = Redirect::to(target).into_response();
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        access_cookie.to_string().parse().unwrap(),
+    );
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        refresh_cookie.to_string().parse().unwrap(),
+    );
+    // New session, new CSRF token (issued on the next request).
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        csrf_removal_cookie().to_string().parse().unwrap(),
+    );
+    response
+}
+
+fn pending_cookie(pending_id: String) -> Cookie<'static> {
+    let mut c = Cookie::new(MFA_PENDING_COOKIE, pending_id);
+    c.set_path("/login");
+    c.set_http_only(true);
+    c.set_same_site(cookie::SameSite::Strict);
+    c.set_max_age(cookie::time::Duration::minutes(5));
+    c
+}
+
+fn pending_removal_cookie() -> Cookie<'static> {
+    let mut c = pending_cookie(String::new());
+    c.make_removal();
+    c
+}
+
 pub async fn logout_handler() -> Response {
     // Clear cookies
     let mut access_cookie = Cookie::new("access_token", "");
@@ -159,6 +368,228 @@ pub async fn logout_handler() -> Response {
         header::SET_COOKIE,
         refresh_cookie.to_string().parse().unwrap(),
     );
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        csrf_removal_cookie().to_string().parse().unwrap(),
+    );
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    use crate::web::middleware::csrf::{csrf_protect, CsrfConfig};
+
+    /// Fake auth-service: `mfa@example.com` needs the code `123456`.
+    async fn start_auth_service() -> (String, Arc<AtomicUsize>) {
+        let logins = Arc::new(AtomicUsize::new(0));
+        let counter = logins.clone();
+        let app = Router::new()
+            .route(
+                "/api/v1/auth/login",
+                post(move |Json(body): Json<Value>| {
+                    let counter = counter.clone();
+                    async move {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        let data = if body["email"] == "mfa@example.com" {
+                            json!({ "mfa_required": true, "mfa_token": "mfa-token-1" })
+                        } else {
+                            json!({ "access_token": "at-plain", "refresh_token": "rt-plain" })
+                        };
+                        Json(json!({ "success": true, "data": data }))
+                    }
+                }),
+            )
+            .route(
+                "/api/v1/auth/mfa/verify",
+                post(|Json(body): Json<Value>| async move {
+                    if body["mfa_token"] == "mfa-token-1" && body["code"] == "123456" {
+                        Ok(Json(json!({
+                            "success": true,
+                            "data": { "access_token": "at-mfa", "refresh_token": "rt-mfa" }
+                        })))
+                    } else {
+                        Err(StatusCode::UNAUTHORIZED)
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), logins)
+    }
+
+    const CSRF: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn app(state: LoginState) -> Router {
+        Router::new()
+            .route("/login", axum::routing::get(login_page).post(login_handler))
+            .route(
+                "/login/mfa",
+                axum::routing::get(mfa_page).post(mfa_verify_handler),
+            )
+            .layer(Extension(state))
+            .layer(axum::middleware::from_fn_with_state(
+                CsrfConfig::default(),
+                csrf_protect,
+            ))
+    }
+
+    async fn post_form(
+        state: &LoginState,
+        uri: &str,
+        cookie: Option<&str>,
+        body: &str,
+    ) -> Response {
+        let cookie = match cookie {
+            Some(c) => format!("csrf_token={}; {}", CSRF, c),
+            None => format!("csrf_token={}", CSRF),
+        };
+        let req = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::COOKIE, cookie);
+        let body = format!("{}&csrf_token={}", body, CSRF);
+        app(state.clone())
+            .oneshot(req.body(Body::from(body)).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn set_cookies(resp: &Response) -> Vec<String> {
+        resp.headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn pending_cookie_from(resp: &Response) -> String {
+        set_cookies(resp)
+            .into_iter()
+            .find(|c| c.starts_with("mfa_pending="))
+            .map(|c| c.split(';').next().unwrap().to_string())
+            .expect("mfa_pending cookie")
+    }
+
+    #[tokio::test]
+    async fn mfa_login_issues_session_only_after_valid_code() {
+        let (base_url, _) = start_auth_service().await;
+        let state = LoginState::new(base_url, MfaPolicy::default());
+
+        let resp = post_form(
+            &state,
+            "/login",
+            None,
+            "email=mfa%40example.com&password=pw&return_to=%2Fchats",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()[header::LOCATION], "/login/mfa");
+        assert!(set_cookies(&resp)
+            .iter()
+            .all(|c| !c.starts_with("access_token=")));
+        let pending = pending_cookie_from(&resp);
+
+        let resp = post_form(&state, "/login/mfa", Some(&pending), "code=000000").await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(set_cookies(&resp).is_empty());
+
+        let resp = post_form(&state, "/login/mfa", Some(&pending), "code=123+456").await;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()[header::LOCATION], "/chats");
+        let cookies = set_cookies(&resp);
+        assert!(cookies.iter().any(|c| c.starts_with("access_token=at-mfa")));
+        assert!(cookies
+            .iter()
+            .any(|c| c.starts_with("refresh_token=rt-mfa")));
+        assert!(cookies.iter().any(|c| c.starts_with("mfa_pending=;")));
+
+        // The pending login is single-use.
+        let resp = post_form(&state, "/login/mfa", Some(&pending), "code=123456").await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn failed_codes_lock_out_the_email() {
+        let (base_url, logins) = start_auth_service().await;
+        let policy = MfaPolicy {
+            max_failures: 3,
+            max_attempts_per_window: 10,
+            ..MfaPolicy::default()
+        };
+        let state = LoginState::new(base_url, policy);
+
+        let resp = post_form(
+            &state,
+            "/login",
+            None,
+            "email=mfa%40example.com&password=pw",
+        )
+        .await;
+        let pending = pending_cookie_from(&resp);
+        for _ in 0..2 {
+            let resp = post_form(&state, "/login/mfa", Some(&pending), "code=111111").await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+        let resp = post_form(&state, "/login/mfa", Some(&pending), "code=111111").await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(set_cookies(&resp)
+            .iter()
+            .any(|c| c.starts_with("mfa_pending=;")));
+
+        // Locked: even the right code on a fresh login is refused up front.
+        let resp = post_form(
+            &state,
+            "/login",
+            None,
+            "email=MFA%40example.com&password=pw",
+        )
+        .await;
+        assert_eq!(logins.load(Ordering::SeqCst), 1);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Te veel onjuiste codes"));
+
+        // Other accounts are unaffected and skip the second step.
+        let resp = post_form(
+            &state,
+            "/login",
+            None,
+            "email=plain%40example.com&password=pw",
+        )
+        .await;
+        assert_eq!(resp.headers()[header::LOCATION], "/activities");
+        assert!(set_cookies(&resp)
+            .iter()
+            .any(|c| c.starts_with("access_token=at-plain")));
+    }
+
+    #[tokio::test]
+    async fn throttles_rapid_code_submissions() {
+        let (base_url, _) = start_auth_service().await;
+        let state = LoginState::new(base_url, MfaPolicy::default());
+
+        let resp = post_form(
+            &state,
+            "/login",
+            None,
+            "email=mfa%40example.com&password=pw",
+        )
+        .await;
+        let pending = pending_cookie_from(&resp);
+        for _ in 0..3 {
+            post_form(&state, "/login/mfa", Some(&pending), "code=111111").await;
+        }
+        let resp = post_form(&state, "/login/mfa", Some(&pending), "code=123456").await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -88,6 +88,7 @@ struct ChatsTemplate {
 #[derive(Template)]
 #[template(path = "error.html")]
 struct ErrorTemplate {
+    title: String,
     message: String,
 }
 
@@ -132,6 +133,7 @@ pub async fn chats_handler(State(pool): State<SqlitePool>) -> Html<String> {
         Err(err) => {
             tracing::error!(error = %err, "chats_handler_failed");
             let template = ErrorTemplate {
+                title: "Er ging iets mis".to_string(),
                 message: "Kon chats niet laden".to_string(),
             };
             Html(
@@ -149,7 +151,9 @@ struct ChatDetailTemplate {
     current_user_id: String,
     conversation: crate::models::ChatConversationRow,
     messages: Vec<crate::database::chat_cache_repo::ChatCacheMessage>,
+    #[allow(dead_code)]
     preview_last_message_at: Option<String>,
+    #[allow(dead_code)]
     preview_last_message_preview: Option<String>,
     build_id: String,
 }
@@ -206,6 +210,7 @@ pub async fn chat_detail_handler(
         }
         Ok(None) => {
             let template = ErrorTemplate {
+                title: "Er ging iets mis".to_string(),
                 message: "Chat niet gevonden (sync nog niet klaar?)".to_string(),
             };
             Html(
@@ -217,6 +222,7 @@ pub async fn chat_detail_handler(
         Err(err) => {
             tracing::error!(error = %err, conversation_id = %conversation_id, "chat_detail_handler_failed");
             let template = ErrorTemplate {
+                title: "Er ging iets mis".to_string(),
                 message: "Kon chat niet laden".to_string(),
             };
             Html(
@@ -8,16 +8,19 @@ use sqlx::SqlitePool;
 
 use crate::services::discovery_service::{self, DiscoveryQuery};
 use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
 
 #[derive(Template)]
 #[template(path = "discovery.html")]
 pub struct DiscoveryTemplate {
     pub users: Vec<crate::models::DiscoveryUserRow>,
     pub filters: discovery_service::AppliedFilters,
+    pub csrf_token: String,
 }
 
 pub async fn discovery_handler(
     Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
     Query(query): Query<DiscoveryQuery>,
     State(pool): State<SqlitePool>,
 ) -> Html<String> {
@@ -30,6 +33,7 @@ pub async fn discovery_handler(
     let template = DiscoveryTemplate {
         users: data.users,
         filters: data.filters,
+        csrf_token: csrf.0,
     };
     Html(template.render().unwrap())
 }
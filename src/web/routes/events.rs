@@ -0,0 +1,60 @@
+use axum::{
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    Extension,
+};
+use futures_util::stream::{self, StreamExt};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::services::live_events_service::{LiveEventHub, LiveItem};
+use crate::web::middleware::auth::AuthenticatedUser;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Live event stream for `layout.html`. The browser's `EventSource` sends
+/// `Last-Event-ID` on reconnect; missed events are replayed from the hub.
+pub async fn events_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(hub): Extension<Arc<LiveEventHub>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let subscription = hub.subscribe(&auth_user.id, last_event_id);
+
+    let ready = Event::default()
+        .event("ready")
+        .retry(RECONNECT_DELAY)
+        .data("{}");
+    let events = stream::unfold(subscription, |mut sub| async move {
+        let item = sub.next().await?;
+        Some((sse_event(item), sub))
+    });
+    let stream = stream::once(async move { ready })
+        .chain(events)
+        .map(Ok::<_, Infallible>);
+
+    Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(HEARTBEAT_INTERVAL)
+            .text("heartbeat"),
+    )
+}
+
+fn sse_event(item: LiveItem) -> Event {
+    match item {
+        LiveItem::Event(e) => Event::default()
+            .id(e.id.to_string())
+            .event(e.name)
+            .data(e.data.to_string()),
+        LiveItem::Missed => Event::default().event("resync").data("{}"),
+    }
+}
@@ -0,0 +1,99 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    Extension, Form,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::services::central_apply_service::CommandError;
+use crate::services::invitation_service;
+use crate::services::redirect_service::local_path;
+use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
+
+#[derive(Template)]
+#[template(path = "invitations.html")]
+pub struct InvitationsTemplate {
+    pub invitations: Vec<invitation_service::InvitationView>,
+    pub notice: Option<String>,
+    pub csrf_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitationsQuery {
+    pub notice: Option<String>,
+}
+
+pub async fn invitations_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<InvitationsQuery>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let invitations = match invitation_service::load_pending_invitations(&pool, &auth_user.id).await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invitations load failed for {}: {}", auth_user.id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let template = InvitationsTemplate {
+        invitations,
+        notice: query.notice,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitationRespondForm {
+    pub action: String, // accept|decline
+    pub return_to: Option<String>,
+}
+
+pub async fn invitation_respond_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(invitation_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<InvitationRespondForm>,
+) -> impl IntoResponse {
+    let notice = match invitation_service::respond_to_invitation(
+        &pool,
+        &auth_user.id,
+        &invitation_id,
+        &form.action,
+    )
+    .await
+    {
+        Ok(_) if form.action.trim() == "accept" => "invite_accepted",
+        Ok(_) => "invite_declined",
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Invitation command rejected: {:?}", outcome.outcome);
+            match outcome.outcome.as_deref() {
+                Some("expired") => "invite_expired",
+                Some("not_found") | Some("already_responded") => "invite_unavailable",
+                Some("activity_full") => "activity_full",
+                _ => "rejected",
+            }
+        }
+        Err(e) => {
+            warn!("Invitation command failed: {}", e);
+            "error"
+        }
+    };
+
+    let target = form
+        .return_to
+        .as_deref()
+        .and_then(local_path)
+        .unwrap_or("/invitations");
+
+    let sep = if target.contains('?') { "&" } else { "?" };
+    Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response()
+}
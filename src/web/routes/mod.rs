@@ -1,9 +1,14 @@
+pub mod account;
 pub mod activities;
 pub mod activity;
 pub mod auth;
 pub mod chat_api;
 pub mod chats;
 pub mod discovery;
+pub mod events;
 pub mod images;
+pub mod invitations;
 pub mod location;
+pub mod notifications;
+pub mod onboarding;
 pub mod user;
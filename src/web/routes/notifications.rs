@@ -0,0 +1,142 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    Extension, Form, Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::services::central_apply_service::CommandError;
+use crate::services::notification_service::{self, NotificationsQuery};
+use crate::services::redirect_service::local_path;
+use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
+
+#[derive(Template)]
+#[template(path = "notifications.html")]
+pub struct NotificationsTemplate {
+    pub page: notification_service::NotificationsPageData,
+    pub csrf_token: String,
+}
+
+pub async fn notifications_handler(
+    Extension(_auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<NotificationsQuery>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    let page = match notification_service::build_notifications_page(&pool, &query).await {
+        Ok(p) => p,
+        Err(e) => {
+            warn!("Notifications load failed: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let template = NotificationsTemplate {
+        page,
+        csrf_token: csrf.0,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Badge source for `layout.html`.
+pub async fn unread_count_handler(
+    Extension(_auth_user): Extension<AuthenticatedUser>,
+    State(pool): State<SqlitePool>,
+) -> impl IntoResponse {
+    match notification_service::unread_count(&pool).await {
+        Ok(count) => Json(json!({ "count": count })).into_response(),
+        Err(e) => {
+            warn!("Unread notification count failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationCommandForm {
+    pub action: String, // read|archive|read_all
+    pub return_to: Option<String>,
+}
+
+pub async fn notification_command_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(notification_id): Path<String>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<NotificationCommandForm>,
+) -> impl IntoResponse {
+    let notice = command_notice(
+        notification_service::create_notification_command(
+            &pool,
+            &auth_user.id,
+            Some(&notification_id),
+            &form.action,
+        )
+        .await
+        .map(|_| ()),
+    );
+    redirect_back(form.return_to.as_deref(), notice)
+}
+
+pub async fn notifications_read_all_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<NotificationCommandForm>,
+) -> impl IntoResponse {
+    let notice = command_notice(
+        notification_service::create_notification_command(&pool, &auth_user.id, None, "read_all")
+            .await
+            .map(|_| ()),
+    );
+    redirect_back(form.return_to.as_deref(), notice)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationActionForm {
+    pub return_to: Option<String>,
+}
+
+pub async fn notification_action_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path((notification_id, action_id)): Path<(String, String)>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<NotificationActionForm>,
+) -> impl IntoResponse {
+    match notification_service::execute_notification_action(
+        &pool,
+        &auth_user.id,
+        &notification_id,
+        &action_id,
+    )
+    .await
+    {
+        Ok((_, Some(next))) => Redirect::to(&next).into_response(),
+        Ok((_, None)) => redirect_back(form.return_to.as_deref(), "action_ok"),
+        Err(e) => redirect_back(form.return_to.as_deref(), command_notice(Err(e))),
+    }
+}
+
+fn command_notice(result: Result<(), CommandError>) -> &'static str {
+    match result {
+        Ok(()) => "ok",
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Notification command rejected: {:?}", outcome.outcome);
+            "rejected"
+        }
+        Err(e) => {
+            warn!("Notification command failed: {}", e);
+            "error"
+        }
+    }
+}
+
+fn redirect_back(return_to: Option<&str>, notice: &str) -> axum::response::Response {
+    let target = return_to.and_then(local_path).unwrap_or("/notifications");
+    let sep = if target.contains('?') { "&" } else { "?" };
+    Redirect::to(&format!("{}{}notice={}", target, sep, notice)).into_response()
+}
@@ -0,0 +1,163 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::services::central_apply_service::CommandError;
+use crate::services::onboarding_service::{
+    self, OnboardingError, OnboardingLocation, WizardStepView,
+};
+use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
+
+/// Where users land once onboarding is done.
+const AFTER_ONBOARDING: &str = "/activities";
+
+#[derive(Template)]
+#[template(path = "onboarding.html")]
+pub struct OnboardingTemplate {
+    pub view: WizardStepView,
+    pub error: Option<String>,
+    pub csrf_token: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct OnboardingQuery {
+    pub step: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OnboardingLocationForm {
+    pub location_name: Option<String>,
+    pub latitude: Option<String>,
+    pub longitude: Option<String>,
+}
+
+pub async fn onboarding_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    Query(query): Query<OnboardingQuery>,
+    State(pool): State<SqlitePool>,
+) -> Response {
+    match onboarding_service::needs_onboarding(&pool, &auth_user.id).await {
+        Ok(true) => {}
+        Ok(false) => return Redirect::to(AFTER_ONBOARDING).into_response(),
+        Err(e) => return failed(e),
+    }
+    match onboarding_service::load_step(&pool, &auth_user.id, query.step).await {
+        Ok(view) => render(StatusCode::OK, view, None, csrf),
+        Err(e) => failed(e),
+    }
+}
+
+/// One catalog page: `step`, repeated `interest_id` and `direction`
+/// (`next` | `back`).
+pub async fn onboarding_step_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    State(pool): State<SqlitePool>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+    };
+    let step = field("step").and_then(|s| s.parse().ok()).unwrap_or(0);
+    let back = field("direction") == Some("back");
+    let interest_ids: Vec<String> = fields
+        .iter()
+        .filter(|(k, _)| k == "interest_id")
+        .map(|(_, v)| v.clone())
+        .collect();
+
+    match onboarding_service::save_page(&pool, &auth_user.id, step, &interest_ids, back).await {
+        Ok(next) => Redirect::to(&format!("/onboarding?step={}", next)).into_response(),
+        Err(OnboardingError::Database(e)) => failed(e),
+        Err(e) => {
+            // Show the page again with what was ticked, not what was saved.
+            let mut view =
+                match onboarding_service::load_step(&pool, &auth_user.id, Some(step)).await {
+                    Ok(view) => view,
+                    Err(e) => return failed(e),
+                };
+            for interest in view.categories.iter_mut().flat_map(|c| &mut c.interests) {
+                interest.selected = interest_ids.contains(&interest.id);
+            }
+            render(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                view,
+                Some(e.to_string()),
+                csrf,
+            )
+        }
+    }
+}
+
+pub async fn onboarding_complete_handler(
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
+    State(pool): State<SqlitePool>,
+    Form(form): Form<OnboardingLocationForm>,
+) -> Response {
+    let coord = |v: &Option<String>| v.as_deref().and_then(|s| s.trim().parse::<f64>().ok());
+    let location = match (
+        form.location_name.as_deref().map(str::trim),
+        coord(&form.latitude),
+        coord(&form.longitude),
+    ) {
+        (Some(name), Some(latitude), Some(longitude)) if !name.is_empty() => {
+            Some(OnboardingLocation {
+                name: name.to_string(),
+                latitude,
+                longitude,
+            })
+        }
+        _ => None,
+    };
+
+    let error = match onboarding_service::complete(&pool, &auth_user.id, location).await {
+        Ok(_) => return Redirect::to(AFTER_ONBOARDING).into_response(),
+        Err(OnboardingError::Database(e)) => return failed(e),
+        Err(OnboardingError::Command(CommandError::Rejected(outcome))) => {
+            warn!("Onboarding command rejected: {:?}", outcome.outcome);
+            "Je profiel kon niet worden opgeslagen. Probeer het zo nog eens.".to_string()
+        }
+        Err(OnboardingError::Command(e)) => {
+            warn!("Onboarding command failed: {}", e);
+            "Je profiel kon niet worden opgeslagen. Probeer het zo nog eens.".to_string()
+        }
+        Err(e) => e.to_string(),
+    };
+
+    match onboarding_service::load_step(&pool, &auth_user.id, None).await {
+        Ok(view) => render(StatusCode::UNPROCESSABLE_ENTITY, view, Some(error), csrf),
+        Err(e) => failed(e),
+    }
+}
+
+fn render(
+    status: StatusCode,
+    view: WizardStepView,
+    error: Option<String>,
+    csrf: CsrfToken,
+) -> Response {
+    let template = OnboardingTemplate {
+        view,
+        error,
+        csrf_token: csrf.0,
+    };
+    (status, Html(template.render().unwrap())).into_response()
+}
+
+fn failed(e: sqlx::Error) -> Response {
+    warn!("Onboarding load failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
@@ -9,10 +9,13 @@ use axum::{
 use sqlx::SqlitePool;
 use tracing::warn;
 
+use crate::services::central_apply_service::CommandError;
 use crate::services::friendship_service;
+use crate::services::redirect_service::local_path;
 use crate::services::user_service;
 use crate::services::user_summary_service;
 use crate::web::middleware::auth::AuthenticatedUser;
+use crate::web::middleware::csrf::CsrfToken;
 
 #[derive(Template)]
 #[template(path = "user.html")]
@@ -45,10 +48,12 @@ pub async fn user_profile_handler(
 #[template(path = "user_summary.html")]
 pub struct UserSummaryTemplate {
     pub user: user_summary_service::UserSummaryView,
+    pub csrf_token: String,
 }
 
 pub async fn user_summary_handler(
     Extension(auth_user): Extension<AuthenticatedUser>,
+    Extension(csrf): Extension<CsrfToken>,
     Path(user_id): Path<String>,
     State(pool): State<SqlitePool>,
 ) -> impl IntoResponse {
@@ -65,7 +70,10 @@ pub async fn user_summary_handler(
         return StatusCode::NOT_FOUND.into_response();
     };
 
-    let template = UserSummaryTemplate { user: view };
+    let template = UserSummaryTemplate {
+        user: view,
+        csrf_token: csrf.0,
+    };
     Html(template.render().unwrap()).into_response()
 }
 
@@ -91,6 +99,10 @@ pub async fn friendship_command_handler(
     .await
     {
         Ok(_) => "ok",
+        Err(CommandError::Rejected(outcome)) => {
+            warn!("Friendship command rejected: {:?}", outcome.outcome);
+            "rejected"
+        }
         Err(e) => {
             warn!("Friendship command failed: {}", e);
             "error"
@@ -100,7 +112,7 @@ pub async fn friendship_command_handler(
     let target = form
         .return_to
         .as_deref()
-        .filter(|s| s.starts_with('/') && !s.starts_with("//") && !s.contains("://"))
+        .and_then(local_path)
         .unwrap_or("/discovery");
 
     let sep = if target.contains('?') { "&" } else { "?" };